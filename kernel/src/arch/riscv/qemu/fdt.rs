@@ -0,0 +1,225 @@
+// 扁平设备树(FDT/DTB)解析，qemu在启动时通过a1寄存器传入设备树地址
+// 设备树规范，see: https://devicetree-specification.readthedocs.io/en/stable/flattened-format.html
+// 解析过程不依赖堆内存，可以在M模式启动阶段使用
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+// 节点最大嵌套深度
+const MAX_DEPTH: usize = 16;
+
+// FDT header，所有字段都是大端序
+#[repr(C)]
+struct FdtHeader {
+    magic: u32,
+    totalsize: u32,
+    off_dt_struct: u32,
+    off_dt_strings: u32,
+    off_mem_rsvmap: u32,
+    version: u32,
+    last_comp_version: u32,
+    boot_cpuid_phys: u32,
+    size_dt_strings: u32,
+    size_dt_struct: u32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    structs: &'a [u8], // structure block
+    strings: &'a [u8], // strings block
+    total_size: usize,
+}
+
+// 设备树节点，props指向节点名之后的structure block
+#[derive(Clone, Copy)]
+pub struct FdtNode<'a> {
+    pub name: &'a str,
+    pub depth: usize,
+    pub addr_cells: usize, // 父节点的#address-cells，用于解析当前节点的reg
+    pub size_cells: usize, // 父节点的#size-cells
+    props: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    // 从物理地址解析设备树，magic不匹配时返回None
+    pub unsafe fn from_addr(addr: usize) -> Option<Fdt<'static>> {
+        if addr == 0 || addr % 4 != 0 {
+            return None;
+        }
+        let header = (addr as *const FdtHeader).as_ref().unwrap();
+        if u32::from_be(header.magic) != FDT_MAGIC {
+            return None;
+        }
+        let total_size = u32::from_be(header.totalsize) as usize;
+        let struct_off = u32::from_be(header.off_dt_struct) as usize;
+        let struct_size = u32::from_be(header.size_dt_struct) as usize;
+        let strings_off = u32::from_be(header.off_dt_strings) as usize;
+        let strings_size = u32::from_be(header.size_dt_strings) as usize;
+        if struct_off + struct_size > total_size || strings_off + strings_size > total_size {
+            return None;
+        }
+        let data = core::slice::from_raw_parts(addr as *const u8, total_size);
+        return Some(Fdt {
+            structs: &data[struct_off..struct_off + struct_size],
+            strings: &data[strings_off..strings_off + strings_size],
+            total_size: total_size,
+        });
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.total_size
+    }
+
+    // 深度优先遍历所有节点
+    pub fn walk<F: FnMut(&FdtNode<'a>)>(&self, mut f: F) {
+        let mut off = 0;
+        let mut depth = 0;
+        // cells[d]是深度为d的节点的父节点的(#address-cells, #size-cells)，缺省值为(2, 1)
+        let mut cells = [(2usize, 1usize); MAX_DEPTH + 1];
+        while off + 4 <= self.structs.len() {
+            let token = be32(self.structs, off);
+            off += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = cstr(self.structs, off);
+                    off = align4(off + name.len() + 1);
+                    let (addr_cells, size_cells) = cells[depth.min(MAX_DEPTH)];
+                    let node = FdtNode {
+                        name: name,
+                        depth: depth,
+                        addr_cells: addr_cells,
+                        size_cells: size_cells,
+                        props: &self.structs[off..],
+                        strings: self.strings,
+                    };
+                    if depth < MAX_DEPTH {
+                        cells[depth + 1] = (
+                            node.prop_u32("#address-cells").unwrap_or(2) as usize,
+                            node.prop_u32("#size-cells").unwrap_or(1) as usize,
+                        );
+                    }
+                    f(&node);
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    depth = depth.saturating_sub(1);
+                }
+                FDT_PROP => {
+                    let len = be32(self.structs, off) as usize;
+                    off = align4(off + 8 + len);
+                }
+                FDT_NOP => {}
+                FDT_END => break,
+                _ => break,
+            }
+        }
+    }
+}
+
+impl<'a> FdtNode<'a> {
+    // 查找节点的属性值
+    pub fn prop(&self, name: &str) -> Option<&'a [u8]> {
+        let mut off = 0;
+        while off + 4 <= self.props.len() {
+            match be32(self.props, off) {
+                FDT_NOP => off += 4,
+                FDT_PROP => {
+                    let len = be32(self.props, off + 4) as usize;
+                    let name_off = be32(self.props, off + 8) as usize;
+                    let value_off = off + 12;
+                    if name_off < self.strings.len() && cstr(self.strings, name_off) == name {
+                        return self.props.get(value_off..value_off + len);
+                    }
+                    off = align4(value_off + len);
+                }
+                // 属性之后是子节点或节点结束
+                _ => break,
+            }
+        }
+        return None;
+    }
+
+    pub fn prop_u32(&self, name: &str) -> Option<u32> {
+        self.prop(name)
+            .filter(|value| value.len() >= 4)
+            .map(|value| be32(value, 0))
+    }
+
+    pub fn prop_str(&self, name: &str) -> Option<&'a str> {
+        self.prop(name).map(|value| cstr(value, 0))
+    }
+
+    // compatible属性是字符串列表，判断是否包含compat
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        if let Some(value) = self.prop("compatible") {
+            return value
+                .split(|b| *b == 0)
+                .any(|s| s == compat.as_bytes());
+        }
+        return false;
+    }
+
+    pub fn device_type(&self) -> Option<&'a str> {
+        self.prop_str("device_type")
+    }
+
+    // 节点是否可用，缺省status的节点视为可用
+    pub fn is_enabled(&self) -> bool {
+        match self.prop_str("status") {
+            Some(status) => status == "okay" || status == "ok",
+            None => true,
+        }
+    }
+
+    // 节点reg属性的第index个(base, size)
+    pub fn reg(&self, index: usize) -> Option<(usize, usize)> {
+        let value = self.prop("reg")?;
+        let entry = (self.addr_cells + self.size_cells) * 4;
+        let off = index * entry;
+        if entry == 0 || off + entry > value.len() {
+            return None;
+        }
+        let base = read_cells(value, off, self.addr_cells);
+        let size = read_cells(value, off + self.addr_cells * 4, self.size_cells);
+        return Some((base, size));
+    }
+
+    // 节点interrupts属性的第一个中断号
+    pub fn interrupt(&self) -> Option<usize> {
+        self.prop_u32("interrupts").map(|irq| irq as usize)
+    }
+}
+
+// 读取连续n个大端序cell组成的数值
+pub fn read_cells(data: &[u8], off: usize, n: usize) -> usize {
+    let mut value: usize = 0;
+    for i in 0..n {
+        value = (value << 32) | be32(data, off + i * 4) as usize;
+    }
+    return value;
+}
+
+pub fn be32(data: &[u8], off: usize) -> u32 {
+    if off + 4 > data.len() {
+        return 0;
+    }
+    return u32::from_be_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]]);
+}
+
+// 读取off位置开始的以\0结尾的字符串
+fn cstr(data: &[u8], off: usize) -> &str {
+    let bytes = &data[off.min(data.len())..];
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    return core::str::from_utf8(&bytes[0..len]).unwrap_or("");
+}
+
+#[inline]
+fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
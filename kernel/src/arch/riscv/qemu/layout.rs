@@ -1,9 +1,15 @@
+use super::fdt::{be32, read_cells, Fdt, FdtNode};
+use crate::config::{KERNEL_STACK_BOTTOM, MAX_CPUS, PAGE_SIZE};
+use alloc::vec::Vec;
+
+// qemu virt机器的默认布局，设备树不可用时使用
 pub const UART0: usize = 0x10000000;
 pub const UART_PLIC: usize = 10;
 
 pub const CLINT0: usize = 0x02000000;
-pub const CLINT_MTIME: usize = CLINT0 + 0xBFF8;
-pub const CLINT_MTIMECMP: usize = CLINT0 + 0x4000;
+// mtime和mtimecmp相对CLINT基址的偏移
+pub const CLINT_MTIME_OFFSET: usize = 0xBFF8;
+pub const CLINT_MTIMECMP_OFFSET: usize = 0x4000;
 
 pub const VIRTIO0: usize = 0x10001000;
 pub const SECTOR_SIZE: usize = 512;
@@ -14,20 +20,279 @@ pub const SHUTDOWN0: usize = 0x10_0000;
 pub const VIRT_PLIC: usize = 0x0c00_0000;
 
 pub const PCIE0: usize = 0x3000_0000;
-pub const E1000_REGS: usize = 0x4000_0000;
-
-// MMIO地址范围
-pub const MMIO: &[(usize, usize)] = &[
-    (UART0, UART0 + 0x1000),
-    (CLINT0, CLINT0 + 0xc000),
-    (VIRTIO0, VIRTIO0 + 0x1000),
-    (SHUTDOWN0, SHUTDOWN0 + 0x1000),
-    (VIRT_PLIC, VIRT_PLIC + 0x30_0000),
-    (E1000_REGS, E1000_REGS + 0x10000000),
-];
-
-// PLIC中断源列表
-pub const PLIC_SOURCES: &[usize] = &[
-    UART_PLIC,       // uart
-    VIRTIO_BLK_PLIC, // virtio blk
-];
+pub const PCIE_MMIO: usize = 0x4000_0000;
+pub const PCIE_MMIO64: usize = 0x4_0000_0000;
+// qemu virt的PCI INTA~INTD连接到PLIC的32~35号中断
+pub const PCIE_IRQ: usize = 32;
+
+pub const PHYS_MEM_START: usize = 0x8000_0000;
+pub const PHYS_MEM_LIMIT: usize = 0x8800_0000;
+
+// qemu virt最多有8个virtio-mmio插槽
+pub const MAX_VIRTIO_SLOTS: usize = 8;
+// 设备树interrupt-map中最多记录的PCI INTx路由项
+pub const MAX_PCI_INTX: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MmioRegion {
+    pub base: usize,
+    pub size: usize,
+}
+
+// virtio-mmio插槽，每个插槽有独立的寄存器组和中断号
+#[derive(Clone, Copy, Debug)]
+pub struct VirtioSlot {
+    pub base: usize,
+    pub irq: usize,
+}
+
+// PCI INTx路由项，设备地址和中断引脚经过mask后匹配到PLIC中断号
+#[derive(Clone, Copy, Debug)]
+pub struct PciIntx {
+    pub addr: u32, // phys.hi中的device/function部分
+    pub pin: u32,  // INTA~INTD：1~4
+    pub irq: usize,
+}
+
+// 机器布局，启动时由hart0从设备树中解析
+pub struct Board {
+    pub mem_start: usize,
+    pub mem_end: usize,
+    pub harts: usize,
+    pub uart: MmioRegion,
+    pub uart_irq: usize,
+    pub clint: MmioRegion,
+    pub plic: MmioRegion,
+    pub plic_ndev: usize,
+    pub shutdown: MmioRegion,
+    pub pcie_ecam: MmioRegion,
+    pub pcie_mmio: MmioRegion,   // 32位PCI MMIO窗口
+    pub pcie_mmio64: MmioRegion, // 64位PCI MMIO窗口
+    pub pci_intx: [PciIntx; MAX_PCI_INTX],
+    pub pci_intx_count: usize,
+    pub pci_intx_mask: (u32, u32), // interrupt-map-mask的(phys.hi, pin)
+    pub virtio: [VirtioSlot; MAX_VIRTIO_SLOTS],
+    pub virtio_count: usize,
+    pub dtb: MmioRegion,
+}
+
+static mut BOARD: Board = Board::qemu_default();
+
+pub fn board() -> &'static Board {
+    unsafe { &BOARD }
+}
+
+// 解析a1传入的设备树，只能在hart0启动时调用一次
+pub unsafe fn init_board(dtb: usize) {
+    if let Some(fdt) = Fdt::from_addr(dtb) {
+        BOARD.load_fdt(&fdt, dtb);
+    }
+}
+
+impl Board {
+    const fn qemu_default() -> Self {
+        let mut virtio = [VirtioSlot { base: 0, irq: 0 }; MAX_VIRTIO_SLOTS];
+        let mut i = 0;
+        while i < MAX_VIRTIO_SLOTS {
+            virtio[i] = VirtioSlot {
+                base: VIRTIO0 + i * 0x1000,
+                irq: i + 1,
+            };
+            i += 1;
+        }
+        Self {
+            mem_start: PHYS_MEM_START,
+            mem_end: PHYS_MEM_LIMIT,
+            harts: 1,
+            uart: MmioRegion { base: UART0, size: 0x1000 },
+            uart_irq: UART_PLIC,
+            clint: MmioRegion { base: CLINT0, size: 0x10000 },
+            plic: MmioRegion { base: VIRT_PLIC, size: 0x60_0000 },
+            plic_ndev: 95,
+            shutdown: MmioRegion { base: SHUTDOWN0, size: 0x1000 },
+            pcie_ecam: MmioRegion { base: PCIE0, size: 0x1000_0000 },
+            pcie_mmio: MmioRegion { base: PCIE_MMIO, size: 0x4000_0000 },
+            pcie_mmio64: MmioRegion { base: PCIE_MMIO64, size: 0x4_0000_0000 },
+            pci_intx: [PciIntx { addr: 0, pin: 0, irq: 0 }; MAX_PCI_INTX],
+            pci_intx_count: 0,
+            pci_intx_mask: (0x1800, 0x7),
+            virtio: virtio,
+            virtio_count: MAX_VIRTIO_SLOTS,
+            dtb: MmioRegion { base: 0, size: 0 },
+        }
+    }
+
+    fn load_fdt(&mut self, fdt: &Fdt, dtb: usize) {
+        let mut harts = 0;
+        let mut virtio_count = 0;
+        fdt.walk(|node| {
+            if !node.is_enabled() {
+                return;
+            }
+            if node.device_type() == Some("memory") {
+                if let Some((base, size)) = node.reg(0) {
+                    self.mem_start = base;
+                    self.mem_end = base + size;
+                }
+            } else if node.device_type() == Some("cpu") {
+                // hartid超过MAX_CPUS的cpu在启动时已经停住，不参与调度
+                match node.reg(0) {
+                    Some((hartid, _)) if hartid >= MAX_CPUS => {}
+                    _ => harts += 1,
+                }
+            } else if node.is_compatible("ns16550a") {
+                if let Some(region) = reg_region(node) {
+                    self.uart = region;
+                }
+                self.uart_irq = node.interrupt().unwrap_or(self.uart_irq);
+            } else if node.is_compatible("riscv,clint0") || node.is_compatible("sifive,clint0") {
+                if let Some(region) = reg_region(node) {
+                    self.clint = region;
+                }
+            } else if node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0") {
+                if let Some(region) = reg_region(node) {
+                    self.plic = region;
+                }
+                if let Some(ndev) = node.prop_u32("riscv,ndev") {
+                    self.plic_ndev = ndev as usize;
+                }
+            } else if node.is_compatible("virtio,mmio") {
+                if let (Some((base, _)), Some(irq)) = (node.reg(0), node.interrupt()) {
+                    if virtio_count < MAX_VIRTIO_SLOTS {
+                        self.virtio[virtio_count] = VirtioSlot { base, irq };
+                        virtio_count += 1;
+                    }
+                }
+            } else if node.is_compatible("pci-host-ecam-generic") {
+                if let Some(region) = reg_region(node) {
+                    self.pcie_ecam = region;
+                }
+                self.load_pci_ranges(node);
+                self.load_pci_interrupt_map(node);
+            } else if node.is_compatible("sifive,test0") {
+                if let Some(region) = reg_region(node) {
+                    self.shutdown = region;
+                }
+            }
+        });
+        if harts > 0 {
+            self.harts = harts.min(MAX_CPUS);
+        }
+        self.virtio_count = virtio_count;
+        // qemu按地址从高到低生成virtio节点，按地址排序
+        self.virtio[0..virtio_count].sort_unstable_by_key(|slot| slot.base);
+        // 物理内存需要直接映射到内核地址空间，不能超过内核栈区域
+        self.mem_end = self.mem_end.min(KERNEL_STACK_BOTTOM);
+        self.dtb = MmioRegion {
+            base: dtb,
+            size: fdt.total_size(),
+        };
+    }
+
+    // 解析PCI host bridge的ranges属性，找到32位和64位memory空间窗口
+    fn load_pci_ranges(&mut self, node: &FdtNode) {
+        let child_cells = node.prop_u32("#address-cells").unwrap_or(3) as usize;
+        let size_cells = node.prop_u32("#size-cells").unwrap_or(2) as usize;
+        let parent_cells = node.addr_cells;
+        let entry = (child_cells + parent_cells + size_cells) * 4;
+        if let Some(ranges) = node.prop("ranges") {
+            let mut off = 0;
+            while entry > 0 && off + entry <= ranges.len() {
+                // phys.hi的24-25位是地址空间类型，0b10是32位memory空间，0b11是64位memory空间
+                let space = (be32(ranges, off) >> 24) & 0x3;
+                let cpu_addr = read_cells(ranges, off + child_cells * 4, parent_cells);
+                let size = read_cells(ranges, off + (child_cells + parent_cells) * 4, size_cells);
+                let region = MmioRegion {
+                    base: cpu_addr,
+                    size: size,
+                };
+                if space == 0x2 {
+                    self.pcie_mmio = region;
+                } else if space == 0x3 {
+                    self.pcie_mmio64 = region;
+                }
+                off += entry;
+            }
+        }
+    }
+
+    // 解析PCI host bridge的interrupt-map，PLIC的#address-cells为0，#interrupt-cells为1
+    fn load_pci_interrupt_map(&mut self, node: &FdtNode) {
+        let child_cells = node.prop_u32("#address-cells").unwrap_or(3) as usize;
+        let intr_cells = node.prop_u32("#interrupt-cells").unwrap_or(1) as usize;
+        if let Some(mask) = node.prop("interrupt-map-mask") {
+            self.pci_intx_mask = (be32(mask, 0), be32(mask, child_cells * 4));
+        }
+        // child unit address + child interrupt + phandle + parent interrupt
+        let entry = (child_cells + intr_cells + 2) * 4;
+        let mut count = 0;
+        if let Some(map) = node.prop("interrupt-map") {
+            let mut off = 0;
+            while off + entry <= map.len() && count < MAX_PCI_INTX {
+                self.pci_intx[count] = PciIntx {
+                    addr: be32(map, off),
+                    pin: be32(map, off + child_cells * 4),
+                    irq: be32(map, off + entry - 4) as usize,
+                };
+                count += 1;
+                off += entry;
+            }
+        }
+        self.pci_intx_count = count;
+    }
+
+    // 根总线上设备dev的INTx引脚连接的PLIC中断号
+    pub fn pci_intx_irq(&self, dev: u32, pin: u32) -> usize {
+        if self.pci_intx_count == 0 {
+            // 没有设备树时使用qemu virt的固定路由
+            return PCIE_IRQ + ((pin as usize - 1 + dev as usize) % 4);
+        }
+        let (addr_mask, pin_mask) = self.pci_intx_mask;
+        let addr = (dev << 11) & addr_mask;
+        self.pci_intx[0..self.pci_intx_count]
+            .iter()
+            .find(|e| e.addr & addr_mask == addr && e.pin & pin_mask == pin & pin_mask)
+            .map(|e| e.irq)
+            .unwrap_or(0)
+    }
+
+    pub fn virtio_slots(&self) -> &[VirtioSlot] {
+        &self.virtio[0..self.virtio_count]
+    }
+
+    // 可分配的物理内存上界，qemu将设备树放在内存末尾，分配器需要跳过
+    pub fn alloc_end(&self) -> usize {
+        if self.dtb.size != 0 && self.dtb.base >= self.mem_start && self.dtb.base < self.mem_end {
+            return self.dtb.base & !(PAGE_SIZE - 1);
+        }
+        return self.mem_end;
+    }
+
+    pub fn clint_mtime(&self) -> usize {
+        self.clint.base + CLINT_MTIME_OFFSET
+    }
+
+    pub fn clint_mtimecmp(&self, hartid: usize) -> usize {
+        self.clint.base + CLINT_MTIMECMP_OFFSET + 8 * hartid
+    }
+
+    // 需要映射到内核地址空间的MMIO地址范围，PCI设备的BAR在分配时单独映射
+    pub fn mmio_regions(&self) -> Vec<MmioRegion> {
+        let mut regions = Vec::new();
+        regions.push(self.uart);
+        regions.push(self.clint);
+        regions.push(self.plic);
+        regions.push(self.shutdown);
+        for slot in self.virtio_slots() {
+            regions.push(MmioRegion {
+                base: slot.base,
+                size: 0x1000,
+            });
+        }
+        return regions;
+    }
+}
+
+fn reg_region(node: &FdtNode) -> Option<MmioRegion> {
+    node.reg(0).map(|(base, size)| MmioRegion { base, size })
+}
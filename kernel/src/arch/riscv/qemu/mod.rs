@@ -1 +1,2 @@
+pub mod fdt;
 pub mod layout;
@@ -1,4 +1,4 @@
-use super::qemu::layout::{CLINT0, CLINT_MTIME, CLINT_MTIMECMP};
+use super::qemu::layout::board;
 use core::arch::asm;
 use core::ptr;
 #[inline]
@@ -25,17 +25,17 @@ pub unsafe fn read_tp() -> usize {
 
 #[inline]
 pub unsafe fn read_mtime() -> usize {
-    ptr::read_volatile(CLINT_MTIME as *const usize)
+    ptr::read_volatile(board().clint_mtime() as *const usize)
 }
 
 #[inline]
 pub unsafe fn read_mtimecmp(mhartid: usize) -> usize {
-    ptr::read_volatile((CLINT_MTIMECMP + 8 * mhartid) as *const usize)
+    ptr::read_volatile(board().clint_mtimecmp(mhartid) as *const usize)
 }
 
 #[inline]
 unsafe fn write_mtimecmp(mhartid: usize, value: usize) {
-    let offset = CLINT_MTIMECMP + 8 * mhartid;
+    let offset = board().clint_mtimecmp(mhartid);
     ptr::write_volatile(offset as *mut usize, value);
 }
 
@@ -48,7 +48,7 @@ pub unsafe fn add_mtimecmp(mhartid: usize, interval: usize) {
 
 #[inline]
 pub fn mtime_cmp_addr(mhartid: usize) -> usize {
-    return CLINT_MTIMECMP + 8 * mhartid;
+    return board().clint_mtimecmp(mhartid);
 }
 
 #[inline]
@@ -1,13 +1,25 @@
+use crate::arch::riscv::qemu::layout;
 use crate::config;
 use crate::rust_main;
 use crate::timer;
 use core::arch::asm;
+use core::sync::atomic::{AtomicU8, Ordering};
 use riscv::register::*;
 
+// hart0是否已经解析完设备树
+static BOARD_INITED: AtomicU8 = AtomicU8::new(0);
+
 // 引导内核启动，设置M模式下的寄存器，之后跳转到内核入口进入S模式
 #[no_mangle]
-pub fn rust_start() {
+pub fn rust_start(hartid: usize, dtb: usize) {
     unsafe {
+        // hart0解析设备树，其他hart等待解析完成后再访问CLINT等设备
+        if hartid == 0 {
+            layout::init_board(dtb);
+            BOARD_INITED.store(1, Ordering::SeqCst);
+        } else {
+            while BOARD_INITED.load(Ordering::SeqCst) == 0 {}
+        }
         // 设置mstatus， 使mret 返回supervisor模式
         mstatus::set_mpp(mstatus::MPP::Supervisor);
         // 设置mepc，mret的跳转到rust_main
@@ -20,7 +32,7 @@ pub fn rust_start() {
         sie::set_ssoft();
         sie::set_sext();
         // 设置物理地址范围
-        pmpaddr0::write(config::PHYS_MEM_LIMIT - 1);
+        pmpaddr0::write(layout::board().mem_end - 1);
         // 物理地址保护，RWX=1, A=TOR, 范围[0,pmpaddr0)
         pmpcfg0::write(0b1111);
         if config::ENABLE_TIMER {
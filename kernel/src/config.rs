@@ -1,14 +1,23 @@
-pub const CPUS: usize = 1;
-pub const PHYS_MEM_LIMIT: usize = 0x8800_0000;
-pub const KERNEL_HEAP_SIZE: usize = 0x100_0000;
+// 支持的最大cpu数量，实际数量由设备树决定
+pub const MAX_CPUS: usize = 8;
+// 内核堆初始大小，空间不足时从物理页分配器扩展
+pub const KERNEL_HEAP_INIT_SIZE: usize = 0x20_0000;
+// 内核堆每次至少扩展256KiB
+pub const KERNEL_HEAP_GROW_SIZE: usize = 256 * 1024;
 pub const PAGE_SIZE: usize = 4096;
 
 pub const TIME_FREQ: usize = 10000000;
 pub const TIME_FREQ_MS: usize = TIME_FREQ / 1000;
 pub const TIME_FREQ_US: usize = TIME_FREQ / 1000_000;
 
-// 每个进程的用户态栈大小：
+// 每个线程初始的用户态栈大小，之后按需向下增长
 pub const USER_STACK_SIZE: usize = 8 * 1024;
+// 每个线程的用户栈虚拟地址区域大小，区域底部的守护页不会被映射
+pub const USER_STACK_REGION_SIZE: usize = 8 * 1024 * 1024;
+// 默认的栈大小上限，可以通过setrlimit修改
+pub const USER_STACK_LIMIT: usize = 1024 * 1024;
+// 栈大小上限的最大值，栈不能增长到守护页
+pub const USER_STACK_LIMIT_MAX: usize = USER_STACK_REGION_SIZE - GUARD_PAGE;
 // 每个进程的内核栈大小：4KiB
 pub const KERNEL_STACK_SIZE: usize = 4 * 1024;
 // 虚拟地址最大值
@@ -18,6 +27,14 @@ pub const TRAMPOLINE: usize = MAX_VA - PAGE_SIZE;
 pub const GUARD_PAGE: usize = PAGE_SIZE;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
+// 共享内存等动态映射的虚拟地址区域，位于用户堆和线程栈之间
+pub const MMAP_BOTTOM: usize = 0x4000_0000;
+pub const MMAP_TOP: usize = 0xc000_0000;
+// 用户堆的最高地址，brk不能进入动态映射区域
+pub const USER_HEAP_TOP: usize = MMAP_BOTTOM;
+// 用户线程栈区域，位于动态映射区域之上，每个线程的栈按tid依次排列
+pub const USER_STACK_BOTTOM: usize = MMAP_TOP;
+
 // 最大pid值
 pub const MAX_PID: usize = 1 << 15;
 // 最大的内核栈数量
@@ -54,9 +71,9 @@ pub fn task_trap_context_position(tid: usize) -> usize {
     TRAMPOLINE - (tid + 1) * PAGE_SIZE
 }
 
-// 获取线程在用户空间的栈位置
+// 获取线程在用户空间的初始栈位置，栈位于线程栈区域的顶端
 pub fn task_user_stack_position(user_stack_bottom: usize, tid: usize) -> (usize, usize) {
-    let stack_bottom = user_stack_bottom + tid * USER_STACK_SIZE;
-    let stack_top = stack_bottom + USER_STACK_SIZE;
+    let stack_top = user_stack_bottom + (tid + 1) * USER_STACK_REGION_SIZE;
+    let stack_bottom = stack_top - USER_STACK_SIZE;
     return (stack_bottom, stack_top);
 }
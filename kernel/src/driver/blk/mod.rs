@@ -3,10 +3,11 @@ pub mod virtio_block;
 
 use crate::config::{BlockDeviceType, BLOCK_DEVICE_TYPE};
 use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
 use lazy_static::lazy_static;
 use mem_block::MemoryBlockDevice;
 use simplefs::block_device::BlockDevice;
-use virtio_block::VirtIOBlock;
+use virtio_block::{VirtIOBlock, VIRTIO_BLOCK_BASE};
 
 lazy_static! {
     pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = unsafe {
@@ -15,7 +16,9 @@ lazy_static! {
             BlockDeviceType::MEMORY => block_device = Arc::new(MemoryBlockDevice::new()),
             BlockDeviceType::VIRTIO => {
                 panic!("virtio block not available");
-                let virtio_block = VirtIOBlock::new();
+                let base = VIRTIO_BLOCK_BASE.load(Ordering::SeqCst);
+                assert!(base != 0, "no virtio block device");
+                let virtio_block = VirtIOBlock::new(base);
                 virtio_block.init();
                 block_device = Arc::new(virtio_block);
             }
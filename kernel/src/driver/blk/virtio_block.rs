@@ -1,10 +1,13 @@
 use crate::arch::riscv::qemu::layout::SECTOR_SIZE;
 use crate::config::PAGE_SIZE;
+use crate::config::{BlockDeviceType, BLOCK_DEVICE_TYPE};
+use crate::driver::device::{register_irq, Device, Driver};
 use crate::driver::virtio::*;
 use crate::mem::address::*;
 use alloc::sync::Arc;
-use array_macro::array;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::lazy_static;
+use array_macro::array;
 use simplefs::block_device::BlockDevice;
 use simplefs::layout::BLOCK_SIZE;
 use spin::Mutex;
@@ -13,14 +16,46 @@ pub struct VirtIOBlock {
     queue: Mutex<VirtQueue>,
 }
 
+// probe到的virtio块设备所在插槽的寄存器基址
+pub static VIRTIO_BLOCK_BASE: AtomicUsize = AtomicUsize::new(0);
+
 lazy_static! {
-    static ref VIRTIO_BLOCK: VirtIOBlock = VirtIOBlock::new();
+    pub static ref VIRTIO_BLOCK_DRIVER: Arc<VirtIOBlockDriver> = Arc::new(VirtIOBlockDriver);
+}
+
+pub struct VirtIOBlockDriver;
+
+impl Driver for VirtIOBlockDriver {
+    fn name(&self) -> &'static str {
+        "virtio-blk"
+    }
+    fn compatible(&self) -> &'static [&'static str] {
+        &["virtio,device2"]
+    }
+    fn probe(&self, dev: &Device) -> bool {
+        // 只有配置使用virtio块设备时才绑定，只支持一个块设备
+        if let BlockDeviceType::MEMORY = BLOCK_DEVICE_TYPE {
+            return false;
+        }
+        if VIRTIO_BLOCK_BASE.load(Ordering::SeqCst) != 0 {
+            return false;
+        }
+        VIRTIO_BLOCK_BASE.store(dev.base, Ordering::SeqCst);
+        register_irq(dev.irq, VIRTIO_BLOCK_DRIVER.clone());
+        true
+    }
+    fn irq(&self, _irq: usize) {
+        unsafe {
+            ack_interrupt(VIRTIO_BLOCK_BASE.load(Ordering::SeqCst));
+        }
+    }
+    fn shutdown(&self) {}
 }
 
 impl VirtIOBlock {
-    pub fn new() -> Self {
+    pub fn new(base: usize) -> Self {
         Self {
-            queue: Mutex::new(VirtQueue::new()),
+            queue: Mutex::new(VirtQueue::new(base)),
         }
     }
 
@@ -28,13 +63,15 @@ impl VirtIOBlock {
         self.queue
             .lock()
             .init(VIRTIO_DEVICE_BLOCK, 0, |mut features| {
-                features &= !(1u32 << VIRTIO_BLK_F_RO);
-                features &= !(1u32 << VIRTIO_BLK_F_SCSI);
-                features &= !(1u32 << VIRTIO_BLK_F_CONFIG_WCE);
-                features &= !(1u32 << VIRTIO_BLK_F_MQ);
-                features &= !(1u32 << VIRTIO_F_ANY_LAYOUT);
-                features &= !(1u32 << VIRTIO_RING_F_EVENT_IDX);
-                features &= !(1u32 << VIRTIO_RING_F_INDIRECT_DESC);
+                features &= !(1u64 << VIRTIO_BLK_F_RO);
+                features &= !(1u64 << VIRTIO_BLK_F_SCSI);
+                features &= !(1u64 << VIRTIO_BLK_F_CONFIG_WCE);
+                features &= !(1u64 << VIRTIO_BLK_F_MQ);
+                features &= !(1u64 << VIRTIO_F_ANY_LAYOUT);
+                features &= !(1u64 << VIRTIO_RING_F_EVENT_IDX);
+                features &= !(1u64 << VIRTIO_RING_F_INDIRECT_DESC);
+                // 高32位只接受VERSION_1
+                features &= 0xffff_ffff | (1u64 << VIRTIO_F_VERSION_1);
                 return features;
             });
     }
@@ -0,0 +1,180 @@
+use super::plic;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// 设备所在的总线
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bus {
+    Platform = 0,   // 设备树中的平台设备
+    Pci = 1,        // PCI总线设备
+    VirtioMmio = 2, // virtio-mmio插槽中的设备
+}
+
+// 扫描总线得到的设备描述
+#[derive(Clone)]
+pub struct Device {
+    pub name: String,       // 设备名，如：pci 00:02.0
+    pub compatible: String, // 用于匹配驱动，如：ns16550a、pci8086,100e、virtio,device2
+    pub bus: Bus,
+    pub base: usize, // 寄存器基址，PCI设备为配置空间地址
+    pub irq: usize,  // PLIC中断号，0表示没有中断
+}
+
+// 设备驱动，每个驱动是一个全局对象，自己管理绑定的设备
+pub trait Driver: Send + Sync {
+    fn name(&self) -> &'static str;
+    // 驱动支持的compatible列表
+    fn compatible(&self) -> &'static [&'static str];
+    // 探测并初始化设备，成功返回true，设备绑定到该驱动
+    fn probe(&self, dev: &Device) -> bool;
+    // 驱动注册的中断处理
+    fn irq(&self, irq: usize);
+    // 关机前停止设备
+    fn shutdown(&self);
+}
+
+struct DeviceEntry {
+    device: Device,
+    driver: Option<Arc<dyn Driver>>,
+}
+
+lazy_static! {
+    static ref DRIVERS: Mutex<Vec<Arc<dyn Driver>>> = Mutex::new(Vec::new());
+    static ref DEVICES: Mutex<Vec<DeviceEntry>> = Mutex::new(Vec::new());
+    // 中断号 -> 处理中断的驱动
+    static ref IRQ_TABLE: Mutex<BTreeMap<usize, Arc<dyn Driver>>> = Mutex::new(BTreeMap::new());
+}
+
+pub fn register_driver(driver: Arc<dyn Driver>) {
+    DRIVERS.lock().push(driver);
+}
+
+// 总线扫描到新设备后加入设备表
+pub fn add_device(device: Device) {
+    kernel!(
+        "[device] found {}, compatible: {}, base: {:#x}, irq: {}",
+        device.name,
+        device.compatible,
+        device.base,
+        device.irq
+    );
+    DEVICES.lock().push(DeviceEntry {
+        device: device,
+        driver: None,
+    });
+}
+
+// 为未绑定的设备匹配驱动
+pub fn probe_devices() {
+    let count = DEVICES.lock().len();
+    for i in 0..count {
+        let devices = DEVICES.lock();
+        if devices[i].driver.is_some() {
+            continue;
+        }
+        let device = devices[i].device.clone();
+        drop(devices);
+        let drivers: Vec<Arc<dyn Driver>> = DRIVERS
+            .lock()
+            .iter()
+            .filter(|d| d.compatible().contains(&device.compatible.as_str()))
+            .map(|d| Arc::clone(d))
+            .collect();
+        // probe时不持有设备表的锁，驱动可能在probe中注册中断
+        for driver in drivers {
+            if driver.probe(&device) {
+                kernel!("[device] {} bound to driver {}", device.name, driver.name());
+                DEVICES.lock()[i].driver = Some(driver);
+                break;
+            }
+        }
+    }
+}
+
+// 注册中断处理，并在PLIC上使能该中断
+pub fn register_irq(irq: usize, driver: Arc<dyn Driver>) {
+    if irq == 0 {
+        return;
+    }
+    IRQ_TABLE.lock().insert(irq, driver);
+    plic::enable_irq(irq);
+}
+
+// 将中断分发给注册的驱动，没有驱动处理返回false
+pub fn dispatch_irq(irq: usize) -> bool {
+    let driver = IRQ_TABLE.lock().get(&irq).map(|d| Arc::clone(d));
+    match driver {
+        Some(driver) => {
+            driver.irq(irq);
+            true
+        }
+        None => false,
+    }
+}
+
+// 关闭所有已绑定的设备，关机路径可能在持有锁时进入，使用try_lock
+pub fn shutdown_devices() {
+    if let Some(devices) = DEVICES.try_lock() {
+        devices
+            .iter()
+            .filter_map(|entry| entry.driver.as_ref())
+            .for_each(|driver| driver.shutdown());
+    }
+}
+
+// 设备信息，通过ls_dev系统调用返回给用户程序
+#[repr(C)]
+pub struct DeviceInfo {
+    pub name: [u8; 32],
+    pub compatible: [u8; 32],
+    pub driver: [u8; 16],
+    pub bus: u32,
+    pub irq: u32,
+    pub base: usize,
+}
+
+impl DeviceInfo {
+    fn new(entry: &DeviceEntry) -> Self {
+        let mut info = Self {
+            name: [0; 32],
+            compatible: [0; 32],
+            driver: [0; 16],
+            bus: entry.device.bus as u32,
+            irq: entry.device.irq as u32,
+            base: entry.device.base,
+        };
+        copy_str(&mut info.name, entry.device.name.as_str());
+        copy_str(&mut info.compatible, entry.device.compatible.as_str());
+        if let Some(driver) = entry.driver.as_ref() {
+            copy_str(&mut info.driver, driver.name());
+        }
+        return info;
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+pub fn list_devices() -> Vec<DeviceInfo> {
+    DEVICES
+        .lock()
+        .iter()
+        .map(|entry| DeviceInfo::new(entry))
+        .collect()
+}
+
+// 复制字符串，保留结尾的\0
+fn copy_str(dst: &mut [u8], src: &str) {
+    let len = src.len().min(dst.len() - 1);
+    dst[0..len].copy_from_slice(&src.as_bytes()[0..len]);
+}
@@ -1,12 +1,43 @@
 pub mod blk;
+pub mod device;
 pub mod net;
 pub mod pci;
 pub mod plic;
 pub mod uart;
 pub mod virtio;
 
+use crate::arch::riscv::qemu::layout::board;
+use alloc::string::String;
+use alloc::sync::Arc;
+use device::{add_device, register_driver, Bus, Device};
+
 pub fn init() {
-    pci::scan_pci_bus();
+    register_drivers();
     plic::init_plic();
+    // 扫描平台设备和各个总线上的设备，之后为设备匹配驱动
+    scan_platform_devices();
+    pci::scan_pci_bus();
+    virtio::scan_virtio_mmio();
+    device::probe_devices();
     blk::init_blk();
 }
+
+// 内核支持的所有驱动
+fn register_drivers() {
+    register_driver(uart::UART_DRIVER.clone());
+    register_driver(net::e1000::E1000_DRIVER.clone());
+    register_driver(net::virtio_net::VIRTIO_NET_DRIVER.clone());
+    register_driver(blk::virtio_block::VIRTIO_BLOCK_DRIVER.clone());
+}
+
+// 设备树中的平台设备
+fn scan_platform_devices() {
+    let board = board();
+    add_device(Device {
+        name: String::from("uart0"),
+        compatible: String::from("ns16550a"),
+        bus: Bus::Platform,
+        base: board.uart.base,
+        irq: board.uart_irq,
+    });
+}
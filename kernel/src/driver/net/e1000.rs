@@ -1,18 +1,30 @@
-use crate::arch::riscv::qemu::layout::E1000_REGS;
+use super::{receive, register_net_device, NetDevice};
 use crate::config::PAGE_SIZE;
-use crate::mem::allocator::{alloc, dealloc, Frame};
-use alloc::collections::BTreeMap;
+use crate::driver::device::{register_irq, Device, Driver};
+use crate::driver::pci;
+use crate::mem::allocator::{alloc_contiguous, ContiguousFrames};
+use crate::net::net_buffer;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::bitflags;
-use core::sync::atomic::{fence, Ordering};
+use core::mem::size_of;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
 use lazy_static::lazy_static;
+use spin::mutex::Mutex;
 
-pub struct E1000Device<'a> {
-    rx_ring: &'a mut [RxDesc],
-    tx_ring: &'a mut [TxDesc],
-    mbuf_size: usize,
-    frames: BTreeMap<usize, Frame>,
+// e1000网卡驱动，see: https://pdos.csail.mit.edu/6.828/2019/readings/hardware/8254x_GBe_SDM.pdf
+pub struct E1000Device {
+    inner: Mutex<E1000Inner>,
+}
+
+struct E1000Inner {
+    rx_ring: &'static mut [RxDesc],
+    tx_ring: &'static mut [TxDesc],
+    rx_bufs: Vec<usize>, // 每个rx desc对应的buffer地址
+    tx_bufs: Vec<usize>,
+    frames: Vec<ContiguousFrames>, // ring和buffer占用的物理页，设备通过DMA访问
+    mac: [u8; 6],
+    id: usize, // 网卡编号
 }
 
 // RecieveDescriptor 接收网络包的描述
@@ -66,107 +78,301 @@ bitflags! {
 }
 
 lazy_static! {
-    pub static ref E1000_NETWORK_DEV: Arc<E1000Device<'static>> = Arc::new(E1000Device::new());
+    pub static ref E1000_NETWORK_DEV: Arc<E1000Device> = Arc::new(E1000Device::new());
+    pub static ref E1000_DRIVER: Arc<E1000Driver> = Arc::new(E1000Driver);
 }
 
-pub fn e1000_init() {
+// e1000寄存器基址，由PCI扫描时分配的BAR0决定
+static E1000_REGS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn e1000_init(regs: usize) {
+    E1000_REGS.store(regs, Ordering::SeqCst);
     E1000_NETWORK_DEV.init();
+    let id = register_net_device(E1000_NETWORK_DEV.clone());
+    E1000_NETWORK_DEV.inner.lock().id = id;
 }
 
-impl<'a> E1000Device<'a> {
-    pub fn new() -> Self {
-        // 分配tx和rx ring的物理内存页
-        let tx_ring_pages =
-            (TX_RING_SIZE * core::mem::size_of::<TxDesc>() + PAGE_SIZE - 1) / PAGE_SIZE;
-        let rx_ring_pages =
-            (RX_RING_SIZE * core::mem::size_of::<RxDesc>() + PAGE_SIZE - 1) / PAGE_SIZE;
-        let mut frames: BTreeMap<usize, Frame> = BTreeMap::new();
-
-        let tx_ring = unsafe {
-            core::slice::from_raw_parts_mut(
-                alloc_frames(&mut frames, tx_ring_pages) as *mut TxDesc,
-                TX_RING_SIZE,
-            )
-        };
-        let rx_ring = unsafe {
-            core::slice::from_raw_parts_mut(
-                alloc_frames(&mut frames, rx_ring_pages) as *mut RxDesc,
-                RX_RING_SIZE,
-            )
+pub struct E1000Driver;
+
+impl Driver for E1000Driver {
+    fn name(&self) -> &'static str {
+        "e1000"
+    }
+    fn compatible(&self) -> &'static [&'static str] {
+        &["pci8086,100e", "pci8086,100f", "pci8086,10d3"]
+    }
+    fn probe(&self, dev: &Device) -> bool {
+        // 只支持一个e1000设备
+        if E1000_REGS.load(Ordering::SeqCst) != 0 {
+            return false;
+        }
+        // BAR[0]是E1000的寄存器基址，由PCI扫描时分配
+        let regs = match pci::bar(dev.base, 0) {
+            Some(regs) => regs,
+            None => return false,
         };
-        // 分配tx和rx 的buffers
-        let tx_buf_pages = (TX_RING_SIZE * BUF_SIZE + PAGE_SIZE - 1) / PAGE_SIZE;
-        let rx_buf_pages = (RX_RING_SIZE * BUF_SIZE + PAGE_SIZE - 1) / PAGE_SIZE;
-
-        let mut tx_buf_addr = alloc_frames(&mut frames, tx_buf_pages);
-        for i in 0..tx_buf_pages {
-            tx_ring[i].buffer = tx_buf_addr as u64;
-            tx_buf_addr += BUF_SIZE;
+        pci::enable_device(dev.base);
+        e1000_init(regs);
+        register_irq(dev.irq, E1000_DRIVER.clone());
+        true
+    }
+    fn irq(&self, _irq: usize) {
+        E1000_NETWORK_DEV.handle_irq();
+    }
+    fn shutdown(&self) {
+        // 关闭所有中断，停止收发
+        write_reg(E1000_IMC, u32::MAX);
+        write_reg(E1000_RCTL, 0u32);
+        write_reg(E1000_TCTL, 0u32);
+    }
+}
+
+impl E1000Device {
+    pub fn new() -> Self {
+        let mut frames: Vec<ContiguousFrames> = Vec::new();
+        // 每个ring有256个16字节的desc，正好占用一个物理页
+        let tx_addr = alloc_dma(&mut frames, TX_RING_SIZE * size_of::<TxDesc>());
+        let rx_addr = alloc_dma(&mut frames, RX_RING_SIZE * size_of::<RxDesc>());
+        let tx_ring =
+            unsafe { core::slice::from_raw_parts_mut(tx_addr as *mut TxDesc, TX_RING_SIZE) };
+        let rx_ring =
+            unsafe { core::slice::from_raw_parts_mut(rx_addr as *mut RxDesc, RX_RING_SIZE) };
+        // 分配tx和rx的buffers，每个ring的buffer在一块物理地址连续的内存中
+        let tx_bufs = alloc_buffers(&mut frames, TX_RING_SIZE);
+        let rx_bufs = alloc_buffers(&mut frames, RX_RING_SIZE);
+        for i in 0..TX_RING_SIZE {
+            tx_ring[i] = TxDesc::new();
+            tx_ring[i].buffer = tx_bufs[i] as u64;
+            // 初始时所有tx desc都可用
+            tx_ring[i].status = E1000_TXD_STAT_DD;
         }
-        let mut rx_buf_addr = alloc_frames(&mut frames, rx_buf_pages);
-        for i in 0..rx_buf_pages {
-            tx_ring[i].buffer = rx_buf_addr as u64;
-            rx_buf_addr += BUF_SIZE;
+        for i in 0..RX_RING_SIZE {
+            rx_ring[i] = RxDesc::new();
+            rx_ring[i].buffer = rx_bufs[i] as u64;
         }
-
         return Self {
-            rx_ring: rx_ring,
-            tx_ring: tx_ring,
-            mbuf_size: BUF_SIZE,
-            frames: frames,
+            inner: Mutex::new(E1000Inner {
+                rx_ring: rx_ring,
+                tx_ring: tx_ring,
+                rx_bufs: rx_bufs,
+                tx_bufs: tx_bufs,
+                frames: frames,
+                mac: [0; 6],
+                id: 0,
+            }),
         };
     }
 
     pub fn init(&self) {
-        // disable interrupt
-        write_reg(E1000_IMS, 0);
+        let mut inner = self.inner.lock();
+        // 关闭中断，重置设备
+        write_reg(E1000_IMC, u32::MAX);
+        let ctl: u32 = read_reg(E1000_CTL);
+        write_reg(E1000_CTL, ctl | E1000_CTL_RST as u32);
+        while read_reg::<u32>(E1000_CTL) & E1000_CTL_RST as u32 != 0 {}
+        // 重置后中断会重新打开
+        write_reg(E1000_IMC, u32::MAX);
 
-        // Device RESET
-        let ctl: usize = read_reg(E1000_CTL);
-        write_reg(E1000_CTL, ctl | E1000_CTL_RST);
-        write_reg(E1000_IMS, 1);
+        // MAC地址保存在Receive Address寄存器RAL0、RAH0中
+        let ral: u32 = read_reg(E1000_RA);
+        let rah: u32 = read_reg(E1000_RA + 4);
+        inner.mac = [
+            ral as u8,
+            (ral >> 8) as u8,
+            (ral >> 16) as u8,
+            (ral >> 24) as u8,
+            rah as u8,
+            (rah >> 8) as u8,
+        ];
+        // 地址有效位
+        write_reg(E1000_RA + 4, rah | E1000_RAH_AV);
+        // 清空多播表
+        for i in 0..128 {
+            write_reg(E1000_MTA + i * 4, 0u32);
+        }
 
         // 初始化TxRing地址，长度
-        write_reg(E1000_TDBAL, &self.tx_ring as *const _ as u32);
+        write_reg(E1000_TDBAL, inner.tx_ring.as_ptr() as usize as u32);
+        write_reg(E1000_TDBAH, (inner.tx_ring.as_ptr() as usize >> 32) as u32);
         write_reg(
             E1000_TDLEN,
-            (self.tx_ring.len() * core::mem::size_of::<TxDesc>()) as u32,
+            (inner.tx_ring.len() * core::mem::size_of::<TxDesc>()) as u32,
         );
         write_reg(E1000_TDT, 0u32);
         write_reg(E1000_TDH, 0u32);
+        // 开启发送，填充短包，冲突阈值和距离使用手册推荐值
+        write_reg(
+            E1000_TCTL,
+            (E1000_TCTL_EN
+                | E1000_TCTL_PSP
+                | (0x10 << E1000_TCTL_CT_SHIFT)
+                | (0x40 << E1000_TCTL_COLD_SHIFT)) as u32,
+        );
+        write_reg(E1000_TIPG, (10 | (8 << 10) | (6 << 20)) as u32);
+
         // 初始化RxRing地址，长度
-        write_reg(E1000_RDBAL, &self.rx_ring as *const _ as u32);
+        write_reg(E1000_RDBAL, inner.rx_ring.as_ptr() as usize as u32);
+        write_reg(E1000_RDBAH, (inner.rx_ring.as_ptr() as usize >> 32) as u32);
         write_reg(
             E1000_RDLEN,
-            (self.rx_ring.len() * core::mem::size_of::<RxDesc>()) as u32,
+            (inner.rx_ring.len() * core::mem::size_of::<RxDesc>()) as u32,
         );
-        write_reg(E1000_RDT, 0u32);
+        // RDT指向最后一个可用desc，head和tail之间的desc都交给硬件
         write_reg(E1000_RDH, 0u32);
+        write_reg(E1000_RDT, (RX_RING_SIZE - 1) as u32);
+        // 开启接收，接收广播包，2048字节buffer，去掉CRC
+        write_reg(
+            E1000_RCTL,
+            (E1000_RCTL_EN | E1000_RCTL_BAM | E1000_RCTL_SZ_2048 | E1000_RCTL_SECRC) as u32,
+        );
+        // 每收到一个包就产生中断
+        write_reg(E1000_RDTR, 0u32);
+        write_reg(E1000_RADV, 0u32);
+
+        // Set Link Up
+        let ctl: u32 = read_reg(E1000_CTL);
+        write_reg(E1000_CTL, ctl | E1000_CTL_SLU as u32);
+        // 接收、接收队列溢出和链路状态变化中断
+        write_reg(
+            E1000_IMS,
+            E1000_ICR_RXT0 | E1000_ICR_RXO | E1000_ICR_RXDMT0 | E1000_ICR_LSC,
+        );
+        fence(Ordering::SeqCst);
+        let mac = inner.mac;
+        drop(inner);
+        kernel!(
+            "[e1000] initialized, mac: {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}, link: {}",
+            mac[0],
+            mac[1],
+            mac[2],
+            mac[3],
+            mac[4],
+            mac[5],
+            if self.link_up() { "up" } else { "down" }
+        );
+    }
+
+    // 中断处理，取出所有收到的帧交给协议栈
+    fn handle_irq(&self) {
+        // 读ICR清除中断
+        let icr: u32 = read_reg(E1000_ICR);
+        if icr & E1000_ICR_LSC != 0 {
+            kernel!("[e1000] link {}", if self.link_up() { "up" } else { "down" });
+        }
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let mut inner = self.inner.lock();
+        loop {
+            // RDT之后的第一个desc是下一个硬件写完的desc
+            let idx = (read_reg::<u32>(E1000_RDT) as usize + 1) % RX_RING_SIZE;
+            let status = RxDescStatus::from_bits_truncate(inner.rx_ring[idx].status);
+            if !status.contains(RxDescStatus::DD) {
+                break;
+            }
+            // 只处理单个buffer能装下的帧，有错误的帧直接丢弃
+            if status.contains(RxDescStatus::EOP) && inner.rx_ring[idx].error == 0 {
+                let len = inner.rx_ring[idx].length as usize;
+                let data =
+                    unsafe { core::slice::from_raw_parts(inner.rx_bufs[idx] as *const u8, len) };
+                frames.push(net_buffer(data));
+            }
+            inner.rx_ring[idx].status = 0;
+            fence(Ordering::SeqCst);
+            // 把desc还给硬件
+            write_reg(E1000_RDT, idx as u32);
+        }
+        let id = inner.id;
+        drop(inner);
+        for frame in frames {
+            receive(id, frame.as_slice());
+        }
+    }
+}
+
+impl NetDevice for E1000Device {
+    fn name(&self) -> &'static str {
+        "e1000"
+    }
+
+    fn mac_addr(&self) -> [u8; 6] {
+        self.inner.lock().mac
+    }
+
+    fn send(&self, frame: &[u8]) -> bool {
+        if frame.len() > DATA_MAX {
+            return false;
+        }
+        let mut inner = self.inner.lock();
+        let idx = read_reg::<u32>(E1000_TDT) as usize;
+        // DD未置位说明硬件还没有发送完该desc，发送队列已满
+        if inner.tx_ring[idx].status & E1000_TXD_STAT_DD == 0 {
+            return false;
+        }
+        let buf =
+            unsafe { core::slice::from_raw_parts_mut(inner.tx_bufs[idx] as *mut u8, BUF_SIZE) };
+        buf[0..frame.len()].copy_from_slice(frame);
+        let desc = &mut inner.tx_ring[idx];
+        desc.length = frame.len() as u16;
+        desc.cmd = (E1000_TXD_CMD_EOP | E1000_TXD_CMD_RS) as u8;
+        desc.status = 0;
+        fence(Ordering::SeqCst);
+        write_reg(E1000_TDT, ((idx + 1) % TX_RING_SIZE) as u32);
+        true
+    }
 
-        //todo more initialization
+    fn link_up(&self) -> bool {
+        read_reg::<u32>(E1000_STATUS) & E1000_STATUS_LU != 0
     }
 }
 
-fn alloc_frames(frames: &mut BTreeMap<usize, Frame>, pages: usize) -> usize {
-    let mut first_page: Option<usize> = None;
-    for _ in 0..pages {
-        let frame = alloc().unwrap();
-        if let None = first_page {
-            first_page = Some(frame.ppn.base_addr())
+impl RxDesc {
+    fn new() -> Self {
+        Self {
+            buffer: 0,
+            length: 0,
+            checksum: 0,
+            status: 0,
+            error: 0,
+            special: 0,
         }
-        frames.insert(frame.ppn.0, frame);
     }
-    return first_page.unwrap();
+}
+
+impl TxDesc {
+    fn new() -> Self {
+        Self {
+            buffer: 0,
+            length: 0,
+            checksum_offset: 0,
+            cmd: 0,
+            status: 0,
+            checksum_start: 0,
+            special: 0,
+        }
+    }
+}
+
+// 分配size字节物理地址连续的内存，返回起始地址
+fn alloc_dma(frames: &mut Vec<ContiguousFrames>, size: usize) -> usize {
+    let block = alloc_contiguous((size + PAGE_SIZE - 1) / PAGE_SIZE).unwrap();
+    let addr = block.base_addr();
+    frames.push(block);
+    return addr;
+}
+
+// 分配count个BUF_SIZE大小的buffer，返回每个buffer的地址
+fn alloc_buffers(frames: &mut Vec<ContiguousFrames>, count: usize) -> Vec<usize> {
+    let base = alloc_dma(frames, count * BUF_SIZE);
+    return (0..count).map(|i| base + i * BUF_SIZE).collect();
 }
 
 fn write_reg<T: Sized>(offset: usize, val: T) {
     unsafe {
-        core::ptr::write_volatile((E1000_REGS + offset) as *mut T, val);
+        core::ptr::write_volatile((E1000_REGS.load(Ordering::Relaxed) + offset) as *mut T, val);
     }
 }
 
 fn read_reg<T: Sized>(offset: usize) -> T {
-    unsafe { core::ptr::read_volatile((E1000_REGS + offset) as *const _) }
+    unsafe { core::ptr::read_volatile((E1000_REGS.load(Ordering::Relaxed) + offset) as *const _) }
 }
 
 const RX_DESC_SPECIAL_OTHER: u16 = 0;
@@ -179,8 +385,10 @@ const RX_RING_SIZE: usize = 256;
 const BUF_SIZE: usize = 2048;
 
 const E1000_CTL: usize = 0x00000;
+const E1000_STATUS: usize = 0x00008;
 const E1000_ICR: usize = 0x000C0;
 const E1000_IMS: usize = 0x000D0;
+const E1000_IMC: usize = 0x000D8;
 const E1000_RCTL: usize = 0x00100;
 const E1000_TCTL: usize = 0x00400;
 const E1000_TIPG: usize = 0x00410;
@@ -193,12 +401,26 @@ const E1000_RDT: usize = 0x02818;
 const E1000_RDLEN: usize = 0x02808;
 const E1000_RSRPD: usize = 0x02C00;
 const E1000_TDBAL: usize = 0x03800;
+const E1000_TDBAH: usize = 0x03804;
 const E1000_TDLEN: usize = 0x03808;
 const E1000_TDH: usize = 0x03810;
 const E1000_TDT: usize = 0x03818;
 const E1000_MTA: usize = 0x05200;
 const E1000_RA: usize = 0x05400;
 
+/* Receive Address High */
+const E1000_RAH_AV: u32 = 1 << 31; /* address valid */
+
+/* Device Status */
+const E1000_STATUS_LU: u32 = 0x00000002; /* link up */
+
+/* Interrupt Cause */
+const E1000_ICR_TXDW: u32 = 0x00000001; /* transmit desc written back */
+const E1000_ICR_LSC: u32 = 0x00000004; /* link status change */
+const E1000_ICR_RXDMT0: u32 = 0x00000010; /* rx desc min threshold */
+const E1000_ICR_RXO: u32 = 0x00000040; /* rx overrun */
+const E1000_ICR_RXT0: u32 = 0x00000080; /* rx timer intr */
+
 /* Device Control */
 const E1000_CTL_SLU: usize = 0x00000040;
 const E1000_CTL_FRCSPD: usize = 0x00000800;
@@ -0,0 +1,68 @@
+use super::{receive, register_net_device, NetDevice};
+use crate::net::net_buffer;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+pub const LOOPBACK_NAME: &str = "lo";
+// 队列中最多缓存的帧
+const MAX_QUEUED_FRAMES: usize = 256;
+// 每次poll最多投递的帧，避免协议栈互相回复时一直占用cpu
+const MAX_DELIVER_FRAMES: usize = 64;
+
+// 回环网卡，发送的帧放入队列，poll时交给协议栈的接收路径
+// 不能在send中直接调用接收回调：发送方可能持有协议栈的锁（如TCP连接）
+pub struct Loopback {
+    queue: Mutex<VecDeque<Vec<u8>>>,
+}
+
+lazy_static! {
+    static ref LOOPBACK: Mutex<Option<(usize, Arc<Loopback>)>> = Mutex::new(None);
+}
+
+impl NetDevice for Loopback {
+    fn name(&self) -> &'static str {
+        LOOPBACK_NAME
+    }
+    fn mac_addr(&self) -> [u8; 6] {
+        [0; 6]
+    }
+    fn send(&self, frame: &[u8]) -> bool {
+        let mut queue = self.queue.lock();
+        if queue.len() >= MAX_QUEUED_FRAMES {
+            return false;
+        }
+        queue.push_back(net_buffer(frame));
+        return true;
+    }
+    fn link_up(&self) -> bool {
+        true
+    }
+}
+
+// 注册回环网卡，返回网卡编号
+pub fn init() -> usize {
+    let dev = Arc::new(Loopback {
+        queue: Mutex::new(VecDeque::new()),
+    });
+    let id = register_net_device(dev.clone());
+    *LOOPBACK.lock() = Some((id, dev));
+    return id;
+}
+
+// 把队列中的帧交给协议栈
+pub fn poll() {
+    let (id, dev) = match LOOPBACK.lock().as_ref() {
+        Some((id, dev)) => (*id, Arc::clone(dev)),
+        None => return,
+    };
+    for _ in 0..MAX_DELIVER_FRAMES {
+        let frame = match dev.queue.lock().pop_front() {
+            Some(frame) => frame,
+            None => break,
+        };
+        receive(id, &frame);
+    }
+}
@@ -1 +1,54 @@
 pub mod e1000;
+pub mod loopback;
+pub mod virtio_net;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// 网卡设备接口，协议栈通过该接口收发以太网帧，不关心具体的网卡类型
+pub trait NetDevice: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn mac_addr(&self) -> [u8; 6];
+    // 发送一个以太网帧，发送队列已满或帧过长时返回false
+    fn send(&self, frame: &[u8]) -> bool;
+    // 链路是否连通
+    fn link_up(&self) -> bool;
+}
+
+// 协议栈的接收回调，参数为网卡编号和收到的以太网帧
+pub type RxHook = fn(usize, &[u8]);
+
+lazy_static! {
+    static ref NET_DEVICES: Mutex<Vec<Arc<dyn NetDevice>>> = Mutex::new(Vec::new());
+    static ref RX_HOOK: Mutex<Option<RxHook>> = Mutex::new(None);
+}
+
+// 注册网卡，返回网卡编号
+pub fn register_net_device(dev: Arc<dyn NetDevice>) -> usize {
+    let mut devices = NET_DEVICES.lock();
+    devices.push(dev);
+    return devices.len() - 1;
+}
+
+pub fn net_device(id: usize) -> Option<Arc<dyn NetDevice>> {
+    NET_DEVICES.lock().get(id).map(|dev| Arc::clone(dev))
+}
+
+pub fn net_devices() -> Vec<Arc<dyn NetDevice>> {
+    NET_DEVICES.lock().iter().map(|dev| Arc::clone(dev)).collect()
+}
+
+pub fn set_rx_hook(hook: RxHook) {
+    *RX_HOOK.lock() = Some(hook);
+}
+
+// 网卡驱动收到帧后调用，没有协议栈时丢弃
+pub fn receive(id: usize, frame: &[u8]) {
+    let hook = *RX_HOOK.lock();
+    match hook {
+        Some(hook) => hook(id, frame),
+        None => debug!("[net] dev{} dropped frame, len: {}", id, frame.len()),
+    }
+}
@@ -0,0 +1,264 @@
+use super::{receive, register_net_device, NetDevice};
+use crate::config::PAGE_SIZE;
+use crate::driver::device::{register_irq, Device, Driver};
+use crate::driver::virtio::*;
+use crate::mem::allocator::{alloc_contiguous, ContiguousFrames};
+use crate::net::net_buffer;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// virtio-net驱动，see: virtio-v1.1.pdf，5.1
+
+// device feature bits
+const VIRTIO_NET_F_MAC: u8 = 5;
+const VIRTIO_NET_F_STATUS: u8 = 16;
+
+// config space: mac[6], status u16
+const VIRTIO_NET_CONFIG_MAC: usize = 0;
+const VIRTIO_NET_CONFIG_STATUS: usize = 6;
+const VIRTIO_NET_S_LINK_UP: u16 = 1;
+
+const RX_QUEUE: u32 = 0;
+const TX_QUEUE: u32 = 1;
+
+// VERSION_1下每个帧前都有12字节的virtio_net_hdr
+const NET_HDR_SIZE: usize = 12;
+const BUF_SIZE: usize = 2048;
+// 以太网帧的最大长度（不包含CRC）
+const FRAME_MAX: usize = 1514;
+
+pub struct VirtIONet {
+    base: usize,
+    irq: usize,
+    features: u64,
+    mac: [u8; 6],
+    rx: Mutex<RxQueue>,
+    tx: Mutex<TxQueue>,
+    id: Mutex<usize>, // 网卡编号
+}
+
+// VirtQueue按页对齐，放在堆上避免占用过多的栈空间
+struct RxQueue {
+    queue: Box<VirtQueue>,
+    bufs: [usize; QUEUE_SIZE], // desc id -> 放入该desc的buffer
+}
+
+struct TxQueue {
+    queue: Box<VirtQueue>,
+    free_bufs: Vec<usize>,
+    inflight: [usize; QUEUE_SIZE], // desc id -> 正在发送的buffer
+}
+
+lazy_static! {
+    static ref VIRTIO_NETS: Mutex<Vec<Arc<VirtIONet>>> = Mutex::new(Vec::new());
+    // buffer占用的物理页
+    static ref NET_BUF_FRAMES: Mutex<Vec<ContiguousFrames>> = Mutex::new(Vec::new());
+    pub static ref VIRTIO_NET_DRIVER: Arc<VirtIONetDriver> = Arc::new(VirtIONetDriver);
+}
+
+pub struct VirtIONetDriver;
+
+impl Driver for VirtIONetDriver {
+    fn name(&self) -> &'static str {
+        "virtio-net"
+    }
+    fn compatible(&self) -> &'static [&'static str] {
+        &["virtio,device1"]
+    }
+    fn probe(&self, dev: &Device) -> bool {
+        let net = match unsafe { VirtIONet::new(dev.base, dev.irq) } {
+            Some(net) => Arc::new(net),
+            None => return false,
+        };
+        // queue的地址已经交给设备，必须在放入Arc之后再初始化
+        unsafe {
+            net.init();
+        }
+        let id = register_net_device(net.clone());
+        *net.id.lock() = id;
+        VIRTIO_NETS.lock().push(net);
+        register_irq(dev.irq, VIRTIO_NET_DRIVER.clone());
+        true
+    }
+    fn irq(&self, irq: usize) {
+        let nets: Vec<Arc<VirtIONet>> = VIRTIO_NETS
+            .lock()
+            .iter()
+            .filter(|net| net.irq == irq)
+            .map(|net| net.clone())
+            .collect();
+        for net in nets {
+            net.handle_irq();
+        }
+    }
+    fn shutdown(&self) {
+        for net in VIRTIO_NETS.lock().iter() {
+            // 重置设备，停止DMA
+            unsafe {
+                write(net.base, VIRTIO_MMIO_STATUS, 0);
+            }
+        }
+    }
+}
+
+impl VirtIONet {
+    // 协商features，读取MAC地址
+    unsafe fn new(base: usize, irq: usize) -> Option<Self> {
+        let mut accepted = 0;
+        let ok = negotiate(base, VIRTIO_DEVICE_NETWORK, |features| {
+            // 只使用MAC和STATUS，不使用checksum offload和合并buffer
+            accepted = features
+                & ((1u64 << VIRTIO_NET_F_MAC)
+                    | (1u64 << VIRTIO_NET_F_STATUS)
+                    | (1u64 << VIRTIO_F_VERSION_1));
+            accepted
+        });
+        if !ok {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        if accepted & (1u64 << VIRTIO_NET_F_MAC) != 0 {
+            for i in 0..6 {
+                mac[i] = read_config::<u8>(base, VIRTIO_NET_CONFIG_MAC + i);
+            }
+        }
+        return Some(Self {
+            base: base,
+            irq: irq,
+            features: accepted,
+            mac: mac,
+            rx: Mutex::new(RxQueue {
+                queue: Box::new(VirtQueue::new(base)),
+                bufs: [0; QUEUE_SIZE],
+            }),
+            tx: Mutex::new(TxQueue {
+                queue: Box::new(VirtQueue::new(base)),
+                free_bufs: alloc_buffers(QUEUE_SIZE),
+                inflight: [0; QUEUE_SIZE],
+            }),
+            id: Mutex::new(0),
+        });
+    }
+
+    unsafe fn init(&self) {
+        let mut rx = self.rx.lock();
+        rx.queue.setup(RX_QUEUE);
+        self.tx.lock().queue.setup(TX_QUEUE);
+        // 把所有rx buffer交给设备
+        for buf in alloc_buffers(QUEUE_SIZE) {
+            let data = core::slice::from_raw_parts(buf as *const u8, BUF_SIZE);
+            let head = rx.queue.add(&[data], &[true]).unwrap();
+            rx.bufs[head as usize] = buf;
+        }
+        driver_ok(self.base);
+        rx.queue.notify(RX_QUEUE);
+        drop(rx);
+        kernel!(
+            "[virtio-net] initialized at {:#x}, mac: {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}, link: {}",
+            self.base,
+            self.mac[0],
+            self.mac[1],
+            self.mac[2],
+            self.mac[3],
+            self.mac[4],
+            self.mac[5],
+            if self.link_up() { "up" } else { "down" }
+        );
+    }
+
+    // 中断处理，取出收到的帧交给协议栈，并回收发送完的buffer
+    fn handle_irq(&self) {
+        unsafe {
+            ack_interrupt(self.base);
+        }
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let mut rx = self.rx.lock();
+        while let Some((id, len)) = rx.queue.pop_used() {
+            let buf = rx.bufs[id as usize];
+            let len = len as usize;
+            if len > NET_HDR_SIZE {
+                let data = unsafe {
+                    core::slice::from_raw_parts((buf + NET_HDR_SIZE) as *const u8, len - NET_HDR_SIZE)
+                };
+                frames.push(net_buffer(data));
+            }
+            // buffer重新放回rx queue
+            unsafe {
+                let data = core::slice::from_raw_parts(buf as *const u8, BUF_SIZE);
+                let head = rx.queue.add(&[data], &[true]).unwrap();
+                rx.bufs[head as usize] = buf;
+            }
+        }
+        unsafe {
+            rx.queue.notify(RX_QUEUE);
+        }
+        drop(rx);
+        self.tx.lock().recycle();
+        let id = *self.id.lock();
+        for frame in frames {
+            receive(id, frame.as_slice());
+        }
+    }
+}
+
+impl TxQueue {
+    // 回收设备已经发送完的buffer
+    fn recycle(&mut self) {
+        while let Some((id, _)) = self.queue.pop_used() {
+            let buf = self.inflight[id as usize];
+            self.free_bufs.push(buf);
+        }
+    }
+}
+
+impl NetDevice for VirtIONet {
+    fn name(&self) -> &'static str {
+        "virtio-net"
+    }
+
+    fn mac_addr(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    fn send(&self, frame: &[u8]) -> bool {
+        if frame.len() > FRAME_MAX {
+            return false;
+        }
+        let mut tx = self.tx.lock();
+        tx.recycle();
+        let buf = match tx.free_bufs.pop() {
+            Some(buf) => buf,
+            None => return false,
+        };
+        let len = NET_HDR_SIZE + frame.len();
+        let data = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        // virtio_net_hdr全部为0，不使用offload
+        data[0..NET_HDR_SIZE].fill(0);
+        data[NET_HDR_SIZE..].copy_from_slice(frame);
+        unsafe {
+            let head = tx.queue.add(&[&data[..]], &[false]).unwrap();
+            tx.inflight[head as usize] = buf;
+            tx.queue.notify(TX_QUEUE);
+        }
+        true
+    }
+
+    fn link_up(&self) -> bool {
+        if self.features & (1u64 << VIRTIO_NET_F_STATUS) == 0 {
+            return true;
+        }
+        let status: u16 = unsafe { read_config(self.base, VIRTIO_NET_CONFIG_STATUS) };
+        status & VIRTIO_NET_S_LINK_UP != 0
+    }
+}
+
+// 分配count个BUF_SIZE大小的buffer，所有buffer在一块物理地址连续的内存中
+fn alloc_buffers(count: usize) -> Vec<usize> {
+    let block = alloc_contiguous((count * BUF_SIZE + PAGE_SIZE - 1) / PAGE_SIZE).unwrap();
+    let base = block.base_addr();
+    NET_BUF_FRAMES.lock().push(block);
+    return (0..count).map(|i| base + i * BUF_SIZE).collect();
+}
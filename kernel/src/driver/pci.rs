@@ -1,104 +1,377 @@
-use super::net::e1000::e1000_init;
-use crate::arch::riscv::qemu::layout::{E1000_REGS, PCIE0};
-use core::mem::size_of;
-use core::ptr;
+use super::device::{add_device, Bus, Device};
+use crate::arch::riscv::qemu::layout::{board, MmioRegion};
+use crate::config::PAGE_SIZE;
+use crate::mem::kernel::map_mmio;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
 use core::sync::atomic::{fence, Ordering};
 
+// PCI配置空间，see: PCI Local Bus Specification 3.0, 6.1
+const PCI_VENDOR_ID: usize = 0x00;
+const PCI_DEVICE_ID: usize = 0x02;
+const PCI_COMMAND: usize = 0x04;
+const PCI_STATUS: usize = 0x06;
+const PCI_CLASS_REVISION: usize = 0x08;
+const PCI_HEADER_TYPE: usize = 0x0e;
+const PCI_BAR0: usize = 0x10;
+const PCI_CAPABILITY_LIST: usize = 0x34;
+const PCI_INTERRUPT_LINE: usize = 0x3c;
+const PCI_INTERRUPT_PIN: usize = 0x3d;
+
+// PCI-to-PCI bridge配置空间（type 1 header）
+const PCI_PRIMARY_BUS: usize = 0x18;
+const PCI_SECONDARY_BUS: usize = 0x19;
+const PCI_SUBORDINATE_BUS: usize = 0x1a;
+const PCI_IO_BASE: usize = 0x1c;
+const PCI_IO_LIMIT: usize = 0x1d;
+const PCI_MEMORY_BASE: usize = 0x20;
+const PCI_MEMORY_LIMIT: usize = 0x22;
+const PCI_PREF_MEMORY_BASE: usize = 0x24;
+const PCI_PREF_MEMORY_LIMIT: usize = 0x26;
+const PCI_PREF_BASE_UPPER32: usize = 0x28;
+const PCI_PREF_LIMIT_UPPER32: usize = 0x2c;
+
+const PCI_COMMAND_IO: u16 = 1 << 0;
+const PCI_COMMAND_MEMORY: u16 = 1 << 1;
+const PCI_COMMAND_MASTER: u16 = 1 << 2;
+const PCI_STATUS_CAP_LIST: u16 = 1 << 4;
+
 const PCI_HEADER_TYPE_EP: u8 = 0;
 const PCI_HEADER_TYPE_BRIDGE: u8 = 1;
+const PCI_HEADER_TYPE_MULTI_FUNC: u8 = 0x80;
+
+const PCI_BAR_IO: u32 = 1 << 0;
+const PCI_BAR_TYPE_64: u32 = 0b10 << 1;
+const PCI_BAR_PREFETCH: u32 = 1 << 3;
 
-#[derive(Clone, Debug)]
-#[repr(C)]
-struct PCIHeader {
-    vendor: u16,
-    device: u16,
-    command: u16,
-    status: u16,
-    revision_and_class: u32,
-    cache_line_size: u8,
-    latency_timer: u8,
-    header_type: u8,
-    bist: u8,
+// capability id
+pub const PCI_CAP_PM: u8 = 0x01;
+pub const PCI_CAP_MSI: u8 = 0x05;
+pub const PCI_CAP_VENDOR: u8 = 0x09;
+pub const PCI_CAP_EXP: u8 = 0x10;
+pub const PCI_CAP_MSIX: u8 = 0x11;
+
+// bridge的memory窗口以1MiB为单位
+const BRIDGE_WINDOW_ALIGN: usize = 1 << 20;
+// 一条总线在ECAM中占用1MiB配置空间
+const ECAM_BUS_SIZE: usize = 1 << 20;
+
+// 从host bridge的地址范围中分配BAR地址
+struct MmioWindow {
+    next: usize,
+    end: usize,
 }
 
-#[derive(Debug, Clone)]
-#[repr(C)]
-struct PCIEndpointHeader {
-    shared: PCIHeader,
-    base_address_registers: [u32; 6],
-    card_bus_cis_ptr: u32,
-    sub_vendor: u16,
-    sub_id: u16,
-    expansion_rom_base_addr: u32,
-    capability_ptr: u8,
-    reserved: [u8; 7],
-    interrupt_line: u8,
-    interrupt_pin: u8,
-    min_gnt: u8,
-    max_lat: u8,
+impl MmioWindow {
+    fn new(region: MmioRegion) -> Self {
+        Self {
+            next: region.base,
+            end: region.base + region.size,
+        }
+    }
+
+    fn alloc(&mut self, size: usize, align: usize) -> Option<usize> {
+        let addr = (self.next + align - 1) & !(align - 1);
+        if addr + size > self.end {
+            return None;
+        }
+        self.next = addr + size;
+        return Some(addr);
+    }
+
+    fn align(&mut self, align: usize) {
+        self.next = ((self.next + align - 1) & !(align - 1)).min(self.end);
+    }
 }
 
-#[derive(Debug, Clone)]
-#[repr(C)]
-struct PCIBridgeHeader {
-    shared: PCIHeader,
+// 递归扫描总线，为设备分配总线号、BAR和中断
+struct Scanner {
+    mem32: MmioWindow,
+    mem64: MmioWindow,
+    next_bus: usize,
+    max_bus: usize,
+    bridges: Vec<u32>, // 从根总线到当前总线经过的bridge的device号
 }
 
-// 扫描pci总线，扫描到可用设备后对设备驱动初始化
+// 扫描pci总线，扫描到的设备加入设备表，由驱动probe
 pub fn scan_pci_bus() {
-    for dev in 0..32 {
-        let off = bdf(0, dev, 0) as usize;
-        let base = PCIE0 + off * 4;
-        let vendor: u16 = read(base + 0x0);
-        let device: u16 = read(base + 0x2);
-        // 无效设备
-        if vendor == 0xffff || device == 0xffff {
-            continue;
+    let board = board();
+    let mut scanner = Scanner {
+        mem32: MmioWindow::new(board.pcie_mmio),
+        mem64: MmioWindow::new(board.pcie_mmio64),
+        next_bus: 1,
+        max_bus: (board.pcie_ecam.size / ECAM_BUS_SIZE).min(256),
+        bridges: Vec::new(),
+    };
+    scanner.scan_bus(0);
+}
+
+impl Scanner {
+    fn scan_bus(&mut self, bus: usize) {
+        for dev in 0..32 {
+            for func in 0..8 {
+                let cfg = cfg_addr(bus, dev, func);
+                let vendor: u16 = read(cfg + PCI_VENDOR_ID);
+                // 无效设备
+                if vendor == 0xffff {
+                    if func == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                let header_type: u8 = read(cfg + PCI_HEADER_TYPE);
+                match header_type & !PCI_HEADER_TYPE_MULTI_FUNC {
+                    PCI_HEADER_TYPE_EP => self.init_endpoint(bus, dev, func, cfg),
+                    PCI_HEADER_TYPE_BRIDGE => self.init_bridge(bus, dev, func, cfg),
+                    _ => {}
+                }
+                // 非多功能设备只有function 0
+                if func == 0 && header_type & PCI_HEADER_TYPE_MULTI_FUNC == 0 {
+                    break;
+                }
+            }
         }
-        let header_type: u8 = read(base + 96 + 16);
-        // 暂时不处理桥接设备
-        if header_type == PCI_HEADER_TYPE_BRIDGE {
-            continue;
+    }
+
+    fn init_endpoint(&mut self, bus: usize, dev: usize, func: usize, cfg: usize) {
+        let vendor: u16 = read(cfg + PCI_VENDOR_ID);
+        let device: u16 = read(cfg + PCI_DEVICE_ID);
+        let class: u32 = read(cfg + PCI_CLASS_REVISION);
+        let name = format!("pci {:02x}:{:02x}.{}", bus, dev, func);
+        kernel!(
+            "[PCI] {} vendor: {:#x}, id: {:#x}, class: {:#x}, caps: [{}]",
+            name,
+            vendor,
+            device,
+            class >> 8,
+            capability_names(cfg)
+        );
+        // 分配BAR时关闭设备的地址译码
+        write::<u16>(cfg + PCI_COMMAND, 0);
+        self.assign_bars(cfg, 6);
+        let pin: u8 = read(cfg + PCI_INTERRUPT_PIN);
+        let irq = if pin != 0 {
+            self.route_intx(dev as u32, pin as u32)
+        } else {
+            0
+        };
+        write::<u8>(cfg + PCI_INTERRUPT_LINE, irq as u8);
+        write::<u16>(
+            cfg + PCI_COMMAND,
+            PCI_COMMAND_IO | PCI_COMMAND_MEMORY | PCI_COMMAND_MASTER,
+        );
+        fence(Ordering::SeqCst);
+        add_device(Device {
+            name: name,
+            compatible: format!("pci{:x},{:x}", vendor, device),
+            bus: Bus::Pci,
+            base: cfg,
+            irq: irq,
+        });
+    }
+
+    fn init_bridge(&mut self, bus: usize, dev: usize, func: usize, cfg: usize) {
+        if self.next_bus >= self.max_bus {
+            error!("[PCI] no bus number left for bridge {:02x}:{:02x}.{}", bus, dev, func);
+            return;
         }
-        let header: PCIEndpointHeader = read(base);
+        write::<u16>(cfg + PCI_COMMAND, 0);
+        self.assign_bars(cfg, 2);
+        let secondary = self.next_bus;
+        self.next_bus += 1;
+        write::<u8>(cfg + PCI_PRIMARY_BUS, bus as u8);
+        write::<u8>(cfg + PCI_SECONDARY_BUS, secondary as u8);
+        // 扫描完子总线之前，先允许转发所有总线号的配置请求
+        write::<u8>(cfg + PCI_SUBORDINATE_BUS, 0xff);
+
+        self.mem32.align(BRIDGE_WINDOW_ALIGN);
+        self.mem64.align(BRIDGE_WINDOW_ALIGN);
+        let (mem_base, pref_base) = (self.mem32.next, self.mem64.next);
+        self.bridges.push(dev as u32);
+        self.scan_bus(secondary);
+        self.bridges.pop();
+        self.mem32.align(BRIDGE_WINDOW_ALIGN);
+        self.mem64.align(BRIDGE_WINDOW_ALIGN);
+        let (mem_limit, pref_limit) = (self.mem32.next, self.mem64.next);
+        let subordinate = self.next_bus - 1;
+        write::<u8>(cfg + PCI_SUBORDINATE_BUS, subordinate as u8);
+
+        // 关闭io窗口，base大于limit
+        write::<u8>(cfg + PCI_IO_BASE, 0xf0);
+        write::<u8>(cfg + PCI_IO_LIMIT, 0);
+        // memory窗口：寄存器的15:4位对应地址的31:20位
+        if mem_limit > mem_base {
+            write::<u16>(cfg + PCI_MEMORY_BASE, ((mem_base >> 16) & 0xfff0) as u16);
+            write::<u16>(cfg + PCI_MEMORY_LIMIT, (((mem_limit - 1) >> 16) & 0xfff0) as u16);
+        } else {
+            write::<u16>(cfg + PCI_MEMORY_BASE, 0xfff0);
+            write::<u16>(cfg + PCI_MEMORY_LIMIT, 0);
+        }
+        // 64位prefetchable窗口，低4位为1表示64位地址
+        if pref_limit > pref_base {
+            write::<u16>(cfg + PCI_PREF_MEMORY_BASE, (((pref_base >> 16) & 0xfff0) | 1) as u16);
+            write::<u16>(
+                cfg + PCI_PREF_MEMORY_LIMIT,
+                ((((pref_limit - 1) >> 16) & 0xfff0) | 1) as u16,
+            );
+            write::<u32>(cfg + PCI_PREF_BASE_UPPER32, (pref_base >> 32) as u32);
+            write::<u32>(cfg + PCI_PREF_LIMIT_UPPER32, ((pref_limit - 1) >> 32) as u32);
+        } else {
+            write::<u16>(cfg + PCI_PREF_MEMORY_BASE, 0xfff0);
+            write::<u16>(cfg + PCI_PREF_MEMORY_LIMIT, 0);
+            write::<u32>(cfg + PCI_PREF_BASE_UPPER32, 0);
+            write::<u32>(cfg + PCI_PREF_LIMIT_UPPER32, 0);
+        }
+        write::<u16>(cfg + PCI_COMMAND, PCI_COMMAND_MEMORY | PCI_COMMAND_MASTER);
+        fence(Ordering::SeqCst);
         kernel!(
-            "[PCI Bus0] found device, vendor: {:#x}, id: {:#x}, interrupt pin: {}, cap: {:#x}",
-            header.shared.vendor,
-            header.shared.device,
-            header.interrupt_pin,
-            header.capability_ptr,
+            "[PCI] bridge {:02x}:{:02x}.{}, bus [{}, {}], mem [{:#x}, {:#x}), pref [{:#x}, {:#x})",
+            bus,
+            dev,
+            func,
+            secondary,
+            subordinate,
+            mem_base,
+            mem_limit,
+            pref_base,
+            pref_limit
         );
+    }
+
+    // 探测BAR大小并分配地址，io BAR暂不分配
+    fn assign_bars(&mut self, cfg: usize, count: usize) {
+        let mut i = 0;
+        while i < count {
+            let off = cfg + PCI_BAR0 + i * 4;
+            let orig: u32 = read(off);
+            write::<u32>(off, 0xffff_ffff);
+            let mask: u32 = read(off);
+            write::<u32>(off, orig);
+            let is_64 = orig & PCI_BAR_IO == 0 && orig & (0b11 << 1) == PCI_BAR_TYPE_64;
+            if mask == 0 || orig & PCI_BAR_IO != 0 {
+                i += 1;
+                continue;
+            }
+            // 写全1后读回的值为地址掩码，取反加一得到BAR大小
+            let mut size_mask = (mask & !0xf) as u64;
+            if is_64 {
+                let orig_hi: u32 = read(off + 4);
+                write::<u32>(off + 4, 0xffff_ffff);
+                let mask_hi: u32 = read(off + 4);
+                write::<u32>(off + 4, orig_hi);
+                size_mask |= (mask_hi as u64) << 32;
+            } else {
+                size_mask |= 0xffff_ffff_0000_0000;
+            }
+            let size = (!size_mask).wrapping_add(1) as usize;
+            // 64位prefetchable BAR优先放在64位窗口，其他BAR放在32位窗口
+            let align = size.max(PAGE_SIZE);
+            let addr = if is_64 && orig & PCI_BAR_PREFETCH != 0 {
+                self.mem64
+                    .alloc(size, align)
+                    .or_else(|| self.mem32.alloc(size, align))
+            } else {
+                self.mem32.alloc(size, align)
+            };
+            match addr {
+                Some(addr) => {
+                    write::<u32>(off, (addr as u32 & !0xf) | (orig & 0xf));
+                    if is_64 {
+                        write::<u32>(off + 4, (addr >> 32) as u32);
+                    }
+                    map_mmio(addr, size);
+                    debug!("[PCI] BAR{} at {:#x}, size: {:#x}", i, addr, size);
+                }
+                None => error!("[PCI] no mmio space for BAR{}, size: {:#x}", i, size),
+            }
+            i += if is_64 { 2 } else { 1 };
+        }
+    }
 
-        init_device(vendor, device, base);
+    // 经过每一级bridge时按照设备号旋转INTx引脚，最终在根总线上查找设备树的中断路由
+    fn route_intx(&self, dev: u32, pin: u32) -> usize {
+        let (mut dev, mut pin) = (dev, pin);
+        for bridge_dev in self.bridges.iter().rev() {
+            pin = ((pin - 1 + dev) % 4) + 1;
+            dev = *bridge_dev;
+        }
+        return board().pci_intx_irq(dev, pin);
     }
 }
 
-fn init_device(vendor: u16, device: u16, base: usize) {
-    match (vendor, device) {
-        // e1000 device
-        (0x8086, 0x100e) | (0x8086, 0x100f) | (0x8086, 0x10d3) => {
-            unsafe {
-                // write cmd, io space/mem space/mastering
-                ptr::write_volatile((base + size_of::<u32>()) as *mut u16, 7);
-                fence(Ordering::SeqCst);
-                for i in 0..6 {
-                    let addr = base + (4 + i) * size_of::<u32>();
-                    let val = ptr::read_volatile(addr as *const u32);
-                    ptr::write_volatile(addr as *mut u32, 0xffffffff);
-                    fence(Ordering::SeqCst);
-                    ptr::write_volatile(addr as *mut u32, val);
-                }
-                // BAR[0]设置成E1000的各个寄存器基址
-                ptr::write_volatile((base + 4 * size_of::<u32>()) as *mut u32, E1000_REGS as u32);
-                e1000_init();
+// 设备第i个memory BAR分配到的地址，io BAR或未分配返回None
+pub fn bar(cfg: usize, i: usize) -> Option<usize> {
+    let low: u32 = read(cfg + PCI_BAR0 + i * 4);
+    if low & PCI_BAR_IO != 0 {
+        return None;
+    }
+    let mut addr = (low & !0xf) as usize;
+    if low & (0b11 << 1) == PCI_BAR_TYPE_64 {
+        let high: u32 = read(cfg + PCI_BAR0 + (i + 1) * 4);
+        addr |= (high as usize) << 32;
+    }
+    if addr == 0 {
+        return None;
+    }
+    return Some(addr);
+}
+
+// 遍历capability链表，返回(capability id, 配置空间偏移)
+pub fn capabilities(cfg: usize) -> Vec<(u8, usize)> {
+    let mut caps = Vec::new();
+    let status: u16 = read(cfg + PCI_STATUS);
+    if status & PCI_STATUS_CAP_LIST == 0 {
+        return caps;
+    }
+    let mut ptr = (read::<u8>(cfg + PCI_CAPABILITY_LIST) & !0x3) as usize;
+    // 配置空间最多容纳48个capability，防止链表成环
+    while ptr != 0 && caps.len() < 48 {
+        let id: u8 = read(cfg + ptr);
+        caps.push((id, ptr));
+        ptr = (read::<u8>(cfg + ptr + 1) & !0x3) as usize;
+    }
+    return caps;
+}
+
+pub fn find_capability(cfg: usize, id: u8) -> Option<usize> {
+    capabilities(cfg)
+        .into_iter()
+        .find(|(cap, _)| *cap == id)
+        .map(|(_, off)| off)
+}
+
+fn capability_names(cfg: usize) -> String {
+    let mut names = String::new();
+    for (i, (id, _)) in capabilities(cfg).iter().enumerate() {
+        if i != 0 {
+            names.push_str(", ");
+        }
+        match *id {
+            PCI_CAP_PM => names.push_str("PM"),
+            PCI_CAP_MSI => names.push_str("MSI"),
+            PCI_CAP_VENDOR => names.push_str("Vendor"),
+            PCI_CAP_EXP => names.push_str("PCIe"),
+            PCI_CAP_MSIX => names.push_str("MSI-X"),
+            other => {
+                write!(names, "{:#x}", other).unwrap();
             }
         }
-        _ => {}
     }
+    return names;
 }
 
-fn bdf(bus: u32, device: u32, func: u32) -> u32 {
-    return (bus << 16) | (device << 11) | (func << 8);
+// 打开设备的memory space和bus mastering
+pub fn enable_device(cfg: usize) {
+    let command: u16 = read(cfg + PCI_COMMAND);
+    write::<u16>(cfg + PCI_COMMAND, command | PCI_COMMAND_MEMORY | PCI_COMMAND_MASTER);
+    fence(Ordering::SeqCst);
+}
+
+// ECAM中bus:device.function的配置空间地址
+fn cfg_addr(bus: usize, device: usize, func: usize) -> usize {
+    return board().pcie_ecam.base + ((bus << 20) | (device << 15) | (func << 12));
 }
 
 fn read<T: Sized>(addr: usize) -> T {
@@ -110,5 +383,3 @@ fn write<T: Sized>(addr: usize, val: T) {
         (addr as *mut T).write_volatile(val);
     }
 }
-
-const PCI_HEADER_COMMAND: usize = 4;
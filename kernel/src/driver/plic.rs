@@ -1,7 +1,6 @@
-use crate::arch::riscv::qemu::layout::{
-    PLIC_SOURCES, UART0, UART_PLIC, VIRTIO_BLK_PLIC, VIRT_PLIC,
-};
-use crate::driver::uart;
+use super::device::dispatch_irq;
+use crate::arch::riscv::qemu::layout::board;
+use crate::task::scheduler::cpuid;
 
 #[allow(clippy::upper_case_acronyms)]
 pub struct PLIC {
@@ -22,18 +21,12 @@ impl IntrTargetPriority {
 
 pub fn init_plic() {
     use riscv::register::sie;
-    let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let hart_id = 0usize;
-    // Machine的阈值设为1，Supervisor阈值设为0，使中断优先级低于1的都由Supervisor处理
-    plic.set_threshold(hart_id, IntrTargetPriority::Machine, 1);
-    plic.set_threshold(hart_id, IntrTargetPriority::Supervisor, 0);
-
-    // 中断源，uart：10，blk：8
-    for src_id in PLIC_SOURCES {
-        // cpu中断使能
-        plic.enable(hart_id, IntrTargetPriority::Supervisor, *src_id);
-        // 设置src中断的优先级为1
-        plic.set_priority(*src_id, 1);
+    let board = board();
+    let mut plic = unsafe { PLIC::new(board.plic.base) };
+    for hart_id in 0..board.harts {
+        // Machine的阈值设为1，Supervisor阈值设为0，使中断优先级低于1的都由Supervisor处理
+        plic.set_threshold(hart_id, IntrTargetPriority::Machine, 1);
+        plic.set_threshold(hart_id, IntrTargetPriority::Supervisor, 0);
     }
 
     // Supervisor外设中断使能
@@ -42,26 +35,39 @@ pub fn init_plic() {
     }
 }
 
+// 使能一个中断源，由驱动注册中断时调用
+pub fn enable_irq(src_id: usize) {
+    let board = board();
+    let mut plic = unsafe { PLIC::new(board.plic.base) };
+    for hart_id in 0..board.harts {
+        // cpu中断使能
+        plic.enable(hart_id, IntrTargetPriority::Supervisor, src_id);
+    }
+    // 设置src中断的优先级为1
+    plic.set_priority(src_id, 1);
+}
+
 pub fn handle_irq() {
-    let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
+    let board = board();
+    let hart_id = cpuid();
+    let mut plic = unsafe { PLIC::new(board.plic.base) };
     // 读PLIC的 Claim 寄存器获得外设中断号
-    let src = plic.claim(0, IntrTargetPriority::Supervisor);
-    match src as usize {
-        UART_PLIC => {
-            uart::handle_irq();
-        }
-        VIRTIO_BLK_PLIC => {
-            panic!("virtio blk over plic not implemented");
-        }
-        _ => panic!("unsupported IRQ {}", src),
+    let src = plic.claim(hart_id, IntrTargetPriority::Supervisor);
+    // 中断已经被其他cpu处理
+    if src == 0 {
+        return;
+    }
+    // 交给注册了该中断的驱动处理
+    if !dispatch_irq(src as usize) {
+        panic!("unsupported IRQ {}", src);
     }
     // 中断完成
-    plic.complete(0, IntrTargetPriority::Supervisor, src);
+    plic.complete(hart_id, IntrTargetPriority::Supervisor, src);
 }
 
 impl PLIC {
     fn priority_addr(&self, intr_source_id: usize) -> usize {
-        assert!(intr_source_id > 0 && intr_source_id <= 132);
+        assert!(intr_source_id > 0 && intr_source_id <= board().plic_ndev);
         self.base_addr + intr_source_id * 4
     }
     fn hart_id_with_priority(hart_id: usize, target_priority: IntrTargetPriority) -> usize {
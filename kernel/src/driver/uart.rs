@@ -1,5 +1,7 @@
-use crate::arch::riscv::qemu::layout::UART0;
+use super::device::{register_irq, Device, Driver};
+use crate::arch::riscv::qemu::layout::board;
 use alloc::collections::VecDeque;
+use alloc::sync::Arc;
 use core::fmt::*;
 use lazy_static::lazy_static;
 use spin::mutex::Mutex;
@@ -29,6 +31,30 @@ pub struct Uart {
 
 lazy_static! {
     pub static ref UART: Mutex<Uart> = Mutex::new(Uart::new());
+    pub static ref UART_DRIVER: Arc<UartDriver> = Arc::new(UartDriver);
+}
+
+// uart在内核启动时就已经初始化，驱动只负责注册接收中断
+pub struct UartDriver;
+
+impl Driver for UartDriver {
+    fn name(&self) -> &'static str {
+        "uart16550"
+    }
+    fn compatible(&self) -> &'static [&'static str] {
+        &["ns16550a", "ns16550"]
+    }
+    fn probe(&self, dev: &Device) -> bool {
+        register_irq(dev.irq, UART_DRIVER.clone());
+        true
+    }
+    fn irq(&self, _irq: usize) {
+        handle_irq();
+    }
+    fn shutdown(&self) {
+        // 关闭uart中断
+        write_reg(IER, 0x0);
+    }
 }
 
 // 从console读取一个字节，直接从写缓冲中读取
@@ -109,7 +135,7 @@ impl Uart {
 }
 
 fn reg_addr(reg: usize) -> usize {
-    return UART0 + reg;
+    return board().uart.base + reg;
 }
 
 fn write_reg(reg: usize, val: u8) {
@@ -1,4 +1,6 @@
-use crate::arch::riscv::qemu::layout::VIRTIO0;
+use super::device::{add_device, Bus, Device};
+use crate::arch::riscv::qemu::layout::board;
+use alloc::format;
 use crate::config::PAGE_SIZE;
 use crate::mem::address::*;
 use alloc::vec::Vec;
@@ -54,76 +56,58 @@ pub struct VirtQueue {
     pub used: VirtQueueUsed,
     free_descs: [bool; QUEUE_SIZE],
     pub used_idx: u16,
+    base: usize, // 设备所在virtio-mmio插槽的寄存器基址
 }
 
 impl VirtQueue {
-    pub fn new() -> Self {
+    pub fn new(base: usize) -> Self {
         Self {
             desc: array![_ => VirtQueueDesc::new(); QUEUE_SIZE],
             avail: VirtQueueAvail::new(),
             used: VirtQueueUsed::new(),
             free_descs: [true; QUEUE_SIZE],
             used_idx: 0,
+            base: base,
         }
     }
     // 初始化驱动过程，see：virtio-v1.1.pdf，3.1.1
-    pub unsafe fn init<F: FnOnce(u32) -> u32>(
+    pub unsafe fn init<F: FnOnce(u64) -> u64>(
         &self,
         device_type: u32,
         queue_sel: u32,
         set_features: F,
     ) {
-        // check device
-        if read(VIRTIO_MMIO_MAGIC_VALUE) != VIRTIO_MAGIC_NUM
-            || read(VIRTIO_MMIO_VERSION) != 2
-            || read(VIRTIO_MMIO_DEVICE_ID) != device_type
-            || read(VIRTIO_MMIO_VENDOR_ID) != 0x554d4551
-        {
-            panic!("can not find virtio block device");
+        if !negotiate(self.base, device_type, set_features) {
+            panic!("can not find virtio device {} at {:#x}", device_type, self.base);
         }
-        // 设置ACKNOWLEDGE
-        let mut status: u32 = 0;
-        status |= VIRTIO_CONFIG_S_ACKNOWLEDGE;
-        write(VIRTIO_MMIO_STATUS, status);
-        // 设置DRIVER
-        status |= VIRTIO_CONFIG_S_DRIVER;
-        write(VIRTIO_MMIO_STATUS, status);
-
-        // 读取features bits
-        let mut features: u32 = read(VIRTIO_MMIO_DEVICE_FEATURES);
-        // 修改features，协商driver需要的features
-        features = set_features(features);
-        write(VIRTIO_MMIO_DRIVER_FEATURES, features);
-
-        status |= VIRTIO_CONFIG_S_FEATURES_OK;
-        write(VIRTIO_MMIO_STATUS, status);
-        // 设置DRIVER_OK，driver初始化结束
-        status |= VIRTIO_CONFIG_S_DRIVER_OK;
-        write(VIRTIO_MMIO_STATUS, status);
-
-        // 配置当前virt_queue, see: virtio-v1.1.pdf，4.2.3.2
-        write(VIRTIO_MMIO_QUEUE_SEL, queue_sel);
-        if read(VIRTIO_MMIO_QUEUE_READY) != 0 {
+        self.setup(queue_sel);
+        driver_ok(self.base);
+    }
+
+    // 配置当前virt_queue, see: virtio-v1.1.pdf，4.2.3.2
+    pub unsafe fn setup(&self, queue_sel: u32) {
+        write(self.base, VIRTIO_MMIO_QUEUE_SEL, queue_sel);
+        if read(self.base, VIRTIO_MMIO_QUEUE_READY) != 0 {
             panic!("virt queue already initialized");
         }
         // 设置queue size
-        let max_queue = read(VIRTIO_MMIO_QUEUE_NUM_MAX);
+        let max_queue = read(self.base, VIRTIO_MMIO_QUEUE_NUM_MAX);
         if QUEUE_SIZE > max_queue as usize {
             panic!("queue size too large")
         } else {
-            write(VIRTIO_MMIO_QUEUE_NUM, QUEUE_SIZE as u32);
+            write(self.base, VIRTIO_MMIO_QUEUE_NUM, QUEUE_SIZE as u32);
         }
         // 写入desc、driver、device的访问地址
         let desc_addr = &self.desc as *const VirtQueueDesc as usize;
         let avail_addr = &self.avail as *const VirtQueueAvail as usize;
         let used_addr = &self.used as *const VirtQueueUsed as usize;
-        write(VIRTIO_MMIO_QUEUE_DESC_LOW, desc_addr as u32);
-        write(VIRTIO_MMIO_QUEUE_DESC_HIGH, (desc_addr >> 32) as u32);
-        write(VIRTIO_MMIO_QUEUE_DRIVER_LOW, avail_addr as u32);
-        write(VIRTIO_MMIO_QUEUE_DRIVER_HIGH, (avail_addr >> 32) as u32);
-        write(VIRTIO_MMIO_QUEUE_DEVICE_LOW, used_addr as u32);
-        write(VIRTIO_MMIO_QUEUE_DEVICE_HIGH, (used_addr >> 32) as u32);
-        write(VIRTIO_MMIO_QUEUE_READY, 1);
+        write(self.base, VIRTIO_MMIO_QUEUE_DESC_LOW, desc_addr as u32);
+        write(self.base, VIRTIO_MMIO_QUEUE_DESC_HIGH, (desc_addr >> 32) as u32);
+        write(self.base, VIRTIO_MMIO_QUEUE_DRIVER_LOW, avail_addr as u32);
+        write(self.base, VIRTIO_MMIO_QUEUE_DRIVER_HIGH, (avail_addr >> 32) as u32);
+        write(self.base, VIRTIO_MMIO_QUEUE_DEVICE_LOW, used_addr as u32);
+        write(self.base, VIRTIO_MMIO_QUEUE_DEVICE_HIGH, (used_addr >> 32) as u32);
+        write(self.base, VIRTIO_MMIO_QUEUE_READY, 1);
         fence(Ordering::SeqCst);
     }
 
@@ -154,13 +138,13 @@ impl VirtQueue {
         self.avail.ring[avail_idx] = head;
         fence(Ordering::SeqCst);
         // 增加avail idx
-        self.avail.idx += 1;
+        self.avail.idx = self.avail.idx.wrapping_add(1);
         fence(Ordering::SeqCst);
         Some(head)
     }
 
     pub unsafe fn notify(&self, sel: u32) {
-        write(VIRTIO_MMIO_QUEUE_NOTIFY, sel);
+        write(self.base, VIRTIO_MMIO_QUEUE_NOTIFY, sel);
     }
 
     pub fn can_pop(&self) -> bool {
@@ -168,6 +152,23 @@ impl VirtQueue {
         return self.used.idx != self.used_idx;
     }
 
+    // 取出一个device已经处理完的buffer，返回(desc链的第一个desc, 写入的长度)
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        if !self.can_pop() {
+            return None;
+        }
+        let elem = &self.used.ring[self.used_idx as usize % QUEUE_SIZE];
+        let (id, len) = (elem.id as u16, elem.len);
+        self.free_desc_chain(id as usize);
+        self.used_idx = self.used_idx.wrapping_add(1);
+        return Some((id, len));
+    }
+
+    // 空闲的desc数量
+    pub fn num_free(&self) -> usize {
+        self.free_descs.iter().filter(|free| **free).count()
+    }
+
     fn alloc_desc(&mut self) -> Option<usize> {
         for i in 0..QUEUE_SIZE {
             if self.free_descs[i] {
@@ -252,14 +253,14 @@ impl VirtQueueUsedElem {
 }
 
 #[inline]
-pub unsafe fn read(offset: usize) -> u32 {
-    let ptr = (VIRTIO0 + offset) as *const u32;
+pub unsafe fn read(base: usize, offset: usize) -> u32 {
+    let ptr = (base + offset) as *const u32;
     return ptr.read_volatile();
 }
 
 #[inline]
-pub unsafe fn write(offset: usize, value: u32) {
-    let ptr = (VIRTIO0 + offset) as *mut u32;
+pub unsafe fn write(base: usize, offset: usize, value: u32) {
+    let ptr = (base + offset) as *mut u32;
     ptr.write_volatile(value);
 }
 
@@ -272,7 +273,9 @@ pub const VIRTIO_MMIO_VERSION: usize = 0x004;
 pub const VIRTIO_MMIO_DEVICE_ID: usize = 0x008;
 pub const VIRTIO_MMIO_VENDOR_ID: usize = 0x00c;
 pub const VIRTIO_MMIO_DEVICE_FEATURES: usize = 0x010;
+pub const VIRTIO_MMIO_DEVICE_FEATURES_SEL: usize = 0x014;
 pub const VIRTIO_MMIO_DRIVER_FEATURES: usize = 0x020;
+pub const VIRTIO_MMIO_DRIVER_FEATURES_SEL: usize = 0x024;
 pub const VIRTIO_MMIO_GUEST_PAGE_SIZE: usize = 0x028;
 pub const VIRTIO_MMIO_QUEUE_SEL: usize = 0x030;
 pub const VIRTIO_MMIO_QUEUE_NUM_MAX: usize = 0x034;
@@ -284,6 +287,7 @@ pub const VIRTIO_MMIO_QUEUE_NOTIFY: usize = 0x050;
 pub const VIRTIO_MMIO_INTERRUPT_STATUS: usize = 0x060;
 pub const VIRTIO_MMIO_INTERRUPT_ACK: usize = 0x064;
 pub const VIRTIO_MMIO_STATUS: usize = 0x070;
+pub const VIRTIO_MMIO_CONFIG: usize = 0x100;
 
 // config bits
 pub const VIRTIO_CONFIG_S_ACKNOWLEDGE: u32 = 1;
@@ -299,6 +303,7 @@ pub const VIRTIO_BLK_F_MQ: u8 = 12;
 pub const VIRTIO_F_ANY_LAYOUT: u8 = 27;
 pub const VIRTIO_RING_F_INDIRECT_DESC: u8 = 28;
 pub const VIRTIO_RING_F_EVENT_IDX: u8 = 29;
+pub const VIRTIO_F_VERSION_1: u8 = 32;
 
 pub const VIRTIO_DEVICE_NETWORK: u32 = 1;
 pub const VIRTIO_DEVICE_BLOCK: u32 = 2;
@@ -309,3 +314,87 @@ const VIRTIO_MMIO_QUEUE_DRIVER_LOW: usize = 0x090;
 const VIRTIO_MMIO_QUEUE_DRIVER_HIGH: usize = 0x094;
 const VIRTIO_MMIO_QUEUE_DEVICE_LOW: usize = 0x0a0;
 const VIRTIO_MMIO_QUEUE_DEVICE_HIGH: usize = 0x0a4;
+
+// 重置设备并协商features，features_ok未被设备接受时返回false
+pub unsafe fn negotiate<F: FnOnce(u64) -> u64>(
+    base: usize,
+    device_type: u32,
+    set_features: F,
+) -> bool {
+    // check device
+    if read(base, VIRTIO_MMIO_MAGIC_VALUE) != VIRTIO_MAGIC_NUM
+        || read(base, VIRTIO_MMIO_VERSION) != 2
+        || read(base, VIRTIO_MMIO_DEVICE_ID) != device_type
+        || read(base, VIRTIO_MMIO_VENDOR_ID) != 0x554d4551
+    {
+        return false;
+    }
+    // 重置设备
+    write(base, VIRTIO_MMIO_STATUS, 0);
+    // 设置ACKNOWLEDGE
+    let mut status: u32 = 0;
+    status |= VIRTIO_CONFIG_S_ACKNOWLEDGE;
+    write(base, VIRTIO_MMIO_STATUS, status);
+    // 设置DRIVER
+    status |= VIRTIO_CONFIG_S_DRIVER;
+    write(base, VIRTIO_MMIO_STATUS, status);
+
+    // 读取64位features bits，通过FEATURES_SEL选择低32位和高32位
+    write(base, VIRTIO_MMIO_DEVICE_FEATURES_SEL, 0);
+    let low = read(base, VIRTIO_MMIO_DEVICE_FEATURES) as u64;
+    write(base, VIRTIO_MMIO_DEVICE_FEATURES_SEL, 1);
+    let high = read(base, VIRTIO_MMIO_DEVICE_FEATURES) as u64;
+    // 修改features，协商driver需要的features
+    let features = set_features(low | (high << 32));
+    write(base, VIRTIO_MMIO_DRIVER_FEATURES_SEL, 0);
+    write(base, VIRTIO_MMIO_DRIVER_FEATURES, features as u32);
+    write(base, VIRTIO_MMIO_DRIVER_FEATURES_SEL, 1);
+    write(base, VIRTIO_MMIO_DRIVER_FEATURES, (features >> 32) as u32);
+
+    status |= VIRTIO_CONFIG_S_FEATURES_OK;
+    write(base, VIRTIO_MMIO_STATUS, status);
+    // 设备不支持协商的features时不会保留FEATURES_OK
+    return read(base, VIRTIO_MMIO_STATUS) & VIRTIO_CONFIG_S_FEATURES_OK != 0;
+}
+
+// 设置DRIVER_OK，driver初始化结束，需要在所有queue配置完成后调用
+pub unsafe fn driver_ok(base: usize) {
+    let status = read(base, VIRTIO_MMIO_STATUS);
+    write(base, VIRTIO_MMIO_STATUS, status | VIRTIO_CONFIG_S_DRIVER_OK);
+    fence(Ordering::SeqCst);
+}
+
+// 读取设备的config空间
+pub unsafe fn read_config<T: Sized>(base: usize, offset: usize) -> T {
+    ((base + VIRTIO_MMIO_CONFIG + offset) as *const T).read_volatile()
+}
+
+// 扫描所有virtio-mmio插槽，有设备的插槽加入设备表
+pub fn scan_virtio_mmio() {
+    for slot in board().virtio_slots() {
+        unsafe {
+            if read(slot.base, VIRTIO_MMIO_MAGIC_VALUE) != VIRTIO_MAGIC_NUM {
+                continue;
+            }
+            // device id为0表示空插槽
+            let device_id = read(slot.base, VIRTIO_MMIO_DEVICE_ID);
+            if device_id == 0 {
+                continue;
+            }
+            add_device(Device {
+                name: format!("virtio@{:x}", slot.base),
+                compatible: format!("virtio,device{:x}", device_id),
+                bus: Bus::VirtioMmio,
+                base: slot.base,
+                irq: slot.irq,
+            });
+        }
+    }
+}
+
+// 读取并应答设备中断，返回中断状态
+pub unsafe fn ack_interrupt(base: usize) -> u32 {
+    let status = read(base, VIRTIO_MMIO_INTERRUPT_STATUS);
+    write(base, VIRTIO_MMIO_INTERRUPT_ACK, status & 0x3);
+    return status;
+}
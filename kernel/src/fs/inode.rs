@@ -1,5 +1,7 @@
+use super::page_cache::update_page_cache;
 use super::{File, FileStat, UserBuffer};
 use crate::driver::blk::BLOCK_DEVICE;
+use crate::ipc::pipe::{open_fifo, Pipe};
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -47,6 +49,7 @@ pub struct OSInode {
     readable: bool,
     writable: bool,
     inner: Mutex<OSInodeInner>,
+    pipe: Option<Pipe>, // 打开的命名管道，读写通过管道缓冲区进行
 }
 
 pub struct OSInodeInner {
@@ -56,16 +59,37 @@ pub struct OSInodeInner {
 
 pub fn open_file(path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
     let (readable, writable) = flags.is_read_write();
-    if flags.contains(OpenFlags::CREATE) {
+    let inode = if flags.contains(OpenFlags::CREATE) {
         // 文件是否存在，不存在时需要创建
         if let Ok(inode) = find(path) {
-            return Ok(inode);
+            inode
         } else {
             return create(path, flags.is_dir(), readable, writable);
         }
     } else {
-        return find(path);
+        find(path)?
+    };
+    // 命名管道连接到共享的管道缓冲区，只读或只写时阻塞到对端打开
+    if inode.is_fifo() {
+        return Ok(Arc::new(inode.open_fifo(readable, writable)));
+    }
+    return Ok(inode);
+}
+
+// 在path创建命名管道
+pub fn mkfifo(path: &str) -> Result<(), isize> {
+    let s = String::from(path);
+    let mut parts: Vec<_> = s.split("/").collect();
+    let filename = parts.pop().unwrap();
+    if filename.is_empty() {
+        return Err(FILE_NOT_FOUND_ERROR);
+    }
+    let parent = find(parts.join("/").as_str())?;
+    if !parent.is_dir() {
+        return Err(NOT_DIR_ERROR);
     }
+    let inner = parent.inner.lock();
+    return inner.inode.mkfifo(filename).map(|_| ());
 }
 
 pub fn find(path: &str) -> Result<Arc<OSInode>, isize> {
@@ -135,9 +159,18 @@ impl OSInode {
             readable,
             writable,
             inner: Mutex::new(OSInodeInner { offset: 0, inode }),
+            pipe: None,
         }
     }
 
+    fn open_fifo(&self, readable: bool, writable: bool) -> OSInode {
+        let inode = Arc::clone(&self.inner.lock().inode);
+        let pipe = open_fifo(inode.inode_seq(), readable, writable);
+        let mut fifo = OSInode::new(readable, writable, inode);
+        fifo.pipe = Some(pipe);
+        return fifo;
+    }
+
     pub fn ls(&self) -> Option<Vec<String>> {
         self.inner.lock().inode.ls()
     }
@@ -174,15 +207,35 @@ impl OSInode {
         stat.size = inode_stat.size;
         stat.inode = inode_stat.inode;
         stat.dir = inode_stat.dir;
+        stat.fifo = inode_stat.fifo;
     }
 
     pub fn is_dir(&self) -> bool {
         self.inner.lock().inode.is_dir()
     }
+
+    pub fn is_fifo(&self) -> bool {
+        self.inner.lock().inode.is_fifo()
+    }
+
+    pub fn readable(&self) -> bool {
+        self.readable
+    }
+
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+
+    pub fn inode(&self) -> Arc<Inode> {
+        Arc::clone(&self.inner.lock().inode)
+    }
 }
 
 impl File for OSInode {
     fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
+        if let Some(pipe) = &self.pipe {
+            return pipe.read(buf);
+        }
         let mut inner = self.inner.lock();
         let size = inner.inode.size();
         if inner.offset == size {
@@ -198,9 +251,13 @@ impl File for OSInode {
         return read_len;
     }
     fn write<'a>(&self, buf: &mut UserBuffer) -> usize {
+        if let Some(pipe) = &self.pipe {
+            return pipe.write(buf);
+        }
         let mut inner = self.inner.lock();
         buf.foreach(|bytes| {
             inner.inode.write(inner.offset, bytes);
+            update_page_cache(&inner.inode, inner.offset as usize, bytes);
             inner.offset += bytes.len() as u32;
             return true;
         });
@@ -214,6 +271,9 @@ impl File for OSInode {
     }
 
     fn lseek(&self, off: u32, from: u8) -> isize {
+        if self.pipe.is_some() {
+            return -1;
+        }
         let offset: usize;
         let mut inner = self.inner.lock();
         let size = inner.inode.size();
@@ -225,6 +285,13 @@ impl File for OSInode {
         }
         return inner.offset as isize;
     }
+
+    fn as_inode(&self) -> Option<&OSInode> {
+        if self.pipe.is_some() {
+            return None;
+        }
+        Some(self)
+    }
 }
 
 impl OSInode {
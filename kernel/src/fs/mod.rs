@@ -1,8 +1,12 @@
 use crate::config::PAGE_SIZE;
+use crate::ipc::mqueue::MqDescriptor;
+use inode::OSInode;
+use crate::net::socket::Socket;
 use crate::task::scheduler::current_proc;
 use alloc::vec::Vec;
 
 pub mod inode;
+pub mod page_cache;
 pub mod stdio;
 
 pub trait File: Send + Sync {
@@ -10,6 +14,18 @@ pub trait File: Send + Sync {
     fn write<'a>(&self, buf: &mut UserBuffer) -> usize;
     fn fstat(&self) -> Option<FileStat>;
     fn lseek(&self, offset: u32, from: u8) -> isize;
+    // socket文件返回socket接口，用于bind、sendto等系统调用
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        None
+    }
+    // 消息队列返回描述符，用于mq_timedsend等系统调用
+    fn as_mqueue(&self) -> Option<&MqDescriptor> {
+        None
+    }
+    // 普通文件返回inode，用于mmap
+    fn as_inode(&self) -> Option<&OSInode> {
+        None
+    }
 }
 
 // 文件状态struct
@@ -21,6 +37,7 @@ pub struct FileStat {
     pub io_block: u32,     // IO块大小
     pub index_blocks: u32, // 索引块数量
     pub dir: bool,
+    pub fifo: bool,
 }
 
 impl FileStat {
@@ -32,6 +49,7 @@ impl FileStat {
             io_block: 0,
             index_blocks: 0,
             dir: false,
+            fifo: false,
         }
     }
 }
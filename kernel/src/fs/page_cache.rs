@@ -0,0 +1,95 @@
+use crate::config::PAGE_SIZE;
+use crate::mem::allocator::Frame;
+use crate::mem::swap::alloc_user_frame;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use lazy_static::lazy_static;
+use simplefs::vfs::Inode;
+use spin::mutex::Mutex;
+
+// 文件的页缓存，映射同一个文件的所有进程共享缓存中的物理页
+pub struct PageCache {
+    inode: Arc<Inode>,
+    pages: Mutex<BTreeMap<usize, Arc<Frame>>>, // 文件页号 -> 物理页
+}
+
+lazy_static! {
+    // 以inode编号为key，所有映射都解除后缓存被释放
+    static ref PAGE_CACHES: Mutex<BTreeMap<u32, Weak<PageCache>>> = Mutex::new(BTreeMap::new());
+}
+
+// 获取文件的页缓存，不存在时创建
+pub fn page_cache(inode: Arc<Inode>) -> Arc<PageCache> {
+    let mut caches = PAGE_CACHES.lock();
+    caches.retain(|_, cache| cache.strong_count() > 0);
+    let seq = inode.inode_seq();
+    if let Some(cache) = caches.get(&seq).and_then(|cache| cache.upgrade()) {
+        return cache;
+    }
+    let cache = Arc::new(PageCache {
+        inode: inode,
+        pages: Mutex::new(BTreeMap::new()),
+    });
+    caches.insert(seq, Arc::downgrade(&cache));
+    return cache;
+}
+
+// 文件被写入时更新它的页缓存，文件没有被映射时不需要处理
+pub fn update_page_cache(inode: &Inode, offset: usize, data: &[u8]) {
+    let cache = PAGE_CACHES
+        .lock()
+        .get(&inode.inode_seq())
+        .and_then(|cache| cache.upgrade());
+    if let Some(cache) = cache {
+        cache.update(offset, data);
+    }
+}
+
+impl PageCache {
+    // 获取文件的第index页，不在缓存中时从文件读取，超过文件末尾的部分为0
+    pub fn get_page(&self, index: usize) -> Option<Arc<Frame>> {
+        let mut pages = self.pages.lock();
+        if let Some(frame) = pages.get(&index) {
+            return Some(Arc::clone(frame));
+        }
+        let frame = alloc_user_frame()?;
+        let bytes = frame.ppn.as_bytes();
+        bytes.fill(0);
+        let start = index * PAGE_SIZE;
+        let size = self.inode.size() as usize;
+        if start < size {
+            let len = (size - start).min(PAGE_SIZE);
+            self.inode.read(start as u32, &mut bytes[0..len]);
+        }
+        let frame = Arc::new(frame);
+        pages.insert(index, Arc::clone(&frame));
+        return Some(frame);
+    }
+
+    // write系统调用写入文件后，更新缓存中对应的页，映射该文件的进程能看到写入的数据
+    fn update(&self, offset: usize, data: &[u8]) {
+        let pages = self.pages.lock();
+        let end = offset + data.len();
+        for (index, frame) in pages.range(offset / PAGE_SIZE..(end + PAGE_SIZE - 1) / PAGE_SIZE) {
+            let start = (index * PAGE_SIZE).max(offset);
+            let stop = ((index + 1) * PAGE_SIZE).min(end);
+            frame.ppn.as_bytes()[start - index * PAGE_SIZE..stop - index * PAGE_SIZE]
+                .copy_from_slice(&data[start - offset..stop - offset]);
+        }
+    }
+
+    // 将缓存页写回文件，只写文件大小以内的部分，mmap不会改变文件大小
+    pub fn write_back(&self, index: usize) {
+        let pages = self.pages.lock();
+        let frame = match pages.get(&index) {
+            Some(frame) => frame,
+            None => return,
+        };
+        let start = index * PAGE_SIZE;
+        let size = self.inode.size() as usize;
+        if start < size {
+            let len = (size - start).min(PAGE_SIZE);
+            self.inode.write(start as u32, &frame.ppn.as_bytes()[0..len]);
+        }
+    }
+}
@@ -1 +1,4 @@
+pub mod mqueue;
 pub mod pipe;
+pub mod shm;
+pub mod unix;
@@ -0,0 +1,268 @@
+use crate::config::TIME_FREQ_MS;
+use crate::fs::{File, FileStat, UserBuffer};
+use crate::task::scheduler::yield_current_task;
+use crate::timer::get_time;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// 消息队列，保留消息边界，按优先级从高到低接收，同优先级先进先出
+// mq_open的flags，读写方式和CREATE与文件的OpenFlags一致
+pub const MQ_RDONLY: usize = 0;
+pub const MQ_WRONLY: usize = 1 << 0;
+pub const MQ_RDWR: usize = 1 << 1;
+pub const MQ_CREATE: usize = 1 << 9;
+pub const MQ_EXCL: usize = 1 << 10; // 与CREATE一起使用，队列已存在时失败
+pub const MQ_NONBLOCK: usize = 1 << 11;
+
+pub const MQ_PRIO_MAX: usize = 32;
+// 创建队列时没有指定属性使用的默认值
+const DEFAULT_MAX_MSGS: usize = 10;
+const DEFAULT_MSG_SIZE: usize = 256;
+const MAX_MSGS_LIMIT: usize = 64;
+const MSG_SIZE_LIMIT: usize = 8192;
+
+// 消息队列系统调用的错误码，超时和非阻塞的错误码与socket一致
+pub const MQ_ERROR: isize = -1;
+pub const MQ_EXIST_ERROR: isize = -2;
+pub const MQ_NOT_FOUND_ERROR: isize = -3;
+pub const MQ_TIMEOUT_ERROR: isize = -6;
+pub const MQ_WOULD_BLOCK_ERROR: isize = -8;
+pub const MQ_MSG_SIZE_ERROR: isize = -12; // 发送的消息过长或接收的缓冲区过小
+
+// 用户程序使用的mq_attr
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MqAttr {
+    pub flags: usize, // 只有MQ_NONBLOCK有效
+    pub max_msgs: usize,
+    pub msg_size: usize,
+    pub cur_msgs: usize,
+}
+
+struct Message {
+    prio: usize,
+    data: Vec<u8>,
+}
+
+struct MessageQueueInner {
+    messages: Vec<Message>, // 按优先级从高到低排列
+    max_msgs: usize,
+    msg_size: usize,
+}
+
+pub struct MessageQueue {
+    inner: Mutex<MessageQueueInner>,
+}
+
+lazy_static! {
+    // 所有进程共享的命名队列，mq_unlink之前一直存在
+    static ref QUEUES: Mutex<BTreeMap<String, Arc<MessageQueue>>> = Mutex::new(BTreeMap::new());
+}
+
+impl MessageQueue {
+    fn new(max_msgs: usize, msg_size: usize) -> Self {
+        Self {
+            inner: Mutex::new(MessageQueueInner {
+                messages: Vec::new(),
+                max_msgs: max_msgs,
+                msg_size: msg_size,
+            }),
+        }
+    }
+}
+
+// 打开或创建命名队列，attr为None时使用默认属性
+pub fn mq_open(name: &str, flags: usize, attr: Option<MqAttr>) -> Result<MqDescriptor, isize> {
+    if name.is_empty() {
+        return Err(MQ_ERROR);
+    }
+    let mut queues = QUEUES.lock();
+    let queue = match queues.get(name) {
+        Some(queue) => {
+            if flags & MQ_CREATE != 0 && flags & MQ_EXCL != 0 {
+                return Err(MQ_EXIST_ERROR);
+            }
+            Arc::clone(queue)
+        }
+        None => {
+            if flags & MQ_CREATE == 0 {
+                return Err(MQ_NOT_FOUND_ERROR);
+            }
+            let (max_msgs, msg_size) = match attr {
+                Some(attr) => (attr.max_msgs, attr.msg_size),
+                None => (DEFAULT_MAX_MSGS, DEFAULT_MSG_SIZE),
+            };
+            if max_msgs == 0 || max_msgs > MAX_MSGS_LIMIT || msg_size == 0 || msg_size > MSG_SIZE_LIMIT {
+                return Err(MQ_ERROR);
+            }
+            let queue = Arc::new(MessageQueue::new(max_msgs, msg_size));
+            queues.insert(String::from(name), Arc::clone(&queue));
+            queue
+        }
+    };
+    let (readable, writable) = if flags & MQ_WRONLY != 0 {
+        (false, true)
+    } else if flags & MQ_RDWR != 0 {
+        (true, true)
+    } else {
+        (true, false)
+    };
+    return Ok(MqDescriptor {
+        queue: queue,
+        readable: readable,
+        writable: writable,
+        nonblock: Mutex::new(flags & MQ_NONBLOCK != 0),
+    });
+}
+
+// 删除队列的名字，已经打开的描述符仍然可以使用
+pub fn mq_unlink(name: &str) -> isize {
+    match QUEUES.lock().remove(name) {
+        Some(_) => 0,
+        None => MQ_NOT_FOUND_ERROR,
+    }
+}
+
+// 打开的队列，在fd表中是File
+pub struct MqDescriptor {
+    queue: Arc<MessageQueue>,
+    readable: bool,
+    writable: bool,
+    nonblock: Mutex<bool>,
+}
+
+impl MqDescriptor {
+    // 队列满时等待，timeout单位ms，None表示一直等待
+    pub fn send(&self, data: &[u8], prio: usize, timeout: Option<usize>) -> isize {
+        if !self.writable || prio >= MQ_PRIO_MAX {
+            return MQ_ERROR;
+        }
+        let nonblock = *self.nonblock.lock();
+        let deadline = timeout.map(|t| get_time() / TIME_FREQ_MS + t);
+        loop {
+            let mut inner = self.queue.inner.lock();
+            if data.len() > inner.msg_size {
+                return MQ_MSG_SIZE_ERROR;
+            }
+            if inner.messages.len() < inner.max_msgs {
+                // 插入到所有优先级不低于prio的消息之后
+                let pos = inner
+                    .messages
+                    .iter()
+                    .position(|msg| msg.prio < prio)
+                    .unwrap_or(inner.messages.len());
+                inner.messages.insert(
+                    pos,
+                    Message {
+                        prio: prio,
+                        data: data.to_vec(),
+                    },
+                );
+                return 0;
+            }
+            drop(inner);
+            if let Some(code) = Self::check_wait(nonblock, deadline) {
+                return code;
+            }
+            yield_current_task();
+        }
+    }
+
+    // 接收优先级最高的消息，返回(长度, 优先级)，buf需要能放下队列的最大消息长度
+    pub fn receive(&self, buf: &mut [u8], timeout: Option<usize>) -> Result<(usize, usize), isize> {
+        if !self.readable {
+            return Err(MQ_ERROR);
+        }
+        let nonblock = *self.nonblock.lock();
+        let deadline = timeout.map(|t| get_time() / TIME_FREQ_MS + t);
+        loop {
+            let mut inner = self.queue.inner.lock();
+            if buf.len() < inner.msg_size {
+                return Err(MQ_MSG_SIZE_ERROR);
+            }
+            if !inner.messages.is_empty() {
+                let msg = inner.messages.remove(0);
+                buf[0..msg.data.len()].copy_from_slice(&msg.data);
+                return Ok((msg.data.len(), msg.prio));
+            }
+            drop(inner);
+            if let Some(code) = Self::check_wait(nonblock, deadline) {
+                return Err(code);
+            }
+            yield_current_task();
+        }
+    }
+
+    // 不能继续等待时返回错误码
+    fn check_wait(nonblock: bool, deadline: Option<usize>) -> Option<isize> {
+        if nonblock {
+            return Some(MQ_WOULD_BLOCK_ERROR);
+        }
+        match deadline {
+            Some(deadline) if get_time() / TIME_FREQ_MS >= deadline => Some(MQ_TIMEOUT_ERROR),
+            _ => None,
+        }
+    }
+
+    pub fn attr(&self) -> MqAttr {
+        let inner = self.queue.inner.lock();
+        MqAttr {
+            flags: if *self.nonblock.lock() { MQ_NONBLOCK } else { 0 },
+            max_msgs: inner.max_msgs,
+            msg_size: inner.msg_size,
+            cur_msgs: inner.messages.len(),
+        }
+    }
+
+    // 只能修改是否非阻塞
+    pub fn set_nonblock(&self, nonblock: bool) {
+        *self.nonblock.lock() = nonblock;
+    }
+
+    pub fn msg_size(&self) -> usize {
+        self.queue.inner.lock().msg_size
+    }
+}
+
+// read和write按优先级0收发一条消息
+impl File for MqDescriptor {
+    fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
+        let mut data = vec![0u8; self.msg_size()];
+        match self.receive(&mut data, None) {
+            Ok((len, _)) if len > 0 => {
+                let len = len.min(buf.length());
+                buf.write(0, &data[0..len]);
+                len
+            }
+            _ => 0,
+        }
+    }
+    // 消息过长时在分配缓冲区之前返回0
+    fn write<'a>(&self, buf: &mut UserBuffer) -> usize {
+        if buf.length() > self.msg_size() {
+            return 0;
+        }
+        let mut data = vec![0u8; buf.length()];
+        if data.len() > 0 {
+            buf.read(0, &mut data);
+        }
+        if self.send(&data, 0, None) < 0 {
+            return 0;
+        }
+        return data.len();
+    }
+    fn fstat(&self) -> Option<FileStat> {
+        None
+    }
+    fn lseek(&self, _offset: u32, _from: u8) -> isize {
+        -1
+    }
+    fn as_mqueue(&self) -> Option<&MqDescriptor> {
+        Some(self)
+    }
+}
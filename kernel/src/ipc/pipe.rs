@@ -1,10 +1,13 @@
 use crate::fs::{File, UserBuffer};
-use crate::task::scheduler::yield_current_task;
+use crate::sync::wait_queue::WaitQueue;
+use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
 use alloc::vec::Vec;
+use lazy_static::lazy_static;
 use spin::mutex::Mutex;
 
-const RING_BUFFER_SIZE: usize = 32;
+pub const RING_BUFFER_SIZE: usize = 4096;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum PipeStatus {
@@ -16,41 +19,62 @@ pub enum PipeStatus {
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<Mutex<PipeRingBuffer>>,
+    buffer: Arc<PipeBuffer>,
+}
+
+// 管道两端共享的缓冲区，读写端阻塞时在等待队列中等待
+pub struct PipeBuffer {
+    inner: Mutex<PipeRingBuffer>,
+    read_wait: WaitQueue,  // 等待数据写入的读端
+    write_wait: WaitQueue, // 等待缓冲区空间的写端
+    open_wait: WaitQueue,  // 等待对端打开的命名管道
 }
 
 pub struct PipeRingBuffer {
-    array: [u8; RING_BUFFER_SIZE],
+    array: Vec<u8>,
     read_idx: usize,
     write_idx: usize,
     status: PipeStatus,
-    write_end: Option<Weak<Pipe>>,
+    readers: usize, // 打开的读端数量，为0时写入失败
+    writers: usize, // 打开的写端数量，为0且缓冲区为空时读取返回0
+    // 读写端被打开的总次数，命名管道open时用来判断对端是否打开过
+    read_opens: usize,
+    write_opens: usize,
 }
 
-// 创建一个管道，返回写端和读端
+// 创建一个管道，返回读端和写端
 pub fn create_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
-    let write_end = Arc::new(Pipe::new(false, true, Arc::clone(&buffer)));
+    let buffer = PipeBuffer::new();
     let read_end = Arc::new(Pipe::new(true, false, Arc::clone(&buffer)));
-    buffer.lock().set_write_end(&write_end);
+    let write_end = Arc::new(Pipe::new(false, true, buffer));
     return (read_end, write_end);
 }
 
+impl PipeBuffer {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(PipeRingBuffer::new()),
+            read_wait: WaitQueue::new(),
+            write_wait: WaitQueue::new(),
+            open_wait: WaitQueue::new(),
+        })
+    }
+}
+
 impl PipeRingBuffer {
     fn new() -> Self {
         Self {
-            array: [0u8; RING_BUFFER_SIZE],
+            array: vec![0u8; RING_BUFFER_SIZE],
             read_idx: 0,
             write_idx: 0,
             status: PipeStatus::EMPTY,
-            write_end: None,
+            readers: 0,
+            writers: 0,
+            read_opens: 0,
+            write_opens: 0,
         }
     }
 
-    fn set_write_end(&mut self, pipe: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(pipe));
-    }
-
     fn available_bytes(&self) -> usize {
         if self.status == PipeStatus::EMPTY {
             return 0;
@@ -63,18 +87,56 @@ impl PipeRingBuffer {
         }
     }
 
-    fn write_end_closed(&self) -> bool {
-        if let Some(write_end) = &self.write_end {
-            return write_end.upgrade().unwrap().writable;
+    fn free_bytes(&self) -> usize {
+        return RING_BUFFER_SIZE - self.available_bytes();
+    }
+
+    // 读取len个字节到用户缓冲区的开头，len不超过available_bytes
+    fn read_to(&mut self, buf: &mut UserBuffer, len: usize) {
+        let first = len.min(RING_BUFFER_SIZE - self.read_idx);
+        buf.write(0, &self.array[self.read_idx..self.read_idx + first]);
+        if first < len {
+            buf.write(first, &self.array[0..len - first]);
+        }
+        self.read_idx = (self.read_idx + len) % RING_BUFFER_SIZE;
+        self.status = if self.read_idx == self.write_idx {
+            PipeStatus::EMPTY
         } else {
-            return false;
+            PipeStatus::AVAILABLE
+        };
+    }
+
+    // 从用户缓冲区的offset处写入len个字节，len不超过free_bytes
+    fn write_from(&mut self, buf: &UserBuffer, offset: usize, len: usize) {
+        let first = len.min(RING_BUFFER_SIZE - self.write_idx);
+        buf.read(offset, &mut self.array[self.write_idx..self.write_idx + first]);
+        if first < len {
+            buf.read(offset + first, &mut self.array[0..len - first]);
         }
+        self.write_idx = (self.write_idx + len) % RING_BUFFER_SIZE;
+        self.status = if self.read_idx == self.write_idx {
+            PipeStatus::FULL
+        } else {
+            PipeStatus::AVAILABLE
+        };
     }
 }
 
 impl Pipe {
-    fn new(read: bool, write: bool, buf: Arc<Mutex<PipeRingBuffer>>) -> Self {
-        assert!(read != write);
+    // 打开管道的一端，命名管道以读写方式打开时两端都可用
+    pub fn new(read: bool, write: bool, buf: Arc<PipeBuffer>) -> Self {
+        assert!(read || write);
+        let mut inner = buf.inner.lock();
+        if read {
+            inner.readers += 1;
+            inner.read_opens += 1;
+        }
+        if write {
+            inner.writers += 1;
+            inner.write_opens += 1;
+        }
+        drop(inner);
+        buf.open_wait.wake_all();
         Self {
             readable: read,
             writable: write,
@@ -83,20 +145,115 @@ impl Pipe {
     }
 }
 
-// todo read write
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut inner = self.buffer.inner.lock();
+        if self.readable {
+            inner.readers -= 1;
+        }
+        if self.writable {
+            inner.writers -= 1;
+        }
+        drop(inner);
+        // 对端关闭时阻塞的读写端需要返回
+        self.buffer.read_wait.wake_all();
+        self.buffer.write_wait.wake_all();
+    }
+}
+
 impl File for Pipe {
+    // 缓冲区为空时等待，所有写端关闭后返回0
     fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
-        panic!("not implemented");
-        0
+        if !self.readable || buf.length() == 0 {
+            return 0;
+        }
+        loop {
+            let mut inner = self.buffer.inner.lock();
+            let len = inner.available_bytes().min(buf.length());
+            if len == 0 {
+                if inner.writers == 0 {
+                    return 0;
+                }
+                self.buffer.read_wait.wait(inner);
+                continue;
+            }
+            inner.read_to(buf, len);
+            drop(inner);
+            self.buffer.write_wait.wake_all();
+            return len;
+        }
     }
+
+    // 写入全部数据，缓冲区满时等待，所有读端关闭后停止写入
+    // 每次从用户缓冲区直接拷贝不超过缓冲区空闲空间的数据
     fn write<'a>(&self, buf: &mut UserBuffer) -> usize {
-        panic!("not implemented");
-        0
+        if !self.writable || buf.length() == 0 {
+            return 0;
+        }
+        let mut written = 0;
+        while written < buf.length() {
+            let mut inner = self.buffer.inner.lock();
+            if inner.readers == 0 {
+                break;
+            }
+            let len = inner.free_bytes().min(buf.length() - written);
+            if len == 0 {
+                self.buffer.write_wait.wait(inner);
+                continue;
+            }
+            inner.write_from(buf, written, len);
+            written += len;
+            drop(inner);
+            self.buffer.read_wait.wake_all();
+        }
+        return written;
     }
+
     fn fstat(&self) -> Option<crate::fs::FileStat> {
         None
     }
-    fn lseek(&self, offset: u32, from: u8) -> isize {
+    fn lseek(&self, _offset: u32, _from: u8) -> isize {
         -1
     }
 }
+
+lazy_static! {
+    // 命名管道的缓冲区，以inode编号为key，所有端都关闭后缓冲区被释放
+    static ref FIFOS: Mutex<BTreeMap<u32, Weak<PipeBuffer>>> = Mutex::new(BTreeMap::new());
+}
+
+// 打开命名管道，只读或只写打开时等待对端打开，读写方式打开时不等待
+pub fn open_fifo(inode_seq: u32, readable: bool, writable: bool) -> Pipe {
+    let mut fifos = FIFOS.lock();
+    fifos.retain(|_, buffer| buffer.strong_count() > 0);
+    let buffer = match fifos.get(&inode_seq).and_then(|buffer| buffer.upgrade()) {
+        Some(buffer) => buffer,
+        None => {
+            let buffer = PipeBuffer::new();
+            fifos.insert(inode_seq, Arc::downgrade(&buffer));
+            buffer
+        }
+    };
+    drop(fifos);
+    let (read_opens, write_opens) = {
+        let inner = buffer.inner.lock();
+        (inner.read_opens, inner.write_opens)
+    };
+    let pipe = Pipe::new(readable, writable, Arc::clone(&buffer));
+    if readable && writable {
+        return pipe;
+    }
+    // 对端已经打开，或者在等待期间打开过
+    loop {
+        let inner = buffer.inner.lock();
+        let ready = if readable {
+            inner.writers > 0 || inner.write_opens > write_opens
+        } else {
+            inner.readers > 0 || inner.read_opens > read_opens
+        };
+        if ready {
+            return pipe;
+        }
+        buffer.open_wait.wait(inner);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::config::PAGE_SIZE;
+use crate::mem::allocator::Frame;
+use crate::mem::swap::alloc_user_frame;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// System V风格的共享内存段，每个段是一组物理页
+// 进程attach时将同一组Arc<Frame>映射到自己的地址空间，最后一个引用被释放时物理页才被回收
+pub const IPC_PRIVATE: usize = 0; // 总是创建新的段，只能通过shmid访问
+pub const IPC_CREAT: usize = 1 << 9;
+pub const IPC_EXCL: usize = 1 << 10; // 与IPC_CREAT一起使用，key已存在时失败
+pub const SHM_RDONLY: usize = 1 << 12; // 只读attach
+
+// shmctl的命令
+pub const IPC_RMID: usize = 0;
+pub const IPC_STAT: usize = 2;
+
+const SHM_MAX_SIZE: usize = 16 << 20;
+const SHM_MAX_SEGMENTS: usize = 128;
+
+pub const SHM_ERROR: isize = -1;
+pub const SHM_EXIST_ERROR: isize = -2;
+pub const SHM_NOT_FOUND_ERROR: isize = -3;
+
+// 用户程序使用的shmid_ds，只保留段大小和attach数量
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ShmStat {
+    pub size: usize,
+    pub attaches: usize,
+}
+
+pub struct ShmSegment {
+    key: usize,
+    size: usize,
+    frames: Vec<Arc<Frame>>,
+}
+
+struct ShmTable {
+    next_id: usize,
+    segments: BTreeMap<usize, Arc<ShmSegment>>, // shmid -> 共享内存段
+}
+
+lazy_static! {
+    static ref SHM_TABLE: Mutex<ShmTable> = Mutex::new(ShmTable {
+        next_id: 1,
+        segments: BTreeMap::new(),
+    });
+}
+
+impl ShmSegment {
+    pub fn frames(&self) -> &[Arc<Frame>] {
+        &self.frames
+    }
+
+    // 表中的段自身持有一份引用，其余的引用来自attach的内存段
+    pub fn attaches(&self) -> usize {
+        return Arc::strong_count(&self.frames[0]) - 1;
+    }
+
+    pub fn stat(&self) -> ShmStat {
+        ShmStat {
+            size: self.size,
+            attaches: self.attaches(),
+        }
+    }
+}
+
+// 按key查找或创建共享内存段，返回shmid
+pub fn shm_get(key: usize, size: usize, flags: usize) -> Result<usize, isize> {
+    let mut table = SHM_TABLE.lock();
+    if key != IPC_PRIVATE {
+        let found = table
+            .segments
+            .iter()
+            .find(|(_, seg)| seg.key == key)
+            .map(|(id, seg)| (*id, seg.size));
+        if let Some((id, seg_size)) = found {
+            if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+                return Err(SHM_EXIST_ERROR);
+            }
+            if size > seg_size {
+                return Err(SHM_ERROR);
+            }
+            return Ok(id);
+        }
+        if flags & IPC_CREAT == 0 {
+            return Err(SHM_NOT_FOUND_ERROR);
+        }
+    }
+    if size == 0 || size > SHM_MAX_SIZE || table.segments.len() >= SHM_MAX_SEGMENTS {
+        return Err(SHM_ERROR);
+    }
+    let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    let mut frames: Vec<Arc<Frame>> = Vec::with_capacity(pages);
+    for _ in 0..pages {
+        // 物理页不足时，已经分配的页随frames一起释放
+        let frame = alloc_user_frame().ok_or(SHM_ERROR)?;
+        frame.ppn.as_bytes().fill(0);
+        frames.push(Arc::new(frame));
+    }
+    let id = table.next_id;
+    table.next_id += 1;
+    table.segments.insert(
+        id,
+        Arc::new(ShmSegment {
+            key: key,
+            size: size,
+            frames: frames,
+        }),
+    );
+    return Ok(id);
+}
+
+pub fn shm_segment(shmid: usize) -> Option<Arc<ShmSegment>> {
+    SHM_TABLE.lock().segments.get(&shmid).map(Arc::clone)
+}
+
+// 删除段的shmid，已经attach的进程仍然可以访问，全部detach后物理页被回收
+pub fn shm_remove(shmid: usize) -> isize {
+    match SHM_TABLE.lock().segments.remove(&shmid) {
+        Some(_) => 0,
+        None => SHM_NOT_FOUND_ERROR,
+    }
+}
@@ -0,0 +1,560 @@
+use crate::fs::inode::{open_file, OpenFlags};
+use crate::fs::{File, FileStat, UserBuffer};
+use crate::net::socket::*;
+use crate::sync::wait_queue::WaitQueue;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// unix域socket，用于本机进程间通信
+// 流式socket的每个方向使用一个缓冲区
+const STREAM_BUF_SIZE: usize = 16 * 1024;
+// 数据报socket最多缓存的报文数量和报文长度
+const MAX_QUEUED_DATAGRAMS: usize = 64;
+const MAX_DATAGRAM_SIZE: usize = 16 * 1024;
+const MAX_BACKLOG: usize = 32;
+
+// 单向的字节流缓冲区
+struct Channel {
+    inner: Mutex<ChannelInner>,
+    read_wait: WaitQueue,
+    write_wait: WaitQueue,
+}
+
+struct ChannelInner {
+    buf: VecDeque<u8>,
+    write_closed: bool, // 写端关闭，读完剩余数据后返回0
+    read_closed: bool,  // 读端关闭，写入返回错误
+}
+
+impl Channel {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(ChannelInner {
+                buf: VecDeque::new(),
+                write_closed: false,
+                read_closed: false,
+            }),
+            read_wait: WaitQueue::new(),
+            write_wait: WaitQueue::new(),
+        })
+    }
+
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, isize> {
+        loop {
+            let mut inner = self.inner.lock();
+            if !inner.buf.is_empty() {
+                let len = buf.len().min(inner.buf.len());
+                for (i, byte) in inner.buf.drain(0..len).enumerate() {
+                    buf[i] = byte;
+                }
+                drop(inner);
+                self.write_wait.wake_all();
+                return Ok(len);
+            }
+            if inner.write_closed || inner.read_closed {
+                return Ok(0);
+            }
+            if nonblock {
+                return Err(WOULD_BLOCK_ERROR);
+            }
+            self.read_wait.wait(inner);
+        }
+    }
+
+    // 写入全部数据，缓冲区满时阻塞
+    fn write(&self, data: &[u8]) -> isize {
+        let mut written = 0;
+        while written < data.len() {
+            let mut inner = self.inner.lock();
+            if inner.read_closed || inner.write_closed {
+                return if written > 0 { written as isize } else { BROKEN_PIPE_ERROR };
+            }
+            let space = STREAM_BUF_SIZE - inner.buf.len();
+            if space == 0 {
+                self.write_wait.wait(inner);
+                continue;
+            }
+            let len = space.min(data.len() - written);
+            inner.buf.extend(data[written..written + len].iter());
+            written += len;
+            drop(inner);
+            self.read_wait.wake_all();
+        }
+        return written as isize;
+    }
+
+    fn close_read(&self) {
+        self.inner.lock().read_closed = true;
+        self.write_wait.wake_all();
+        self.read_wait.wake_all();
+    }
+
+    fn close_write(&self) {
+        self.inner.lock().write_closed = true;
+        self.read_wait.wake_all();
+    }
+}
+
+// 数据报socket的接收队列
+struct DatagramQueue {
+    inner: Mutex<DatagramInner>,
+    wait: WaitQueue,
+}
+
+struct DatagramInner {
+    queue: VecDeque<Vec<u8>>,
+    closed: bool,
+}
+
+impl DatagramQueue {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(DatagramInner {
+                queue: VecDeque::new(),
+                closed: false,
+            }),
+            wait: WaitQueue::new(),
+        })
+    }
+
+    fn push(&self, data: &[u8]) -> isize {
+        if data.len() > MAX_DATAGRAM_SIZE {
+            return SOCKET_ERROR;
+        }
+        let mut inner = self.inner.lock();
+        if inner.closed {
+            return CONN_REFUSED_ERROR;
+        }
+        if inner.queue.len() >= MAX_QUEUED_DATAGRAMS {
+            return WOULD_BLOCK_ERROR;
+        }
+        inner.queue.push_back(data.to_vec());
+        drop(inner);
+        self.wait.wake_one();
+        return data.len() as isize;
+    }
+
+    // 缓冲区不足时丢弃报文剩余的部分
+    fn pop(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, isize> {
+        loop {
+            let mut inner = self.inner.lock();
+            if let Some(data) = inner.queue.pop_front() {
+                let len = data.len().min(buf.len());
+                buf[0..len].copy_from_slice(&data[0..len]);
+                return Ok(len);
+            }
+            if nonblock {
+                return Err(WOULD_BLOCK_ERROR);
+            }
+            self.wait.wait(inner);
+        }
+    }
+
+    fn close(&self) {
+        let mut inner = self.inner.lock();
+        inner.closed = true;
+        inner.queue.clear();
+        drop(inner);
+        self.wait.wake_all();
+    }
+}
+
+// 监听socket，connect创建的连接在队列中等待accept
+struct Listener {
+    inner: Mutex<ListenerInner>,
+    wait: WaitQueue,
+}
+
+struct ListenerInner {
+    queue: VecDeque<UnixSocket>,
+    backlog: usize,
+    closed: bool,
+}
+
+enum UnixState {
+    Unconnected,
+    Listening(Arc<Listener>),
+    Connected { rx: Arc<Channel>, tx: Arc<Channel> },
+    Datagram {
+        queue: Arc<DatagramQueue>,
+        peer: Option<Weak<DatagramQueue>>,
+    },
+}
+
+// 路径绑定的socket
+#[derive(Clone)]
+enum Binding {
+    Listener(Weak<Listener>),
+    Datagram(Weak<DatagramQueue>),
+}
+
+lazy_static! {
+    static ref BINDINGS: Mutex<BTreeMap<String, Binding>> = Mutex::new(BTreeMap::new());
+}
+
+fn binding_alive(binding: &Binding) -> bool {
+    match binding {
+        Binding::Listener(l) => l.strong_count() > 0,
+        Binding::Datagram(q) => q.strong_count() > 0,
+    }
+}
+
+enum Endpoint {
+    Stream(Arc<Channel>),
+    Datagram(Arc<DatagramQueue>),
+}
+
+struct UnixInner {
+    path: Option<String>,
+    state: UnixState,
+}
+
+pub struct UnixSocket {
+    socket_type: usize,
+    inner: Mutex<UnixInner>,
+}
+
+impl UnixSocket {
+    pub fn new(socket_type: usize) -> Option<Self> {
+        let state = match socket_type {
+            SOCK_STREAM => UnixState::Unconnected,
+            SOCK_DGRAM => UnixState::Datagram {
+                queue: DatagramQueue::new(),
+                peer: None,
+            },
+            _ => return None,
+        };
+        Some(Self::with_state(socket_type, state))
+    }
+
+    fn with_state(socket_type: usize, state: UnixState) -> Self {
+        Self {
+            socket_type: socket_type,
+            inner: Mutex::new(UnixInner {
+                path: None,
+                state: state,
+            }),
+        }
+    }
+
+    // 创建一对互相连接的socket
+    pub fn pair(socket_type: usize) -> Option<(Self, Self)> {
+        match socket_type {
+            SOCK_STREAM => {
+                let (a, b) = (Channel::new(), Channel::new());
+                let first = UnixState::Connected {
+                    rx: Arc::clone(&a),
+                    tx: Arc::clone(&b),
+                };
+                let second = UnixState::Connected { rx: b, tx: a };
+                Some((
+                    Self::with_state(socket_type, first),
+                    Self::with_state(socket_type, second),
+                ))
+            }
+            SOCK_DGRAM => {
+                let (a, b) = (DatagramQueue::new(), DatagramQueue::new());
+                let first = UnixState::Datagram {
+                    queue: Arc::clone(&a),
+                    peer: Some(Arc::downgrade(&b)),
+                };
+                let second = UnixState::Datagram {
+                    queue: b,
+                    peer: Some(Arc::downgrade(&a)),
+                };
+                Some((
+                    Self::with_state(socket_type, first),
+                    Self::with_state(socket_type, second),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn lookup(path: &str) -> Option<Binding> {
+        let bindings = BINDINGS.lock();
+        bindings.get(path).filter(|b| binding_alive(b)).cloned()
+    }
+
+    fn close(&self) {
+        let mut inner = self.inner.lock();
+        let state = core::mem::replace(&mut inner.state, UnixState::Unconnected);
+        let path = inner.path.take();
+        drop(inner);
+        match state {
+            UnixState::Listening(listener) => {
+                let mut l = listener.inner.lock();
+                l.closed = true;
+                // 还没有accept的连接随之关闭
+                let queued: Vec<UnixSocket> = l.queue.drain(..).collect();
+                drop(l);
+                listener.wait.wake_all();
+                drop(queued);
+            }
+            UnixState::Connected { rx, tx } => {
+                rx.close_read();
+                tx.close_write();
+            }
+            UnixState::Datagram { queue, .. } => queue.close(),
+            UnixState::Unconnected => {}
+        }
+        // 路径可能已经被其他socket重新绑定，只移除失效的绑定
+        if let Some(path) = path {
+            let mut bindings = BINDINGS.lock();
+            if bindings.get(&path).map(|b| !binding_alive(b)).unwrap_or(false) {
+                bindings.remove(&path);
+            }
+        }
+    }
+
+    // 已连接的流式socket返回通道，数据报socket返回接收队列
+    fn rx(&self) -> Result<Endpoint, isize> {
+        match &self.inner.lock().state {
+            UnixState::Connected { rx, .. } => Ok(Endpoint::Stream(Arc::clone(rx))),
+            UnixState::Datagram { queue, .. } => Ok(Endpoint::Datagram(Arc::clone(queue))),
+            _ => Err(NOT_CONNECTED_ERROR),
+        }
+    }
+
+    fn tx(&self) -> Result<Endpoint, isize> {
+        match &self.inner.lock().state {
+            UnixState::Connected { tx, .. } => Ok(Endpoint::Stream(Arc::clone(tx))),
+            UnixState::Datagram { peer: Some(peer), .. } => match peer.upgrade() {
+                Some(peer) => Ok(Endpoint::Datagram(peer)),
+                None => Err(CONN_REFUSED_ERROR),
+            },
+            _ => Err(NOT_CONNECTED_ERROR),
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Socket for UnixSocket {
+    fn domain(&self) -> usize {
+        AF_UNIX
+    }
+
+    fn bind(&self, _addr: SocketAddr) -> isize {
+        SOCKET_ERROR
+    }
+
+    fn connect(&self, _addr: SocketAddr) -> isize {
+        SOCKET_ERROR
+    }
+
+    // 绑定到文件系统中的路径，路径不存在时创建一个文件
+    // 与Linux不同，关闭socket后可以直接重新绑定同一个路径
+    fn bind_path(&self, path: &str) -> isize {
+        let mut inner = self.inner.lock();
+        if inner.path.is_some() {
+            return SOCKET_ERROR;
+        }
+        let mut bindings = BINDINGS.lock();
+        bindings.retain(|_, b| binding_alive(b));
+        if bindings.contains_key(path) {
+            return ADDR_IN_USE_ERROR;
+        }
+        match open_file(path, OpenFlags::CREATE | OpenFlags::RDWR) {
+            Ok(inode) if !inode.is_dir() => {}
+            _ => return SOCKET_ERROR,
+        }
+        if let UnixState::Datagram { queue, .. } = &inner.state {
+            bindings.insert(String::from(path), Binding::Datagram(Arc::downgrade(queue)));
+        }
+        inner.path = Some(String::from(path));
+        return 0;
+    }
+
+    fn connect_path(&self, path: &str) -> isize {
+        let binding = Self::lookup(path);
+        let mut inner = self.inner.lock();
+        if let UnixState::Datagram { peer, .. } = &mut inner.state {
+            return match binding {
+                Some(Binding::Datagram(queue)) => {
+                    *peer = Some(queue);
+                    0
+                }
+                _ => CONN_REFUSED_ERROR,
+            };
+        }
+        if !matches!(inner.state, UnixState::Unconnected) {
+            return SOCKET_ERROR;
+        }
+        let listener = match binding {
+            Some(Binding::Listener(listener)) => listener.upgrade(),
+            _ => None,
+        };
+        let listener = match listener {
+            Some(listener) => listener,
+            None => return CONN_REFUSED_ERROR,
+        };
+        let mut l = listener.inner.lock();
+        if l.closed || l.queue.len() >= l.backlog {
+            return CONN_REFUSED_ERROR;
+        }
+        // 连接立即建立，服务端的socket放入accept队列
+        let (c2s, s2c) = (Channel::new(), Channel::new());
+        let server = UnixState::Connected {
+            rx: Arc::clone(&c2s),
+            tx: Arc::clone(&s2c),
+        };
+        l.queue.push_back(Self::with_state(SOCK_STREAM, server));
+        drop(l);
+        listener.wait.wake_one();
+        inner.state = UnixState::Connected { rx: s2c, tx: c2s };
+        return 0;
+    }
+
+    fn listen(&self, backlog: usize) -> isize {
+        let mut inner = self.inner.lock();
+        if self.socket_type != SOCK_STREAM || !matches!(inner.state, UnixState::Unconnected) {
+            return SOCKET_ERROR;
+        }
+        let path = match &inner.path {
+            Some(path) => path.clone(),
+            None => return SOCKET_ERROR,
+        };
+        let mut bindings = BINDINGS.lock();
+        if bindings.get(&path).map(|b| binding_alive(b)).unwrap_or(false) {
+            return ADDR_IN_USE_ERROR;
+        }
+        let listener = Arc::new(Listener {
+            inner: Mutex::new(ListenerInner {
+                queue: VecDeque::new(),
+                backlog: backlog.max(1).min(MAX_BACKLOG),
+                closed: false,
+            }),
+            wait: WaitQueue::new(),
+        });
+        bindings.insert(path, Binding::Listener(Arc::downgrade(&listener)));
+        inner.state = UnixState::Listening(listener);
+        return 0;
+    }
+
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize> {
+        let listener = match &self.inner.lock().state {
+            UnixState::Listening(listener) => Arc::clone(listener),
+            _ => return Err(SOCKET_ERROR),
+        };
+        loop {
+            let mut l = listener.inner.lock();
+            if let Some(socket) = l.queue.pop_front() {
+                return Ok((Arc::new(socket), SocketAddr::unspecified()));
+            }
+            if l.closed {
+                return Err(SOCKET_ERROR);
+            }
+            listener.wait.wait(l);
+        }
+    }
+
+    fn shutdown(&self, how: usize) -> isize {
+        match &self.inner.lock().state {
+            UnixState::Connected { rx, tx } => {
+                if how == SHUT_RD || how == SHUT_RDWR {
+                    rx.close_read();
+                }
+                if how == SHUT_WR || how == SHUT_RDWR {
+                    tx.close_write();
+                }
+                0
+            }
+            _ => NOT_CONNECTED_ERROR,
+        }
+    }
+
+    fn send_to(&self, data: &[u8], addr: Option<SocketAddr>) -> isize {
+        if addr.is_some() {
+            return SOCKET_ERROR;
+        }
+        match self.tx() {
+            Ok(Endpoint::Stream(tx)) => tx.write(data),
+            Ok(Endpoint::Datagram(peer)) => peer.push(data),
+            Err(code) => code,
+        }
+    }
+
+    fn send_to_path(&self, data: &[u8], path: &str) -> isize {
+        if self.socket_type != SOCK_DGRAM {
+            return SOCKET_ERROR;
+        }
+        match Self::lookup(path) {
+            Some(Binding::Datagram(queue)) => match queue.upgrade() {
+                Some(queue) => queue.push(data),
+                None => CONN_REFUSED_ERROR,
+            },
+            _ => CONN_REFUSED_ERROR,
+        }
+    }
+
+    fn recv_from(&self, buf: &mut [u8], nonblock: bool) -> Result<(usize, SocketAddr), isize> {
+        let len = match self.rx()? {
+            Endpoint::Stream(rx) => rx.read(buf, nonblock)?,
+            Endpoint::Datagram(queue) => queue.pop(buf, nonblock)?,
+        };
+        return Ok((len, SocketAddr::unspecified()));
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn local_path(&self) -> Option<String> {
+        self.inner.lock().path.clone()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    // unix socket使用等待队列阻塞，不支持接收超时
+    fn set_recv_timeout(&self, _timeout: Option<usize>) {}
+
+    fn max_io_size(&self) -> usize {
+        if self.is_stream() {
+            STREAM_BUF_SIZE
+        } else {
+            MAX_DATAGRAM_SIZE
+        }
+    }
+
+    fn is_stream(&self) -> bool {
+        self.socket_type == SOCK_STREAM
+    }
+}
+
+impl File for UnixSocket {
+    fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
+        let mut data = vec![0u8; buf.length().min(STREAM_BUF_SIZE)];
+        match self.recv_from(&mut data, false) {
+            Ok((len, _)) if len > 0 => {
+                buf.write(0, &data[0..len]);
+                len
+            }
+            _ => 0,
+        }
+    }
+    // 流式socket分段写入Channel，数据报超过MAX_DATAGRAM_SIZE时直接返回0
+    fn write<'a>(&self, buf: &mut UserBuffer) -> usize {
+        return write_to_socket(self, buf);
+    }
+    fn fstat(&self) -> Option<FileStat> {
+        None
+    }
+    fn lseek(&self, _offset: u32, _from: u8) -> isize {
+        -1
+    }
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
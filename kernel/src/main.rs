@@ -17,6 +17,7 @@ mod driver;
 mod fs;
 mod ipc;
 mod mem;
+mod net;
 mod proc;
 mod shutdown;
 mod sync;
@@ -42,17 +43,30 @@ pub unsafe fn rust_main() {
         // cpu0 init kernel
         driver::uart::Uart::init();
         console::print_banner();
+        let board = arch::riscv::qemu::layout::board();
+        kernel!(
+            "board: {} harts, memory [{:#x}, {:#x}), {} virtio-mmio slots",
+            board.harts,
+            board.mem_start,
+            board.mem_end,
+            board.virtio_count
+        );
         trap::trap_init();
         mem::init();
         kernel!("kernel memory initialized");
         driver::init();
         kernel!("drivers initialized");
+        net::init();
         proc::init_proc();
         mem::kernel::switch_to_kernel_space();
         kernel!("hart0 booted, kernel initialized");
         KERNEL_INITED.store(1, Ordering::SeqCst);
     } else {
         while KERNEL_INITED.load(Ordering::SeqCst) == 0 {}
+        // 其他cpu使用hart0初始化好的内核页表
+        trap::trap_init();
+        mem::kernel::switch_to_kernel_space();
+        kernel!("hart{} booted", id);
     }
     task::scheduler::schedule();
 }
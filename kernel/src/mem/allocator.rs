@@ -1,6 +1,7 @@
 use super::address::*;
-use crate::config::PHYS_MEM_LIMIT;
-use alloc::vec::Vec;
+use super::heap::heap_stats;
+use crate::arch::riscv::qemu::layout::board;
+use crate::config::PAGE_SIZE;
 use lazy_static::lazy_static;
 use spin::mutex::SpinMutex;
 
@@ -9,11 +10,18 @@ extern "C" {
     fn ekernel();
 }
 
+// 最大的块包含2^MAX_ORDER个物理页，即4MiB
+pub const MAX_ORDER: usize = 10;
+
 pub trait MemAllocator {
     fn init(&mut self);
     fn new() -> Self;
-    fn alloc(&mut self) -> Option<PhysPageNumber>;
-    fn dealloc(&mut self, ppn: PhysPageNumber);
+    // 分配2^order个连续的物理页，返回第一个物理页
+    fn alloc(&mut self, order: usize) -> Option<PhysPageNumber>;
+    fn dealloc(&mut self, ppn: PhysPageNumber, order: usize);
+    fn free_count(&self) -> usize;
+    // 大小为2^order的空闲块数量
+    fn free_blocks(&self, order: usize) -> usize;
 }
 
 // Frame 物理页帧
@@ -21,14 +29,45 @@ pub struct Frame {
     pub ppn: PhysPageNumber,
 }
 
-// 物理页内存分配器
-pub struct VecAllocator {
+// 2^order个物理地址连续的物理页，用于设备DMA
+pub struct ContiguousFrames {
+    pub ppn: PhysPageNumber,
+    pub order: usize,
+}
+
+// 物理内存使用情况，通过mem_info系统调用返回给用户程序
+#[repr(C)]
+pub struct MemInfo {
+    pub total: usize,                         // 可分配的物理页数量
+    pub free: usize,                          // 空闲的物理页数量
+    pub free_blocks: [usize; MAX_ORDER + 1], // 每个order的空闲块数量
+    pub heap_total: usize,                    // 内核堆的大小
+    pub heap_used: usize,                     // 内核堆已经分配的字节数
+}
+
+const NO_BLOCK: usize = usize::MAX;
+const NOT_FREE: u8 = u8::MAX;
+
+// 伙伴系统物理页分配器
+// 空闲块按order组成双向链表，链表指针保存在空闲块第一个物理页的开头，
+// 每个物理页的order记录在管理区域开头的几个物理页中，分配器不使用内核堆，内核堆扩展时可以从这里分配物理页
+pub struct BuddyAllocator {
+    start_ppn: usize,
     end_ppn: usize,
-    current_ppn: usize,
-    recycled: Vec<usize>,
+    heads: [usize; MAX_ORDER + 1],  // 每个order空闲链表的第一个块
+    counts: [usize; MAX_ORDER + 1], // 每个order空闲块的数量
+    orders: &'static mut [u8],      // 空闲块第一个物理页记录块的order，其他物理页为NOT_FREE
+    free: usize,
+}
+
+// 空闲链表的节点，保存在空闲块的第一个物理页中
+#[repr(C)]
+struct FreeBlock {
+    next: usize,
+    prev: usize,
 }
 
-type MemAllocatorImpl = VecAllocator;
+type MemAllocatorImpl = BuddyAllocator;
 
 lazy_static! {
     pub static ref ALLOCATOR: SpinMutex<MemAllocatorImpl> = SpinMutex::new(MemAllocatorImpl::new());
@@ -42,47 +81,198 @@ pub fn init() {
 
 pub fn alloc() -> Option<Frame> {
     let mut allocator = ALLOCATOR.lock();
-    let ppn = allocator.alloc();
+    let ppn = allocator.alloc(0);
     drop(allocator);
     return ppn.map(|p| Frame { ppn: p });
 }
 
+// 分配至少pages个物理地址连续的物理页，页数向上取整到2的幂
+pub fn alloc_contiguous(pages: usize) -> Option<ContiguousFrames> {
+    let order = pages.max(1).next_power_of_two().trailing_zeros() as usize;
+    if order > MAX_ORDER {
+        return None;
+    }
+    let mut allocator = ALLOCATOR.lock();
+    let ppn = allocator.alloc(order);
+    drop(allocator);
+    return ppn.map(|p| ContiguousFrames { ppn: p, order: order });
+}
+
+// 空闲的物理页数量
+pub fn free_frames() -> usize {
+    return ALLOCATOR.lock().free_count();
+}
+
+pub fn mem_info() -> MemInfo {
+    // 内核堆扩展时会先锁住堆再锁住分配器，这里要在锁住分配器之前获取堆的信息
+    let (heap_total, heap_used) = heap_stats();
+    let allocator = ALLOCATOR.lock();
+    let mut info = MemInfo {
+        total: allocator.end_ppn - allocator.start_ppn,
+        free: allocator.free_count(),
+        free_blocks: [0; MAX_ORDER + 1],
+        heap_total: heap_total,
+        heap_used: heap_used,
+    };
+    for order in 0..=MAX_ORDER {
+        info.free_blocks[order] = allocator.free_blocks(order);
+    }
+    return info;
+}
+
 pub fn dealloc(ppn: PhysPageNumber) {
     let mut allocator = ALLOCATOR.lock();
-    allocator.dealloc(ppn);
+    allocator.dealloc(ppn, 0);
     drop(allocator);
 }
 
-impl MemAllocator for VecAllocator {
+impl BuddyAllocator {
+    fn block(ppn: usize) -> &'static mut FreeBlock {
+        unsafe {
+            return ((ppn * PAGE_SIZE) as *mut FreeBlock).as_mut().unwrap();
+        }
+    }
+
+    fn push(&mut self, ppn: usize, order: usize) {
+        let head = self.heads[order];
+        let block = Self::block(ppn);
+        block.next = head;
+        block.prev = NO_BLOCK;
+        if head != NO_BLOCK {
+            Self::block(head).prev = ppn;
+        }
+        self.heads[order] = ppn;
+        self.counts[order] += 1;
+        self.orders[ppn - self.start_ppn] = order as u8;
+    }
+
+    // 从空闲链表中删除块，并清空链表指针，空闲块除链表指针外的数据都是0
+    fn remove(&mut self, ppn: usize, order: usize) {
+        let block = Self::block(ppn);
+        let (next, prev) = (block.next, block.prev);
+        if prev != NO_BLOCK {
+            Self::block(prev).next = next;
+        } else {
+            self.heads[order] = next;
+        }
+        if next != NO_BLOCK {
+            Self::block(next).prev = prev;
+        }
+        block.next = 0;
+        block.prev = 0;
+        self.counts[order] -= 1;
+        self.orders[ppn - self.start_ppn] = NOT_FREE;
+    }
+
+    // ppn开始的块是否是大小为2^order的空闲块
+    fn is_free(&self, ppn: usize, order: usize) -> bool {
+        return ppn >= self.start_ppn
+            && ppn + (1 << order) <= self.end_ppn
+            && self.orders[ppn - self.start_ppn] == order as u8;
+    }
+}
+
+impl MemAllocator for BuddyAllocator {
     fn new() -> Self {
         return Self {
-            current_ppn: 0,
+            start_ppn: 0,
             end_ppn: 0,
-            recycled: Vec::new(),
+            heads: [NO_BLOCK; MAX_ORDER + 1],
+            counts: [0; MAX_ORDER + 1],
+            orders: &mut [],
+            free: 0,
         };
     }
-    // 初始化物理内存区域，[ekernel,PhysLimit)
+    // 初始化物理内存区域，[ekernel,PhysLimit)，按地址对齐切分成尽可能大的块
+    // orders数组占用区域开头的物理页，剩下的物理页由分配器管理
     fn init(&mut self) {
-        self.current_ppn = PhysAddr(ekernel as usize).page_number().0;
-        self.end_ppn = PhysAddr(PHYS_MEM_LIMIT).page_number().0;
+        let start_ppn = PhysAddr(ekernel as usize).page_number().0;
+        self.end_ppn = PhysAddr(board().alloc_end()).page_number().0;
+        let pages = self.end_ppn - start_ppn;
+        self.start_ppn = start_ppn + (pages + PAGE_SIZE - 1) / PAGE_SIZE;
+        self.orders = unsafe {
+            core::slice::from_raw_parts_mut(
+                PhysPageNumber(start_ppn).base_addr() as *mut u8,
+                self.end_ppn - self.start_ppn,
+            )
+        };
+        self.orders.fill(NOT_FREE);
+        let mut ppn = self.start_ppn;
+        while ppn < self.end_ppn {
+            let mut order = MAX_ORDER;
+            while ppn % (1 << order) != 0 || ppn + (1 << order) > self.end_ppn {
+                order -= 1;
+            }
+            self.push(ppn, order);
+            self.free += 1 << order;
+            ppn += 1 << order;
+        }
     }
-    fn alloc(&mut self) -> Option<PhysPageNumber> {
-        // 优先分配回收队列中的页
-        if !self.recycled.is_empty() {
-            return self.recycled.pop().map(|ppn| PhysPageNumber(ppn));
-        } else {
-            if self.current_ppn == self.end_ppn {
-                return None;
-            } else {
-                let ppn = self.current_ppn;
-                self.current_ppn += 1;
-                return Some(PhysPageNumber(ppn));
+
+    fn alloc(&mut self, order: usize) -> Option<PhysPageNumber> {
+        // 找到不小于order的最小空闲块
+        let mut current = order;
+        while current <= MAX_ORDER && self.heads[current] == NO_BLOCK {
+            current += 1;
+        }
+        if current > MAX_ORDER {
+            return None;
+        }
+        let ppn = self.heads[current];
+        self.remove(ppn, current);
+        // 拆分大块，后一半放回空闲链表
+        while current > order {
+            current -= 1;
+            self.push(ppn + (1 << current), current);
+        }
+        self.free -= 1 << order;
+        return Some(PhysPageNumber(ppn));
+    }
+
+    // 回收块，伙伴也空闲时合并成更大的块
+    fn dealloc(&mut self, ppn: PhysPageNumber, order: usize) {
+        let mut ppn = ppn.0;
+        let mut current = order;
+        self.free += 1 << order;
+        while current < MAX_ORDER {
+            let buddy = ppn ^ (1 << current);
+            if !self.is_free(buddy, current) {
+                break;
             }
+            self.remove(buddy, current);
+            ppn = ppn.min(buddy);
+            current += 1;
         }
+        self.push(ppn, current);
+    }
+
+    fn free_count(&self) -> usize {
+        return self.free;
+    }
+
+    fn free_blocks(&self, order: usize) -> usize {
+        return self.counts[order];
+    }
+}
+
+impl ContiguousFrames {
+    pub fn base_addr(&self) -> usize {
+        return self.ppn.base_addr();
     }
 
-    fn dealloc(&mut self, ppn: PhysPageNumber) {
-        self.recycled.push(ppn.0);
+    pub fn size(&self) -> usize {
+        return PAGE_SIZE << self.order;
+    }
+}
+
+impl MemInfo {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
     }
 }
 
@@ -94,3 +284,36 @@ impl Drop for Frame {
         dealloc(self.ppn);
     }
 }
+
+impl Drop for ContiguousFrames {
+    fn drop(&mut self) {
+        unsafe {
+            core::ptr::write_bytes(self.base_addr() as *mut u8, 0, self.size());
+        }
+        let mut allocator = ALLOCATOR.lock();
+        allocator.dealloc(self.ppn, self.order);
+        drop(allocator);
+    }
+}
+
+#[allow(unused)]
+pub fn buddy_test() {
+    let free = free_frames();
+    let a = alloc_contiguous(3).unwrap();
+    assert_eq!(a.order, 2);
+    assert_eq!(a.ppn.0 % 4, 0);
+    let b = alloc().unwrap();
+    let c = alloc_contiguous(1 << MAX_ORDER).unwrap();
+    assert_eq!(c.ppn.0 % (1 << MAX_ORDER), 0);
+    assert_eq!(free_frames(), free - 4 - 1 - (1 << MAX_ORDER));
+    assert!(alloc_contiguous((1 << MAX_ORDER) + 1).is_none());
+    drop(a);
+    drop(b);
+    drop(c);
+    // 全部回收后合并回原来的块
+    assert_eq!(free_frames(), free);
+    let info = mem_info();
+    let pages: usize = (0..=MAX_ORDER).map(|o| info.free_blocks[o] << o).sum();
+    assert_eq!(pages, info.free);
+    println!("buddy_test passed!");
+}
@@ -1,37 +1,44 @@
 use super::address::*;
-use super::allocator::alloc;
 use super::memory_set::*;
+use super::mmap::is_accessible;
 use super::page_table::*;
 use crate::config::*;
 use crate::config::{TRAMPOLINE, TRAP_CONTEXT_BOTTOM};
 use alloc::sync::Arc;
 use alloc::vec;
+use alloc::vec::Vec;
 use elf::endian::AnyEndian;
 use elf::ElfBytes;
+use simplefs::vfs::Inode;
 
 const PT_LOAD: u32 = 1;
+// 64位elf文件头的大小
+const ELF_HEADER_SIZE: usize = 64;
 
 // 应用程序虚拟地址空间布局：
 //
-// | .text | .data | heap | stacks... | trap_ctx ... | trampoline |
-// heap大小固定，stack从高地址逆向增长
-// stacks: 每个线程都有一个用户栈，位置 = base + tid * stack_size
+// | .text | .data | heap | mmap ... | stacks... | trap_ctx ... | trampoline |
+// heap紧跟在elf段之后，通过brk向高地址增长，最高不超过mmap区域，stack从高地址逆向增长
+// stacks: 每个线程都有一个用户栈区域，位置 = base + tid * region_size，栈从区域顶端开始
+// 访问栈下方的页时栈向下增长，直到达到进程的栈大小上限，区域底部的守护页不会被映射
 // trap_ctx: 每个线程独有的陷入上下文，从高地址向低地址按tid逆向排列
 // trampoline在虚拟页最高页，映射到.trampoline代码段
 // kstask: 每个线程有一个内核栈，内核栈由内核的全局id分配，与tid无关
 //
 // memory set 只包括了进程相关的内存区域初始化，线程的栈和上下文映射在tcb创建时完成
+// elf段和线程栈只记录范围，第一次访问触发PageFault时才分配物理页
 impl MemorySet {
-    // 从app的elf文件创建内存集合
-    pub fn from_elf_data(data: &[u8]) -> (Self, usize, usize) {
+    // 从app的elf文件创建内存集合，elf段只记录在文件中的位置，页在第一次访问时读取
+    pub fn from_elf(inode: Arc<Inode>) -> (Self, usize, usize) {
         let mut memset = Self::new();
-        let elf = ElfBytes::<AnyEndian>::minimal_parse(data);
+        let data = read_elf_headers(&inode);
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(data.as_slice());
         if let Err(error) = elf {
             error!("load elf error: {}", error);
             panic!("load elf data failed");
         }
         let elf = elf.unwrap();
-        // 映射elf segments
+        // 记录elf segments
         let segments = elf.segments().unwrap();
         let mut max_vpn = 0;
         for seg in segments {
@@ -39,23 +46,34 @@ impl MemorySet {
                 // vpn range
                 let start_va = VirtAddr(seg.p_vaddr as usize);
                 let end_va = VirtAddr(seg.p_vaddr as usize + seg.p_memsz as usize + PAGE_SIZE);
-                let flags = elf_flags_to_pte_flags(seg.p_flags as usize);
-                memset.insert_area(
-                    MemoryArea::new(
-                        start_va.vpn(),
-                        end_va.vpn(),
-                        MapMode::Indirect,
-                        elf_flags_to_pte_flags(seg.p_flags as usize) | MemPermission::U.bits(),
-                    ), // RWX flags
-                    Some(elf.segment_data(&seg).unwrap()),
-                ); // copy data
+                let mut area = MemoryArea::new(
+                    start_va.vpn(),
+                    end_va.vpn(),
+                    MapMode::Indirect,
+                    elf_flags_to_pte_flags(seg.p_flags as usize) | MemPermission::U.bits(),
+                ); // RWX flags
+                area.elf = Some(ElfSegment {
+                    inode: Arc::clone(&inode),
+                    vaddr: seg.p_vaddr as usize,
+                    offset: seg.p_offset as usize,
+                    file_size: seg.p_filesz as usize,
+                });
+                memset.reserve_area(area);
                 max_vpn = end_va.vpn().0;
             }
         }
         memset.map_trampoline();
-        // 用户空间的线程栈底：elf段之后的第一个页
-        let stack_base = VirtPageNumber(max_vpn + 1).base_addr();
-        return (memset, elf.ehdr.e_entry as usize, stack_base);
+        // 用户堆从elf段之后的第一个页开始，初始大小为0
+        let heap_bottom = VirtPageNumber(max_vpn + 1);
+        memset.areas.push(MemoryArea::new(
+            heap_bottom,
+            heap_bottom,
+            MapMode::Indirect,
+            (MemPermission::R | MemPermission::W | MemPermission::U).bits(),
+        ));
+        memset.heap_bottom = heap_bottom.base_addr();
+        memset.brk = heap_bottom.base_addr();
+        return (memset, elf.ehdr.e_entry as usize, USER_STACK_BOTTOM);
     }
 
     // 从父进程地址空间构建子进程地址空间
@@ -65,24 +83,37 @@ impl MemorySet {
         parent.areas.iter().for_each(|area| {
             // 跳过ustack和trap_ctx
             let area_start = area.start_vpn.base_addr();
-            if area_start < TRAMPOLINE && area_start >= stack_base {
+            let stack_top = stack_base + MAX_THREADS * USER_STACK_REGION_SIZE;
+            if (area_start >= stack_base && area_start < stack_top)
+                || (area_start >= TRAP_CONTEXT_BOTTOM && area_start < TRAMPOLINE)
+            {
                 return;
             }
             let mut child_area =
                 MemoryArea::new(area.start_vpn, area.end_vpn, area.mode, area.perm);
+            child_area.shared = area.shared;
+            child_area.file = area.file.clone();
+            child_area.elf = area.elf.clone();
             // 将子进程的memset的vpn映射到父进程的物理页，并设置不可写（write触发PageFault，实现CopyOnWrite）
-            for vpn in area.start_vpn.0..area.end_vpn.0 {
-                let frame = area.frames.get(&VirtPageNumber(vpn)).unwrap();
-                let flags = set_unwritable(area.perm);
-                memset.page_table.map(VirtPageNumber(vpn), frame.ppn, flags);
+            // 共享内存段保持原来的权限，父子进程继续共享同一组物理页
+            // 文件映射中还没有加载的页不在frames中，子进程访问时再从页缓存加载
+            for (vpn, frame) in area.frames.iter() {
+                let flags = if area.shared {
+                    area.perm
+                } else {
+                    set_unwritable(area.perm)
+                };
+                if is_accessible(area.perm) {
+                    memset.page_table.map(*vpn, frame.ppn, flags);
+                }
                 // 子进程要持有物理页的引用计数，避免父进程丢弃物理页后，物理页被自动回收
-                child_area
-                    .frames
-                    .insert(VirtPageNumber(vpn), Arc::clone(&frame));
+                child_area.frames.insert(*vpn, Arc::clone(frame));
             }
             memset.areas.push(child_area);
         });
 
+        memset.heap_bottom = parent.heap_bottom;
+        memset.brk = parent.brk;
         // 子进程的栈与父进程相同，所以
         memset.map_trampoline();
         return memset;
@@ -90,45 +121,164 @@ impl MemorySet {
 
     // 删除可写权限，使写父进程和子进程写内存触发PageFault，然后在trap中进行CopyOnWrite
     pub fn remove_write_permission(&mut self) {
-        self.areas.iter().for_each(|mut area| {
-            // trap context 父子进程不共享
-            if area.start_vpn != VirtAddr(TRAP_CONTEXT).vpn()
+        self.areas.iter().for_each(|area| {
+            // trap context不是用户页，父子进程不共享，共享内存段不需要CopyOnWrite
+            if area.perm & MemPermission::U.bits() != 0
+                && !area.shared
                 && (MemPermission::W.bits() & area.perm != 0)
             {
-                // 在页表上将每个vpn的pte设置不可写
-                for vpn in area.start_vpn.0..area.end_vpn.0 {
-                    let pte = self.page_table.translate(VirtPageNumber(vpn)).unwrap();
+                // 在页表上将每个已经映射的vpn的pte设置不可写
+                for vpn in area.frames.keys() {
+                    let pte = self.page_table.translate(*vpn).unwrap();
                     pte.set_unwritable();
                 }
             }
         });
     }
 
+    // 写入CopyOnWrite的页，Arc<Frame>的引用计数就是共享该物理页的地址空间数量（页缓存也持有一个引用）
+    // 只剩当前地址空间持有物理页时直接恢复写权限，否则拷贝到新的物理页
+    // 恢复的权限是内存段原来的权限，只读的内存段写入时是权限错误
     pub fn copy_on_write(&mut self, vpn: VirtPageNumber) -> bool {
-        let mut vpn_valid = false;
-        for area in self.areas.iter_mut() {
-            if !area.frames.contains_key(&vpn) {
-                continue;
+        // 共享内存段和没有写权限的内存段，写入错误是权限错误，不能拷贝
+        let index = match self.areas.iter().position(|area| {
+            area.frames.contains_key(&vpn)
+                && !area.shared
+                && area.perm & MemPermission::W.bits() != 0
+        }) {
+            Some(index) => index,
+            None => return false,
+        };
+        let area = &mut self.areas[index];
+        if Arc::get_mut(area.frames.get_mut(&vpn).unwrap()).is_some() {
+            let ppn = area.frames[&vpn].ppn;
+            self.page_table.remap(vpn, ppn, area.perm);
+            return true;
+        }
+        // 新分配一个物理页，内存不足时可能换出其他页
+        let new_frame = match self.alloc_frame() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let area = &mut self.areas[index];
+        // 分配物理页时该页可能被换出，重新访问时再读回
+        // 删除Arc<Frame>使物理页的引用计数减少，最后一个引用删除时被回收
+        let frame = match area.frames.remove(&vpn) {
+            Some(frame) => frame,
+            None => return true,
+        };
+        // 将原来物理页的数据拷贝
+        new_frame
+            .ppn
+            .as_bytes()
+            .copy_from_slice(frame.ppn.as_bytes());
+        self.page_table.remap(vpn, new_frame.ppn, area.perm);
+        area.frames.insert(vpn, Arc::new(new_frame));
+        drop(frame);
+        return true;
+    }
+
+    // 访问线程栈下方的地址时向下扩展栈内存段，新的页在访问时分配
+    // 扩展后的栈大小不能超过limit，也不能进入栈区域底部的守护页
+    pub fn grow_stack(&mut self, vpn: VirtPageNumber, stack_base: usize, limit: usize) -> bool {
+        let va = vpn.base_addr();
+        if va < stack_base || va >= stack_base + MAX_THREADS * USER_STACK_REGION_SIZE {
+            return false;
+        }
+        let region_bottom = va - (va - stack_base) % USER_STACK_REGION_SIZE;
+        let region_top = region_bottom + USER_STACK_REGION_SIZE;
+        if va < region_bottom + GUARD_PAGE || region_top - va > limit {
+            return false;
+        }
+        // 区域中的线程栈，线程已经退出时没有栈
+        let top_vpn = VirtAddr(region_top).vpn();
+        match self.areas.iter_mut().find(|area| area.end_vpn == top_vpn) {
+            Some(area) if vpn < area.start_vpn => {
+                area.start_vpn = vpn;
+                return true;
+            }
+            _ => return false,
+        }
+    }
+
+    // 修改用户堆的结束地址，扩展的页立即分配并清零，收缩时释放超出的页
+    // 返回新的结束地址，失败时返回原来的结束地址，new_brk为0时用来查询当前值
+    pub fn set_brk(&mut self, new_brk: usize) -> usize {
+        if new_brk < self.heap_bottom || new_brk > USER_HEAP_TOP {
+            return self.brk;
+        }
+        let heap_start = VirtAddr(self.heap_bottom).vpn();
+        let old_end = VirtPageNumber((self.brk + PAGE_SIZE - 1) / PAGE_SIZE);
+        let new_end = VirtPageNumber((new_brk + PAGE_SIZE - 1) / PAGE_SIZE);
+        let index = match self.areas.iter().position(|area| area.start_vpn == heap_start) {
+            Some(index) => index,
+            None => return self.brk,
+        };
+        if new_end > old_end {
+            if self.overlaps(old_end, new_end) {
+                return self.brk;
             }
-            vpn_valid = true;
-            // 删除Arc<Frame>使物理页的引用计数减少，最终被回收
-            let frame = area.frames.remove(&vpn).unwrap();
-            // 新分配一个物理页，将原来物理页的数据拷贝
-            let new_frame = alloc().unwrap();
-            new_frame
-                .ppn
-                .as_bytes()
-                .copy_from_slice(frame.ppn.as_bytes());
-            // 修改页表，设置writable，设置新ppn
-            let pte = self.page_table.translate(vpn).unwrap();
-            pte.set_ppn(new_frame.ppn);
-            pte.set_writable();
-            area.frames.insert(vpn, Arc::new(new_frame));
-            drop(frame);
-            break;
+            // 先分配全部物理页，内存不足时堆保持不变
+            let mut frames = Vec::with_capacity(new_end.0 - old_end.0);
+            for _ in old_end.0..new_end.0 {
+                match self.alloc_frame() {
+                    Some(frame) => {
+                        frame.ppn.as_bytes().fill(0);
+                        frames.push(frame);
+                    }
+                    None => return self.brk,
+                }
+            }
+            let area = &mut self.areas[index];
+            for (i, frame) in frames.into_iter().enumerate() {
+                let vpn = VirtPageNumber(old_end.0 + i);
+                self.page_table.map(vpn, frame.ppn, area.perm);
+                area.frames.insert(vpn, Arc::new(frame));
+            }
+            area.end_vpn = new_end;
+        } else if new_end < old_end {
+            let area = &mut self.areas[index];
+            // 已经换出的页在解除映射时释放交换区槽位
+            for vpn in new_end.0..old_end.0 {
+                area.frames.remove(&VirtPageNumber(vpn));
+                self.page_table.unmap(VirtPageNumber(vpn));
+            }
+            area.end_vpn = new_end;
+        }
+        self.brk = new_brk;
+        return new_brk;
+    }
+}
+
+impl ElfSegment {
+    // 读取vpn页中属于段文件内容的部分，页的其余部分保持不变
+    pub fn load_page(&self, vpn: VirtPageNumber, page: &mut [u8]) {
+        let page_start = vpn.base_addr();
+        let start = page_start.max(self.vaddr);
+        let end = (page_start + PAGE_SIZE).min(self.vaddr + self.file_size);
+        if start >= end {
+            return;
         }
-        return vpn_valid;
+        self.inode.read(
+            (self.offset + start - self.vaddr) as u32,
+            &mut page[(start - page_start)..(end - page_start)],
+        );
+    }
+}
+
+// 读取elf头和program header表，段的数据在访问时再读取
+fn read_elf_headers(inode: &Inode) -> Vec<u8> {
+    let mut data = vec![0u8; ELF_HEADER_SIZE];
+    inode.read(0, &mut data);
+    let len = match ElfBytes::<AnyEndian>::minimal_parse(data.as_slice()) {
+        Ok(elf) => elf.ehdr.e_phoff as usize + elf.ehdr.e_phnum as usize * elf.ehdr.e_phentsize as usize,
+        Err(_) => return data,
+    };
+    if len > data.len() {
+        data.resize(len, 0);
+        inode.read(0, &mut data);
     }
+    return data;
 }
 
 // elf flags 转换 pte flags
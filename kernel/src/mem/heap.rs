@@ -1,16 +1,68 @@
-use crate::config::KERNEL_HEAP_SIZE;
+use super::allocator::alloc_contiguous;
+use super::slab::find_cache;
+use crate::config::{KERNEL_HEAP_GROW_SIZE, KERNEL_HEAP_INIT_SIZE, PAGE_SIZE};
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
 
 // 必须为mut，否则会被编译器分配到rodata只读段
-static mut HEAP: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
+static mut HEAP: [u8; KERNEL_HEAP_INIT_SIZE] = [0; KERNEL_HEAP_INIT_SIZE];
+
+// 内核堆，固定大小的常用对象从slab缓存分配，其他对象从buddy堆分配
+// buddy堆初始使用.bss中的HEAP，空间不足时从物理页分配器申请连续的物理页，扩展的内存不会归还
+pub struct KernelHeap(LockedHeap);
 
 #[global_allocator]
-pub static ALLOCATOR: LockedHeap = LockedHeap::new();
+pub static ALLOCATOR: KernelHeap = KernelHeap::new();
 
 pub fn init() {
     unsafe {
         let start = HEAP.as_ptr() as usize;
-        ALLOCATOR.lock().init(start, KERNEL_HEAP_SIZE);
+        ALLOCATOR.0.lock().init(start, KERNEL_HEAP_INIT_SIZE);
+    }
+}
+
+// 内核堆的总大小和已经分配的字节数，不包括slab缓存
+pub fn heap_stats() -> (usize, usize) {
+    let heap = ALLOCATOR.0.lock();
+    return (heap.stats_total_bytes(), heap.stats_alloc_actual());
+}
+
+impl KernelHeap {
+    pub const fn new() -> Self {
+        return Self(LockedHeap::new());
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if let Some(cache) = find_cache(&layout) {
+            return cache.alloc();
+        }
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // 连续物理页的起始地址按块大小对齐，加入堆之后就是一个完整的buddy块
+        let size = layout.size().max(layout.align()).next_power_of_two();
+        let frames = match alloc_contiguous(size.max(KERNEL_HEAP_GROW_SIZE) / PAGE_SIZE) {
+            Some(frames) => frames,
+            None => return null_mut(),
+        };
+        heap.add_to_heap(frames.base_addr(), frames.base_addr() + frames.size());
+        core::mem::forget(frames);
+        match heap.alloc(layout) {
+            Ok(ptr) => ptr.as_ptr(),
+            Err(_) => null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(cache) = find_cache(&layout) {
+            cache.dealloc(ptr);
+            return;
+        }
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout);
     }
 }
 
@@ -27,3 +79,23 @@ pub fn heap_test() {
     drop(v);
     println!("heap_test passed!");
 }
+
+#[allow(unused)]
+pub fn slab_test() {
+    use super::slab::slab_info;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    // 分配超过一个slab的管道缓冲区，全部释放后只保留一个slab
+    let bufs: Vec<Vec<u8>> = (0..32).map(|_| vec![0u8; 4096]).collect();
+    let info = slab_info().into_iter().find(|info| info.size == 4096).unwrap();
+    assert!(info.active >= 32 && info.slabs * info.per_slab >= info.active);
+    drop(bufs);
+    let info = slab_info().into_iter().find(|info| info.size == 4096).unwrap();
+    assert!(info.slabs <= 1 + info.active / info.per_slab);
+    // 堆空间不足时从物理页分配器扩展
+    let (total, _) = heap_stats();
+    let big: Vec<u8> = vec![0u8; KERNEL_HEAP_INIT_SIZE];
+    assert!(heap_stats().0 > total);
+    drop(big);
+    println!("slab_test passed!");
+}
@@ -1,8 +1,8 @@
 use super::address::*;
 use super::memory_set::{MapMode, MemPermission, MemoryArea, MemorySet};
 use super::page_table::PageTable;
-use crate::arch::riscv::qemu::layout::MMIO;
-use crate::config::{KERNEL_STACK_BOTTOM, MAX_VA, PAGE_SIZE, PHYS_MEM_LIMIT, TRAMPOLINE};
+use crate::arch::riscv::qemu::layout::board;
+use crate::config::{KERNEL_STACK_BOTTOM, MAX_VA, PAGE_SIZE, TRAMPOLINE};
 use alloc::vec;
 use lazy_static::lazy_static;
 use spin::mutex::SpinMutex;
@@ -52,6 +52,21 @@ pub fn map_kernel_stack(bottom: usize, top: usize, data: Option<&[u8]>) {
     drop(memset);
 }
 
+// 直接映射设备的MMIO寄存器区域，如PCI设备的BAR
+pub fn map_mmio(base: usize, size: usize) {
+    let mut memset = KERNEL_MEMSET.lock();
+    memset.insert_area(
+        MemoryArea::new(
+            VirtAddr(base).vpn(),
+            VirtAddr(base + size + PAGE_SIZE - 1).vpn(),
+            MapMode::Direct,
+            MemPermission::R.bits() | MemPermission::W.bits(),
+        ),
+        None,
+    );
+    drop(memset);
+}
+
 pub fn unmap_kernel_stack(bottom: usize) {
     let mut memset = KERNEL_MEMSET.lock();
     memset.remove_area(VirtAddr(bottom).vpn());
@@ -72,11 +87,11 @@ impl MemorySet {
         memory_set.map_trampoline();
 
         // 映射低地址的mmio区域
-        for pair in MMIO {
+        for region in board().mmio_regions() {
             memory_set.insert_area(
                 MemoryArea::new(
-                    VirtAddr(pair.0).vpn(),
-                    VirtAddr(pair.1).vpn(),
+                    VirtAddr(region.base).vpn(),
+                    VirtAddr(region.base + region.size).vpn(),
                     MapMode::Direct,
                     MemPermission::R.bits() | MemPermission::W.bits(),
                 ),
@@ -147,7 +162,7 @@ impl MemorySet {
         memory_set.insert_area(
             MemoryArea::new(
                 VirtAddr(ekernel as usize).vpn(),
-                VirtAddr(PHYS_MEM_LIMIT as usize).vpn(),
+                VirtAddr(board().mem_end).vpn(),
                 MapMode::Direct,
                 MemPermission::R.bits() | MemPermission::W.bits(),
             ),
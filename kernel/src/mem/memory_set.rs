@@ -1,12 +1,14 @@
 use super::address::*;
 use super::allocator::{alloc, dealloc, Frame};
 use super::page_table::{PageTable, PageTableEntry};
-use crate::config::{PAGE_SIZE, TRAMPOLINE};
+use crate::config::{MMAP_BOTTOM, MMAP_TOP, PAGE_SIZE, TRAMPOLINE};
+use crate::fs::page_cache::PageCache;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::bitflags;
+use simplefs::vfs::Inode;
 
 bitflags! {
     pub struct MemPermission: usize {
@@ -31,12 +33,34 @@ pub struct MemoryArea {
     pub frames: BTreeMap<VirtPageNumber, Arc<Frame>>, // frames集合，保存内存段拥有的所有物理页
     pub mode: MapMode,                                // 内存段映射模式
     pub perm: usize,
+    pub shared: bool, // 共享内存段，物理页被多个地址空间映射，写入时不做CopyOnWrite
+    pub file: Option<FileMapping>, // 文件映射，页在第一次访问时从页缓存加载
+    pub elf: Option<ElfSegment>,   // elf段，页在第一次访问时从可执行文件读取
+}
+
+// 文件映射的内存段对应的文件页
+#[derive(Clone)]
+pub struct FileMapping {
+    pub cache: Arc<PageCache>,
+    pub page_offset: usize, // start_vpn对应的文件页号
+}
+
+// elf文件中的一个PT_LOAD段，file_size之后到段结束的部分为0
+#[derive(Clone)]
+pub struct ElfSegment {
+    pub inode: Arc<Inode>,
+    pub vaddr: usize,     // 段的起始虚拟地址
+    pub offset: usize,    // 段在文件中的偏移
+    pub file_size: usize, // 段在文件中的大小
 }
 
 // MemorySet 内存集合，多个内存段的集合，通过相同的页表映射
 pub struct MemorySet {
     pub page_table: PageTable,
     pub areas: Vec<MemoryArea>,
+    pub heap_bottom: usize, // 用户堆的起始地址，位于elf段之后
+    pub brk: usize,         // 用户堆的结束地址
+    pub pinned: Option<(VirtPageNumber, VirtPageNumber)>, // 内核正在访问的页，不能被换出
 }
 
 impl MemoryArea {
@@ -52,6 +76,9 @@ impl MemoryArea {
             frames: BTreeMap::new(),
             mode: mode,
             perm: perm,
+            shared: false,
+            file: None,
+            elf: None,
         };
     }
     // 将当前内存段的vpn范围映射到指定的页表，必要时拷贝数据
@@ -89,21 +116,75 @@ impl MemorySet {
         return Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
+            pinned: None,
         };
     }
 
+    // 只记录内存段的范围，页在第一次访问时分配
+    pub fn reserve_area(&mut self, area: MemoryArea) {
+        self.areas.push(area);
+    }
+
     // 内存集合中插入一个内存段
     pub fn insert_area(&mut self, mut area: MemoryArea, data: Option<&[u8]>) {
         area.map(&mut self.page_table, data);
         self.areas.push(area);
     }
 
-    pub fn remove_area(&mut self, start_vpn: VirtPageNumber) {
+    // 将共享的物理页依次映射到start_vpn开始的虚拟页，内存段持有每个物理页的引用
+    pub fn insert_shared_area(&mut self, start_vpn: VirtPageNumber, frames: &[Arc<Frame>], perm: usize) {
+        let end_vpn = VirtPageNumber(start_vpn.0 + frames.len());
+        let mut area = MemoryArea::new(start_vpn, end_vpn, MapMode::Indirect, perm);
+        area.shared = true;
+        for (i, frame) in frames.iter().enumerate() {
+            let vpn = VirtPageNumber(start_vpn.0 + i);
+            self.page_table.map(vpn, frame.ppn, perm);
+            area.frames.insert(vpn, Arc::clone(frame));
+        }
+        self.areas.push(area);
+    }
+
+    // [start_vpn, end_vpn)是否与已有的内存段重叠
+    pub fn overlaps(&self, start_vpn: VirtPageNumber, end_vpn: VirtPageNumber) -> bool {
+        return self
+            .areas
+            .iter()
+            .any(|area| area.start_vpn < end_vpn && start_vpn < area.end_vpn);
+    }
+
+    // 在动态映射区域中找到能放下pages个连续页的最低地址
+    pub fn find_free_area(&self, pages: usize) -> Option<VirtPageNumber> {
+        let mut start = VirtAddr(MMAP_BOTTOM).vpn().0;
+        let end = VirtAddr(MMAP_TOP).vpn().0;
+        let mut ranges: Vec<(usize, usize)> = self
+            .areas
+            .iter()
+            .map(|area| (area.start_vpn.0, area.end_vpn.0))
+            .filter(|(s, e)| *e > start && *s < end)
+            .collect();
+        ranges.sort();
+        for (s, e) in ranges {
+            // 当前内存段之前的空闲区域足够大
+            if s >= start + pages {
+                break;
+            }
+            start = start.max(e);
+        }
+        if start + pages > end {
+            return None;
+        }
+        return Some(VirtPageNumber(start));
+    }
+
+    // 删除包含vpn的内存段，线程栈的起始位置会随着栈增长变化
+    pub fn remove_area(&mut self, vpn: VirtPageNumber) {
         let index = self
             .areas
             .iter_mut()
             .enumerate()
-            .find(|(_, area)| area.start_vpn == start_vpn)
+            .find(|(_, area)| area.start_vpn <= vpn && vpn < area.end_vpn)
             .map(|(i, area)| {
                 for vpn in area.start_vpn.0..area.end_vpn.0 {
                     let vpn = VirtPageNumber(vpn);
@@ -167,6 +248,12 @@ impl MemorySet {
                 end_off = end_va.offset();
             }
             let ppn = self.vpn_to_ppn(VirtPageNumber(vpn)).unwrap();
+            // 内核通过物理地址访问用户页，硬件不会设置D位，可写的页当作已经修改
+            if let Some(pte) = self.page_table.translate(VirtPageNumber(vpn)) {
+                if pte.is_writable() {
+                    pte.set_dirty();
+                }
+            }
             buffers.push(&mut ppn.as_bytes()[start_off..end_off]);
             start_off = 0;
             vpn += 1;
@@ -175,13 +262,14 @@ impl MemorySet {
     }
 
     // 转换一个用户空间的以 \0 结尾的字符串
-    pub fn translate_string(&self, addr: usize) -> String {
+    pub fn translate_string(&mut self, addr: usize) -> String {
         let va = VirtAddr(addr);
         let mut offset = va.offset();
         let mut vpn = va.vpn();
         let mut data: Vec<u8> = Vec::new();
         let mut end = false;
         while !end {
+            self.fault_in(vpn.base_addr(), 1);
             let page = self.vpn_to_ppn(vpn).unwrap().as_bytes();
             for b in (&page[offset..]).iter() {
                 if (*b) == b'\0' {
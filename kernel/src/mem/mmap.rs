@@ -0,0 +1,375 @@
+use super::address::*;
+use super::memory_set::*;
+use super::page_table::{PageTable, PteFlags};
+use super::swap::flush_tlb;
+use crate::config::{MMAP_BOTTOM, MMAP_TOP, PAGE_SIZE};
+use crate::fs::page_cache::PageCache;
+use alloc::sync::Arc;
+
+// mmap的prot和flags，与Linux一致
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1 << 0;
+pub const PROT_WRITE: usize = 1 << 1;
+pub const PROT_EXEC: usize = 1 << 2;
+
+pub const MAP_SHARED: usize = 1 << 0;
+pub const MAP_PRIVATE: usize = 1 << 1;
+pub const MAP_FIXED: usize = 1 << 4; // 必须映射到addr，替换该范围内原有的映射
+pub const MAP_ANONYMOUS: usize = 1 << 5;
+
+pub const MMAP_ERROR: isize = -1;
+pub const MMAP_NO_MEMORY_ERROR: isize = -13;
+
+// prot转换成内存段的权限，RISC-V的页不能只写，可写的页同时可读
+pub fn prot_to_perm(prot: usize) -> usize {
+    let mut perm = MemPermission::U.bits();
+    if prot & (PROT_READ | PROT_WRITE) != 0 {
+        perm |= MemPermission::R.bits();
+    }
+    if prot & PROT_WRITE != 0 {
+        perm |= MemPermission::W.bits();
+    }
+    if prot & PROT_EXEC != 0 {
+        perm |= MemPermission::X.bits();
+    }
+    return perm;
+}
+
+// 没有RWX权限的叶子pte会被当作下一级页表，PROT_NONE的页只保留在内存段中，不在页表映射
+pub fn is_accessible(perm: usize) -> bool {
+    return perm & (MemPermission::R.bits() | MemPermission::W.bits() | MemPermission::X.bits()) != 0;
+}
+
+// 映射的页数，长度为0或者超过动态映射区域时返回None
+fn page_count(len: usize) -> Option<usize> {
+    if len == 0 || len > MMAP_TOP - MMAP_BOTTOM {
+        return None;
+    }
+    return Some(len.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE);
+}
+
+// 检查地址范围是否页对齐并且在动态映射区域内，返回vpn范围
+fn page_range(addr: usize, len: usize) -> Option<(VirtPageNumber, VirtPageNumber)> {
+    if addr % PAGE_SIZE != 0 || len == 0 {
+        return None;
+    }
+    let end = addr.checked_add(len)?;
+    if addr < MMAP_BOTTOM || end > MMAP_TOP {
+        return None;
+    }
+    return Some((VirtAddr(addr).vpn(), VirtPageNumber((end + PAGE_SIZE - 1) / PAGE_SIZE)));
+}
+
+impl MemoryArea {
+    // 在vpn处把内存段分成两段，当前内存段保留[start_vpn, vpn)，返回[vpn, end_vpn)
+    pub fn split_off(&mut self, vpn: VirtPageNumber) -> MemoryArea {
+        let mut tail = MemoryArea::new(vpn, self.end_vpn, self.mode, self.perm);
+        tail.shared = self.shared;
+        tail.file = self.file.as_ref().map(|file| FileMapping {
+            cache: Arc::clone(&file.cache),
+            page_offset: file.page_offset + (vpn.0 - self.start_vpn.0),
+        });
+        tail.elf = self.elf.clone();
+        tail.frames = self.frames.split_off(&vpn);
+        self.end_vpn = vpn;
+        return tail;
+    }
+
+    // 共享文件映射中被修改过的页写回文件
+    pub fn sync_file(&self, page_table: &PageTable) {
+        self.sync_range(page_table, self.start_vpn, self.end_vpn);
+    }
+
+    // 只写回[start, end)中pte的D位被设置的页，写回后清除D位，之后再写入时硬件重新设置
+    fn sync_range(&self, page_table: &PageTable, start: VirtPageNumber, end: VirtPageNumber) {
+        if !self.shared {
+            return;
+        }
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+        for vpn in self.frames.keys().filter(|vpn| **vpn >= start && **vpn < end) {
+            let pte = match page_table.translate(*vpn) {
+                Some(pte) if pte.is_valid() && pte.is_dirty() => pte,
+                _ => continue,
+            };
+            pte.clear_dirty();
+            file.cache
+                .write_back(file.page_offset + (vpn.0 - self.start_vpn.0));
+        }
+        flush_tlb();
+    }
+}
+
+impl MemorySet {
+    // 匿名映射，addr不为0时优先使用addr，返回映射的起始地址
+    // 只保留地址范围，页在第一次访问时分配，内容为0
+    // 共享的匿名映射在fork之后父子进程继续共享，fork时分配还没有访问过的页
+    pub fn mmap_anonymous(
+        &mut self,
+        addr: usize,
+        len: usize,
+        perm: usize,
+        fixed: bool,
+        shared: bool,
+    ) -> Result<usize, isize> {
+        let pages = page_count(len).ok_or(MMAP_ERROR)?;
+        let start_vpn = self.place_mapping(addr, len, fixed)?;
+        let end_vpn = VirtPageNumber(start_vpn.0 + pages);
+        let mut area = MemoryArea::new(start_vpn, end_vpn, MapMode::Indirect, perm);
+        area.shared = shared;
+        self.reserve_area(area);
+        return Ok(start_vpn.base_addr());
+    }
+
+    // fork之前为共享匿名映射中还没有访问过的页分配物理页，使父子进程映射同一组物理页
+    // 物理内存不足时返回false
+    pub fn populate_shared(&mut self) -> bool {
+        for index in 0..self.areas.len() {
+            let area = &self.areas[index];
+            if !area.shared || area.file.is_some() {
+                continue;
+            }
+            for vpn in area.start_vpn.0..area.end_vpn.0 {
+                let vpn = VirtPageNumber(vpn);
+                if self.areas[index].frames.contains_key(&vpn) {
+                    continue;
+                }
+                let frame = match self.alloc_frame() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
+                frame.ppn.as_bytes().fill(0);
+                let area = &mut self.areas[index];
+                if is_accessible(area.perm) {
+                    self.page_table.map(vpn, frame.ppn, area.perm);
+                }
+                area.frames.insert(vpn, Arc::new(frame));
+            }
+        }
+        return true;
+    }
+
+    // 文件映射，page_offset是映射起始位置的文件页号，页在第一次访问时才加载
+    // 私有映射写入时拷贝缓存页，共享映射直接写缓存页，msync或munmap时写回文件
+    pub fn mmap_file(
+        &mut self,
+        addr: usize,
+        len: usize,
+        perm: usize,
+        fixed: bool,
+        shared: bool,
+        cache: Arc<PageCache>,
+        page_offset: usize,
+    ) -> Result<usize, isize> {
+        let pages = page_count(len).ok_or(MMAP_ERROR)?;
+        let start_vpn = self.place_mapping(addr, len, fixed)?;
+        let end_vpn = VirtPageNumber(start_vpn.0 + pages);
+        let mut area = MemoryArea::new(start_vpn, end_vpn, MapMode::Indirect, perm);
+        area.shared = shared;
+        area.file = Some(FileMapping {
+            cache: cache,
+            page_offset: page_offset,
+        });
+        self.areas.push(area);
+        return Ok(start_vpn.base_addr());
+    }
+
+    // 选择映射的位置，MAP_FIXED时解除该范围内原有的映射，否则addr被占用时另外查找
+    fn place_mapping(&mut self, addr: usize, len: usize, fixed: bool) -> Result<VirtPageNumber, isize> {
+        let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let hint = if addr != 0 { page_range(addr, len) } else { None };
+        match hint {
+            Some((start, end)) if fixed => {
+                self.unmap_range(start, end);
+                Ok(start)
+            }
+            None if fixed => Err(MMAP_ERROR),
+            Some((start, end)) if !self.overlaps(start, end) => Ok(start),
+            _ => self.find_free_area(pages).ok_or(MMAP_NO_MEMORY_ERROR),
+        }
+    }
+
+    // 将范围内共享文件映射的页写回文件
+    pub fn msync(&self, addr: usize, len: usize) -> isize {
+        let (start, end) = match page_range(addr, len) {
+            Some(range) => range,
+            None => return MMAP_ERROR,
+        };
+        for area in self.areas.iter() {
+            if area.start_vpn < end && start < area.end_vpn {
+                area.sync_range(&self.page_table, start, end);
+            }
+        }
+        return 0;
+    }
+
+    // 处理用户程序的页错误：分配按需映射的页，读回换出的页，加载文件映射的页，或者对写入的页进行CopyOnWrite
+    // 返回false表示访问的地址没有映射或者没有权限
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNumber, write: bool) -> bool {
+        let index = match self
+            .areas
+            .iter()
+            .position(|area| area.start_vpn <= vpn && vpn < area.end_vpn)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        let area = &mut self.areas[index];
+        if area.frames.contains_key(&vpn) {
+            // 页已经映射，只有写入CopyOnWrite的页是有效的访问
+            return write && self.copy_on_write(vpn);
+        }
+        if !is_accessible(area.perm) || (write && area.perm & MemPermission::W.bits() == 0) {
+            return false;
+        }
+        if self.page_table.swap_slot(vpn).is_some() {
+            return self.swap_in(index, vpn);
+        }
+        if area.file.is_none() {
+            // 匿名页和elf段的页第一次访问时分配，内容为0或者从elf文件读取
+            let frame = match self.alloc_frame() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            frame.ppn.as_bytes().fill(0);
+            let area = &mut self.areas[index];
+            if let Some(elf) = &area.elf {
+                elf.load_page(vpn, frame.ppn.as_bytes());
+            }
+            // 刚加载的页设置访问位，避免马上被换出
+            self.page_table
+                .map(vpn, frame.ppn, area.perm | PteFlags::A.bits());
+            area.frames.insert(vpn, Arc::new(frame));
+            return true;
+        }
+        let area = &mut self.areas[index];
+        let file = area.file.as_ref().unwrap();
+        let frame = match file.cache.get_page(file.page_offset + (vpn.0 - area.start_vpn.0)) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        // 私有映射的缓存页只读映射，写入时拷贝
+        let mut flags = area.perm;
+        if !area.shared {
+            flags &= !MemPermission::W.bits();
+        }
+        self.page_table.map(vpn, frame.ppn, flags);
+        area.frames.insert(vpn, frame);
+        if write && !area.shared {
+            return self.copy_on_write(vpn);
+        }
+        return true;
+    }
+
+    // 内核访问用户缓冲区之前，加载其中还没有加载的页
+    pub fn fault_in(&mut self, addr: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let start = VirtAddr(addr).vpn().0;
+        let end = VirtAddr(addr + len - 1).vpn().0;
+        // 加载后面的页时不能换出前面已经加载的页
+        self.pinned = Some((VirtPageNumber(start), VirtPageNumber(end + 1)));
+        for vpn in start..=end {
+            let vpn = VirtPageNumber(vpn);
+            let loaded = self
+                .areas
+                .iter()
+                .any(|area| area.frames.contains_key(&vpn));
+            if !loaded {
+                self.handle_page_fault(vpn, false);
+            }
+        }
+        self.pinned = None;
+    }
+
+    // 解除范围内的所有映射，部分在范围内的内存段会被分割
+    pub fn munmap(&mut self, addr: usize, len: usize) -> isize {
+        match page_range(addr, len) {
+            Some((start, end)) => {
+                self.unmap_range(start, end);
+                0
+            }
+            None => MMAP_ERROR,
+        }
+    }
+
+    // 修改范围内的页权限，范围内的每一页都必须已经映射
+    pub fn mprotect(&mut self, addr: usize, len: usize, perm: usize) -> isize {
+        let (start, end) = match page_range(addr, len) {
+            Some(range) => range,
+            None => return MMAP_ERROR,
+        };
+        let mapped: usize = self
+            .areas
+            .iter()
+            .filter(|area| area.start_vpn < end && start < area.end_vpn)
+            .map(|area| area.end_vpn.0.min(end.0) - area.start_vpn.0.max(start.0))
+            .sum();
+        if mapped != end.0 - start.0 {
+            return MMAP_ERROR;
+        }
+        self.split_at(start);
+        self.split_at(end);
+        for area in self.areas.iter_mut() {
+            if area.start_vpn < start || area.end_vpn > end {
+                continue;
+            }
+            area.perm = perm;
+            // 重新映射会丢失D位，先把修改过的页写回文件
+            area.sync_file(&self.page_table);
+            for (vpn, frame) in area.frames.iter() {
+                self.page_table.unmap(*vpn);
+                if !is_accessible(perm) {
+                    continue;
+                }
+                // 与其他进程CopyOnWrite共享的页保持只读，写入时再拷贝
+                let mut flags = perm;
+                if !area.shared && Arc::strong_count(frame) > 1 {
+                    flags &= !MemPermission::W.bits();
+                }
+                self.page_table.map(*vpn, frame.ppn, flags);
+            }
+        }
+        return 0;
+    }
+
+    // 把跨越vpn的内存段在vpn处分开
+    fn split_at(&mut self, vpn: VirtPageNumber) {
+        if let Some(i) = self
+            .areas
+            .iter()
+            .position(|area| area.start_vpn < vpn && vpn < area.end_vpn)
+        {
+            let tail = self.areas[i].split_off(vpn);
+            self.areas.push(tail);
+        }
+    }
+
+    fn unmap_range(&mut self, start: VirtPageNumber, end: VirtPageNumber) {
+        self.split_at(start);
+        self.split_at(end);
+        let mut i = 0;
+        while i < self.areas.len() {
+            if self.areas[i].start_vpn >= start && self.areas[i].end_vpn <= end {
+                let mut area = self.areas.remove(i);
+                area.sync_file(&self.page_table);
+                area.unmap(&mut self.page_table);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+// 进程退出时把共享文件映射写回文件，释放换出的页占用的交换区
+impl Drop for MemorySet {
+    fn drop(&mut self) {
+        for area in self.areas.iter() {
+            area.sync_file(&self.page_table);
+        }
+        self.release_swap();
+    }
+}
@@ -5,7 +5,10 @@ pub mod heap;
 pub mod kernel;
 pub mod kernel_stack;
 pub mod memory_set;
+pub mod mmap;
 pub mod page_table;
+pub mod slab;
+pub mod swap;
 
 pub fn init() {
     heap::init();
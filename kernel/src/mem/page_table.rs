@@ -1,5 +1,6 @@
 use super::address::*;
 use super::allocator::{alloc, dealloc, Frame};
+use super::swap::free_slot;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::bitflags;
@@ -30,6 +31,9 @@ bitflags! {
     }
 }
 
+// 换出的页在无效的pte中保存交换区槽位，用软件保留的RSW位标记
+const PTE_SWAPPED: usize = 1 << 8;
+
 #[repr(C)]
 pub struct PageTable {
     pub root_ppn: PhysPageNumber,
@@ -79,6 +83,34 @@ impl PageTableEntry {
     pub fn set_writable(&mut self) {
         self.bits |= PteFlags::W.bits;
     }
+
+    pub fn is_accessed(&self) -> bool {
+        return PteFlags::A.bits & self.bits != 0;
+    }
+
+    pub fn clear_accessed(&mut self) {
+        self.bits &= !PteFlags::A.bits;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        return PteFlags::D.bits & self.bits != 0;
+    }
+
+    pub fn set_dirty(&mut self) {
+        self.bits |= PteFlags::D.bits;
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.bits &= !PteFlags::D.bits;
+    }
+
+    // 页被换出时返回交换区槽位
+    pub fn swap_slot(&self) -> Option<usize> {
+        if self.is_valid() || self.bits & PTE_SWAPPED == 0 {
+            return None;
+        }
+        return Some(self.page_number().0);
+    }
 }
 
 impl PageTable {
@@ -133,13 +165,42 @@ impl PageTable {
         }
     }
 
-    // 解除虚拟页在当前页表的映射
+    // 解除虚拟页在当前页表的映射，页已经换出时释放交换区槽位
     pub fn unmap(&mut self, vpn: VirtPageNumber) {
         self.find_pte(vpn).map(|pte| {
+            if let Some(slot) = pte.swap_slot() {
+                free_slot(slot);
+            }
             *pte = PageTableEntry::new(PhysPageNumber(0), 0);
         });
     }
 
+    // 修改已经映射的页的物理页和权限，权限直接替换为flags
+    pub fn remap(&mut self, vpn: VirtPageNumber, ppn: PhysPageNumber, flags: usize) {
+        self.find_pte(vpn).map(|pte| {
+            *pte = PageTableEntry::new(ppn, flags | PteFlags::V.bits());
+        });
+    }
+
+    // 页换出后在pte中记录交换区槽位
+    pub fn set_swap_slot(&mut self, vpn: VirtPageNumber, slot: usize) {
+        self.find_pte(vpn).map(|pte| {
+            *pte = PageTableEntry::new(PhysPageNumber(slot), PTE_SWAPPED);
+        });
+    }
+
+    pub fn swap_slot(&self, vpn: VirtPageNumber) -> Option<usize> {
+        return self.find_pte(vpn).and_then(|pte| pte.swap_slot());
+    }
+
+    // 取出pte中的交换区槽位并清空pte，槽位由调用者释放
+    pub fn take_swap_slot(&mut self, vpn: VirtPageNumber) -> Option<usize> {
+        let pte = self.find_pte(vpn)?;
+        let slot = pte.swap_slot()?;
+        *pte = PageTableEntry::new(PhysPageNumber(0), 0);
+        return Some(slot);
+    }
+
     // 找到vpn对应的叶子节点页表项
     fn find_pte(&self, vpn: VirtPageNumber) -> Option<&mut PageTableEntry> {
         let levels = divide_vpn(vpn);
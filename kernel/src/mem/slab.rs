@@ -0,0 +1,264 @@
+use super::address::PhysAddr;
+use super::allocator::{alloc_contiguous, ContiguousFrames, MAX_ORDER};
+use crate::config::PAGE_SIZE;
+use crate::ipc::pipe::RING_BUFFER_SIZE;
+use crate::net::NET_BUF_SIZE;
+use crate::proc::pcb::ProcessControlBlock;
+use crate::task::tcb::TaskControlBlock;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+use simplefs::block_cache::CacheEntry;
+use spin::mutex::Mutex;
+
+// 每个slab至少容纳的对象数量
+const SLAB_MIN_OBJECTS: usize = 8;
+// 链表结束
+const NO_SLAB: usize = 0;
+
+// 常用的固定大小内核对象的缓存，内核堆分配内存时按照大小和对齐匹配缓存
+// 大小和对齐相同的其他对象也会从对应的缓存分配
+pub static SLAB_CACHES: [SlabCache; 5] = [
+    SlabCache::for_arc::<TaskControlBlock>("task"),
+    SlabCache::for_arc::<ProcessControlBlock>("process"),
+    SlabCache::for_arc::<Mutex<CacheEntry>>("block-cache"),
+    SlabCache::new("pipe-buffer", RING_BUFFER_SIZE, 1),
+    SlabCache::new("net-buffer", NET_BUF_SIZE, 1),
+];
+
+// 对象缓存，每个slab是一块物理地址连续的内存，开头保存slab头，之后是大小相同的对象
+pub struct SlabCache {
+    name: &'static str,
+    size: usize,   // 对象的大小
+    align: usize,  // 对象的对齐
+    stride: usize, // 相邻两个对象的距离
+    offset: usize, // 第一个对象在slab中的偏移
+    order: usize,  // slab包含2^order个物理页
+    inner: Mutex<SlabCacheInner>,
+}
+
+struct SlabCacheInner {
+    partial: usize, // 有空闲对象的slab链表
+    full: usize,    // 对象已经全部分配的slab链表
+    slabs: usize,
+    active: usize, // 已经分配的对象数量
+    allocs: usize, // 累计分配次数
+    frees: usize,  // 累计释放次数
+}
+
+// slab头，slab按大小对齐，对象地址向下对齐到slab大小就是slab头
+#[repr(C)]
+struct Slab {
+    next: usize,
+    prev: usize,
+    free: usize, // 第一个空闲对象，空闲对象的开头保存下一个空闲对象的地址
+    inuse: usize,
+}
+
+// 缓存的统计信息，通过slab_info系统调用返回给用户程序
+#[repr(C)]
+pub struct SlabInfo {
+    pub name: [u8; 16],
+    pub size: usize,
+    pub per_slab: usize, // 每个slab的对象数量
+    pub pages: usize,    // 每个slab的物理页数量
+    pub slabs: usize,
+    pub active: usize,
+    pub allocs: usize,
+    pub frees: usize,
+}
+
+// 匹配layout的缓存
+pub fn find_cache(layout: &Layout) -> Option<&'static SlabCache> {
+    return SLAB_CACHES
+        .iter()
+        .find(|cache| cache.size == layout.size() && cache.align == layout.align());
+}
+
+pub fn slab_info() -> Vec<SlabInfo> {
+    let mut infos = Vec::new();
+    for cache in SLAB_CACHES.iter() {
+        // 释放锁之后再分配Vec的内存
+        let info = cache.info();
+        infos.push(info);
+    }
+    return infos;
+}
+
+const fn round_up(value: usize, align: usize) -> usize {
+    return (value + align - 1) / align * align;
+}
+
+const fn max(a: usize, b: usize) -> usize {
+    return if a > b { a } else { b };
+}
+
+impl SlabCache {
+    pub const fn new(name: &'static str, size: usize, align: usize) -> Self {
+        // 空闲对象中要保存一个地址
+        let stride = round_up(max(size, size_of::<usize>()), max(align, align_of::<usize>()));
+        let offset = round_up(size_of::<Slab>(), max(align, align_of::<usize>()));
+        let mut order = 0;
+        while order < MAX_ORDER && (PAGE_SIZE << order) - offset < stride * SLAB_MIN_OBJECTS {
+            order += 1;
+        }
+        return Self {
+            name: name,
+            size: size,
+            align: align,
+            stride: stride,
+            offset: offset,
+            order: order,
+            inner: Mutex::new(SlabCacheInner {
+                partial: NO_SLAB,
+                full: NO_SLAB,
+                slabs: 0,
+                active: 0,
+                allocs: 0,
+                frees: 0,
+            }),
+        };
+    }
+
+    // Arc<T>分配的内存是ArcInner<T>，即两个引用计数之后是T
+    pub const fn for_arc<T>(name: &'static str) -> Self {
+        let align = max(align_of::<usize>(), align_of::<T>());
+        let offset = round_up(2 * size_of::<usize>(), align_of::<T>());
+        return Self::new(name, round_up(offset + size_of::<T>(), align), align);
+    }
+
+    fn slab_size(&self) -> usize {
+        return PAGE_SIZE << self.order;
+    }
+
+    fn per_slab(&self) -> usize {
+        return (self.slab_size() - self.offset) / self.stride;
+    }
+
+    pub fn alloc(&self) -> *mut u8 {
+        let mut inner = self.inner.lock();
+        if inner.partial == NO_SLAB {
+            let slab = match self.new_slab() {
+                Some(slab) => slab,
+                None => return null_mut(),
+            };
+            push(&mut inner.partial, slab);
+            inner.slabs += 1;
+        }
+        let addr = inner.partial;
+        let slab = slab_at(addr);
+        let object = slab.free;
+        slab.free = unsafe { *(object as *const usize) };
+        slab.inuse += 1;
+        // 对象全部分配后移到full链表
+        if slab.free == 0 {
+            remove(&mut inner.partial, addr);
+            push(&mut inner.full, addr);
+        }
+        inner.active += 1;
+        inner.allocs += 1;
+        return object as *mut u8;
+    }
+
+    pub fn dealloc(&self, ptr: *mut u8) {
+        let mut inner = self.inner.lock();
+        let object = ptr as usize;
+        let addr = object & !(self.slab_size() - 1);
+        let slab = slab_at(addr);
+        let was_full = slab.free == 0;
+        unsafe { *(object as *mut usize) = slab.free };
+        slab.free = object;
+        slab.inuse -= 1;
+        if was_full {
+            remove(&mut inner.full, addr);
+            push(&mut inner.partial, addr);
+        }
+        inner.active -= 1;
+        inner.frees += 1;
+        // 空的slab还给物理页分配器，只剩一个有空闲对象的slab时保留，避免反复分配
+        if slab.inuse == 0 && !(inner.partial == addr && slab.next == NO_SLAB) {
+            remove(&mut inner.partial, addr);
+            inner.slabs -= 1;
+            drop(ContiguousFrames {
+                ppn: PhysAddr(addr).page_number(),
+                order: self.order,
+            });
+        }
+    }
+
+    // 分配新的slab，把所有对象串成空闲链表
+    fn new_slab(&self) -> Option<usize> {
+        let frames = alloc_contiguous(1 << self.order)?;
+        let addr = frames.base_addr();
+        core::mem::forget(frames);
+        let count = self.per_slab();
+        for i in 0..count {
+            let object = addr + self.offset + i * self.stride;
+            let next = if i + 1 < count { object + self.stride } else { 0 };
+            unsafe { *(object as *mut usize) = next };
+        }
+        let slab = slab_at(addr);
+        slab.free = addr + self.offset;
+        slab.inuse = 0;
+        return Some(addr);
+    }
+
+    fn info(&self) -> SlabInfo {
+        let inner = self.inner.lock();
+        let mut info = SlabInfo {
+            name: [0; 16],
+            size: self.size,
+            per_slab: self.per_slab(),
+            pages: 1 << self.order,
+            slabs: inner.slabs,
+            active: inner.active,
+            allocs: inner.allocs,
+            frees: inner.frees,
+        };
+        let len = self.name.len().min(info.name.len() - 1);
+        info.name[0..len].copy_from_slice(&self.name.as_bytes()[0..len]);
+        return info;
+    }
+}
+
+impl SlabInfo {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+fn slab_at(addr: usize) -> &'static mut Slab {
+    unsafe {
+        return (addr as *mut Slab).as_mut().unwrap();
+    }
+}
+
+fn push(head: &mut usize, addr: usize) {
+    let s = slab_at(addr);
+    s.next = *head;
+    s.prev = NO_SLAB;
+    if *head != NO_SLAB {
+        slab_at(*head).prev = addr;
+    }
+    *head = addr;
+}
+
+fn remove(head: &mut usize, addr: usize) {
+    let s = slab_at(addr);
+    if s.prev != NO_SLAB {
+        slab_at(s.prev).next = s.next;
+    } else {
+        *head = s.next;
+    }
+    if s.next != NO_SLAB {
+        slab_at(s.next).prev = s.prev;
+    }
+    s.next = NO_SLAB;
+    s.prev = NO_SLAB;
+}
@@ -0,0 +1,294 @@
+use super::address::*;
+use super::allocator::{alloc, free_frames, Frame};
+use super::memory_set::*;
+use super::mmap::is_accessible;
+use super::page_table::PteFlags;
+use crate::config::PAGE_SIZE;
+use crate::fs::inode::{open_file, OpenFlags};
+use crate::task::scheduler::{current_running_on_other_cpus, processes, running_on_other_cpus};
+use crate::task::tcb::TaskStatus;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use simplefs::vfs::Inode;
+use spin::mutex::Mutex;
+
+// 交换文件，按页划分成槽位
+const SWAP_FILE: &str = "/swap";
+// 交换区大小：8MiB
+const SWAP_SLOTS: usize = 2048;
+// 物理页不足时每次回收的页数
+const SWAP_BATCH: usize = 16;
+// 保留给内核页表、内核栈等的物理页，用户页不能使用
+const RESERVED_FRAMES: usize = 256;
+
+// 交换区，分配方式与物理页分配器相同
+struct SwapArea {
+    inode: Option<Arc<Inode>>, // 第一次换出时创建交换文件，并分配所有槽位的磁盘空间
+    disabled: bool,            // 无法创建交换文件时关闭交换
+    next_slot: usize,
+    recycled: Vec<usize>,
+    used: usize,
+}
+
+lazy_static! {
+    static ref SWAP_AREA: Mutex<SwapArea> = Mutex::new(SwapArea {
+        inode: None,
+        disabled: false,
+        next_slot: 0,
+        recycled: Vec::new(),
+        used: 0,
+    });
+}
+
+impl SwapArea {
+    fn inode(&mut self) -> Option<Arc<Inode>> {
+        if self.inode.is_none() && !self.disabled {
+            let inode = match open_file(SWAP_FILE, OpenFlags::CREATE | OpenFlags::RDWR) {
+                Ok(file) => file.inode(),
+                Err(code) => {
+                    error!("open swap file failed: {}, swap disabled", code);
+                    self.disabled = true;
+                    return None;
+                }
+            };
+            // 换出时不能再分配磁盘块，文件系统已满时写入会失败
+            if !inode.reserve((SWAP_SLOTS * PAGE_SIZE) as u32) {
+                error!("no space for swap file, swap disabled");
+                self.disabled = true;
+                return None;
+            }
+            self.inode = Some(inode);
+        }
+        return self.inode.clone();
+    }
+
+    fn alloc_slot(&mut self) -> Option<usize> {
+        let slot = if let Some(slot) = self.recycled.pop() {
+            slot
+        } else if self.next_slot < SWAP_SLOTS {
+            self.next_slot += 1;
+            self.next_slot - 1
+        } else {
+            return None;
+        };
+        self.used += 1;
+        return Some(slot);
+    }
+}
+
+// 将一页数据写入交换区，返回槽位，交换区已满时返回None
+fn write_page(data: &[u8]) -> Option<usize> {
+    let mut area = SWAP_AREA.lock();
+    let inode = area.inode()?;
+    let slot = area.alloc_slot()?;
+    if inode.write((slot * PAGE_SIZE) as u32, data) != data.len() {
+        error!("write swap slot {} failed", slot);
+        area.recycled.push(slot);
+        area.used -= 1;
+        return None;
+    }
+    return Some(slot);
+}
+
+// 从交换区读取一页数据，并释放槽位
+fn read_page(slot: usize, data: &mut [u8]) {
+    let mut area = SWAP_AREA.lock();
+    if let Some(inode) = area.inode() {
+        inode.read((slot * PAGE_SIZE) as u32, data);
+    }
+    area.recycled.push(slot);
+    area.used -= 1;
+}
+
+// 释放槽位，换出的页被解除映射时不需要读回
+pub fn free_slot(slot: usize) {
+    let mut area = SWAP_AREA.lock();
+    area.recycled.push(slot);
+    area.used -= 1;
+}
+
+// 交换区中正在使用的槽位数量
+pub fn used_slots() -> usize {
+    return SWAP_AREA.lock().used;
+}
+
+pub fn flush_tlb() {
+    unsafe {
+        core::arch::asm!("sfence.vma zero, zero");
+    }
+}
+
+// 从其他进程回收最近没有访问过的页，正在运行的进程的页表项可能被其他cpu缓存，不能回收
+// 当前进程的pcb已经被借用，不会在这里回收，pcb或线程正在被其他cpu借用的进程也跳过
+fn reclaim_from_processes(count: usize) -> usize {
+    let mut reclaimed = 0;
+    for proc in processes() {
+        if reclaimed >= count {
+            break;
+        }
+        let mut inner = match proc.try_borrow_inner() {
+            Some(inner) => inner,
+            None => continue,
+        };
+        if running_on_other_cpus(proc.pid()) {
+            continue;
+        }
+        let busy = inner.tasks.iter().any(|task| match task.inner.try_borrow() {
+            Some(task) => task.status == TaskStatus::Running,
+            None => true,
+        });
+        if busy {
+            continue;
+        }
+        reclaimed += inner.memory_set.swap_out(count - reclaimed, false);
+    }
+    return reclaimed;
+}
+
+// 分配用户可见的物理页（用户页、页缓存、共享内存），空闲物理页不足时先从其他进程换出页
+// 不使用保留给内核的物理页，物理页不足时返回None
+pub fn alloc_user_frame() -> Option<Frame> {
+    if free_frames() <= RESERVED_FRAMES {
+        reclaim_from_processes(SWAP_BATCH);
+        if free_frames() <= RESERVED_FRAMES {
+            return None;
+        }
+    }
+    return alloc();
+}
+
+impl MemoryArea {
+    // 只有私有的用户页可以换出，共享内存和共享文件映射的页被多个地址空间使用
+    fn swappable(&self) -> bool {
+        return !self.shared
+            && self.mode == MapMode::Indirect
+            && self.perm & MemPermission::U.bits() != 0
+            && is_accessible(self.perm);
+    }
+}
+
+impl MemorySet {
+    // 分配用户页，空闲物理页不足时先从其他进程换出页，再从当前地址空间换出
+    // 交换区已满时返回None，不使用保留的物理页
+    pub fn alloc_frame(&mut self) -> Option<Frame> {
+        // 其他线程在别的cpu上运行时，换出当前地址空间的页只能刷新本cpu的TLB，不能换出
+        if free_frames() <= RESERVED_FRAMES
+            && reclaim_from_processes(SWAP_BATCH) == 0
+            && !current_running_on_other_cpus()
+        {
+            self.swap_out(SWAP_BATCH, true);
+        }
+        return alloc_user_frame();
+    }
+
+    // 按照访问位选择换出的页，访问过的页清除访问位后跳过
+    // force为true时，第一遍没有换出足够的页，第二遍换出剩下的页
+    // 被CopyOnWrite共享的页和页缓存中的页引用计数大于1，不会被换出
+    pub fn swap_out(&mut self, count: usize, force: bool) -> usize {
+        let passes = if force { 2 } else { 1 };
+        let mut swapped = 0;
+        for pass in 0..passes {
+            for area in self.areas.iter_mut() {
+                if swapped >= count {
+                    break;
+                }
+                if !area.swappable() {
+                    continue;
+                }
+                let mut victims: Vec<VirtPageNumber> = Vec::new();
+                for (vpn, frame) in area.frames.iter() {
+                    if swapped + victims.len() >= count {
+                        break;
+                    }
+                    if Arc::strong_count(frame) > 1 {
+                        continue;
+                    }
+                    // 内核正在访问的用户缓冲区
+                    if let Some((start, end)) = self.pinned {
+                        if start <= *vpn && *vpn < end {
+                            continue;
+                        }
+                    }
+                    let pte = match self.page_table.translate(*vpn) {
+                        Some(pte) if pte.is_valid() => pte,
+                        _ => continue,
+                    };
+                    if pass == 0 && pte.is_accessed() {
+                        pte.clear_accessed();
+                        continue;
+                    }
+                    victims.push(*vpn);
+                }
+                for vpn in victims {
+                    let slot = match write_page(area.frames[&vpn].ppn.as_bytes()) {
+                        Some(slot) => slot,
+                        None => {
+                            flush_tlb();
+                            return swapped;
+                        }
+                    };
+                    // 物理页在删除最后一个引用时回收
+                    area.frames.remove(&vpn);
+                    self.page_table.set_swap_slot(vpn, slot);
+                    swapped += 1;
+                }
+            }
+        }
+        flush_tlb();
+        return swapped;
+    }
+
+    // 将换出的页读回area中，返回false表示没有物理页
+    pub fn swap_in(&mut self, index: usize, vpn: VirtPageNumber) -> bool {
+        let frame = match self.alloc_frame() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let slot = match self.page_table.take_swap_slot(vpn) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        read_page(slot, frame.ppn.as_bytes());
+        let area = &mut self.areas[index];
+        self.page_table
+            .map(vpn, frame.ppn, area.perm | PteFlags::A.bits());
+        area.frames.insert(vpn, Arc::new(frame));
+        return true;
+    }
+
+    // fork之前读回所有换出的页，子进程通过CopyOnWrite共享这些页
+    // 读回期间整个地址空间不能被换出，否则已经读回的页可能再次被换出，返回false表示没有足够的物理页
+    pub fn swap_in_all(&mut self) -> bool {
+        if used_slots() == 0 {
+            return true;
+        }
+        let pinned = self.pinned;
+        self.pinned = Some((VirtPageNumber(0), VirtPageNumber(usize::MAX)));
+        let mut ok = true;
+        for index in 0..self.areas.len() {
+            let (start, end) = (self.areas[index].start_vpn.0, self.areas[index].end_vpn.0);
+            for vpn in start..end {
+                if ok && self.page_table.swap_slot(VirtPageNumber(vpn)).is_some() {
+                    ok = self.swap_in(index, VirtPageNumber(vpn));
+                }
+            }
+        }
+        self.pinned = pinned;
+        return ok;
+    }
+
+    // 释放地址空间中所有换出的页占用的槽位
+    pub fn release_swap(&mut self) {
+        if used_slots() == 0 {
+            return;
+        }
+        for area in self.areas.iter() {
+            for vpn in area.start_vpn.0..area.end_vpn.0 {
+                if let Some(slot) = self.page_table.take_swap_slot(VirtPageNumber(vpn)) {
+                    free_slot(slot);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use super::ethernet::{self, ETH_TYPE_ARP, ETH_TYPE_IPV4};
+use super::{now_ms, Interface, Ipv4Addr, MacAddr, BROADCAST_MAC};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// ARP协议，see: RFC 826
+const ARP_HTYPE_ETHERNET: u16 = 1;
+const ARP_OP_REQUEST: u16 = 1;
+const ARP_OP_REPLY: u16 = 2;
+const ARP_PACKET_SIZE: usize = 28;
+
+// 缓存项的有效时间，单位ms
+const ARP_ENTRY_TIMEOUT: usize = 5 * 60 * 1000;
+// 没有收到回复时重发请求的间隔
+const ARP_RETRY_INTERVAL: usize = 1000;
+const ARP_MAX_RETRIES: usize = 3;
+// 每个地址等待解析时最多缓存的IP报文数量
+const ARP_MAX_QUEUED: usize = 16;
+
+struct ArpEntry {
+    mac: MacAddr,
+    expire: usize,
+}
+
+// 正在解析的地址，解析完成前发往该地址的IP报文暂存在packets中
+struct PendingRequest {
+    iface: Interface,
+    ip: Ipv4Addr,
+    packets: Vec<Vec<u8>>,
+    last_request: usize,
+    retries: usize,
+}
+
+lazy_static! {
+    static ref ARP_CACHE: Mutex<BTreeMap<Ipv4Addr, ArpEntry>> = Mutex::new(BTreeMap::new());
+    static ref PENDING: Mutex<Vec<PendingRequest>> = Mutex::new(Vec::new());
+}
+
+pub fn lookup(ip: Ipv4Addr) -> Option<MacAddr> {
+    let mut cache = ARP_CACHE.lock();
+    let now = now_ms();
+    match cache.get(&ip) {
+        Some(entry) if entry.expire > now => Some(entry.mac),
+        Some(_) => {
+            cache.remove(&ip);
+            None
+        }
+        None => None,
+    }
+}
+
+fn update(ip: Ipv4Addr, mac: MacAddr) {
+    ARP_CACHE.lock().insert(
+        ip,
+        ArpEntry {
+            mac: mac,
+            expire: now_ms() + ARP_ENTRY_TIMEOUT,
+        },
+    );
+}
+
+// 把IP报文发给下一跳，MAC地址未知时先发送ARP请求并缓存报文
+pub fn send_ipv4(iface: &Interface, next_hop: Ipv4Addr, packet: Vec<u8>) -> bool {
+    // 回环接口不需要解析MAC地址
+    if iface.loopback {
+        return ethernet::send(iface.dev, iface.mac, iface.mac, ETH_TYPE_IPV4, &packet);
+    }
+    if iface.is_broadcast(next_hop) {
+        return ethernet::send(iface.dev, iface.mac, BROADCAST_MAC, ETH_TYPE_IPV4, &packet);
+    }
+    if let Some(mac) = lookup(next_hop) {
+        return ethernet::send(iface.dev, iface.mac, mac, ETH_TYPE_IPV4, &packet);
+    }
+    let mut pending = PENDING.lock();
+    if let Some(req) = pending.iter_mut().find(|req| req.ip == next_hop) {
+        if req.packets.len() >= ARP_MAX_QUEUED {
+            return false;
+        }
+        req.packets.push(packet);
+        return true;
+    }
+    pending.push(PendingRequest {
+        iface: *iface,
+        ip: next_hop,
+        packets: alloc::vec![packet],
+        last_request: now_ms(),
+        retries: 0,
+    });
+    drop(pending);
+    send_request(iface, next_hop);
+    return true;
+}
+
+fn send_request(iface: &Interface, ip: Ipv4Addr) {
+    let packet = build_packet(ARP_OP_REQUEST, iface.mac, iface.ip, [0; 6], ip);
+    ethernet::send(iface.dev, iface.mac, BROADCAST_MAC, ETH_TYPE_ARP, &packet);
+}
+
+fn build_packet(
+    op: u16,
+    sender_mac: MacAddr,
+    sender_ip: Ipv4Addr,
+    target_mac: MacAddr,
+    target_ip: Ipv4Addr,
+) -> Vec<u8> {
+    let mut packet: Vec<u8> = Vec::with_capacity(ARP_PACKET_SIZE);
+    packet.extend_from_slice(&ARP_HTYPE_ETHERNET.to_be_bytes());
+    packet.extend_from_slice(&ETH_TYPE_IPV4.to_be_bytes());
+    packet.push(6);
+    packet.push(4);
+    packet.extend_from_slice(&op.to_be_bytes());
+    packet.extend_from_slice(&sender_mac);
+    packet.extend_from_slice(&sender_ip.0);
+    packet.extend_from_slice(&target_mac);
+    packet.extend_from_slice(&target_ip.0);
+    return packet;
+}
+
+pub fn receive(iface: &Interface, packet: &[u8]) {
+    if packet.len() < ARP_PACKET_SIZE {
+        return;
+    }
+    let htype = u16::from_be_bytes([packet[0], packet[1]]);
+    let ptype = u16::from_be_bytes([packet[2], packet[3]]);
+    if htype != ARP_HTYPE_ETHERNET || ptype != ETH_TYPE_IPV4 || packet[4] != 6 || packet[5] != 4 {
+        return;
+    }
+    let op = u16::from_be_bytes([packet[6], packet[7]]);
+    let mut sender_mac = [0u8; 6];
+    sender_mac.copy_from_slice(&packet[8..14]);
+    let sender_ip = Ipv4Addr::from_bytes(&packet[14..18]);
+    let target_ip = Ipv4Addr::from_bytes(&packet[24..28]);
+    let for_us = !iface.ip.is_unspecified() && target_ip == iface.ip;
+    // 已经在缓存中的地址总是更新，发给本机的报文把发送方加入缓存
+    let known = ARP_CACHE.lock().contains_key(&sender_ip);
+    if known || for_us {
+        update(sender_ip, sender_mac);
+        flush_pending(sender_ip, sender_mac);
+    }
+    if op == ARP_OP_REQUEST && for_us {
+        let reply = build_packet(ARP_OP_REPLY, iface.mac, iface.ip, sender_mac, sender_ip);
+        ethernet::send(iface.dev, iface.mac, sender_mac, ETH_TYPE_ARP, &reply);
+    }
+}
+
+// 地址解析完成，发送等待的报文
+fn flush_pending(ip: Ipv4Addr, mac: MacAddr) {
+    let mut pending = PENDING.lock();
+    let resolved: Vec<PendingRequest> = pending.drain_filter(|req| req.ip == ip).collect();
+    drop(pending);
+    for req in resolved {
+        for packet in req.packets {
+            ethernet::send(req.iface.dev, req.iface.mac, mac, ETH_TYPE_IPV4, &packet);
+        }
+    }
+}
+
+// 重发超时的ARP请求，超过重试次数后丢弃等待的报文，并清理过期的缓存项
+pub fn poll() {
+    let now = now_ms();
+    let mut pending = PENDING.lock();
+    let dropped: Vec<PendingRequest> = pending
+        .drain_filter(|req| {
+            now >= req.last_request + ARP_RETRY_INTERVAL && req.retries >= ARP_MAX_RETRIES
+        })
+        .collect();
+    let mut retry: Vec<(Interface, Ipv4Addr)> = Vec::new();
+    for req in pending.iter_mut() {
+        if now >= req.last_request + ARP_RETRY_INTERVAL {
+            req.last_request = now;
+            req.retries += 1;
+            retry.push((req.iface, req.ip));
+        }
+    }
+    drop(pending);
+    for req in dropped {
+        debug!(
+            "[arp] {} unreachable, dropped {} packets",
+            req.ip,
+            req.packets.len()
+        );
+    }
+    for (iface, ip) in retry {
+        send_request(&iface, ip);
+    }
+    ARP_CACHE.lock().retain(|_, entry| entry.expire > now);
+}
@@ -0,0 +1,199 @@
+use super::socket::*;
+use super::wait_until;
+use crate::config::TIME_FREQ_US;
+use crate::fs::{File, FileStat, UserBuffer};
+use crate::timer::get_time;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::mem::size_of;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// 抓包，网卡收发的每个帧复制一份到所有打开的抓包socket的环形缓冲区
+// 每个socket最多缓存的帧，满了之后丢弃最旧的帧
+const RING_SIZE: usize = 256;
+// 每个帧最多保存的长度
+const SNAP_LEN: usize = 1514;
+
+pub const DIRECTION_IN: u16 = 0;
+pub const DIRECTION_OUT: u16 = 1;
+
+// 读取抓包socket时，每个帧之前的头部
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CaptureHeader {
+    sec: u32,
+    usec: u32,
+    dev: u16,
+    direction: u16,
+    len: u32,     // 帧的原始长度
+    cap_len: u32, // 保存的长度
+}
+
+struct CapturedFrame {
+    header: CaptureHeader,
+    data: Vec<u8>,
+}
+
+struct Ring {
+    frames: VecDeque<CapturedFrame>,
+    dropped: usize,
+}
+
+lazy_static! {
+    static ref RINGS: Mutex<Vec<Weak<Mutex<Ring>>>> = Mutex::new(Vec::new());
+}
+
+// 收发路径上的抓包点，没有打开的抓包socket时直接返回
+pub fn tap(dev: usize, direction: u16, frame: &[u8]) {
+    let mut rings = RINGS.lock();
+    if rings.is_empty() {
+        return;
+    }
+    rings.retain(|ring| ring.strong_count() > 0);
+    let us = get_time() / TIME_FREQ_US;
+    let cap_len = frame.len().min(SNAP_LEN);
+    for ring in rings.iter().filter_map(|ring| ring.upgrade()) {
+        let mut ring = ring.lock();
+        if ring.frames.len() >= RING_SIZE {
+            ring.frames.pop_front();
+            ring.dropped += 1;
+        }
+        ring.frames.push_back(CapturedFrame {
+            header: CaptureHeader {
+                sec: (us / 1000_000) as u32,
+                usec: (us % 1000_000) as u32,
+                dev: dev as u16,
+                direction: direction,
+                len: frame.len() as u32,
+                cap_len: cap_len as u32,
+            },
+            data: frame[0..cap_len].to_vec(),
+        });
+    }
+}
+
+// AF_PACKET类型的socket，每次读取返回一个CaptureHeader和帧数据
+pub struct CaptureSocket {
+    ring: Arc<Mutex<Ring>>,
+    timeout: Mutex<Option<usize>>,
+}
+
+impl CaptureSocket {
+    pub fn new() -> Self {
+        let ring = Arc::new(Mutex::new(Ring {
+            frames: VecDeque::new(),
+            dropped: 0,
+        }));
+        RINGS.lock().push(Arc::downgrade(&ring));
+        Self {
+            ring: ring,
+            timeout: Mutex::new(None),
+        }
+    }
+}
+
+impl Drop for CaptureSocket {
+    fn drop(&mut self) {
+        let dropped = self.ring.lock().dropped;
+        if dropped > 0 {
+            debug!("[net] capture closed, {} frames dropped", dropped);
+        }
+    }
+}
+
+impl Socket for CaptureSocket {
+    fn domain(&self) -> usize {
+        AF_PACKET
+    }
+
+    fn bind(&self, _addr: SocketAddr) -> isize {
+        SOCKET_ERROR
+    }
+
+    fn connect(&self, _addr: SocketAddr) -> isize {
+        SOCKET_ERROR
+    }
+
+    fn send_to(&self, _data: &[u8], _addr: Option<SocketAddr>) -> isize {
+        SOCKET_ERROR
+    }
+
+    // buf不足以放下头部时返回错误，帧数据放不下时截断
+    fn recv_from(&self, buf: &mut [u8], nonblock: bool) -> Result<(usize, SocketAddr), isize> {
+        let header_size = size_of::<CaptureHeader>();
+        if buf.len() < header_size {
+            return Err(SOCKET_ERROR);
+        }
+        // 检查和取出帧在同一次加锁中完成，避免帧被其他cpu上的任务取走
+        let received = RefCell::new(None);
+        let ready = || match self.ring.lock().frames.pop_front() {
+            Some(frame) => {
+                *received.borrow_mut() = Some(frame);
+                true
+            }
+            None => false,
+        };
+        if nonblock {
+            if !ready() {
+                return Err(WOULD_BLOCK_ERROR);
+            }
+        } else if !wait_until(*self.timeout.lock(), ready) {
+            return Err(TIMEOUT_ERROR);
+        }
+        let mut frame = received.take().unwrap();
+        let len = frame.data.len().min(buf.len() - header_size);
+        frame.header.cap_len = len as u32;
+        let header = unsafe {
+            core::slice::from_raw_parts(&frame.header as *const CaptureHeader as *const u8, header_size)
+        };
+        buf[0..header_size].copy_from_slice(header);
+        buf[header_size..header_size + len].copy_from_slice(&frame.data[0..len]);
+        return Ok((header_size + len, SocketAddr::unspecified()));
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn set_recv_timeout(&self, timeout: Option<usize>) {
+        *self.timeout.lock() = timeout;
+    }
+
+    // 头部加上截断后的帧
+    fn max_io_size(&self) -> usize {
+        size_of::<CaptureHeader>() + SNAP_LEN
+    }
+}
+
+impl File for CaptureSocket {
+    fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
+        let mut data = vec![0u8; buf.length().min(size_of::<CaptureHeader>() + SNAP_LEN)];
+        match self.recv_from(&mut data, false) {
+            Ok((len, _)) => {
+                buf.write(0, &data[0..len]);
+                len
+            }
+            Err(_) => 0,
+        }
+    }
+    fn write<'a>(&self, _buf: &mut UserBuffer) -> usize {
+        0
+    }
+    fn fstat(&self) -> Option<FileStat> {
+        None
+    }
+    fn lseek(&self, _offset: u32, _from: u8) -> isize {
+        -1
+    }
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
@@ -0,0 +1,298 @@
+use super::ipv4::{self, Ipv4Packet, PROTOCOL_UDP};
+use super::socket::SocketAddr;
+use super::udp::build_datagram;
+use super::{configure, interface, now_ms, Ipv4Addr};
+use super::{DEFAULT_DNS, DEFAULT_GATEWAY, DEFAULT_IP, DEFAULT_NETMASK};
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// DHCP客户端，see: RFC 2131、RFC 2132
+pub const DHCP_CLIENT_PORT: u16 = 68;
+const DHCP_SERVER_PORT: u16 = 67;
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+const HTYPE_ETHERNET: u8 = 1;
+const FLAG_BROADCAST: u16 = 0x8000;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+// 固定部分的长度，之后是magic cookie和选项
+const DHCP_FIXED_SIZE: usize = 236;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DNS: u8 = 6;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_ID: u8 = 54;
+const OPTION_PARAMETER_LIST: u8 = 55;
+const OPTION_END: u8 = 255;
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+// 重传间隔和次数，单位ms
+const RETRY_INTERVAL: usize = 2000;
+const MAX_RETRIES: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DhcpState {
+    Selecting,
+    Requesting,
+    Bound,
+    Renewing,
+}
+
+struct DhcpClient {
+    dev: usize,
+    state: DhcpState,
+    xid: u32,
+    offered: Ipv4Addr,
+    server: Ipv4Addr,
+    retries: usize,
+    deadline: usize, // 重传或续租的时间
+    lease_expire: usize,
+}
+
+// DHCP服务器分配的配置
+struct Lease {
+    ip: Ipv4Addr,
+    netmask: Ipv4Addr,
+    gateway: Ipv4Addr,
+    dns: Ipv4Addr,
+    server: Ipv4Addr,
+    lease_time: usize, // 单位s
+}
+
+lazy_static! {
+    static ref CLIENTS: Mutex<Vec<DhcpClient>> = Mutex::new(Vec::new());
+}
+
+// 在接口上开始DHCP，清除接口原来的地址
+pub fn start(dev: usize) -> bool {
+    if interface(dev).map(|i| i.loopback).unwrap_or(true) {
+        return false;
+    }
+    let unspecified = Ipv4Addr::UNSPECIFIED;
+    configure(dev, unspecified, unspecified, unspecified, unspecified);
+    let mut clients = CLIENTS.lock();
+    clients.retain(|c| c.dev != dev);
+    let client = DhcpClient {
+        dev: dev,
+        state: DhcpState::Selecting,
+        xid: (now_ms() as u32) ^ ((dev as u32) << 24),
+        offered: unspecified,
+        server: unspecified,
+        retries: 0,
+        deadline: now_ms() + RETRY_INTERVAL,
+        lease_expire: 0,
+    };
+    send_message(&client, DHCPDISCOVER);
+    clients.push(client);
+    return true;
+}
+
+// 停止接口上的DHCP，接口改为静态配置时调用
+pub fn stop(dev: usize) {
+    CLIENTS.lock().retain(|c| c.dev != dev);
+}
+
+// 处理重传和续租
+pub fn poll() {
+    let now = now_ms();
+    let mut clients = CLIENTS.lock();
+    let mut fallback: Vec<usize> = Vec::new();
+    for client in clients.iter_mut() {
+        if now < client.deadline {
+            continue;
+        }
+        match client.state {
+            DhcpState::Selecting | DhcpState::Requesting => {
+                client.retries += 1;
+                if client.retries > MAX_RETRIES {
+                    fallback.push(client.dev);
+                    continue;
+                }
+                let msg = if client.state == DhcpState::Selecting { DHCPDISCOVER } else { DHCPREQUEST };
+                send_message(client, msg);
+                client.deadline = now + RETRY_INTERVAL;
+            }
+            DhcpState::Bound | DhcpState::Renewing => {
+                if now >= client.lease_expire {
+                    // 租约过期，重新获取地址
+                    kernel!("[net] eth{}: dhcp lease expired", client.dev);
+                    client.state = DhcpState::Selecting;
+                    client.retries = 0;
+                    client.xid = client.xid.wrapping_add(1);
+                    send_message(client, DHCPDISCOVER);
+                    client.deadline = now + RETRY_INTERVAL;
+                } else {
+                    // 续租，没有回复时每隔一段时间重试，直到租约过期
+                    client.state = DhcpState::Renewing;
+                    send_message(client, DHCPREQUEST);
+                    client.deadline = now + (client.lease_expire - now).max(2 * RETRY_INTERVAL) / 2;
+                }
+            }
+        }
+    }
+    clients.retain(|c| !fallback.contains(&c.dev));
+    drop(clients);
+    // 没有DHCP服务器时使用qemu user网络的默认配置
+    for dev in fallback {
+        kernel!("[net] eth{}: dhcp timeout, using default config", dev);
+        configure(dev, DEFAULT_IP, DEFAULT_NETMASK, DEFAULT_GATEWAY, DEFAULT_DNS);
+    }
+}
+
+fn send_message(client: &DhcpClient, msg_type: u8) {
+    let iface = match interface(client.dev) {
+        Some(iface) => iface,
+        None => return,
+    };
+    let mut msg: Vec<u8> = alloc::vec![0u8; DHCP_FIXED_SIZE];
+    msg[0] = BOOTREQUEST;
+    msg[1] = HTYPE_ETHERNET;
+    msg[2] = 6; // hlen
+    msg[4..8].copy_from_slice(&client.xid.to_be_bytes());
+    // 还没有地址时要求服务器广播回复
+    if client.state != DhcpState::Renewing {
+        msg[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+    } else {
+        msg[12..16].copy_from_slice(&iface.ip.0); // ciaddr
+    }
+    msg[28..34].copy_from_slice(&iface.mac); // chaddr
+    msg.extend_from_slice(&MAGIC_COOKIE);
+    msg.extend_from_slice(&[OPTION_MESSAGE_TYPE, 1, msg_type]);
+    if msg_type == DHCPREQUEST && client.state == DhcpState::Requesting {
+        msg.extend_from_slice(&[OPTION_REQUESTED_IP, 4]);
+        msg.extend_from_slice(&client.offered.0);
+        msg.extend_from_slice(&[OPTION_SERVER_ID, 4]);
+        msg.extend_from_slice(&client.server.0);
+    }
+    msg.extend_from_slice(&[
+        OPTION_PARAMETER_LIST,
+        4,
+        OPTION_SUBNET_MASK,
+        OPTION_ROUTER,
+        OPTION_DNS,
+        OPTION_LEASE_TIME,
+    ]);
+    msg.push(OPTION_END);
+    // 续租时单播给服务器，否则从0.0.0.0广播
+    let (src, dst) = if client.state == DhcpState::Renewing {
+        (iface.ip, client.server)
+    } else {
+        (Ipv4Addr::UNSPECIFIED, Ipv4Addr::BROADCAST)
+    };
+    let datagram = build_datagram(src, DHCP_CLIENT_PORT, SocketAddr::new(dst, DHCP_SERVER_PORT), &msg);
+    let next_hop = if dst.is_broadcast() || dst.same_subnet(iface.ip, iface.netmask) {
+        dst
+    } else {
+        iface.gateway
+    };
+    ipv4::send_via(&iface, next_hop, src, dst, PROTOCOL_UDP, &datagram);
+}
+
+// 解析服务器的回复，返回(消息类型, 配置)
+fn parse_reply(data: &[u8], xid: u32) -> Option<(u8, Lease)> {
+    if data.len() < DHCP_FIXED_SIZE + MAGIC_COOKIE.len() || data[0] != BOOTREPLY {
+        return None;
+    }
+    if u32::from_be_bytes([data[4], data[5], data[6], data[7]]) != xid {
+        return None;
+    }
+    if data[DHCP_FIXED_SIZE..DHCP_FIXED_SIZE + 4] != MAGIC_COOKIE {
+        return None;
+    }
+    let mut msg_type = 0;
+    let mut lease = Lease {
+        ip: Ipv4Addr::from_bytes(&data[16..20]), // yiaddr
+        netmask: Ipv4Addr::UNSPECIFIED,
+        gateway: Ipv4Addr::UNSPECIFIED,
+        dns: Ipv4Addr::UNSPECIFIED,
+        server: Ipv4Addr::from_bytes(&data[20..24]), // siaddr
+        lease_time: 0,
+    };
+    let options = &data[DHCP_FIXED_SIZE + 4..];
+    let mut i = 0;
+    while i < options.len() {
+        let code = options[i];
+        if code == OPTION_END {
+            break;
+        }
+        if code == OPTION_PAD {
+            i += 1;
+            continue;
+        }
+        if i + 1 >= options.len() || i + 2 + options[i + 1] as usize > options.len() {
+            break;
+        }
+        let len = options[i + 1] as usize;
+        let value = &options[i + 2..i + 2 + len];
+        match code {
+            OPTION_MESSAGE_TYPE if len >= 1 => msg_type = value[0],
+            OPTION_SUBNET_MASK if len >= 4 => lease.netmask = Ipv4Addr::from_bytes(value),
+            OPTION_ROUTER if len >= 4 => lease.gateway = Ipv4Addr::from_bytes(value),
+            OPTION_DNS if len >= 4 => lease.dns = Ipv4Addr::from_bytes(value),
+            OPTION_SERVER_ID if len >= 4 => lease.server = Ipv4Addr::from_bytes(value),
+            OPTION_LEASE_TIME if len >= 4 => {
+                lease.lease_time = u32::from_be_bytes([value[0], value[1], value[2], value[3]]) as usize
+            }
+            _ => {}
+        }
+        i += 2 + len;
+    }
+    return Some((msg_type, lease));
+}
+
+// 发往68端口的UDP报文
+pub fn receive(packet: &Ipv4Packet, data: &[u8]) {
+    let mut clients = CLIENTS.lock();
+    let client = match clients.iter_mut().find(|c| c.dev == packet.iface.dev) {
+        Some(client) => client,
+        None => return,
+    };
+    let (msg_type, lease) = match parse_reply(data, client.xid) {
+        Some(reply) => reply,
+        None => return,
+    };
+    let now = now_ms();
+    match (client.state, msg_type) {
+        (DhcpState::Selecting, DHCPOFFER) => {
+            client.offered = lease.ip;
+            client.server = lease.server;
+            client.state = DhcpState::Requesting;
+            client.retries = 0;
+            client.deadline = now + RETRY_INTERVAL;
+            send_message(client, DHCPREQUEST);
+        }
+        (DhcpState::Requesting | DhcpState::Renewing, DHCPACK) => {
+            let first = client.state == DhcpState::Requesting;
+            client.state = DhcpState::Bound;
+            client.server = lease.server;
+            client.retries = 0;
+            // 没有租约时间时按1天处理，T1为租约的一半
+            let lease_ms = if lease.lease_time == 0 { 86400 } else { lease.lease_time } * 1000;
+            client.lease_expire = now + lease_ms;
+            client.deadline = now + lease_ms / 2;
+            let dev = client.dev;
+            drop(clients);
+            if first {
+                kernel!("[net] eth{}: dhcp lease {}, {}s", dev, lease.ip, lease_ms / 1000);
+                configure(dev, lease.ip, lease.netmask, lease.gateway, lease.dns);
+            }
+        }
+        (DhcpState::Requesting | DhcpState::Renewing, DHCPNAK) => {
+            // 服务器拒绝，重新开始
+            let dev = client.dev;
+            drop(clients);
+            start(dev);
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,68 @@
+use super::{arp, capture, interface, ipv4, send_frame, MacAddr, BROADCAST_MAC, NET_BUF_SIZE};
+use alloc::vec::Vec;
+
+pub const ETH_HEADER_SIZE: usize = 14;
+// 不包含CRC的最小帧长度，不足时补0
+const ETH_MIN_FRAME: usize = 60;
+pub const ETH_MTU: usize = 1500;
+
+pub const ETH_TYPE_IPV4: u16 = 0x0800;
+pub const ETH_TYPE_ARP: u16 = 0x0806;
+
+// 以太网帧头：目的MAC、源MAC、类型
+pub struct EthernetHeader {
+    pub dst: MacAddr,
+    pub src: MacAddr,
+    pub ether_type: u16,
+}
+
+impl EthernetHeader {
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        if frame.len() < ETH_HEADER_SIZE {
+            return None;
+        }
+        let mut header = Self {
+            dst: [0; 6],
+            src: [0; 6],
+            ether_type: u16::from_be_bytes([frame[12], frame[13]]),
+        };
+        header.dst.copy_from_slice(&frame[0..6]);
+        header.src.copy_from_slice(&frame[6..12]);
+        return Some(header);
+    }
+}
+
+// 网卡收到帧的回调，按类型交给ARP或IP处理
+pub fn receive(dev: usize, frame: &[u8]) {
+    capture::tap(dev, capture::DIRECTION_IN, frame);
+    let header = match EthernetHeader::parse(frame) {
+        Some(header) => header,
+        None => return,
+    };
+    let iface = match interface(dev) {
+        Some(iface) if iface.up => iface,
+        _ => return,
+    };
+    // 只接收发给本机和广播的帧
+    if header.dst != iface.mac && header.dst != BROADCAST_MAC {
+        return;
+    }
+    let payload = &frame[ETH_HEADER_SIZE..];
+    match header.ether_type {
+        ETH_TYPE_ARP => arp::receive(&iface, payload),
+        ETH_TYPE_IPV4 => ipv4::receive(&iface, payload),
+        _ => {}
+    }
+}
+
+// 封装以太网帧并从dev网卡发送
+pub fn send(dev: usize, src: MacAddr, dst: MacAddr, ether_type: u16, payload: &[u8]) -> bool {
+    let len = (ETH_HEADER_SIZE + payload.len()).max(ETH_MIN_FRAME);
+    let mut frame: Vec<u8> = Vec::with_capacity(len.max(NET_BUF_SIZE));
+    frame.extend_from_slice(&dst);
+    frame.extend_from_slice(&src);
+    frame.extend_from_slice(&ether_type.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame.resize(len, 0);
+    return send_frame(dev, frame.as_slice());
+}
@@ -0,0 +1,87 @@
+use super::ipv4::{self, Ipv4Packet, PROTOCOL_ICMP};
+use super::{checksum, Ipv4Addr};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// ICMP协议，see: RFC 792
+const ICMP_HEADER_SIZE: usize = 8;
+// 一个IPv4报文能容纳的最大echo数据长度，65535 - 20 - 8
+pub const MAX_ECHO_DATA_SIZE: usize = 65507;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+// 最多保存的未取走的echo reply
+const MAX_ECHO_REPLIES: usize = 64;
+
+#[derive(Clone, Copy)]
+pub struct EchoReply {
+    pub src: Ipv4Addr,
+    pub id: u16,
+    pub seq: u16,
+    pub ttl: u8,
+    pub len: usize, // echo数据长度
+}
+
+lazy_static! {
+    static ref ECHO_REPLIES: Mutex<VecDeque<EchoReply>> = Mutex::new(VecDeque::new());
+}
+
+pub fn receive(packet: &Ipv4Packet) {
+    let data = packet.payload;
+    if data.len() < ICMP_HEADER_SIZE || checksum(data) != 0 {
+        return;
+    }
+    let id = u16::from_be_bytes([data[4], data[5]]);
+    let seq = u16::from_be_bytes([data[6], data[7]]);
+    match data[0] {
+        ICMP_ECHO_REQUEST => {
+            // 回复相同的id、seq和数据
+            let reply = build_echo(ICMP_ECHO_REPLY, id, seq, &data[ICMP_HEADER_SIZE..]);
+            ipv4::send(packet.src, PROTOCOL_ICMP, &reply);
+        }
+        ICMP_ECHO_REPLY => {
+            let mut replies = ECHO_REPLIES.lock();
+            if replies.len() >= MAX_ECHO_REPLIES {
+                replies.pop_front();
+            }
+            replies.push_back(EchoReply {
+                src: packet.src,
+                id: id,
+                seq: seq,
+                ttl: packet.ttl,
+                len: data.len() - ICMP_HEADER_SIZE,
+            });
+        }
+        _ => {}
+    }
+}
+
+fn build_echo(icmp_type: u8, id: u16, seq: u16, data: &[u8]) -> Vec<u8> {
+    let mut packet: Vec<u8> = Vec::with_capacity(ICMP_HEADER_SIZE + data.len());
+    packet.push(icmp_type);
+    packet.push(0); // code
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(data);
+    let sum = checksum(&packet);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    return packet;
+}
+
+// 发送echo request，数据为len个递增的字节
+pub fn send_echo(dst: Ipv4Addr, id: u16, seq: u16, len: usize) -> bool {
+    let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+    let request = build_echo(ICMP_ECHO_REQUEST, id, seq, &data);
+    return ipv4::send(dst, PROTOCOL_ICMP, &request);
+}
+
+// 取出与(dst, id, seq)匹配的echo reply
+pub fn take_echo_reply(dst: Ipv4Addr, id: u16, seq: u16) -> Option<EchoReply> {
+    let mut replies = ECHO_REPLIES.lock();
+    let idx = replies
+        .iter()
+        .position(|r| r.src == dst && r.id == id && r.seq == seq)?;
+    return replies.remove(idx);
+}
@@ -0,0 +1,285 @@
+use super::ethernet::ETH_MTU;
+use super::{arp, checksum, icmp, now_ms, route, tcp, udp, Interface, Ipv4Addr};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU16, Ordering};
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// IPv4协议，see: RFC 791
+pub const IPV4_HEADER_SIZE: usize = 20;
+pub const PROTOCOL_ICMP: u8 = 1;
+pub const PROTOCOL_TCP: u8 = 6;
+pub const PROTOCOL_UDP: u8 = 17;
+
+const DEFAULT_TTL: u8 = 64;
+// flags和fragment offset字段
+const FLAG_MORE_FRAGMENTS: u16 = 0x2000;
+const FRAGMENT_OFFSET_MASK: u16 = 0x1fff;
+// 分片重组的超时时间，单位ms
+const REASSEMBLY_TIMEOUT: usize = 30 * 1000;
+// 同时进行重组的报文数量上限
+const MAX_REASSEMBLY: usize = 8;
+const MAX_PACKET_SIZE: usize = 65535;
+
+static IP_ID: AtomicU16 = AtomicU16::new(1);
+
+pub struct Ipv4Header {
+    pub header_len: usize,
+    pub total_len: usize,
+    pub id: u16,
+    pub flags_offset: u16,
+    pub ttl: u8,
+    pub protocol: u8,
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+}
+
+impl Ipv4Header {
+    // 解析并检查报文头，版本、长度或校验和错误时返回None
+    pub fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() < IPV4_HEADER_SIZE || packet[0] >> 4 != 4 {
+            return None;
+        }
+        let header_len = ((packet[0] & 0xf) as usize) * 4;
+        let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        if header_len < IPV4_HEADER_SIZE || total_len < header_len || total_len > packet.len() {
+            return None;
+        }
+        if checksum(&packet[0..header_len]) != 0 {
+            return None;
+        }
+        return Some(Self {
+            header_len: header_len,
+            total_len: total_len,
+            id: u16::from_be_bytes([packet[4], packet[5]]),
+            flags_offset: u16::from_be_bytes([packet[6], packet[7]]),
+            ttl: packet[8],
+            protocol: packet[9],
+            src: Ipv4Addr::from_bytes(&packet[12..16]),
+            dst: Ipv4Addr::from_bytes(&packet[16..20]),
+        });
+    }
+
+    fn more_fragments(&self) -> bool {
+        self.flags_offset & FLAG_MORE_FRAGMENTS != 0
+    }
+
+    // 分片在原报文中的偏移，单位字节
+    fn fragment_offset(&self) -> usize {
+        ((self.flags_offset & FRAGMENT_OFFSET_MASK) as usize) * 8
+    }
+}
+
+// 正在重组的报文，(src, dst, id, protocol)相同的分片属于同一个报文
+struct Reassembly {
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    id: u16,
+    protocol: u8,
+    data: Vec<u8>,
+    received: Vec<(usize, usize)>, // 已收到的数据区间[start, end)
+    total: Option<usize>,           // 收到最后一个分片后才知道报文长度
+    expire: usize,
+}
+
+impl Reassembly {
+    fn insert(&mut self, offset: usize, payload: &[u8], last: bool) {
+        let end = offset + payload.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[offset..end].copy_from_slice(payload);
+        self.received.push((offset, end));
+        if last {
+            self.total = Some(end);
+        }
+    }
+
+    fn is_complete(&mut self) -> bool {
+        let total = match self.total {
+            Some(total) => total,
+            None => return false,
+        };
+        self.received.sort_unstable();
+        let mut covered = 0;
+        for (start, end) in self.received.iter() {
+            if *start > covered {
+                return false;
+            }
+            covered = covered.max(*end);
+        }
+        return covered >= total;
+    }
+}
+
+lazy_static! {
+    static ref REASSEMBLY: Mutex<Vec<Reassembly>> = Mutex::new(Vec::new());
+}
+
+// 收到的IP报文的信息，交给上层协议处理
+pub struct Ipv4Packet<'a> {
+    pub iface: &'a Interface,
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub ttl: u8,
+    pub payload: &'a [u8],
+}
+
+pub fn receive(iface: &Interface, packet: &[u8]) {
+    let header = match Ipv4Header::parse(packet) {
+        Some(header) => header,
+        None => return,
+    };
+    // 接口还没有地址时（如DHCP过程中）接收所有报文，回环接口接收发给本机任意地址的报文
+    let accept_all = iface.ip.is_unspecified() || iface.loopback;
+    if !accept_all && header.dst != iface.ip && !iface.is_broadcast(header.dst) {
+        return;
+    }
+    let payload = &packet[header.header_len..header.total_len];
+    if !header.more_fragments() && header.fragment_offset() == 0 {
+        dispatch(iface, &header, payload);
+        return;
+    }
+    if let Some(data) = reassemble(&header, payload) {
+        dispatch(iface, &header, data.as_slice());
+    }
+}
+
+fn dispatch(iface: &Interface, header: &Ipv4Header, payload: &[u8]) {
+    let packet = Ipv4Packet {
+        iface: iface,
+        src: header.src,
+        dst: header.dst,
+        ttl: header.ttl,
+        payload: payload,
+    };
+    match header.protocol {
+        PROTOCOL_ICMP => icmp::receive(&packet),
+        PROTOCOL_TCP => tcp::receive(&packet),
+        PROTOCOL_UDP => udp::receive(&packet),
+        _ => {}
+    }
+}
+
+// 加入一个分片，报文完整时返回重组后的数据
+fn reassemble(header: &Ipv4Header, payload: &[u8]) -> Option<Vec<u8>> {
+    let offset = header.fragment_offset();
+    if offset + payload.len() > MAX_PACKET_SIZE {
+        return None;
+    }
+    let mut list = REASSEMBLY.lock();
+    let idx = list.iter().position(|r| {
+        r.src == header.src && r.dst == header.dst && r.id == header.id && r.protocol == header.protocol
+    });
+    let idx = match idx {
+        Some(idx) => idx,
+        None => {
+            if list.len() >= MAX_REASSEMBLY {
+                return None;
+            }
+            list.push(Reassembly {
+                src: header.src,
+                dst: header.dst,
+                id: header.id,
+                protocol: header.protocol,
+                data: Vec::new(),
+                received: Vec::new(),
+                total: None,
+                expire: now_ms() + REASSEMBLY_TIMEOUT,
+            });
+            list.len() - 1
+        }
+    };
+    let r = &mut list[idx];
+    r.insert(offset, payload, !header.more_fragments());
+    if !r.is_complete() {
+        return None;
+    }
+    let mut r = list.remove(idx);
+    let total = r.total.unwrap();
+    r.data.truncate(total);
+    return Some(r.data);
+}
+
+// 丢弃超时未完成重组的报文
+pub fn poll() {
+    let now = now_ms();
+    REASSEMBLY.lock().retain(|r| r.expire > now);
+}
+
+// 发送IP报文，超过MTU时分片发送，没有路由时返回false
+pub fn send(dst: Ipv4Addr, protocol: u8, payload: &[u8]) -> bool {
+    let (iface, next_hop) = match route(dst) {
+        Some(route) => route,
+        None => return false,
+    };
+    return send_via(&iface, next_hop, iface.ip, dst, protocol, payload);
+}
+
+// 使用指定的源地址发送，TCP连接的源地址在建立连接时已经确定
+pub fn send_from(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, payload: &[u8]) -> bool {
+    let (iface, next_hop) = match route(dst) {
+        Some(route) => route,
+        None => return false,
+    };
+    return send_via(&iface, next_hop, src, dst, protocol, payload);
+}
+
+// 从指定接口发送，src可以与接口地址不同（如DHCP使用0.0.0.0）
+pub fn send_via(
+    iface: &Interface,
+    next_hop: Ipv4Addr,
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    protocol: u8,
+    payload: &[u8],
+) -> bool {
+    if payload.len() + IPV4_HEADER_SIZE > MAX_PACKET_SIZE {
+        return false;
+    }
+    let id = IP_ID.fetch_add(1, Ordering::SeqCst);
+    // 分片的数据长度必须是8的倍数
+    let max_fragment = (ETH_MTU - IPV4_HEADER_SIZE) & !7;
+    let mut offset = 0;
+    loop {
+        let len = (payload.len() - offset).min(max_fragment);
+        let more = offset + len < payload.len();
+        let mut flags_offset = (offset / 8) as u16;
+        if more {
+            flags_offset |= FLAG_MORE_FRAGMENTS;
+        }
+        let mut packet = build_header(src, dst, protocol, id, flags_offset, len);
+        packet.extend_from_slice(&payload[offset..offset + len]);
+        if !arp::send_ipv4(iface, next_hop, packet) {
+            return false;
+        }
+        offset += len;
+        if !more {
+            return true;
+        }
+    }
+}
+
+fn build_header(
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    protocol: u8,
+    id: u16,
+    flags_offset: u16,
+    payload_len: usize,
+) -> Vec<u8> {
+    let mut header: Vec<u8> = Vec::with_capacity(IPV4_HEADER_SIZE + payload_len);
+    header.push(0x45); // version 4, 5个32位字的头部
+    header.push(0); // TOS
+    header.extend_from_slice(&((IPV4_HEADER_SIZE + payload_len) as u16).to_be_bytes());
+    header.extend_from_slice(&id.to_be_bytes());
+    header.extend_from_slice(&flags_offset.to_be_bytes());
+    header.push(DEFAULT_TTL);
+    header.push(protocol);
+    header.extend_from_slice(&[0, 0]);
+    header.extend_from_slice(&src.0);
+    header.extend_from_slice(&dst.0);
+    let sum = checksum(&header[0..IPV4_HEADER_SIZE]);
+    header[10..12].copy_from_slice(&sum.to_be_bytes());
+    return header;
+}
@@ -0,0 +1,284 @@
+pub mod arp;
+pub mod capture;
+pub mod dhcp;
+pub mod ethernet;
+pub mod icmp;
+pub mod ipv4;
+pub mod socket;
+pub mod tcp;
+pub mod udp;
+
+use crate::config::TIME_FREQ_MS;
+use crate::driver::net::loopback::{self, LOOPBACK_NAME};
+use crate::driver::net::{net_device, net_devices, set_rx_hook};
+use crate::driver::plic;
+use crate::task::scheduler::yield_current_task;
+use crate::timer::get_time;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display, Formatter};
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+pub type MacAddr = [u8; 6];
+pub const BROADCAST_MAC: MacAddr = [0xff; 6];
+
+// qemu user网络的默认配置，see: https://wiki.qemu.org/Documentation/Networking
+pub const DEFAULT_IP: Ipv4Addr = Ipv4Addr([10, 0, 2, 15]);
+pub const DEFAULT_NETMASK: Ipv4Addr = Ipv4Addr([255, 255, 255, 0]);
+pub const DEFAULT_GATEWAY: Ipv4Addr = Ipv4Addr([10, 0, 2, 2]);
+pub const DEFAULT_DNS: Ipv4Addr = Ipv4Addr([10, 0, 2, 3]);
+pub const LOOPBACK_IP: Ipv4Addr = Ipv4Addr([127, 0, 0, 1]);
+pub const LOOPBACK_NETMASK: Ipv4Addr = Ipv4Addr([255, 0, 0, 0]);
+// 网络帧缓冲区的大小，能容纳一个完整的以太网帧，从net-buffer缓存分配
+pub const NET_BUF_SIZE: usize = 2048;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ipv4Addr(pub [u8; 4]);
+
+impl Ipv4Addr {
+    pub const UNSPECIFIED: Ipv4Addr = Ipv4Addr([0; 4]);
+    pub const BROADCAST: Ipv4Addr = Ipv4Addr([255; 4]);
+
+    // 系统调用使用u32传递ip地址，a.b.c.d对应(a << 24) | (b << 16) | (c << 8) | d
+    pub fn from_u32(addr: u32) -> Self {
+        Self(addr.to_be_bytes())
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self([data[0], data[1], data[2], data[3]])
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        *self == Self::UNSPECIFIED
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
+    // 是否和addr在同一个子网
+    pub fn same_subnet(&self, addr: Ipv4Addr, netmask: Ipv4Addr) -> bool {
+        self.to_u32() & netmask.to_u32() == addr.to_u32() & netmask.to_u32()
+    }
+}
+
+impl Display for Ipv4Addr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+}
+
+impl Debug for Ipv4Addr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+// 网络接口，一个网卡对应一个接口
+#[derive(Clone, Copy)]
+pub struct Interface {
+    pub dev: usize, // 网卡编号
+    pub mac: MacAddr,
+    pub ip: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    pub dns: Ipv4Addr,
+    pub loopback: bool,
+    pub up: bool, // 接口关闭时不收发报文
+}
+
+impl Interface {
+    // 回环接口为lo，其他接口按网卡编号命名为ethN
+    pub fn name(&self) -> String {
+        if self.loopback {
+            return String::from(LOOPBACK_NAME);
+        }
+        return format!("eth{}", self.dev);
+    }
+
+    // 接口子网内的广播地址
+    pub fn is_broadcast(&self, addr: Ipv4Addr) -> bool {
+        addr.is_broadcast() || addr.to_u32() == self.ip.to_u32() | !self.netmask.to_u32()
+    }
+}
+
+lazy_static! {
+    static ref INTERFACES: Mutex<Vec<Interface>> = Mutex::new(Vec::new());
+}
+
+// 为每个网卡创建接口，第一个网卡通过DHCP获取地址，最后创建回环接口
+pub fn init() {
+    loopback::init();
+    let mut interfaces = INTERFACES.lock();
+    let mut dhcp_dev = None;
+    for (id, dev) in net_devices().iter().enumerate() {
+        let loopback = dev.name() == LOOPBACK_NAME;
+        let iface = Interface {
+            dev: id,
+            mac: dev.mac_addr(),
+            ip: if loopback { LOOPBACK_IP } else { Ipv4Addr::UNSPECIFIED },
+            netmask: if loopback { LOOPBACK_NETMASK } else { Ipv4Addr::UNSPECIFIED },
+            gateway: Ipv4Addr::UNSPECIFIED,
+            dns: Ipv4Addr::UNSPECIFIED,
+            loopback: loopback,
+            up: true,
+        };
+        if !loopback && dhcp_dev.is_none() {
+            dhcp_dev = Some(id);
+        }
+        kernel!("[net] {}: {}, mac {:02x?}", iface.name(), dev.name(), iface.mac);
+        interfaces.push(iface);
+    }
+    drop(interfaces);
+    set_rx_hook(ethernet::receive);
+    if let Some(dev) = dhcp_dev {
+        dhcp::start(dev);
+    }
+}
+
+pub fn interface(dev: usize) -> Option<Interface> {
+    INTERFACES.lock().iter().find(|i| i.dev == dev).map(|i| *i)
+}
+
+pub fn interfaces() -> Vec<Interface> {
+    INTERFACES.lock().clone()
+}
+
+// 设置接口的地址，返回接口是否存在
+pub fn configure(dev: usize, ip: Ipv4Addr, netmask: Ipv4Addr, gateway: Ipv4Addr, dns: Ipv4Addr) -> bool {
+    let mut interfaces = INTERFACES.lock();
+    match interfaces.iter_mut().find(|i| i.dev == dev) {
+        Some(iface) => {
+            iface.ip = ip;
+            iface.netmask = netmask;
+            iface.gateway = gateway;
+            iface.dns = dns;
+            kernel!(
+                "[net] {}: ip {}, netmask {}, gateway {}, dns {}",
+                iface.name(),
+                ip,
+                netmask,
+                gateway,
+                dns
+            );
+            true
+        }
+        None => false,
+    }
+}
+
+// 启用或关闭接口
+pub fn set_up(dev: usize, up: bool) -> bool {
+    let mut interfaces = INTERFACES.lock();
+    match interfaces.iter_mut().find(|i| i.dev == dev) {
+        Some(iface) => {
+            iface.up = up;
+            true
+        }
+        None => false,
+    }
+}
+
+// 目的地址所在的接口，以及下一跳地址
+pub fn route(dst: Ipv4Addr) -> Option<(Interface, Ipv4Addr)> {
+    let interfaces = INTERFACES.lock();
+    // 发给本机地址的报文走回环接口
+    let local = interfaces.iter().any(|i| !i.ip.is_unspecified() && i.ip == dst);
+    if local {
+        if let Some(lo) = interfaces.iter().find(|i| i.loopback && i.up) {
+            return Some((*lo, dst));
+        }
+    }
+    let configured = interfaces.iter().filter(|i| i.up && !i.ip.is_unspecified());
+    // 优先选择同一子网的接口，否则发给默认接口的网关
+    for iface in configured.clone() {
+        if dst.same_subnet(iface.ip, iface.netmask) || (dst.is_broadcast() && !iface.loopback) {
+            return Some((*iface, dst));
+        }
+    }
+    configured
+        .filter(|i| !i.gateway.is_unspecified())
+        .next()
+        .map(|i| (*i, i.gateway))
+}
+
+// 把帧拷贝到NET_BUF_SIZE容量的缓冲区，超过缓冲区大小的帧直接分配
+pub fn net_buffer(frame: &[u8]) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::with_capacity(frame.len().max(NET_BUF_SIZE));
+    buf.extend_from_slice(frame);
+    return buf;
+}
+
+// 发送以太网帧
+pub fn send_frame(dev: usize, frame: &[u8]) -> bool {
+    capture::tap(dev, capture::DIRECTION_OUT, frame);
+    match net_device(dev) {
+        Some(device) => device.send(frame),
+        None => false,
+    }
+}
+
+// 投递回环接口的报文，处理协议栈的超时事件：ARP请求重传、分片重组超时、TCP重传、DHCP
+pub fn poll() {
+    loopback::poll();
+    arp::poll();
+    ipv4::poll();
+    tcp::poll();
+    dhcp::poll();
+}
+
+// 等待ready返回true，超时返回false，timeout单位为ms，None表示一直等待
+// 内核态不响应中断，等待时主动处理网卡中断，然后让出cpu
+pub fn wait_until<F: Fn() -> bool>(timeout: Option<usize>, ready: F) -> bool {
+    let deadline = timeout.map(|t| now_ms() + t);
+    loop {
+        if ready() {
+            return true;
+        }
+        if let Some(deadline) = deadline {
+            if now_ms() >= deadline {
+                return false;
+            }
+        }
+        plic::handle_irq();
+        poll();
+        yield_current_task();
+    }
+}
+
+// 当前时间，单位ms
+pub fn now_ms() -> usize {
+    get_time() / TIME_FREQ_MS
+}
+
+// 累加16位大端序数据，用于计算校验和
+pub fn checksum_add(mut sum: u32, data: &[u8]) -> u32 {
+    let mut i = 0;
+    while i + 1 < data.len() {
+        sum += u16::from_be_bytes([data[i], data[i + 1]]) as u32;
+        i += 2;
+    }
+    // 奇数长度时最后一个字节补0
+    if i < data.len() {
+        sum += (data[i] as u32) << 8;
+    }
+    return sum;
+}
+
+// 折叠进位并取反，得到Internet校验和，see: RFC 1071
+pub fn checksum_fold(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    return !(sum as u16);
+}
+
+pub fn checksum(data: &[u8]) -> u16 {
+    checksum_fold(checksum_add(0, data))
+}
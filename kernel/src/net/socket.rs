@@ -0,0 +1,195 @@
+use super::capture::CaptureSocket;
+use super::tcp::TcpSocket;
+use super::udp::UdpSocket;
+use super::{Ipv4Addr, NET_BUF_SIZE};
+use crate::fs::{File, UserBuffer};
+use crate::ipc::unix::UnixSocket;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+
+// 本机进程间通信的socket，地址为文件系统中的路径
+pub const AF_UNIX: usize = 1;
+pub const AF_INET: usize = 2;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+// 抓包socket，读取网卡收发的所有帧
+pub const AF_PACKET: usize = 17;
+pub const SOCK_RAW: usize = 3;
+
+pub const SOL_SOCKET: usize = 1;
+// 接收超时，选项值为usize类型的毫秒数，0表示不超时
+pub const SO_RCVTIMEO: usize = 20;
+
+// recvfrom的flags，没有数据时立即返回
+pub const MSG_DONTWAIT: usize = 0x40;
+
+// shutdown的how参数
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
+
+// socket系统调用的错误码，与文件系统的错误码不重叠
+pub const SOCKET_ERROR: isize = -1;
+pub const ADDR_IN_USE_ERROR: isize = -4;
+pub const NOT_CONNECTED_ERROR: isize = -5;
+pub const TIMEOUT_ERROR: isize = -6;
+pub const UNREACHABLE_ERROR: isize = -7;
+pub const WOULD_BLOCK_ERROR: isize = -8;
+pub const CONN_REFUSED_ERROR: isize = -9;
+pub const CONN_RESET_ERROR: isize = -10;
+// 对端已经关闭，不能再写入
+pub const BROKEN_PIPE_ERROR: isize = -11;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SocketAddr {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+}
+
+impl SocketAddr {
+    pub fn new(ip: Ipv4Addr, port: u16) -> Self {
+        Self { ip, port }
+    }
+
+    pub fn unspecified() -> Self {
+        Self::new(Ipv4Addr::UNSPECIFIED, 0)
+    }
+}
+
+// 用户程序使用的sockaddr_in，port和addr都是网络字节序
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockAddrIn {
+    pub family: u16,
+    pub port: [u8; 2],
+    pub addr: [u8; 4],
+    pub zero: [u8; 8],
+}
+
+impl SockAddrIn {
+    pub fn from_addr(addr: SocketAddr) -> Self {
+        Self {
+            family: AF_INET as u16,
+            port: addr.port.to_be_bytes(),
+            addr: addr.ip.0,
+            zero: [0; 8],
+        }
+    }
+
+    pub fn to_addr(&self) -> Option<SocketAddr> {
+        if self.family as usize != AF_INET {
+            return None;
+        }
+        Some(SocketAddr::new(Ipv4Addr(self.addr), u16::from_be_bytes(self.port)))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let mut addr = Self::from_addr(SocketAddr::unspecified());
+        addr.family = u16::from_ne_bytes([data[0], data[1]]);
+        addr.port.copy_from_slice(&data[2..4]);
+        addr.addr.copy_from_slice(&data[4..8]);
+        return addr;
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+// socket在fd表中是File，socket相关的系统调用通过File::as_socket取得该接口
+pub trait Socket: Send + Sync {
+    fn bind(&self, addr: SocketAddr) -> isize;
+    fn connect(&self, addr: SocketAddr) -> isize;
+    // addr为None时发送给connect的地址，返回发送的长度
+    fn send_to(&self, data: &[u8], addr: Option<SocketAddr>) -> isize;
+    // 接收数据，返回(数据长度, 对端地址)
+    fn recv_from(&self, buf: &mut [u8], nonblock: bool) -> Result<(usize, SocketAddr), isize>;
+    fn local_addr(&self) -> Option<SocketAddr>;
+    fn peer_addr(&self) -> Option<SocketAddr>;
+    // 接收超时，单位ms，None表示一直等待
+    fn set_recv_timeout(&self, timeout: Option<usize>);
+    // 一次send/recv在内核中拷贝的最大长度，数据报socket为报文的最大长度，流式socket为缓冲区大小
+    fn max_io_size(&self) -> usize {
+        NET_BUF_SIZE
+    }
+    // 流式socket的数据可以分段发送
+    fn is_stream(&self) -> bool {
+        false
+    }
+    // 以下操作只有面向连接的socket支持
+    fn listen(&self, _backlog: usize) -> isize {
+        SOCKET_ERROR
+    }
+    // 返回新连接的socket和对端地址
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize> {
+        Err(SOCKET_ERROR)
+    }
+    fn shutdown(&self, _how: usize) -> isize {
+        SOCKET_ERROR
+    }
+    fn domain(&self) -> usize {
+        AF_INET
+    }
+    // 以下操作只有AF_UNIX的socket支持，地址为路径
+    fn bind_path(&self, _path: &str) -> isize {
+        SOCKET_ERROR
+    }
+    fn connect_path(&self, _path: &str) -> isize {
+        SOCKET_ERROR
+    }
+    fn send_to_path(&self, _data: &[u8], _path: &str) -> isize {
+        SOCKET_ERROR
+    }
+    fn local_path(&self) -> Option<String> {
+        None
+    }
+}
+
+// socket的write，每次从用户缓冲区拷贝不超过max_io_size的数据并发送
+// 数据报不能拆分，超过最大长度时返回0
+pub fn write_to_socket(socket: &dyn Socket, buf: &UserBuffer) -> usize {
+    let len = buf.length();
+    let max_size = socket.max_io_size();
+    if len > max_size && !socket.is_stream() {
+        return 0;
+    }
+    let mut data = vec![0u8; len.min(max_size)];
+    let mut sent = 0;
+    loop {
+        let size = (len - sent).min(max_size);
+        if size > 0 {
+            buf.read(sent, &mut data[0..size]);
+        }
+        let ret = socket.send_to(&data[0..size], None);
+        if ret < 0 {
+            return sent;
+        }
+        sent += ret as usize;
+        if sent >= len || (ret as usize) < size {
+            return sent;
+        }
+    }
+}
+
+pub fn create_socket(domain: usize, socket_type: usize) -> Option<Arc<dyn File>> {
+    if domain == AF_PACKET && socket_type == SOCK_RAW {
+        return Some(Arc::new(CaptureSocket::new()));
+    }
+    if domain == AF_UNIX {
+        return UnixSocket::new(socket_type).map(|socket| Arc::new(socket) as Arc<dyn File>);
+    }
+    if domain != AF_INET {
+        return None;
+    }
+    match socket_type {
+        SOCK_STREAM => Some(Arc::new(TcpSocket::new())),
+        SOCK_DGRAM => Some(Arc::new(UdpSocket::new())),
+        _ => None,
+    }
+}
@@ -0,0 +1,1046 @@
+use super::ipv4::{self, Ipv4Packet, PROTOCOL_TCP};
+use super::socket::*;
+use super::{checksum_add, checksum_fold, now_ms, route, wait_until, Ipv4Addr};
+use crate::fs::{File, FileStat, UserBuffer};
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// TCP协议，see: RFC 793、RFC 1122、RFC 6298
+const TCP_HEADER_SIZE: usize = 20;
+const FLAG_FIN: u8 = 0x01;
+const FLAG_SYN: u8 = 0x02;
+const FLAG_RST: u8 = 0x04;
+const FLAG_PSH: u8 = 0x08;
+const FLAG_ACK: u8 = 0x10;
+
+const OPTION_END: u8 = 0;
+const OPTION_NOP: u8 = 1;
+const OPTION_MSS: u8 = 2;
+
+// 对端没有MSS选项时使用的默认值
+const DEFAULT_MSS: usize = 536;
+// 本机的MSS，以太网MTU减去IP和TCP头部
+const LOCAL_MSS: usize = 1460;
+
+const SEND_BUF_SIZE: usize = 16 * 1024;
+const RECV_BUF_SIZE: usize = 16 * 1024;
+// 最多缓存的乱序报文数量
+const MAX_OUT_OF_ORDER: usize = 16;
+
+// 重传超时，单位ms
+const INITIAL_RTO: usize = 1000;
+const MAX_RTO: usize = 60 * 1000;
+const MAX_RETRIES: usize = 8;
+const MAX_SYN_RETRIES: usize = 5;
+// TIME_WAIT状态的持续时间，2MSL
+const TIME_WAIT_TIMEOUT: usize = 4 * 1000;
+const MAX_BACKLOG: usize = 32;
+
+const EPHEMERAL_START: u16 = 49152;
+const EPHEMERAL_END: u16 = 65535;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+}
+
+// 序号比较，序号空间是循环的
+fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+fn seq_le(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) <= 0
+}
+
+// TCP连接控制块
+pub struct TcpConnection {
+    state: TcpState,
+    local: SocketAddr,
+    remote: SocketAddr,
+    iss: u32,
+    snd_una: u32, // 最早的未确认序号
+    snd_nxt: u32, // 下一个发送序号
+    snd_wnd: u32, // 对端通告的窗口
+    rcv_nxt: u32, // 期望收到的下一个序号
+    mss: usize,
+    send_buf: VecDeque<u8>, // 从snd_una开始的数据，包括已发送未确认和未发送的数据
+    recv_buf: VecDeque<u8>,
+    out_of_order: BTreeMap<u32, Vec<u8>>,
+    fin_pending: bool, // 用户关闭了写端，数据发送完后发送FIN
+    fin_sent: bool,
+    fin_received: bool,
+    read_closed: bool,
+    rto: usize,
+    deadline: Option<usize>, // 重传定时器
+    retries: usize,
+    time_wait_deadline: usize,
+    error: Option<isize>,
+    // 监听socket的backlog和已经完成握手的连接
+    backlog: usize,
+    accept_queue: VecDeque<Arc<Mutex<TcpConnection>>>,
+    // 被动打开的连接所属的监听socket
+    listener: Option<Weak<Mutex<TcpConnection>>>,
+}
+
+lazy_static! {
+    static ref CONNECTIONS: Mutex<Vec<Arc<Mutex<TcpConnection>>>> = Mutex::new(Vec::new());
+    // 被socket绑定的端口
+    static ref BOUND_PORTS: Mutex<BTreeSet<u16>> = Mutex::new(BTreeSet::new());
+    static ref NEXT_EPHEMERAL: Mutex<u16> = Mutex::new(EPHEMERAL_START);
+}
+
+static ISS_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+// 初始序号，随时间递增，see: RFC 793 3.3
+fn gen_iss() -> u32 {
+    let time = (now_ms() as u32).wrapping_mul(250);
+    time.wrapping_add(ISS_COUNTER.fetch_add(64000, Ordering::SeqCst))
+}
+
+impl TcpConnection {
+    fn new(state: TcpState, local: SocketAddr, remote: SocketAddr) -> Self {
+        let iss = gen_iss();
+        Self {
+            state: state,
+            local: local,
+            remote: remote,
+            iss: iss,
+            snd_una: iss,
+            snd_nxt: iss,
+            snd_wnd: 0,
+            rcv_nxt: 0,
+            mss: DEFAULT_MSS,
+            send_buf: VecDeque::new(),
+            recv_buf: VecDeque::new(),
+            out_of_order: BTreeMap::new(),
+            fin_pending: false,
+            fin_sent: false,
+            fin_received: false,
+            read_closed: false,
+            rto: INITIAL_RTO,
+            deadline: None,
+            retries: 0,
+            time_wait_deadline: 0,
+            error: None,
+            backlog: 0,
+            accept_queue: VecDeque::new(),
+            listener: None,
+        }
+    }
+
+    // 通告给对端的接收窗口
+    fn window(&self) -> u16 {
+        (RECV_BUF_SIZE - self.recv_buf.len()).min(u16::MAX as usize) as u16
+    }
+
+    // 已发送未确认的数据长度，不包括SYN和FIN
+    fn in_flight(&self) -> usize {
+        let mut len = self.snd_nxt.wrapping_sub(self.snd_una) as usize;
+        if self.fin_sent {
+            len -= 1;
+        }
+        return len;
+    }
+
+    fn send_segment(&self, seq: u32, flags: u8, payload: &[u8]) {
+        let options: &[u8] = if flags & FLAG_SYN != 0 {
+            &[OPTION_MSS, 4, (LOCAL_MSS >> 8) as u8, LOCAL_MSS as u8]
+        } else {
+            &[]
+        };
+        let ack = if flags & FLAG_ACK != 0 { self.rcv_nxt } else { 0 };
+        let segment = build_segment(
+            self.local,
+            self.remote,
+            seq,
+            ack,
+            flags,
+            self.window(),
+            options,
+            payload,
+        );
+        ipv4::send_from(self.local.ip, self.remote.ip, PROTOCOL_TCP, &segment);
+    }
+
+    fn send_ack(&self) {
+        self.send_segment(self.snd_nxt, FLAG_ACK, &[]);
+    }
+
+    fn send_reset(&self) {
+        self.send_segment(self.snd_nxt, FLAG_RST | FLAG_ACK, &[]);
+    }
+
+    fn arm_timer(&mut self) {
+        if self.deadline.is_none() {
+            self.deadline = Some(now_ms() + self.rto);
+        }
+    }
+
+    // 连接出错或被重置，唤醒等待的用户
+    fn abort(&mut self, error: isize) {
+        self.error = Some(error);
+        self.state = TcpState::Closed;
+        self.deadline = None;
+    }
+
+    // 在窗口允许的范围内发送数据，数据发送完后发送FIN
+    fn output(&mut self) {
+        match self.state {
+            TcpState::Established | TcpState::CloseWait => {}
+            TcpState::FinWait1 | TcpState::Closing | TcpState::LastAck if !self.fin_sent => {}
+            _ => return,
+        }
+        loop {
+            let flight = self.in_flight();
+            let unsent = self.send_buf.len() - flight;
+            let usable = (self.snd_wnd as usize).saturating_sub(flight);
+            let len = unsent.min(usable).min(self.mss);
+            if len == 0 {
+                // 对端窗口为0时由重传定时器发送窗口探测
+                if unsent > 0 && flight == 0 {
+                    self.arm_timer();
+                }
+                break;
+            }
+            let data: Vec<u8> = self.send_buf.range(flight..flight + len).copied().collect();
+            self.send_segment(self.snd_nxt, FLAG_ACK | FLAG_PSH, &data);
+            self.snd_nxt = self.snd_nxt.wrapping_add(len as u32);
+            self.arm_timer();
+        }
+        if self.fin_pending && !self.fin_sent && self.in_flight() == self.send_buf.len() {
+            self.send_segment(self.snd_nxt, FLAG_FIN | FLAG_ACK, &[]);
+            self.snd_nxt = self.snd_nxt.wrapping_add(1);
+            self.fin_sent = true;
+            self.state = match self.state {
+                TcpState::CloseWait => TcpState::LastAck,
+                TcpState::Established => TcpState::FinWait1,
+                state => state,
+            };
+            self.arm_timer();
+        }
+    }
+
+    // 重传定时器超时，重传最早的未确认报文
+    fn on_timeout(&mut self) {
+        let flight = self.in_flight();
+        let syn = matches!(self.state, TcpState::SynSent | TcpState::SynReceived);
+        // 窗口探测，不计入重传次数
+        if !syn && flight == 0 && !self.fin_sent && self.send_buf.len() > 0 {
+            let probe = [self.send_buf[0]];
+            self.send_segment(self.snd_nxt, FLAG_ACK, &probe);
+            self.snd_nxt = self.snd_nxt.wrapping_add(1);
+            self.deadline = Some(now_ms() + self.rto);
+            return;
+        }
+        if flight == 0 && !self.fin_sent && !syn {
+            self.deadline = None;
+            return;
+        }
+        self.retries += 1;
+        let max_retries = if syn { MAX_SYN_RETRIES } else { MAX_RETRIES };
+        if self.retries > max_retries {
+            self.send_reset();
+            self.abort(TIMEOUT_ERROR);
+            return;
+        }
+        match self.state {
+            TcpState::SynSent => self.send_segment(self.iss, FLAG_SYN, &[]),
+            TcpState::SynReceived => self.send_segment(self.iss, FLAG_SYN | FLAG_ACK, &[]),
+            _ => {
+                if flight > 0 {
+                    let len = flight.min(self.mss);
+                    let data: Vec<u8> = self.send_buf.range(0..len).copied().collect();
+                    self.send_segment(self.snd_una, FLAG_ACK | FLAG_PSH, &data);
+                } else {
+                    self.send_segment(self.snd_nxt.wrapping_sub(1), FLAG_FIN | FLAG_ACK, &[]);
+                }
+            }
+        }
+        // 指数退避
+        self.rto = (self.rto * 2).min(MAX_RTO);
+        self.deadline = Some(now_ms() + self.rto);
+    }
+
+    // 处理对端的确认，返回FIN是否已被确认
+    fn handle_ack(&mut self, ack: u32, window: u16) -> bool {
+        if seq_lt(self.snd_una, ack) && seq_le(ack, self.snd_nxt) {
+            let acked = ack.wrapping_sub(self.snd_una) as usize;
+            let data_acked = acked.min(self.send_buf.len());
+            self.send_buf.drain(0..data_acked);
+            self.snd_una = ack;
+            self.retries = 0;
+            self.rto = INITIAL_RTO;
+            self.deadline = None;
+            if self.snd_una != self.snd_nxt {
+                self.arm_timer();
+            }
+        }
+        self.snd_wnd = window as u32;
+        return self.fin_sent && self.snd_una == self.snd_nxt;
+    }
+
+    // 接收数据，seq之前的部分已经收到时裁掉，超出窗口的数据暂存为乱序报文
+    fn receive_data(&mut self, seq: u32, mut data: &[u8]) {
+        let mut seq = seq;
+        if seq_lt(seq, self.rcv_nxt) {
+            let dup = self.rcv_nxt.wrapping_sub(seq) as usize;
+            if dup >= data.len() {
+                return;
+            }
+            data = &data[dup..];
+            seq = self.rcv_nxt;
+        }
+        if seq != self.rcv_nxt {
+            if self.out_of_order.len() < MAX_OUT_OF_ORDER {
+                self.out_of_order.insert(seq, data.to_vec());
+            }
+            return;
+        }
+        let len = data.len().min(RECV_BUF_SIZE - self.recv_buf.len());
+        self.recv_buf.extend(data[0..len].iter());
+        self.rcv_nxt = self.rcv_nxt.wrapping_add(len as u32);
+        // 合并已经连续的乱序报文
+        loop {
+            let next = self
+                .out_of_order
+                .keys()
+                .find(|s| seq_le(**s, self.rcv_nxt))
+                .copied();
+            match next {
+                Some(s) => {
+                    let segment = self.out_of_order.remove(&s).unwrap();
+                    let skip = self.rcv_nxt.wrapping_sub(s) as usize;
+                    if skip < segment.len() {
+                        let len = (segment.len() - skip).min(RECV_BUF_SIZE - self.recv_buf.len());
+                        self.recv_buf.extend(segment[skip..skip + len].iter());
+                        self.rcv_nxt = self.rcv_nxt.wrapping_add(len as u32);
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+struct Segment<'a> {
+    src_port: u16,
+    dst_port: u16,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    window: u16,
+    mss: Option<usize>,
+    payload: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < TCP_HEADER_SIZE {
+            return None;
+        }
+        let header_len = ((data[12] >> 4) as usize) * 4;
+        if header_len < TCP_HEADER_SIZE || header_len > data.len() {
+            return None;
+        }
+        let mut segment = Self {
+            src_port: u16::from_be_bytes([data[0], data[1]]),
+            dst_port: u16::from_be_bytes([data[2], data[3]]),
+            seq: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            ack: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+            flags: data[13],
+            window: u16::from_be_bytes([data[14], data[15]]),
+            mss: None,
+            payload: &data[header_len..],
+        };
+        // 解析选项，只关心MSS
+        let options = &data[TCP_HEADER_SIZE..header_len];
+        let mut i = 0;
+        while i < options.len() {
+            match options[i] {
+                OPTION_END => break,
+                OPTION_NOP => i += 1,
+                kind => {
+                    if i + 1 >= options.len() || options[i + 1] < 2 {
+                        break;
+                    }
+                    let len = options[i + 1] as usize;
+                    if kind == OPTION_MSS && len == 4 && i + 4 <= options.len() {
+                        segment.mss = Some(u16::from_be_bytes([options[i + 2], options[i + 3]]) as usize);
+                    }
+                    i += len;
+                }
+            }
+        }
+        return Some(segment);
+    }
+
+    // 报文占用的序号长度，SYN和FIN各占一个序号
+    fn seq_len(&self) -> u32 {
+        let mut len = self.payload.len() as u32;
+        if self.flags & FLAG_SYN != 0 {
+            len += 1;
+        }
+        if self.flags & FLAG_FIN != 0 {
+            len += 1;
+        }
+        return len;
+    }
+}
+
+fn tcp_checksum(src: Ipv4Addr, dst: Ipv4Addr, segment: &[u8]) -> u16 {
+    let mut sum = checksum_add(0, &src.0);
+    sum = checksum_add(sum, &dst.0);
+    sum += PROTOCOL_TCP as u32;
+    sum += segment.len() as u32;
+    return checksum_fold(checksum_add(sum, segment));
+}
+
+fn build_segment(
+    local: SocketAddr,
+    remote: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    window: u16,
+    options: &[u8],
+    payload: &[u8],
+) -> Vec<u8> {
+    let header_len = TCP_HEADER_SIZE + (options.len() + 3) / 4 * 4;
+    let mut segment: Vec<u8> = Vec::with_capacity(header_len + payload.len());
+    segment.extend_from_slice(&local.port.to_be_bytes());
+    segment.extend_from_slice(&remote.port.to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    segment.push(((header_len / 4) as u8) << 4);
+    segment.push(flags);
+    segment.extend_from_slice(&window.to_be_bytes());
+    segment.extend_from_slice(&[0, 0, 0, 0]); // checksum、urgent pointer
+    segment.extend_from_slice(options);
+    segment.resize(header_len, OPTION_END);
+    segment.extend_from_slice(payload);
+    let sum = tcp_checksum(local.ip, remote.ip, &segment);
+    segment[16..18].copy_from_slice(&sum.to_be_bytes());
+    return segment;
+}
+
+// 回复RST，see: RFC 793 3.4 Reset Generation
+fn reset_for(local: SocketAddr, remote: SocketAddr, segment: &Segment) {
+    if segment.flags & FLAG_RST != 0 {
+        return;
+    }
+    let reset = if segment.flags & FLAG_ACK != 0 {
+        build_segment(local, remote, segment.ack, 0, FLAG_RST, 0, &[], &[])
+    } else {
+        let ack = segment.seq.wrapping_add(segment.seq_len());
+        build_segment(local, remote, 0, ack, FLAG_RST | FLAG_ACK, 0, &[], &[])
+    };
+    ipv4::send_from(local.ip, remote.ip, PROTOCOL_TCP, &reset);
+}
+
+// 查找报文所属的连接，优先匹配完整的四元组，其次是监听socket
+fn find_connection(local: SocketAddr, remote: SocketAddr) -> Option<Arc<Mutex<TcpConnection>>> {
+    let connections = CONNECTIONS.lock();
+    let mut listener = None;
+    for conn in connections.iter() {
+        let c = conn.lock();
+        if c.local.port != local.port || c.state == TcpState::Closed {
+            continue;
+        }
+        if c.state == TcpState::Listen {
+            if c.local.ip.is_unspecified() || c.local.ip == local.ip {
+                listener = Some(Arc::clone(conn));
+            }
+        } else if c.remote == remote && c.local.ip == local.ip {
+            return Some(Arc::clone(conn));
+        }
+    }
+    return listener;
+}
+
+fn add_connection(conn: Arc<Mutex<TcpConnection>>) {
+    CONNECTIONS.lock().push(conn);
+}
+
+pub fn receive(packet: &Ipv4Packet) {
+    if tcp_checksum(packet.src, packet.dst, packet.payload) != 0 {
+        return;
+    }
+    let segment = match Segment::parse(packet.payload) {
+        Some(segment) => segment,
+        None => return,
+    };
+    let local = SocketAddr::new(packet.dst, segment.dst_port);
+    let remote = SocketAddr::new(packet.src, segment.src_port);
+    let conn = match find_connection(local, remote) {
+        Some(conn) => conn,
+        None => {
+            reset_for(local, remote, &segment);
+            return;
+        }
+    };
+    let mut c = conn.lock();
+    match c.state {
+        TcpState::Listen => {
+            if segment.flags & FLAG_RST != 0 {
+                return;
+            }
+            if segment.flags & FLAG_ACK != 0 || segment.flags & FLAG_SYN == 0 {
+                reset_for(local, remote, &segment);
+                return;
+            }
+            if c.accept_queue.len() >= c.backlog {
+                return;
+            }
+            drop(c);
+            // 被动打开，新建SYN_RECEIVED状态的连接
+            let mut child = TcpConnection::new(TcpState::SynReceived, local, remote);
+            child.rcv_nxt = segment.seq.wrapping_add(1);
+            child.mss = segment.mss.unwrap_or(DEFAULT_MSS).min(LOCAL_MSS);
+            child.snd_wnd = segment.window as u32;
+            child.listener = Some(Arc::downgrade(&conn));
+            child.send_segment(child.iss, FLAG_SYN | FLAG_ACK, &[]);
+            child.snd_nxt = child.iss.wrapping_add(1);
+            child.arm_timer();
+            add_connection(Arc::new(Mutex::new(child)));
+        }
+        TcpState::SynSent => handle_syn_sent(&mut c, &segment),
+        TcpState::Closed => {}
+        _ => {
+            handle_segment(&mut c, &segment);
+            // 握手完成，加入监听socket的accept队列
+            if c.state != TcpState::SynReceived && c.listener.is_some() {
+                let listener = c.listener.take().unwrap();
+                let established = c.state != TcpState::Closed;
+                drop(c);
+                let listener = listener.upgrade().filter(|l| l.lock().state == TcpState::Listen);
+                match listener {
+                    Some(listener) if established => {
+                        listener.lock().accept_queue.push_back(Arc::clone(&conn));
+                    }
+                    // 监听socket已经关闭，重置连接
+                    None if established => {
+                        let mut c = conn.lock();
+                        c.send_reset();
+                        c.abort(CONN_RESET_ERROR);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn handle_syn_sent(c: &mut TcpConnection, segment: &Segment) {
+    let ack_ok = segment.flags & FLAG_ACK != 0
+        && seq_lt(c.iss, segment.ack)
+        && seq_le(segment.ack, c.snd_nxt);
+    if segment.flags & FLAG_ACK != 0 && !ack_ok {
+        reset_for(c.local, c.remote, segment);
+        return;
+    }
+    if segment.flags & FLAG_RST != 0 {
+        if ack_ok {
+            c.abort(CONN_REFUSED_ERROR);
+        }
+        return;
+    }
+    if segment.flags & FLAG_SYN == 0 {
+        return;
+    }
+    c.rcv_nxt = segment.seq.wrapping_add(1);
+    c.mss = segment.mss.unwrap_or(DEFAULT_MSS).min(LOCAL_MSS);
+    c.snd_wnd = segment.window as u32;
+    if ack_ok {
+        c.snd_una = segment.ack;
+        c.state = TcpState::Established;
+        c.deadline = None;
+        c.retries = 0;
+        c.rto = INITIAL_RTO;
+        c.send_ack();
+    } else {
+        // 同时打开
+        c.state = TcpState::SynReceived;
+        c.send_segment(c.iss, FLAG_SYN | FLAG_ACK, &[]);
+    }
+}
+
+// 处理已同步状态的报文，see: RFC 793 3.9 SEGMENT ARRIVES
+fn handle_segment(c: &mut TcpConnection, segment: &Segment) {
+    // 检查序号是否在接收窗口内
+    let len = segment.seq_len();
+    let wnd = c.window() as u32;
+    let start_ok = seq_le(c.rcv_nxt, segment.seq) && seq_lt(segment.seq, c.rcv_nxt.wrapping_add(wnd.max(1)));
+    let end = segment.seq.wrapping_add(len.max(1) - 1);
+    let end_ok = seq_le(c.rcv_nxt, end) && seq_lt(end, c.rcv_nxt.wrapping_add(wnd.max(1)));
+    let acceptable = if len == 0 {
+        segment.seq == c.rcv_nxt || (wnd > 0 && start_ok)
+    } else {
+        wnd > 0 && (start_ok || end_ok)
+    };
+    if !acceptable {
+        if segment.flags & FLAG_RST == 0 {
+            c.send_ack();
+        }
+        return;
+    }
+    if segment.flags & FLAG_RST != 0 {
+        // 被动打开的连接收到RST直接关闭，不通知用户
+        let error = if c.state == TcpState::SynReceived && c.listener.is_some() {
+            CONN_REFUSED_ERROR
+        } else {
+            CONN_RESET_ERROR
+        };
+        c.abort(error);
+        return;
+    }
+    if segment.flags & FLAG_SYN != 0 {
+        c.send_reset();
+        c.abort(CONN_RESET_ERROR);
+        return;
+    }
+    if segment.flags & FLAG_ACK == 0 {
+        return;
+    }
+    if c.state == TcpState::SynReceived {
+        if seq_lt(c.snd_una, segment.ack) && seq_le(segment.ack, c.snd_nxt) {
+            c.state = TcpState::Established;
+            c.snd_una = segment.ack;
+            c.snd_wnd = segment.window as u32;
+            c.deadline = None;
+            c.retries = 0;
+            c.rto = INITIAL_RTO;
+        } else {
+            reset_for(c.local, c.remote, segment);
+            return;
+        }
+    }
+    if seq_lt(c.snd_nxt, segment.ack) {
+        // 确认了还没有发送的数据
+        c.send_ack();
+        return;
+    }
+    let fin_acked = c.handle_ack(segment.ack, segment.window);
+    match c.state {
+        TcpState::FinWait1 if fin_acked => c.state = TcpState::FinWait2,
+        TcpState::Closing if fin_acked => {
+            c.state = TcpState::TimeWait;
+            c.time_wait_deadline = now_ms() + TIME_WAIT_TIMEOUT;
+        }
+        TcpState::LastAck if fin_acked => {
+            c.state = TcpState::Closed;
+            return;
+        }
+        TcpState::TimeWait => {
+            // 对端重传了FIN，重新确认
+            if segment.flags & FLAG_FIN != 0 {
+                c.send_ack();
+                c.time_wait_deadline = now_ms() + TIME_WAIT_TIMEOUT;
+            }
+            return;
+        }
+        _ => {}
+    }
+    let receiving = matches!(
+        c.state,
+        TcpState::Established | TcpState::FinWait1 | TcpState::FinWait2
+    );
+    if receiving && !segment.payload.is_empty() {
+        c.receive_data(segment.seq, segment.payload);
+        c.send_ack();
+    }
+    // FIN在所有数据之后才处理
+    let fin_seq = segment.seq.wrapping_add(segment.payload.len() as u32);
+    if segment.flags & FLAG_FIN != 0 && fin_seq == c.rcv_nxt && !c.fin_received {
+        c.rcv_nxt = c.rcv_nxt.wrapping_add(1);
+        c.fin_received = true;
+        c.send_ack();
+        c.state = match c.state {
+            TcpState::SynReceived | TcpState::Established => TcpState::CloseWait,
+            TcpState::FinWait1 if fin_acked => TcpState::TimeWait,
+            TcpState::FinWait1 => TcpState::Closing,
+            TcpState::FinWait2 => TcpState::TimeWait,
+            state => state,
+        };
+        if c.state == TcpState::TimeWait {
+            c.time_wait_deadline = now_ms() + TIME_WAIT_TIMEOUT;
+            c.deadline = None;
+        }
+    }
+    c.output();
+}
+
+// 处理重传定时器和TIME_WAIT超时，回收关闭的连接
+pub fn poll() {
+    let now = now_ms();
+    let connections: Vec<Arc<Mutex<TcpConnection>>> = CONNECTIONS.lock().clone();
+    let mut closed: Vec<Arc<Mutex<TcpConnection>>> = Vec::new();
+    for conn in connections {
+        let mut c = conn.lock();
+        if c.state == TcpState::TimeWait && now >= c.time_wait_deadline {
+            c.state = TcpState::Closed;
+        }
+        if let Some(deadline) = c.deadline {
+            if now >= deadline && c.state != TcpState::Closed {
+                c.on_timeout();
+            }
+        }
+        if c.state == TcpState::Closed {
+            drop(c);
+            closed.push(conn);
+        }
+    }
+    if !closed.is_empty() {
+        CONNECTIONS
+            .lock()
+            .retain(|conn| !closed.iter().any(|c| Arc::ptr_eq(c, conn)));
+    }
+}
+
+fn port_in_use(port: u16) -> bool {
+    if BOUND_PORTS.lock().contains(&port) {
+        return true;
+    }
+    CONNECTIONS.lock().iter().any(|conn| {
+        let c = conn.lock();
+        c.local.port == port && c.state != TcpState::Closed
+    })
+}
+
+fn alloc_ephemeral() -> Option<u16> {
+    let mut next = NEXT_EPHEMERAL.lock();
+    for _ in EPHEMERAL_START..=EPHEMERAL_END {
+        let port = *next;
+        *next = if port == EPHEMERAL_END { EPHEMERAL_START } else { port + 1 };
+        if !port_in_use(port) {
+            return Some(port);
+        }
+    }
+    return None;
+}
+
+struct TcpSocketInner {
+    local: Option<SocketAddr>,
+    bound_port: Option<u16>, // socket占用的端口，socket关闭时释放
+    conn: Option<Arc<Mutex<TcpConnection>>>,
+    timeout: Option<usize>,
+}
+
+pub struct TcpSocket {
+    inner: Mutex<TcpSocketInner>,
+}
+
+impl TcpSocket {
+    pub fn new() -> Self {
+        Self::with_connection(None, None)
+    }
+
+    fn with_connection(local: Option<SocketAddr>, conn: Option<Arc<Mutex<TcpConnection>>>) -> Self {
+        Self {
+            inner: Mutex::new(TcpSocketInner {
+                local: local,
+                bound_port: None,
+                conn: conn,
+                timeout: None,
+            }),
+        }
+    }
+
+    fn connection(&self) -> Result<Arc<Mutex<TcpConnection>>, isize> {
+        self.inner.lock().conn.clone().ok_or(NOT_CONNECTED_ERROR)
+    }
+
+    fn bind_port(inner: &mut TcpSocketInner, addr: SocketAddr) -> isize {
+        if inner.local.is_some() {
+            return SOCKET_ERROR;
+        }
+        let port = if addr.port == 0 {
+            match alloc_ephemeral() {
+                Some(port) => port,
+                None => return ADDR_IN_USE_ERROR,
+            }
+        } else if port_in_use(addr.port) {
+            return ADDR_IN_USE_ERROR;
+        } else {
+            addr.port
+        };
+        BOUND_PORTS.lock().insert(port);
+        inner.bound_port = Some(port);
+        inner.local = Some(SocketAddr::new(addr.ip, port));
+        return 0;
+    }
+
+    // 关闭socket，已连接时发送FIN，连接在后台完成关闭
+    fn close(&self) {
+        let mut inner = self.inner.lock();
+        if let Some(port) = inner.bound_port.take() {
+            BOUND_PORTS.lock().remove(&port);
+        }
+        let conn = match inner.conn.take() {
+            Some(conn) => conn,
+            None => return,
+        };
+        drop(inner);
+        let mut c = conn.lock();
+        match c.state {
+            TcpState::Listen => {
+                c.state = TcpState::Closed;
+                let queued: Vec<_> = c.accept_queue.drain(..).collect();
+                drop(c);
+                // 重置还没有accept的连接
+                for child in queued {
+                    let mut child = child.lock();
+                    child.send_reset();
+                    child.abort(CONN_RESET_ERROR);
+                }
+            }
+            TcpState::SynSent => c.state = TcpState::Closed,
+            TcpState::SynReceived | TcpState::Established | TcpState::CloseWait => {
+                // 还有没读取的数据时直接重置连接，see: RFC 2525 2.17
+                if !c.recv_buf.is_empty() {
+                    c.send_reset();
+                    c.abort(CONN_RESET_ERROR);
+                } else {
+                    c.fin_pending = true;
+                    c.read_closed = true;
+                    c.output();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for TcpSocket {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Socket for TcpSocket {
+    fn bind(&self, addr: SocketAddr) -> isize {
+        return Self::bind_port(&mut self.inner.lock(), addr);
+    }
+
+    fn connect(&self, addr: SocketAddr) -> isize {
+        let mut inner = self.inner.lock();
+        if inner.conn.is_some() {
+            return SOCKET_ERROR;
+        }
+        let src = match route(addr.ip) {
+            Some((iface, _)) => iface.ip,
+            None => return UNREACHABLE_ERROR,
+        };
+        if inner.local.is_none() {
+            let ret = Self::bind_port(&mut inner, SocketAddr::unspecified());
+            if ret != 0 {
+                return ret;
+            }
+        }
+        let local = SocketAddr::new(src, inner.local.unwrap().port);
+        inner.local = Some(local);
+        let mut conn = TcpConnection::new(TcpState::SynSent, local, addr);
+        conn.send_segment(conn.iss, FLAG_SYN, &[]);
+        conn.snd_nxt = conn.iss.wrapping_add(1);
+        conn.arm_timer();
+        let conn = Arc::new(Mutex::new(conn));
+        inner.conn = Some(Arc::clone(&conn));
+        drop(inner);
+        add_connection(Arc::clone(&conn));
+        // 等待握手完成，失败时由重传定时器超时
+        wait_until(None, || {
+            !matches!(conn.lock().state, TcpState::SynSent | TcpState::SynReceived)
+        });
+        let c = conn.lock();
+        if c.state == TcpState::Established || c.state == TcpState::CloseWait {
+            return 0;
+        }
+        return c.error.unwrap_or(CONN_REFUSED_ERROR);
+    }
+
+    fn listen(&self, backlog: usize) -> isize {
+        let mut inner = self.inner.lock();
+        if inner.conn.is_some() {
+            return SOCKET_ERROR;
+        }
+        let local = match inner.local {
+            Some(local) => local,
+            None => return SOCKET_ERROR,
+        };
+        let mut conn = TcpConnection::new(TcpState::Listen, local, SocketAddr::unspecified());
+        conn.backlog = backlog.max(1).min(MAX_BACKLOG);
+        let conn = Arc::new(Mutex::new(conn));
+        inner.conn = Some(Arc::clone(&conn));
+        drop(inner);
+        add_connection(conn);
+        return 0;
+    }
+
+    fn accept(&self) -> Result<(Arc<dyn File>, SocketAddr), isize> {
+        let listener = self.connection().map_err(|_| SOCKET_ERROR)?;
+        if listener.lock().state != TcpState::Listen {
+            return Err(SOCKET_ERROR);
+        }
+        let timeout = self.inner.lock().timeout;
+        let ready = wait_until(timeout, || {
+            let l = listener.lock();
+            !l.accept_queue.is_empty() || l.state != TcpState::Listen
+        });
+        if !ready {
+            return Err(TIMEOUT_ERROR);
+        }
+        let conn = match listener.lock().accept_queue.pop_front() {
+            Some(conn) => conn,
+            None => return Err(SOCKET_ERROR),
+        };
+        let (local, remote) = {
+            let c = conn.lock();
+            (c.local, c.remote)
+        };
+        let socket = TcpSocket::with_connection(Some(local), Some(conn));
+        return Ok((Arc::new(socket), remote));
+    }
+
+    fn shutdown(&self, how: usize) -> isize {
+        let conn = match self.connection() {
+            Ok(conn) => conn,
+            Err(code) => return code,
+        };
+        let mut c = conn.lock();
+        if how == SHUT_RD || how == SHUT_RDWR {
+            c.read_closed = true;
+        }
+        if how == SHUT_WR || how == SHUT_RDWR {
+            match c.state {
+                TcpState::Established | TcpState::CloseWait | TcpState::SynReceived => {
+                    c.fin_pending = true;
+                    c.output();
+                }
+                _ => {}
+            }
+        }
+        return 0;
+    }
+
+    // TCP忽略addr参数，数据发送给已连接的对端
+    fn send_to(&self, data: &[u8], _addr: Option<SocketAddr>) -> isize {
+        let conn = match self.connection() {
+            Ok(conn) => conn,
+            Err(code) => return code,
+        };
+        let mut sent = 0;
+        while sent < data.len() {
+            // 等待发送缓冲区有空间
+            wait_until(None, || {
+                let c = conn.lock();
+                c.send_buf.len() < SEND_BUF_SIZE || c.error.is_some() || c.fin_pending
+            });
+            let mut c = conn.lock();
+            if let Some(error) = c.error {
+                return error;
+            }
+            if c.fin_pending || !matches!(c.state, TcpState::Established | TcpState::CloseWait) {
+                return NOT_CONNECTED_ERROR;
+            }
+            let len = (data.len() - sent).min(SEND_BUF_SIZE - c.send_buf.len());
+            c.send_buf.extend(data[sent..sent + len].iter());
+            sent += len;
+            c.output();
+        }
+        return sent as isize;
+    }
+
+    fn recv_from(&self, buf: &mut [u8], nonblock: bool) -> Result<(usize, SocketAddr), isize> {
+        let conn = self.connection()?;
+        let timeout = self.inner.lock().timeout;
+        let ready = || {
+            let c = conn.lock();
+            !c.recv_buf.is_empty() || c.fin_received || c.read_closed || c.error.is_some()
+        };
+        if nonblock {
+            if !ready() {
+                return Err(WOULD_BLOCK_ERROR);
+            }
+        } else if !wait_until(timeout, ready) {
+            return Err(TIMEOUT_ERROR);
+        }
+        let mut c = conn.lock();
+        if c.recv_buf.is_empty() {
+            // 对端关闭或本端关闭了读端时返回0
+            if let Some(error) = c.error {
+                return Err(error);
+            }
+            return Ok((0, c.remote));
+        }
+        let old_window = c.window() as usize;
+        let len = buf.len().min(c.recv_buf.len());
+        for (i, byte) in c.recv_buf.drain(0..len).enumerate() {
+            buf[i] = byte;
+        }
+        // 窗口从小于MSS恢复时通知对端
+        if old_window < c.mss && c.window() as usize >= c.mss && c.state != TcpState::Closed {
+            c.send_ack();
+        }
+        return Ok((len, c.remote));
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.lock().local
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.connection().ok().map(|conn| conn.lock().remote)
+    }
+
+    fn set_recv_timeout(&self, timeout: Option<usize>) {
+        self.inner.lock().timeout = timeout;
+    }
+
+    fn max_io_size(&self) -> usize {
+        SEND_BUF_SIZE.max(RECV_BUF_SIZE)
+    }
+
+    fn is_stream(&self) -> bool {
+        true
+    }
+}
+
+impl File for TcpSocket {
+    fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
+        let mut data = vec![0u8; buf.length().min(self.max_io_size())];
+        match self.recv_from(&mut data, false) {
+            Ok((len, _)) if len > 0 => {
+                buf.write(0, &data[0..len]);
+                len
+            }
+            _ => 0,
+        }
+    }
+    fn write<'a>(&self, buf: &mut UserBuffer) -> usize {
+        return write_to_socket(self, buf);
+    }
+    fn fstat(&self) -> Option<FileStat> {
+        None
+    }
+    fn lseek(&self, _offset: u32, _from: u8) -> isize {
+        -1
+    }
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
@@ -0,0 +1,291 @@
+use super::ipv4::{self, Ipv4Packet, PROTOCOL_UDP};
+use super::socket::*;
+use super::dhcp::{self, DHCP_CLIENT_PORT};
+use super::{checksum_add, checksum_fold, route, wait_until, Ipv4Addr};
+use crate::fs::{File, FileStat, UserBuffer};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use lazy_static::lazy_static;
+use spin::mutex::Mutex;
+
+// UDP协议，see: RFC 768
+const UDP_HEADER_SIZE: usize = 8;
+// 每个socket最多缓存的未读取报文数量
+const MAX_QUEUED_DATAGRAMS: usize = 64;
+const MAX_DATAGRAM_SIZE: usize = 65507;
+// 临时端口范围
+const EPHEMERAL_START: u16 = 49152;
+const EPHEMERAL_END: u16 = 65535;
+
+struct UdpInner {
+    local: Option<SocketAddr>,
+    peer: Option<SocketAddr>,
+    queue: VecDeque<(SocketAddr, Vec<u8>)>,
+    timeout: Option<usize>,
+}
+
+pub struct UdpSocket {
+    inner: Arc<Mutex<UdpInner>>,
+}
+
+lazy_static! {
+    // 端口号 -> 绑定该端口的socket
+    static ref UDP_PORTS: Mutex<BTreeMap<u16, Weak<Mutex<UdpInner>>>> = Mutex::new(BTreeMap::new());
+    static ref NEXT_EPHEMERAL: Mutex<u16> = Mutex::new(EPHEMERAL_START);
+}
+
+impl UdpSocket {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(UdpInner {
+                local: None,
+                peer: None,
+                queue: VecDeque::new(),
+                timeout: None,
+            })),
+        }
+    }
+
+    // 绑定端口，port为0时分配临时端口
+    fn bind_port(&self, inner: &mut UdpInner, addr: SocketAddr) -> isize {
+        if inner.local.is_some() {
+            return SOCKET_ERROR;
+        }
+        let mut ports = UDP_PORTS.lock();
+        ports.retain(|_, socket| socket.strong_count() > 0);
+        let port = if addr.port == 0 {
+            match alloc_ephemeral(&ports) {
+                Some(port) => port,
+                None => return ADDR_IN_USE_ERROR,
+            }
+        } else if ports.contains_key(&addr.port) {
+            return ADDR_IN_USE_ERROR;
+        } else {
+            addr.port
+        };
+        ports.insert(port, Arc::downgrade(&self.inner));
+        inner.local = Some(SocketAddr::new(addr.ip, port));
+        return 0;
+    }
+}
+
+fn alloc_ephemeral(ports: &BTreeMap<u16, Weak<Mutex<UdpInner>>>) -> Option<u16> {
+    let mut next = NEXT_EPHEMERAL.lock();
+    for _ in EPHEMERAL_START..=EPHEMERAL_END {
+        let port = *next;
+        *next = if port == EPHEMERAL_END { EPHEMERAL_START } else { port + 1 };
+        if !ports.contains_key(&port) {
+            return Some(port);
+        }
+    }
+    return None;
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        if let Some(local) = self.inner.lock().local {
+            let mut ports = UDP_PORTS.lock();
+            let bound_here = ports
+                .get(&local.port)
+                .map(|socket| socket.as_ptr() == Arc::as_ptr(&self.inner))
+                .unwrap_or(false);
+            if bound_here {
+                ports.remove(&local.port);
+            }
+        }
+    }
+}
+
+impl Socket for UdpSocket {
+    fn bind(&self, addr: SocketAddr) -> isize {
+        let mut inner = self.inner.lock();
+        return self.bind_port(&mut inner, addr);
+    }
+
+    fn connect(&self, addr: SocketAddr) -> isize {
+        let mut inner = self.inner.lock();
+        if inner.local.is_none() {
+            let ret = self.bind_port(&mut inner, SocketAddr::unspecified());
+            if ret != 0 {
+                return ret;
+            }
+        }
+        inner.peer = Some(addr);
+        return 0;
+    }
+
+    fn send_to(&self, data: &[u8], addr: Option<SocketAddr>) -> isize {
+        if data.len() > MAX_DATAGRAM_SIZE {
+            return SOCKET_ERROR;
+        }
+        let mut inner = self.inner.lock();
+        let dst = match addr.or(inner.peer) {
+            Some(dst) => dst,
+            None => return NOT_CONNECTED_ERROR,
+        };
+        if inner.local.is_none() {
+            let ret = self.bind_port(&mut inner, SocketAddr::unspecified());
+            if ret != 0 {
+                return ret;
+            }
+        }
+        let local = inner.local.unwrap();
+        drop(inner);
+        let src = match route(dst.ip) {
+            Some((iface, _)) => iface.ip,
+            None => return UNREACHABLE_ERROR,
+        };
+        let datagram = build_datagram(src, local.port, dst, data);
+        if !ipv4::send(dst.ip, PROTOCOL_UDP, &datagram) {
+            return UNREACHABLE_ERROR;
+        }
+        return data.len() as isize;
+    }
+
+    fn recv_from(&self, buf: &mut [u8], nonblock: bool) -> Result<(usize, SocketAddr), isize> {
+        let mut inner = self.inner.lock();
+        if inner.local.is_none() {
+            return Err(NOT_CONNECTED_ERROR);
+        }
+        let timeout = inner.timeout;
+        drop(inner);
+        // 检查队列和取出报文在同一次加锁中完成，避免报文被其他cpu上的任务取走
+        let received = RefCell::new(None);
+        let ready = || match self.inner.lock().queue.pop_front() {
+            Some(datagram) => {
+                *received.borrow_mut() = Some(datagram);
+                true
+            }
+            None => false,
+        };
+        if nonblock {
+            if !ready() {
+                return Err(WOULD_BLOCK_ERROR);
+            }
+        } else if !wait_until(timeout, ready) {
+            return Err(TIMEOUT_ERROR);
+        }
+        let (src, data) = received.take().unwrap();
+        // 缓冲区不足时丢弃报文剩余的部分
+        let len = data.len().min(buf.len());
+        buf[0..len].copy_from_slice(&data[0..len]);
+        return Ok((len, src));
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.lock().local
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.inner.lock().peer
+    }
+
+    fn set_recv_timeout(&self, timeout: Option<usize>) {
+        self.inner.lock().timeout = timeout;
+    }
+
+    fn max_io_size(&self) -> usize {
+        MAX_DATAGRAM_SIZE
+    }
+}
+
+// 已连接的socket可以使用read、write
+impl File for UdpSocket {
+    fn read<'a>(&self, buf: &mut UserBuffer) -> usize {
+        let mut data = vec![0u8; buf.length().min(MAX_DATAGRAM_SIZE)];
+        match self.recv_from(&mut data, false) {
+            Ok((len, _)) if len > 0 => {
+                buf.write(0, &data[0..len]);
+                len
+            }
+            _ => 0,
+        }
+    }
+    // 超过MAX_DATAGRAM_SIZE的报文在分配缓冲区之前返回0
+    fn write<'a>(&self, buf: &mut UserBuffer) -> usize {
+        return write_to_socket(self, buf);
+    }
+    fn fstat(&self) -> Option<FileStat> {
+        None
+    }
+    fn lseek(&self, _offset: u32, _from: u8) -> isize {
+        -1
+    }
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+}
+
+// UDP校验和包含源、目的地址组成的伪首部
+fn udp_checksum(src: Ipv4Addr, dst: Ipv4Addr, datagram: &[u8]) -> u16 {
+    let mut sum = checksum_add(0, &src.0);
+    sum = checksum_add(sum, &dst.0);
+    sum += PROTOCOL_UDP as u32;
+    sum += datagram.len() as u32;
+    return checksum_fold(checksum_add(sum, datagram));
+}
+
+pub fn build_datagram(src: Ipv4Addr, src_port: u16, dst: SocketAddr, data: &[u8]) -> Vec<u8> {
+    let len = UDP_HEADER_SIZE + data.len();
+    let mut datagram: Vec<u8> = Vec::with_capacity(len);
+    datagram.extend_from_slice(&src_port.to_be_bytes());
+    datagram.extend_from_slice(&dst.port.to_be_bytes());
+    datagram.extend_from_slice(&(len as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(data);
+    let mut sum = udp_checksum(src, dst.ip, &datagram);
+    // 计算结果为0时发送0xffff，0表示没有校验和
+    if sum == 0 {
+        sum = 0xffff;
+    }
+    datagram[6..8].copy_from_slice(&sum.to_be_bytes());
+    return datagram;
+}
+
+pub fn receive(packet: &Ipv4Packet) {
+    let data = packet.payload;
+    if data.len() < UDP_HEADER_SIZE {
+        return;
+    }
+    let src_port = u16::from_be_bytes([data[0], data[1]]);
+    let dst_port = u16::from_be_bytes([data[2], data[3]]);
+    let len = u16::from_be_bytes([data[4], data[5]]) as usize;
+    let sum = u16::from_be_bytes([data[6], data[7]]);
+    if len < UDP_HEADER_SIZE || len > data.len() {
+        return;
+    }
+    let datagram = &data[0..len];
+    if sum != 0 && udp_checksum(packet.src, packet.dst, datagram) != 0 {
+        return;
+    }
+    // DHCP客户端在内核中
+    if dst_port == DHCP_CLIENT_PORT {
+        dhcp::receive(packet, &datagram[UDP_HEADER_SIZE..]);
+        return;
+    }
+    let socket = UDP_PORTS.lock().get(&dst_port).and_then(|s| s.upgrade());
+    let socket = match socket {
+        Some(socket) => socket,
+        None => return,
+    };
+    let src = SocketAddr::new(packet.src, src_port);
+    let mut inner = socket.lock();
+    let local = inner.local.unwrap();
+    // 绑定了具体地址的socket只接收发往该地址的报文
+    if !local.ip.is_unspecified() && local.ip != packet.dst && !packet.iface.is_broadcast(packet.dst) {
+        return;
+    }
+    // 已连接的socket只接收对端发来的报文
+    if let Some(peer) = inner.peer {
+        if peer != src {
+            return;
+        }
+    }
+    if inner.queue.len() >= MAX_QUEUED_DATAGRAMS {
+        return;
+    }
+    inner.queue.push_back((src, datagram[UDP_HEADER_SIZE..].to_vec()));
+}
@@ -7,6 +7,5 @@ pub mod pid;
 
 pub fn init_proc() {
     let shell = open_file("/bin/shell", OpenFlags::RDONLY).unwrap();
-    let data = shell.read_all();
-    pcb::ProcessControlBlock::from_elf_data(data.as_slice());
+    pcb::ProcessControlBlock::from_elf(shell.inode());
 }
@@ -14,6 +14,8 @@ use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefMut;
+use core::sync::atomic::{AtomicBool, Ordering};
+use simplefs::vfs::Inode;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ProcessState {
@@ -24,9 +26,18 @@ pub enum ProcessState {
     Zombie,
 }
 
+// 资源限制，cur为当前生效的限制，不能超过max
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
 pub struct ProcessControlBlock {
     pid: Pid,
     pub stack_base: usize,
+    exited: AtomicBool, // 进程已经结束，其他cpu上的线程不借用inner也能检查
     inner: SafeCell<InnerPCB>,
 }
 
@@ -42,19 +53,21 @@ pub struct InnerPCB {
     pub mutex_table: Vec<Option<Arc<dyn Mutex>>>, // 进程持有的mutex表，option表示一个mutex槽位是否空闲
     pub cond_table: Vec<Option<Arc<Cond>>>,
     pub fd_table: Vec<Option<Arc<dyn File>>>, // 进程持有的fd表
+    pub stack_limit: RLimit,                  // 每个线程栈的大小上限
 }
 
 impl ProcessControlBlock {
-    // 从elf数据创建PCB
-    pub fn from_elf_data(data: &[u8]) -> Arc<ProcessControlBlock> {
+    // 从elf文件创建PCB
+    pub fn from_elf(inode: Arc<Inode>) -> Arc<ProcessControlBlock> {
         let pid = alloc_pid().unwrap();
-        // 从elf数据创建用户地址空间
-        let (memset, entry_point, stack_base) = MemorySet::from_elf_data(data);
+        let mem_size = inode.size() as usize;
+        // 从elf文件创建用户地址空间，elf段在访问时才从文件读取
+        let (memset, entry_point, stack_base) = MemorySet::from_elf(inode);
         let kernel_satp = crate::mem::kernel::kernel_satp();
 
         let trap_context_ppn = memset.vpn_to_ppn(VirtAddr(TRAP_CONTEXT).vpn()).unwrap();
         let mut inner = InnerPCB {
-            mem_size: data.len(),
+            mem_size: mem_size,
             memory_set: memset,
             parent: None,
             children: Vec::new(),
@@ -69,10 +82,15 @@ impl ProcessControlBlock {
                 Some(Arc::new(Stdout {})), // fd=1, stdout
                 Some(Arc::new(Stdout {})), // fd=2, stderr -> stdout
             ],
+            stack_limit: RLimit {
+                cur: USER_STACK_LIMIT,
+                max: USER_STACK_LIMIT_MAX,
+            },
         };
         let proc = Arc::new(Self {
             pid: pid,
             stack_base: stack_base,
+            exited: AtomicBool::new(false),
             inner: SafeCell::new(inner),
         });
         // 创建main线程，然后将main线程交给调度器
@@ -85,9 +103,15 @@ impl ProcessControlBlock {
     }
 
     // fork 子进程
-    pub fn fork(parent: Arc<ProcessControlBlock>, tid: usize) -> Arc<ProcessControlBlock> {
-        let pid = alloc_pid().unwrap();
+    // 物理内存不足，无法读回父进程换出的页时返回None
+    pub fn fork(parent: Arc<ProcessControlBlock>, tid: usize) -> Option<Arc<ProcessControlBlock>> {
         let mut p_inner = parent.borrow_inner();
+        // 子进程只拷贝驻留内存的页，换出的页需要先读回
+        // 共享匿名映射先分配全部物理页，分配时可能换出私有页，所以在读回换出的页之前
+        if !p_inner.memory_set.populate_shared() || !p_inner.memory_set.swap_in_all() {
+            return None;
+        }
+        let pid = alloc_pid().unwrap();
         let memset = MemorySet::from_parent(&p_inner.memory_set, parent.stack_base);
         let kernel_satp = crate::mem::kernel::kernel_satp();
 
@@ -113,10 +137,12 @@ impl ProcessControlBlock {
             mutex_table: Vec::new(),
             cond_table: Vec::new(),
             fd_table: fd_table,
+            stack_limit: p_inner.stack_limit,
         };
         let pcb = Arc::new(ProcessControlBlock {
             stack_base: parent.stack_base,
             pid: pid,
+            exited: AtomicBool::new(false),
             inner: SafeCell::new(inner),
         });
         p_inner.children.push(Arc::clone(&pcb));
@@ -141,33 +167,54 @@ impl ProcessControlBlock {
         // 提交线程给调度器
         push_task(Arc::clone(&child_task));
         pcb.borrow_inner().tasks.push(child_task);
-        return pcb;
+        return Some(pcb);
     }
 
-    pub fn copy_on_write(&self, vpn: VirtPageNumber) -> bool {
+    // 处理用户页错误，返回false表示访问无效
+    pub fn handle_page_fault(&self, vpn: VirtPageNumber, write: bool) -> bool {
         let mut inner = self.inner.borrow();
-        let vpn_valid = inner.memory_set.copy_on_write(vpn);
+        let limit = inner.stack_limit.cur;
+        if inner.memory_set.handle_page_fault(vpn, write) {
+            return true;
+        }
+        // 地址没有映射时，可能是线程栈需要增长
+        let vpn_valid = inner.memory_set.grow_stack(vpn, self.stack_base, limit)
+            && inner.memory_set.handle_page_fault(vpn, write);
         return vpn_valid;
     }
 
+    // 内核访问的用户地址可能在还没有增长的栈中，先扩展栈
+    fn grow_stack(&self, inner: &mut InnerPCB, addr: usize) {
+        let limit = inner.stack_limit.cur;
+        inner
+            .memory_set
+            .grow_stack(VirtAddr(addr).vpn(), self.stack_base, limit);
+    }
+
     pub fn user_satp(&self) -> usize {
         return self.inner.borrow().memory_set.satp();
     }
     // 转换虚拟地址buffer到物理页集合
     pub fn translate_buffer(&self, addr: usize, len: usize) -> Vec<&'static mut [u8]> {
-        let inner = self.inner.borrow();
+        let mut inner = self.inner.borrow();
+        // 按需分配的页和文件映射的页可能还没有加载
+        self.grow_stack(&mut inner, addr);
+        inner.memory_set.fault_in(addr, len);
         let res = inner.memory_set.translate_buffer(addr, len);
         drop(inner);
         return res;
     }
 
     pub fn translate_string(&self, addr: usize) -> alloc::string::String {
-        let inner = self.inner.borrow();
+        let mut inner = self.inner.borrow();
+        self.grow_stack(&mut inner, addr);
         return inner.memory_set.translate_string(addr);
     }
 
     pub fn translate_va(&self, va: usize) -> usize {
-        let inner = self.inner.borrow();
+        let mut inner = self.inner.borrow();
+        self.grow_stack(&mut inner, va);
+        inner.memory_set.fault_in(va, 1);
         inner.memory_set.va_to_pa(VirtAddr(va)).unwrap().0
     }
 
@@ -175,10 +222,24 @@ impl ProcessControlBlock {
         self.inner.borrow()
     }
 
+    // pcb正在被使用时返回None
+    pub fn try_borrow_inner(&self) -> Option<RefMut<'_, InnerPCB>> {
+        self.inner.try_borrow()
+    }
+
     pub fn pid(&self) -> usize {
         return self.pid.0;
     }
 
+    pub fn is_exited(&self) -> bool {
+        return self.exited.load(Ordering::SeqCst);
+    }
+
+    // 标记进程结束，已经结束时返回false
+    pub fn mark_exited(&self) -> bool {
+        return !self.exited.swap(true, Ordering::SeqCst);
+    }
+
     pub fn alloc_tid(&self) -> usize {
         self.borrow_inner().tid_allocator.alloc().unwrap()
     }
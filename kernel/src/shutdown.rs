@@ -1,4 +1,5 @@
-use crate::arch::riscv::qemu::layout::SHUTDOWN0;
+use crate::arch::riscv::qemu::layout::board;
+use crate::driver::device::shutdown_devices;
 
 pub const RESET_TYPE_SHUTDOWN: usize = 0x0000_0000;
 pub const RESET_TYPE_COLD_REBOOT: usize = 0x0000_0001;
@@ -11,7 +12,7 @@ const TEST_PASS: u32 = 0x5555;
 const TEST_RESET: u32 = 0x7777;
 
 pub fn system_reset(reset_type: usize, reset_reason: usize) {
-    const VIRT_TEST: *mut u32 = SHUTDOWN0 as *mut u32;
+    let virt_test = board().shutdown.base as *mut u32;
 
     let mut value = match reset_type {
         RESET_TYPE_SHUTDOWN => TEST_PASS,
@@ -25,7 +26,7 @@ pub fn system_reset(reset_type: usize, reset_reason: usize) {
     }
 
     unsafe {
-        core::ptr::write_volatile(VIRT_TEST, value);
+        core::ptr::write_volatile(virt_test, value);
     }
     panic!("unreachable");
 }
@@ -37,9 +38,11 @@ pub fn panic_shutdown() {
 
 #[inline]
 pub fn shutdown() {
+    shutdown_devices();
     system_reset(RESET_TYPE_SHUTDOWN, RESET_REASON_NO_REASON);
 }
 #[inline]
 pub fn reboot() {
+    shutdown_devices();
     system_reset(RESET_TYPE_COLD_REBOOT, RESET_REASON_NO_REASON);
 }
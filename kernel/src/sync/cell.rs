@@ -16,4 +16,8 @@ impl<T> SafeCell<T> {
     pub fn borrow(&self) -> RefMut<'_, T> {
         return self.inner.borrow_mut();
     }
+    // 已经被借用时返回None
+    pub fn try_borrow(&self) -> Option<RefMut<'_, T>> {
+        return self.inner.try_borrow_mut().ok();
+    }
 }
@@ -1,3 +1,4 @@
 pub mod cell;
 pub mod cond;
 pub mod mutex;
+pub mod wait_queue;
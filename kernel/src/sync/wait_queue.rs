@@ -0,0 +1,46 @@
+use crate::task::scheduler::{current_task, push_task, schedule_idle};
+use crate::task::tcb::{TaskControlBlock, TaskStatus};
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use spin::mutex::MutexGuard;
+
+// 等待队列，线程阻塞等待某个条件，条件满足时由其他线程唤醒
+pub struct WaitQueue {
+    queue: spin::Mutex<VecDeque<Weak<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            queue: spin::Mutex::new(VecDeque::new()),
+        }
+    }
+
+    // 当前线程加入队列后释放guard并阻塞，被唤醒后需要重新检查条件
+    // 释放guard之前先标记为阻塞，避免唤醒发生在阻塞之前而丢失
+    pub fn wait<T>(&self, guard: MutexGuard<T>) {
+        let task = current_task();
+        task.inner.borrow().status = TaskStatus::Blocked;
+        self.queue.lock().push_back(Arc::downgrade(&task));
+        drop(guard);
+        push_task(task);
+        schedule_idle();
+    }
+
+    pub fn wake_one(&self) {
+        while let Some(task) = self.queue.lock().pop_front() {
+            // 跳过已经退出的线程
+            if let Some(task) = task.upgrade() {
+                task.wake_up();
+                return;
+            }
+        }
+    }
+
+    pub fn wake_all(&self) {
+        let tasks: VecDeque<_> = self.queue.lock().drain(..).collect();
+        for task in tasks.iter().filter_map(|task| task.upgrade()) {
+            task.wake_up();
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use crate::driver::device::{list_devices, DeviceInfo};
+use crate::fs::UserBuffer;
+use core::mem::size_of;
+
+// 列出内核发现的设备，写入用户的DeviceInfo数组，返回设备总数
+pub fn sys_ls_dev(buf_ptr: usize, count: usize) -> isize {
+    let devices = list_devices();
+    let len = count.min(devices.len()) * size_of::<DeviceInfo>();
+    if len > 0 {
+        let mut buf = UserBuffer::from_current_proc(buf_ptr, len);
+        for (i, info) in devices.iter().take(count).enumerate() {
+            buf.write(i * size_of::<DeviceInfo>(), info.as_bytes());
+        }
+    }
+    return devices.len() as isize;
+}
@@ -1,4 +1,4 @@
-use crate::fs::inode::{find, open_file, OSInode, OpenFlags};
+use crate::fs::inode::{find, mkfifo, open_file, OSInode, OpenFlags};
 use crate::fs::FileStat;
 use crate::fs::UserBuffer;
 use crate::task::scheduler::{current_proc, current_task_translate_string};
@@ -42,6 +42,16 @@ pub fn sys_open(path: usize, flags: u32) -> isize {
     }
 }
 
+// 创建命名管道，文件已存在时返回FILE_EXIST_ERROR
+pub fn sys_mkfifo(path: usize) -> isize {
+    let proc = current_proc();
+    let name = proc.translate_string(path);
+    match mkfifo(name.as_str()) {
+        Ok(()) => 0,
+        Err(code) => code,
+    }
+}
+
 pub fn sys_close(fd: usize) -> isize {
     let proc = current_proc();
     let mut inner = proc.borrow_inner();
@@ -99,7 +109,7 @@ pub fn sys_lseek(fd: usize, offset: u32, from: u8) -> isize {
 pub fn sys_ls_dir(path_ptr: usize, res: usize, size: usize) -> isize {
     let proc = current_proc();
     let name = proc.translate_string(path_ptr);
-    if let Ok(file) = open_file(name.as_str(), OpenFlags::RDONLY) {
+    if let Ok(file) = find(name.as_str()) {
         if !file.is_dir() {
             return -2;
         }
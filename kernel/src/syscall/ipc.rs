@@ -1,5 +1,14 @@
+use crate::config::{MMAP_BOTTOM, MMAP_TOP, PAGE_SIZE};
+use crate::fs::{File, UserBuffer};
+use crate::ipc::mqueue::*;
 use crate::ipc::pipe::create_pipe;
+use crate::ipc::shm::*;
+use crate::mem::address::{VirtAddr, VirtPageNumber};
+use crate::mem::memory_set::MemPermission;
 use crate::task::scheduler::current_proc;
+use alloc::sync::Arc;
+use alloc::vec;
+use core::mem::size_of;
 
 pub fn sys_pipe(addr: usize) -> isize {
     let proc = current_proc();
@@ -8,12 +17,199 @@ pub fn sys_pipe(addr: usize) -> isize {
         let ptr = proc.translate_va(addr) as *mut usize;
         fd_table = core::slice::from_raw_parts_mut(ptr, 2);
     }
-    let (r_fd, w_fd) = (proc.alloc_fd(), proc.alloc_fd());
     let (r_pipe, w_pipe) = create_pipe();
-
+    // alloc_fd不会占用fd，分配写端之前先放入读端
+    let r_fd = proc.alloc_fd();
     proc.borrow_inner().fd_table[r_fd] = Some(r_pipe);
+    let w_fd = proc.alloc_fd();
     proc.borrow_inner().fd_table[w_fd] = Some(w_pipe);
     fd_table[0] = r_fd;
     fd_table[1] = w_fd;
     0
 }
+
+fn mq_descriptor(fd: usize) -> Option<Arc<dyn File>> {
+    let proc = current_proc();
+    let inner = proc.borrow_inner();
+    let file = inner.fd_table.get(fd)?.as_ref()?;
+    if file.as_mqueue().is_none() {
+        return None;
+    }
+    return Some(Arc::clone(file));
+}
+
+fn read_mq_attr(attr_ptr: usize) -> MqAttr {
+    let mut data = [0u8; size_of::<MqAttr>()];
+    UserBuffer::from_current_proc(attr_ptr, size_of::<MqAttr>()).read(0, &mut data);
+    return unsafe { core::ptr::read_unaligned(data.as_ptr() as *const MqAttr) };
+}
+
+// 打开命名消息队列，attr_ptr为0时使用默认属性，返回fd
+pub fn sys_mq_open(name_ptr: usize, flags: usize, attr_ptr: usize) -> isize {
+    let proc = current_proc();
+    let name = proc.translate_string(name_ptr);
+    let attr = if attr_ptr == 0 { None } else { Some(read_mq_attr(attr_ptr)) };
+    match mq_open(name.as_str(), flags, attr) {
+        Ok(descriptor) => {
+            let fd = proc.alloc_fd();
+            proc.borrow_inner().fd_table[fd] = Some(Arc::new(descriptor));
+            fd as isize
+        }
+        Err(code) => code,
+    }
+}
+
+pub fn sys_mq_unlink(name_ptr: usize) -> isize {
+    let name = current_proc().translate_string(name_ptr);
+    return mq_unlink(name.as_str());
+}
+
+// timeout单位ms，0表示一直等待
+pub fn sys_mq_timedsend(fd: usize, buf_ptr: usize, len: usize, prio: usize, timeout: usize) -> isize {
+    let file = match mq_descriptor(fd) {
+        Some(file) => file,
+        None => return MQ_ERROR,
+    };
+    // 先检查长度再分配内核缓冲区
+    let descriptor = file.as_mqueue().unwrap();
+    if len > descriptor.msg_size() {
+        return MQ_MSG_SIZE_ERROR;
+    }
+    let mut data = vec![0u8; len];
+    if len > 0 {
+        UserBuffer::from_current_proc(buf_ptr, len).read(0, &mut data);
+    }
+    let timeout = if timeout == 0 { None } else { Some(timeout) };
+    return descriptor.send(&data, prio, timeout);
+}
+
+// 返回消息长度，消息的优先级写入prio_ptr指向的u32
+pub fn sys_mq_timedreceive(fd: usize, buf_ptr: usize, len: usize, prio_ptr: usize, timeout: usize) -> isize {
+    let file = match mq_descriptor(fd) {
+        Some(file) => file,
+        None => return MQ_ERROR,
+    };
+    // 缓冲区需要能放下最长的消息，内核只分配msg_size字节
+    let descriptor = file.as_mqueue().unwrap();
+    let msg_size = descriptor.msg_size();
+    if len < msg_size {
+        return MQ_MSG_SIZE_ERROR;
+    }
+    let mut data = vec![0u8; msg_size];
+    let timeout = if timeout == 0 { None } else { Some(timeout) };
+    match descriptor.receive(&mut data, timeout) {
+        Ok((size, prio)) => {
+            if size > 0 {
+                UserBuffer::from_current_proc(buf_ptr, size).write(0, &data[0..size]);
+            }
+            if prio_ptr != 0 {
+                let mut buf = UserBuffer::from_current_proc(prio_ptr, size_of::<u32>());
+                buf.write(0, &(prio as u32).to_ne_bytes());
+            }
+            size as isize
+        }
+        Err(code) => code,
+    }
+}
+
+// 读取队列属性写入old_attr_ptr，new_attr_ptr不为0时设置是否非阻塞
+pub fn sys_mq_getsetattr(fd: usize, new_attr_ptr: usize, old_attr_ptr: usize) -> isize {
+    let file = match mq_descriptor(fd) {
+        Some(file) => file,
+        None => return MQ_ERROR,
+    };
+    let descriptor = file.as_mqueue().unwrap();
+    let old = descriptor.attr();
+    if new_attr_ptr != 0 {
+        let attr = read_mq_attr(new_attr_ptr);
+        descriptor.set_nonblock(attr.flags & MQ_NONBLOCK != 0);
+    }
+    if old_attr_ptr != 0 {
+        let data = unsafe {
+            core::slice::from_raw_parts(&old as *const MqAttr as *const u8, size_of::<MqAttr>())
+        };
+        UserBuffer::from_current_proc(old_attr_ptr, size_of::<MqAttr>()).write(0, data);
+    }
+    return 0;
+}
+
+// 获取或创建共享内存段，返回shmid
+pub fn sys_shmget(key: usize, size: usize, flags: usize) -> isize {
+    match shm_get(key, size, flags) {
+        Ok(id) => id as isize,
+        Err(code) => code,
+    }
+}
+
+// 将共享内存段映射到当前进程，addr为0时由内核选择地址，返回映射的起始地址
+pub fn sys_shmat(shmid: usize, addr: usize, flags: usize) -> isize {
+    let segment = match shm_segment(shmid) {
+        Some(segment) => segment,
+        None => return SHM_NOT_FOUND_ERROR,
+    };
+    let pages = segment.frames().len();
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner();
+    let start_vpn = if addr == 0 {
+        match inner.memory_set.find_free_area(pages) {
+            Some(vpn) => vpn,
+            None => return SHM_ERROR,
+        }
+    } else {
+        // 指定的地址必须页对齐，并且在动态映射区域内没有被占用
+        let start_vpn = VirtAddr(addr).vpn();
+        let end_vpn = VirtPageNumber(start_vpn.0 + pages);
+        if addr % PAGE_SIZE != 0
+            || addr < MMAP_BOTTOM
+            || end_vpn.base_addr() > MMAP_TOP
+            || inner.memory_set.overlaps(start_vpn, end_vpn)
+        {
+            return SHM_ERROR;
+        }
+        start_vpn
+    };
+    let mut perm = MemPermission::R.bits() | MemPermission::U.bits();
+    if flags & SHM_RDONLY == 0 {
+        perm |= MemPermission::W.bits();
+    }
+    inner
+        .memory_set
+        .insert_shared_area(start_vpn, segment.frames(), perm);
+    return start_vpn.base_addr() as isize;
+}
+
+// 解除addr处的共享内存映射，物理页的引用随内存段一起释放
+pub fn sys_shmdt(addr: usize) -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner();
+    let start_vpn = VirtAddr(addr).vpn();
+    let attached = inner
+        .memory_set
+        .areas
+        .iter()
+        .any(|area| area.shared && area.start_vpn == start_vpn);
+    if addr % PAGE_SIZE != 0 || !attached {
+        return SHM_ERROR;
+    }
+    inner.memory_set.remove_area(start_vpn);
+    return 0;
+}
+
+// IPC_RMID删除段，IPC_STAT将ShmStat写入buf_ptr
+pub fn sys_shmctl(shmid: usize, cmd: usize, buf_ptr: usize) -> isize {
+    match cmd {
+        IPC_RMID => shm_remove(shmid),
+        IPC_STAT if buf_ptr != 0 => {
+            let stat = match shm_segment(shmid) {
+                Some(segment) => segment.stat(),
+                None => return SHM_NOT_FOUND_ERROR,
+            };
+            let data = unsafe {
+                core::slice::from_raw_parts(&stat as *const ShmStat as *const u8, size_of::<ShmStat>())
+            };
+            UserBuffer::from_current_proc(buf_ptr, size_of::<ShmStat>()).write(0, data);
+            0
+        }
+        _ => SHM_ERROR,
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config::PAGE_SIZE;
+use crate::fs::page_cache::page_cache;
+use crate::fs::UserBuffer;
+use crate::mem::allocator::{mem_info, MemInfo};
+use crate::mem::mmap::*;
+use crate::mem::slab::{slab_info, SlabInfo};
+use crate::task::scheduler::current_proc;
+use core::mem::size_of;
+
+// 设置用户堆的结束地址，返回新的结束地址，失败时返回原来的值，addr为0时返回当前值
+pub fn sys_brk(addr: usize) -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner();
+    return inner.memory_set.set_brk(addr) as isize;
+}
+
+// 匿名映射忽略fd和offset，文件映射的offset必须页对齐，返回映射的起始地址
+pub fn sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    let shared = flags & MAP_SHARED != 0;
+    if shared == (flags & MAP_PRIVATE != 0) {
+        return MMAP_ERROR;
+    }
+    let fixed = flags & MAP_FIXED != 0;
+    let perm = prot_to_perm(prot);
+    let proc = current_proc();
+    if flags & MAP_ANONYMOUS != 0 {
+        let mut inner = proc.borrow_inner();
+        return match inner.memory_set.mmap_anonymous(addr, len, perm, fixed, shared) {
+            Ok(addr) => addr as isize,
+            Err(code) => code,
+        };
+    }
+    if offset % PAGE_SIZE != 0 {
+        return MMAP_ERROR;
+    }
+    let inode = {
+        let inner = proc.borrow_inner();
+        let file = match inner.fd_table.get(fd).and_then(|file| file.as_ref()) {
+            Some(file) => file,
+            None => return MMAP_ERROR,
+        };
+        let inode = match file.as_inode() {
+            Some(inode) if !inode.is_dir() => inode,
+            _ => return MMAP_ERROR,
+        };
+        // 共享的可写映射会写回文件，文件需要以可写方式打开
+        if !inode.readable() || (shared && prot & PROT_WRITE != 0 && !inode.writable()) {
+            return MMAP_ERROR;
+        }
+        inode.inode()
+    };
+    let cache = page_cache(inode);
+    let mut inner = proc.borrow_inner();
+    match inner
+        .memory_set
+        .mmap_file(addr, len, perm, fixed, shared, cache, offset / PAGE_SIZE)
+    {
+        Ok(addr) => addr as isize,
+        Err(code) => code,
+    }
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner();
+    return inner.memory_set.munmap(addr, len);
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    let proc = current_proc();
+    let mut inner = proc.borrow_inner();
+    return inner.memory_set.mprotect(addr, len, prot_to_perm(prot));
+}
+
+// 共享文件映射的页写回文件，flags被忽略，写回总是同步完成
+pub fn sys_msync(addr: usize, len: usize, _flags: usize) -> isize {
+    let proc = current_proc();
+    let inner = proc.borrow_inner();
+    return inner.memory_set.msync(addr, len);
+}
+
+// 将物理内存使用情况写入用户的MemInfo
+pub fn sys_mem_info(buf_ptr: usize) -> isize {
+    let info = mem_info();
+    let mut buf = UserBuffer::from_current_proc(buf_ptr, size_of::<MemInfo>());
+    buf.write(0, info.as_bytes());
+    return 0;
+}
+
+// 将slab缓存的统计信息写入用户的SlabInfo数组，返回缓存总数
+pub fn sys_slab_info(buf_ptr: usize, count: usize) -> isize {
+    let caches = slab_info();
+    let len = count.min(caches.len()) * size_of::<SlabInfo>();
+    if len > 0 {
+        let mut buf = UserBuffer::from_current_proc(buf_ptr, len);
+        for (i, info) in caches.iter().take(count).enumerate() {
+            buf.write(i * size_of::<SlabInfo>(), info.as_bytes());
+        }
+    }
+    return caches.len() as isize;
+}
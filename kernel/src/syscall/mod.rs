@@ -1,5 +1,8 @@
+pub mod device;
 pub mod fs;
 pub mod ipc;
+pub mod mem;
+pub mod net;
 pub mod proc;
 pub mod sync;
 pub mod task;
@@ -11,12 +14,26 @@ const SYSCALL_STAT: usize = 2001;
 const SYSCALL_FSTAT: usize = 2002;
 const SYSCALL_LSEEK: usize = 2003;
 const SYSCALL_LS_DIR: usize = 2004;
+const SYSCALL_MKFIFO: usize = 2005;
+
+const SYSCALL_LS_DEV: usize = 2010;
+const SYSCALL_MEM_INFO: usize = 2011;
+const SYSCALL_SLAB_INFO: usize = 2012;
+
+const SYSCALL_PING: usize = 2020;
+const SYSCALL_PING_REPLY: usize = 2021;
+const SYSCALL_IF_LIST: usize = 2022;
+const SYSCALL_IF_CONFIG: usize = 2023;
+const SYSCALL_IF_SET_UP: usize = 2024;
+const SYSCALL_DHCP: usize = 2025;
 
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_EXEC: usize = 221;
@@ -35,8 +52,34 @@ const SYSCALL_COND_WAIT: usize = 1032;
 const SYSCALL_COND_SIGNAL: usize = 1031;
 
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_TIMEDSEND: usize = 182;
+const SYSCALL_MQ_TIMEDRECEIVE: usize = 183;
+const SYSCALL_MQ_GETSETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MSYNC: usize = 227;
 
-pub fn handle_syscall(id: usize, args: [usize; 3]) -> isize {
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_ACCEPT: usize = 202;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SETSOCKOPT: usize = 208;
+const SYSCALL_SHUTDOWN: usize = 210;
+
+pub fn handle_syscall(id: usize, args: [usize; 6]) -> isize {
     match id {
         SYSCALL_WRITE => {
             return fs::sys_write(args[0], args[1], args[2]);
@@ -44,11 +87,13 @@ pub fn handle_syscall(id: usize, args: [usize; 3]) -> isize {
         SYSCALL_EXIT => proc::sys_exit(args[0] as i32),
         SYSCALL_YIELD => proc::sys_yield(),
         SYSCALL_READ => fs::sys_read(args[0], args[1], args[2]),
-        SYSCALL_FORK => proc::sys_fork() as isize,
+        SYSCALL_FORK => proc::sys_fork(),
         SYSCALL_CREATE_THREAD => task::create_thread(args[0], args[1]) as isize,
         SYSCALL_WAIT_TID => task::wait_tid(args[0]),
         SYSCALL_WAITPID => proc::sys_waitpid(args[0]),
         SYSCALL_SPAWN => proc::sys_spawn(args[0], args[1], args[2]),
+        SYSCALL_GETRLIMIT => proc::sys_getrlimit(args[0], args[1]),
+        SYSCALL_SETRLIMIT => proc::sys_setrlimit(args[0], args[1]),
         SYSCALL_MUTEX_CREATE => sync::mutex_create(args[0] == 1),
         SYSCALL_MUTEX_LOCK => sync::mutex_lock(args[0] as isize),
         SYSCALL_MUTEX_UNLOCK => sync::mutex_unlock(args[0] as isize),
@@ -63,6 +108,43 @@ pub fn handle_syscall(id: usize, args: [usize; 3]) -> isize {
         SYSCALL_FSTAT => fs::sys_fstat(args[0], args[1]),
         SYSCALL_LSEEK => fs::sys_lseek(args[0], args[1] as u32, args[2] as u8),
         SYSCALL_LS_DIR => fs::sys_ls_dir(args[0], args[1], args[2]),
+        SYSCALL_MKFIFO => fs::sys_mkfifo(args[0]),
+        SYSCALL_PIPE => ipc::sys_pipe(args[0]),
+        SYSCALL_MQ_OPEN => ipc::sys_mq_open(args[0], args[1], args[2]),
+        SYSCALL_MQ_UNLINK => ipc::sys_mq_unlink(args[0]),
+        SYSCALL_MQ_TIMEDSEND => ipc::sys_mq_timedsend(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_MQ_TIMEDRECEIVE => ipc::sys_mq_timedreceive(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_MQ_GETSETATTR => ipc::sys_mq_getsetattr(args[0], args[1], args[2]),
+        SYSCALL_SHMGET => ipc::sys_shmget(args[0], args[1], args[2]),
+        SYSCALL_SHMCTL => ipc::sys_shmctl(args[0], args[1], args[2]),
+        SYSCALL_SHMAT => ipc::sys_shmat(args[0], args[1], args[2]),
+        SYSCALL_SHMDT => ipc::sys_shmdt(args[0]),
+        SYSCALL_BRK => mem::sys_brk(args[0]),
+        SYSCALL_MMAP => mem::sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MUNMAP => mem::sys_munmap(args[0], args[1]),
+        SYSCALL_MPROTECT => mem::sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MSYNC => mem::sys_msync(args[0], args[1], args[2]),
+        SYSCALL_LS_DEV => device::sys_ls_dev(args[0], args[1]),
+        SYSCALL_MEM_INFO => mem::sys_mem_info(args[0]),
+        SYSCALL_SLAB_INFO => mem::sys_slab_info(args[0], args[1]),
+        SYSCALL_PING => net::sys_ping(args[0], args[1], args[2]),
+        SYSCALL_PING_REPLY => net::sys_ping_reply(args[0], args[1]),
+        SYSCALL_IF_LIST => net::sys_if_list(args[0], args[1]),
+        SYSCALL_IF_CONFIG => net::sys_if_config(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_IF_SET_UP => net::sys_if_set_up(args[0], args[1]),
+        SYSCALL_DHCP => net::sys_dhcp(args[0]),
+
+        SYSCALL_SOCKET => net::sys_socket(args[0], args[1], args[2]),
+        SYSCALL_SOCKETPAIR => net::sys_socketpair(args[0], args[1], args[2], args[3]),
+        SYSCALL_BIND => net::sys_bind(args[0], args[1], args[2]),
+        SYSCALL_LISTEN => net::sys_listen(args[0], args[1]),
+        SYSCALL_ACCEPT => net::sys_accept(args[0], args[1], args[2]),
+        SYSCALL_CONNECT => net::sys_connect(args[0], args[1], args[2]),
+        SYSCALL_GETSOCKNAME => net::sys_getsockname(args[0], args[1], args[2]),
+        SYSCALL_SENDTO => net::sys_sendto(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_RECVFROM => net::sys_recvfrom(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_SETSOCKOPT => net::sys_setsockopt(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_SHUTDOWN => net::sys_shutdown(args[0], args[1]),
         _ => {
             debug!("unsupported syscall: {}", id);
             panic!("unsupported syscall");
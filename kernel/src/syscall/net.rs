@@ -0,0 +1,426 @@
+use crate::fs::{File, UserBuffer};
+use crate::ipc::unix::UnixSocket;
+use crate::net::socket::*;
+use crate::driver::net::net_device;
+use crate::net::{self, dhcp, icmp, Ipv4Addr};
+use crate::task::scheduler::current_proc;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use core::mem::size_of;
+
+// 发送ICMP echo request，id使用当前进程的pid
+pub fn sys_ping(ip: usize, seq: usize, len: usize) -> isize {
+    if len > icmp::MAX_ECHO_DATA_SIZE {
+        return -1;
+    }
+    net::poll();
+    let id = current_proc().pid() as u16;
+    if icmp::send_echo(Ipv4Addr::from_u32(ip as u32), id, seq as u16, len) {
+        return 0;
+    }
+    return -1;
+}
+
+// 查询echo reply，收到时返回ttl，还没有收到返回-2
+pub fn sys_ping_reply(ip: usize, seq: usize) -> isize {
+    net::poll();
+    let id = current_proc().pid() as u16;
+    match icmp::take_echo_reply(Ipv4Addr::from_u32(ip as u32), id, seq as u16) {
+        Some(reply) => reply.ttl as isize,
+        None => -2,
+    }
+}
+
+// 接口信息，用户程序的ifconfig使用
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IfInfo {
+    name: [u8; 8],
+    dev: u32,
+    ip: u32,
+    netmask: u32,
+    gateway: u32,
+    dns: u32,
+    mac: [u8; 6],
+    flags: u16,
+}
+
+const IFF_UP: u16 = 1;
+const IFF_LOOPBACK: u16 = 2;
+const IFF_RUNNING: u16 = 4; // 链路连通
+
+// 把接口信息写入buf，最多count个，返回接口的总数
+pub fn sys_if_list(buf_ptr: usize, count: usize) -> isize {
+    let interfaces = net::interfaces();
+    for (i, iface) in interfaces.iter().take(count).enumerate() {
+        let mut info = IfInfo {
+            name: [0; 8],
+            dev: iface.dev as u32,
+            ip: iface.ip.to_u32(),
+            netmask: iface.netmask.to_u32(),
+            gateway: iface.gateway.to_u32(),
+            dns: iface.dns.to_u32(),
+            mac: iface.mac,
+            flags: 0,
+        };
+        let name = iface.name();
+        let len = name.len().min(info.name.len() - 1);
+        info.name[0..len].copy_from_slice(&name.as_bytes()[0..len]);
+        if iface.up {
+            info.flags |= IFF_UP;
+        }
+        if iface.loopback {
+            info.flags |= IFF_LOOPBACK;
+        }
+        if net_device(iface.dev).map(|dev| dev.link_up()).unwrap_or(false) {
+            info.flags |= IFF_RUNNING;
+        }
+        let data = unsafe {
+            core::slice::from_raw_parts(&info as *const IfInfo as *const u8, size_of::<IfInfo>())
+        };
+        let mut buf = UserBuffer::from_current_proc(buf_ptr + i * size_of::<IfInfo>(), size_of::<IfInfo>());
+        buf.write(0, data);
+    }
+    return interfaces.len() as isize;
+}
+
+// 静态配置接口地址，停止接口上的DHCP
+pub fn sys_if_config(dev: usize, ip: usize, netmask: usize, gateway: usize, dns: usize) -> isize {
+    dhcp::stop(dev);
+    let ok = net::configure(
+        dev,
+        Ipv4Addr::from_u32(ip as u32),
+        Ipv4Addr::from_u32(netmask as u32),
+        Ipv4Addr::from_u32(gateway as u32),
+        Ipv4Addr::from_u32(dns as u32),
+    );
+    if ok {
+        return 0;
+    }
+    return -1;
+}
+
+pub fn sys_if_set_up(dev: usize, up: usize) -> isize {
+    if net::set_up(dev, up != 0) {
+        return 0;
+    }
+    return -1;
+}
+
+// 在接口上重新开始DHCP，用户程序通过if_list查询结果
+pub fn sys_dhcp(dev: usize) -> isize {
+    if dhcp::start(dev) {
+        return 0;
+    }
+    return -1;
+}
+
+// 取得fd对应的socket文件，fd不是socket时返回None
+fn socket_file(fd: usize) -> Option<Arc<dyn File>> {
+    let proc = current_proc();
+    let inner = proc.borrow_inner();
+    let file = inner.fd_table.get(fd)?.as_ref()?;
+    if file.as_socket().is_none() {
+        return None;
+    }
+    return Some(Arc::clone(file));
+}
+
+// 从用户空间读取sockaddr_in
+fn read_sockaddr(addr_ptr: usize, addr_len: usize) -> Option<SocketAddr> {
+    if addr_ptr == 0 || addr_len < size_of::<SockAddrIn>() {
+        return None;
+    }
+    let buf = UserBuffer::from_current_proc(addr_ptr, size_of::<SockAddrIn>());
+    let mut data = [0u8; size_of::<SockAddrIn>()];
+    buf.read(0, &mut data);
+    return SockAddrIn::from_bytes(&data).to_addr();
+}
+
+// 把地址写入用户空间的sockaddr_in，addr_len_ptr指向u32类型的地址长度
+fn write_sockaddr(addr: SocketAddr, addr_ptr: usize, addr_len_ptr: usize) {
+    if addr_ptr == 0 {
+        return;
+    }
+    let sockaddr = SockAddrIn::from_addr(addr);
+    let mut buf = UserBuffer::from_current_proc(addr_ptr, size_of::<SockAddrIn>());
+    buf.write(0, sockaddr.as_bytes());
+    if addr_len_ptr != 0 {
+        let len = size_of::<SockAddrIn>() as u32;
+        let mut buf = UserBuffer::from_current_proc(addr_len_ptr, size_of::<u32>());
+        buf.write(0, &len.to_ne_bytes());
+    }
+}
+
+// sockaddr_un中路径的最大长度，包括结尾的'\0'
+const UNIX_PATH_MAX: usize = 108;
+
+// 从用户空间读取sockaddr_un的路径，路径以'\0'结尾或占满整个地址
+fn read_sockaddr_un(addr_ptr: usize, addr_len: usize) -> Option<String> {
+    if addr_ptr == 0 || addr_len <= size_of::<u16>() {
+        return None;
+    }
+    let len = addr_len.min(size_of::<u16>() + UNIX_PATH_MAX);
+    let mut data = [0u8; size_of::<u16>() + UNIX_PATH_MAX];
+    UserBuffer::from_current_proc(addr_ptr, len).read(0, &mut data[0..len]);
+    if u16::from_ne_bytes([data[0], data[1]]) as usize != AF_UNIX {
+        return None;
+    }
+    let path = &data[size_of::<u16>()..len];
+    let end = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+    if end == 0 {
+        return None;
+    }
+    return core::str::from_utf8(&path[0..end]).ok().map(String::from);
+}
+
+// 把路径写入用户空间的sockaddr_un，没有绑定路径的socket只写入family
+fn write_sockaddr_un(path: &str, addr_ptr: usize, addr_len_ptr: usize) {
+    if addr_ptr == 0 {
+        return;
+    }
+    let path_len = path.len().min(UNIX_PATH_MAX - 1);
+    let mut data = vec![0u8; size_of::<u16>() + path_len + 1];
+    data[0..2].copy_from_slice(&(AF_UNIX as u16).to_ne_bytes());
+    data[2..2 + path_len].copy_from_slice(&path.as_bytes()[0..path_len]);
+    UserBuffer::from_current_proc(addr_ptr, data.len()).write(0, &data);
+    if addr_len_ptr != 0 {
+        let len = data.len() as u32;
+        let mut buf = UserBuffer::from_current_proc(addr_len_ptr, size_of::<u32>());
+        buf.write(0, &len.to_ne_bytes());
+    }
+}
+
+fn is_unix(file: &Arc<dyn File>) -> bool {
+    file.as_socket().unwrap().domain() == AF_UNIX
+}
+
+pub fn sys_socket(domain: usize, socket_type: usize, _protocol: usize) -> isize {
+    match create_socket(domain, socket_type) {
+        Some(socket) => {
+            let proc = current_proc();
+            let fd = proc.alloc_fd();
+            proc.borrow_inner().fd_table[fd] = Some(socket);
+            fd as isize
+        }
+        None => SOCKET_ERROR,
+    }
+}
+
+// 创建一对互相连接的AF_UNIX socket，两个fd写入sv_ptr指向的i32数组
+pub fn sys_socketpair(domain: usize, socket_type: usize, _protocol: usize, sv_ptr: usize) -> isize {
+    if domain != AF_UNIX || sv_ptr == 0 {
+        return SOCKET_ERROR;
+    }
+    let (first, second) = match UnixSocket::pair(socket_type) {
+        Some(pair) => pair,
+        None => return SOCKET_ERROR,
+    };
+    let proc = current_proc();
+    // alloc_fd不会占用fd，分配第二个fd之前先放入第一个socket
+    let fd0 = proc.alloc_fd();
+    proc.borrow_inner().fd_table[fd0] = Some(Arc::new(first));
+    let fd1 = proc.alloc_fd();
+    proc.borrow_inner().fd_table[fd1] = Some(Arc::new(second));
+    let mut fds = [0u8; 2 * size_of::<i32>()];
+    fds[0..4].copy_from_slice(&(fd0 as i32).to_ne_bytes());
+    fds[4..8].copy_from_slice(&(fd1 as i32).to_ne_bytes());
+    UserBuffer::from_current_proc(sv_ptr, fds.len()).write(0, &fds);
+    return 0;
+}
+
+pub fn sys_bind(fd: usize, addr_ptr: usize, addr_len: usize) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    if is_unix(&file) {
+        return match read_sockaddr_un(addr_ptr, addr_len) {
+            Some(path) => file.as_socket().unwrap().bind_path(&path),
+            None => SOCKET_ERROR,
+        };
+    }
+    match read_sockaddr(addr_ptr, addr_len) {
+        Some(addr) => file.as_socket().unwrap().bind(addr),
+        None => SOCKET_ERROR,
+    }
+}
+
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    match socket_file(fd) {
+        Some(file) => file.as_socket().unwrap().listen(backlog),
+        None => SOCKET_ERROR,
+    }
+}
+
+// 等待新连接，为连接分配新的fd，对端地址写入addr_ptr
+pub fn sys_accept(fd: usize, addr_ptr: usize, addr_len_ptr: usize) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    match file.as_socket().unwrap().accept() {
+        Ok((socket, peer)) => {
+            let proc = current_proc();
+            let fd = proc.alloc_fd();
+            proc.borrow_inner().fd_table[fd] = Some(socket);
+            if is_unix(&file) {
+                write_sockaddr_un("", addr_ptr, addr_len_ptr);
+            } else {
+                write_sockaddr(peer, addr_ptr, addr_len_ptr);
+            }
+            fd as isize
+        }
+        Err(code) => code,
+    }
+}
+
+pub fn sys_connect(fd: usize, addr_ptr: usize, addr_len: usize) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    if is_unix(&file) {
+        return match read_sockaddr_un(addr_ptr, addr_len) {
+            Some(path) => file.as_socket().unwrap().connect_path(&path),
+            None => SOCKET_ERROR,
+        };
+    }
+    match read_sockaddr(addr_ptr, addr_len) {
+        Some(addr) => file.as_socket().unwrap().connect(addr),
+        None => SOCKET_ERROR,
+    }
+}
+
+pub fn sys_getsockname(fd: usize, addr_ptr: usize, addr_len_ptr: usize) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    if is_unix(&file) {
+        let path = file.as_socket().unwrap().local_path().unwrap_or(String::new());
+        write_sockaddr_un(&path, addr_ptr, addr_len_ptr);
+        return 0;
+    }
+    let addr = file
+        .as_socket()
+        .unwrap()
+        .local_addr()
+        .unwrap_or(SocketAddr::unspecified());
+    write_sockaddr(addr, addr_ptr, addr_len_ptr);
+    return 0;
+}
+
+pub fn sys_sendto(
+    fd: usize,
+    buf_ptr: usize,
+    len: usize,
+    _flags: usize,
+    addr_ptr: usize,
+    addr_len: usize,
+) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    let socket = file.as_socket().unwrap();
+    // 数据报不能拆分，超过最大长度时直接返回错误
+    let max_size = socket.max_io_size();
+    if len > max_size && !socket.is_stream() {
+        return SOCKET_ERROR;
+    }
+    let mut path = None;
+    let mut addr = None;
+    if is_unix(&file) && addr_ptr != 0 {
+        path = read_sockaddr_un(addr_ptr, addr_len);
+        if path.is_none() {
+            return SOCKET_ERROR;
+        }
+    } else {
+        addr = read_sockaddr(addr_ptr, addr_len);
+        if addr_ptr != 0 && addr.is_none() {
+            return SOCKET_ERROR;
+        }
+    }
+    // 流式socket每次拷贝不超过缓冲区大小的数据并发送
+    let mut data = vec![0u8; len.min(max_size)];
+    let mut sent = 0;
+    loop {
+        let size = (len - sent).min(max_size);
+        if size > 0 {
+            UserBuffer::from_current_proc(buf_ptr + sent, size).read(0, &mut data[0..size]);
+        }
+        let ret = match &path {
+            Some(path) => socket.send_to_path(&data[0..size], path),
+            None => socket.send_to(&data[0..size], addr),
+        };
+        if ret < 0 {
+            return if sent > 0 { sent as isize } else { ret };
+        }
+        sent += ret as usize;
+        if sent >= len || (ret as usize) < size {
+            return sent as isize;
+        }
+    }
+}
+
+pub fn sys_recvfrom(
+    fd: usize,
+    buf_ptr: usize,
+    len: usize,
+    flags: usize,
+    addr_ptr: usize,
+    addr_len_ptr: usize,
+) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    // 一次最多接收max_io_size字节，剩余的数据留给下一次接收
+    let socket = file.as_socket().unwrap();
+    let mut data = vec![0u8; len.min(socket.max_io_size())];
+    let nonblock = flags & MSG_DONTWAIT != 0;
+    match socket.recv_from(&mut data, nonblock) {
+        Ok((size, src)) => {
+            if size > 0 {
+                UserBuffer::from_current_proc(buf_ptr, size).write(0, &data[0..size]);
+            }
+            if is_unix(&file) {
+                write_sockaddr_un("", addr_ptr, addr_len_ptr);
+            } else {
+                write_sockaddr(src, addr_ptr, addr_len_ptr);
+            }
+            size as isize
+        }
+        Err(code) => code,
+    }
+}
+
+pub fn sys_setsockopt(fd: usize, level: usize, name: usize, val_ptr: usize, val_len: usize) -> isize {
+    let file = match socket_file(fd) {
+        Some(file) => file,
+        None => return SOCKET_ERROR,
+    };
+    if level != SOL_SOCKET || val_len < size_of::<usize>() {
+        return SOCKET_ERROR;
+    }
+    let mut value = [0u8; size_of::<usize>()];
+    UserBuffer::from_current_proc(val_ptr, size_of::<usize>()).read(0, &mut value);
+    let value = usize::from_ne_bytes(value);
+    match name {
+        SO_RCVTIMEO => {
+            let timeout = if value == 0 { None } else { Some(value) };
+            file.as_socket().unwrap().set_recv_timeout(timeout);
+            0
+        }
+        _ => SOCKET_ERROR,
+    }
+}
+
+pub fn sys_shutdown(fd: usize, how: usize) -> isize {
+    match socket_file(fd) {
+        Some(file) => file.as_socket().unwrap().shutdown(how),
+        None => SOCKET_ERROR,
+    }
+}
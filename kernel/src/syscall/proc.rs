@@ -1,8 +1,8 @@
-use crate::fs::inode::{open_file, OpenFlags};
+use crate::fs::inode::find;
 use crate::mem::address::VirtAddr;
 use crate::mem::kernel;
 use crate::proc::loader::load_kernel_app;
-use crate::proc::pcb::{ProcessControlBlock, ProcessState};
+use crate::proc::pcb::{ProcessControlBlock, ProcessState, RLimit};
 use crate::task::scheduler::{
     add_process, current_proc, current_task, current_task_translate_buffer,
     current_task_trap_context, exit_current_task, push_task, remove_process, schedule_idle,
@@ -24,13 +24,18 @@ pub fn sys_yield() -> isize {
     return 0;
 }
 
-pub fn sys_fork() -> usize {
+pub const FORK_NO_MEMORY_ERROR: isize = -12;
+
+pub fn sys_fork() -> isize {
     let task = current_task();
     let parent = task.inner.borrow().process.upgrade().unwrap();
-    let child = ProcessControlBlock::fork(Arc::clone(&parent), task.tid);
+    let child = match ProcessControlBlock::fork(Arc::clone(&parent), task.tid) {
+        Some(child) => child,
+        None => return FORK_NO_MEMORY_ERROR,
+    };
     add_process(Arc::clone(&child));
     debug!("child pid: {}", child.pid());
-    child.pid()
+    child.pid() as isize
 }
 
 pub fn sys_waitpid(pid: usize) -> isize {
@@ -61,6 +66,39 @@ pub fn sys_waitpid(pid: usize) -> isize {
     0
 }
 
+// 目前只支持栈大小的限制
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_ERROR: isize = -1;
+
+pub fn sys_getrlimit(resource: usize, limit_ptr: usize) -> isize {
+    if resource != RLIMIT_STACK {
+        return RLIMIT_ERROR;
+    }
+    let proc = current_proc();
+    let limit = proc.borrow_inner().stack_limit;
+    let ptr = proc.translate_va(limit_ptr) as *mut RLimit;
+    unsafe {
+        ptr.write_volatile(limit);
+    }
+    return 0;
+}
+
+// cur不能超过max，max只能降低，不能提高
+pub fn sys_setrlimit(resource: usize, limit_ptr: usize) -> isize {
+    if resource != RLIMIT_STACK {
+        return RLIMIT_ERROR;
+    }
+    let proc = current_proc();
+    let ptr = proc.translate_va(limit_ptr) as *const RLimit;
+    let limit = unsafe { ptr.read_volatile() };
+    let mut inner = proc.borrow_inner();
+    if limit.cur > limit.max || limit.max > inner.stack_limit.max {
+        return RLIMIT_ERROR;
+    }
+    inner.stack_limit = limit;
+    return 0;
+}
+
 pub fn sys_spawn(ptr: usize, args: usize, args_count: usize) -> isize {
     let parent = current_proc();
     // 获取命令行参数数组地址
@@ -77,13 +115,14 @@ pub fn sys_spawn(ptr: usize, args: usize, args_count: usize) -> isize {
     }
     let app_name = parent.translate_string(ptr);
     // 文件系统加载app数据
-    if let Ok(file) = open_file(app_name.as_str(), OpenFlags::RDONLY) {
-        let proc = ProcessControlBlock::from_elf_data(file.read_all().as_slice());
+    if let Ok(file) = find(app_name.as_str()) {
+        let proc = ProcessControlBlock::from_elf(file.inode());
         let mut child_inner = proc.borrow_inner();
         let mut parent_inner = parent.borrow_inner();
         // 设置父子进程关系
         parent_inner.children.push(Arc::clone(&proc));
         child_inner.parent = Some(Arc::clone(&parent));
+        child_inner.stack_limit = parent_inner.stack_limit;
         drop(parent_inner);
         // 将命令行参数压入主线程用户栈
         let main_task = child_inner.tasks[0].as_ref();
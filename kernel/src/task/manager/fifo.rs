@@ -4,6 +4,7 @@ use crate::sync::cell::SafeCell;
 use crate::task::tcb::{TaskControlBlock, TaskStatus};
 use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 // FIFO任务管理器
 pub struct FIFOTaskManager {
@@ -35,6 +36,10 @@ impl TaskManager for FIFOTaskManager {
 
     fn pop_task(&self) -> Option<Arc<TaskControlBlock>> {
         let mut inner = self.inner.borrow();
+        // 进程结束时被标记为退出的线程不会再运行，从队列中删除
+        inner
+            .queue
+            .retain(|task| task.inner.borrow().status != TaskStatus::Exit);
         let poped = inner
             .queue
             .iter()
@@ -60,4 +65,9 @@ impl TaskManager for FIFOTaskManager {
         let mut inner = self.inner.borrow();
         inner.processes.remove(&pid);
     }
+
+    fn processes(&self) -> Vec<Arc<ProcessControlBlock>> {
+        let inner = self.inner.borrow();
+        return inner.processes.values().cloned().collect();
+    }
 }
@@ -1,6 +1,7 @@
 use super::tcb::TaskControlBlock;
 use crate::proc::pcb::ProcessControlBlock;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 pub mod fifo;
 pub mod stride;
@@ -11,4 +12,5 @@ pub trait TaskManager: Send + Sync {
     fn pop_task(&self) -> Option<Arc<TaskControlBlock>>;
     fn add_process(&self, proc: Arc<ProcessControlBlock>);
     fn remove_process(&self, pid: usize);
+    fn processes(&self) -> Vec<Arc<ProcessControlBlock>>;
 }
@@ -42,11 +42,13 @@ impl TaskManager for StrideManager {
         let mut not_ready: Vec<TCBHolder> = Vec::new();
         // 找到stride最小且处于READY状态的task
         while let Some(holder) = inner.pqueue.pop() {
-            if holder.0.inner.borrow().status == TaskStatus::Ready {
+            let status = holder.0.inner.borrow().status;
+            if status == TaskStatus::Ready {
                 // 被调度一次，增加stride
                 holder.0.increase_stride();
                 return Some(holder.0);
-            } else {
+            } else if status != TaskStatus::Exit {
+                // 进程结束时被标记为退出的线程不会再运行，不放回队列
                 not_ready.push(holder);
             }
         }
@@ -66,6 +68,10 @@ impl TaskManager for StrideManager {
     fn remove_process(&self, pid: usize) {
         self.inner.borrow().processes.remove(&pid);
     }
+
+    fn processes(&self) -> Vec<Arc<ProcessControlBlock>> {
+        return self.inner.borrow().processes.values().cloned().collect();
+    }
 }
 
 impl Ord for TCBHolder {
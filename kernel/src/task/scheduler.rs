@@ -3,8 +3,9 @@ use super::manager::fifo::FIFOTaskManager;
 use super::manager::stride::StrideManager;
 use super::manager::TaskManager;
 use super::tcb::{TaskControlBlock, TaskStatus};
+use crate::arch::riscv::qemu::layout::board;
 use crate::arch::riscv::register::read_tp;
-use crate::config::{task_trap_context_position, ManagerType, CPUS, TASK_MANAGER};
+use crate::config::{task_trap_context_position, ManagerType, MAX_CPUS, TASK_MANAGER};
 use crate::proc::pcb::{ProcessControlBlock, ProcessState};
 use crate::proc::pid::Pid;
 use crate::sync::cell::SafeCell;
@@ -14,6 +15,7 @@ use alloc::collections::VecDeque;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::lazy_static;
 use spin::mutex::SpinMutex;
 
@@ -38,7 +40,7 @@ lazy_static! {
 lazy_static! {
     pub static ref PROCESSORS: Vec<SafeCell<Processor>> = {
         let mut v: Vec<SafeCell<Processor>> = Vec::new();
-        for i in 0..CPUS {
+        for i in 0..board().harts {
             v.push(SafeCell::new(Processor::new()));
         }
         return v;
@@ -49,6 +51,26 @@ pub fn cpuid() -> usize {
     unsafe { read_tp() }
 }
 
+// 每个cpu正在运行的进程pid，换出页时用来判断页表项是否可能被其他cpu的TLB缓存
+const NO_PROCESS: usize = usize::MAX;
+const IDLE_CPU: AtomicUsize = AtomicUsize::new(NO_PROCESS);
+static RUNNING_PIDS: [AtomicUsize; MAX_CPUS] = [IDLE_CPU; MAX_CPUS];
+
+// 进程是否有线程正在其他cpu上运行
+pub fn running_on_other_cpus(pid: usize) -> bool {
+    let id = cpuid();
+    return RUNNING_PIDS
+        .iter()
+        .enumerate()
+        .any(|(cpu, running)| cpu != id && running.load(Ordering::SeqCst) == pid);
+}
+
+// 当前进程是否有其他线程正在其他cpu上运行
+pub fn current_running_on_other_cpus() -> bool {
+    let pid = RUNNING_PIDS[cpuid()].load(Ordering::SeqCst);
+    return pid != NO_PROCESS && running_on_other_cpus(pid);
+}
+
 // 处理器调度循环
 pub fn schedule() {
     let processor = PROCESSORS.get(cpuid()).unwrap();
@@ -58,6 +80,7 @@ pub fn schedule() {
         if let Some(tcb) = pop_task() {
             let new_ctx = tcb.context_addr() as *const TaskContext;
             let old_ctx = p.idle_context_ptr();
+            RUNNING_PIDS[cpuid()].store(tcb.pid, Ordering::SeqCst);
             p.current_task = Some(tcb);
             drop(p);
             // switch函数调用之后，idle_ctx的ra将保存schedule循环的pc
@@ -65,6 +88,7 @@ pub fn schedule() {
             unsafe {
                 __switch(old_ctx, new_ctx);
             }
+            RUNNING_PIDS[cpuid()].store(NO_PROCESS, Ordering::SeqCst);
         }
     }
 }
@@ -92,27 +116,62 @@ pub fn exit_current_task(exit_code: i32) {
     // 是main线程，退出进程
     if inner.tid == 0 {
         let proc = inner.process.upgrade().unwrap();
-        let mut inner_pcb = proc.borrow_inner();
-        inner_pcb.exit_code = exit_code;
-        inner_pcb.tasks.clear();
-        // 子进程变成僵尸进程，等待父进程wait回收资源
-        if let Some(parent) = &inner_pcb.parent {
-            inner_pcb.status = ProcessState::Zombie;
-        } else {
-            inner_pcb.status = ProcessState::Exit;
-            // 没有父进程，删除PCB的所有权，回收资源
-            remove_process(proc.pid());
-        }
-        inner_pcb.children.iter_mut().for_each(|child| {
-            child.borrow_inner().parent = None;
-        });
-        drop(inner_pcb);
+        exit_process(&proc, &task, exit_code);
         drop(proc);
     }
     drop(inner);
     drop(task);
 }
 
+// 结束当前线程所在的整个进程，用于线程出错时
+pub fn exit_current_process(exit_code: i32) {
+    let task = current_task();
+    let proc = current_proc();
+    exit_process(&proc, &task, exit_code);
+    drop(proc);
+    drop(task);
+    exit_current_task(exit_code);
+}
+
+// 当前线程所在的进程已经被其他线程结束时，返回进程的退出码
+pub fn current_process_exit_code() -> Option<i32> {
+    let task = current_task();
+    let proc = task.inner.borrow().process.upgrade();
+    return match proc {
+        Some(proc) if proc.is_exited() => Some(proc.borrow_inner().exit_code),
+        Some(_) => None,
+        None => Some(0),
+    };
+}
+
+// 设置进程的退出码，其他线程标记为退出，不会再被调度
+// 正在其他cpu上运行或者被借用的线程在返回用户态之前退出
+fn exit_process(proc: &Arc<ProcessControlBlock>, current: &Arc<TaskControlBlock>, exit_code: i32) {
+    if !proc.mark_exited() {
+        return;
+    }
+    let mut inner_pcb = proc.borrow_inner();
+    inner_pcb.exit_code = exit_code;
+    for task in inner_pcb.tasks.iter().filter(|task| !Arc::ptr_eq(task, current)) {
+        if let Some(mut task_inner) = task.inner.try_borrow() {
+            task_inner.exit_code.get_or_insert(exit_code as isize);
+            task_inner.status = TaskStatus::Exit;
+        }
+    }
+    inner_pcb.tasks.clear();
+    // 子进程变成僵尸进程，等待父进程wait回收资源
+    if let Some(parent) = &inner_pcb.parent {
+        inner_pcb.status = ProcessState::Zombie;
+    } else {
+        inner_pcb.status = ProcessState::Exit;
+        // 没有父进程，删除PCB的所有权，回收资源
+        remove_process(proc.pid());
+    }
+    inner_pcb.children.iter_mut().for_each(|child| {
+        child.borrow_inner().parent = None;
+    });
+}
+
 pub fn block_current_task() {
     let task = current_task();
     let mut task_inner = task.inner.borrow();
@@ -188,6 +247,11 @@ pub fn remove_process(pid: usize) {
     MANAGER.lock().remove_process(pid);
 }
 
+// 所有进程的pcb
+pub fn processes() -> Vec<Arc<ProcessControlBlock>> {
+    MANAGER.lock().processes()
+}
+
 extern "C" {
     // cpu切换任务上下文的汇编函数
     fn __switch(old_ctx: *mut TaskContext, new_ctx: *const TaskContext);
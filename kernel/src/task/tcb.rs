@@ -29,13 +29,14 @@ pub enum TaskStatus {
 // 内核线程控制块
 pub struct TaskControlBlock {
     pub tid: usize,
+    pub pid: usize, // 所属进程的pid，调度时不需要借用inner
     pub kernel_stack: KernelStack, // 内核栈地址
     pub inner: SafeCell<TaskControlBlockInner>,
 }
 
 pub struct TaskControlBlockInner {
     pub tid: usize,                         // 线程id
-    pub stack: usize,                       // 线程用户栈底地址
+    pub stack: usize,                       // 线程初始的用户栈底地址
     pub process: Weak<ProcessControlBlock>, // 进程控制块引用
     pub trap_ctx_ppn: PhysPageNumber,       // trap上下文
     pub task_context: TaskContext,          // 线程上下文
@@ -68,6 +69,7 @@ impl TaskControlBlock {
         map_kernel_stack(kstack_bottom, kstack_top, None);
         let tcb = Self {
             tid: tid,
+            pid: process.pid(),
             kernel_stack: kstask,
             inner: SafeCell::new(inner),
         };
@@ -105,7 +107,7 @@ impl TaskControlBlock {
         p_memset
             .areas
             .iter()
-            .find(|area| area.start_vpn.base_addr() == ustack_bottom) // 找到父进程的用户栈MemoryArea
+            .find(|area| area.end_vpn.base_addr() == ustack_top) // 找到父进程的用户栈MemoryArea，栈可能已经向下增长
             .map(|area| {
                 let memset = &mut process.borrow_inner().memory_set;
                 let mut frames: BTreeMap<VirtPageNumber, Arc<Frame>> = BTreeMap::new();
@@ -114,7 +116,7 @@ impl TaskControlBlock {
                     memset.page_table.map(
                         VirtPageNumber(vpn.0),
                         frame.ppn,
-                        MemPermission::R.bits() | MemPermission::U.bits(), // 去除写权限，使PageFault触发COW
+                        area.perm & !MemPermission::W.bits(), // 去除写权限，使PageFault触发COW
                     );
                     frames.insert(VirtPageNumber(vpn.0), Arc::clone(frame)); // 增加一个frame的引用
                 }
@@ -123,6 +125,9 @@ impl TaskControlBlock {
                     end_vpn: area.end_vpn,
                     mode: Indirect,
                     perm: area.perm,
+                    shared: false,
+                    file: None,
+                    elf: None,
                     frames: frames,
                 });
             });
@@ -162,6 +167,7 @@ impl TaskControlBlock {
         };
         return Self {
             tid: p_inner.tid,
+            pid: process.pid(),
             kernel_stack: kstack,
             inner: SafeCell::new(inner_tcb),
         };
@@ -270,16 +276,14 @@ impl TaskControlBlockInner {
         trap_ctx: usize,
     ) {
         let mem_set = &mut process.borrow_inner().memory_set;
-        // user stack
-        mem_set.insert_area(
-            MemoryArea::new(
-                VirtAddr(ustack_bottom).vpn(),
-                VirtAddr(ustack_top).vpn(),
-                Indirect,
-                MemPermission::R.bits() | MemPermission::W.bits() | MemPermission::U.bits(),
-            ),
-            None,
-        );
+        // user stack，栈页在第一次访问时分配
+        mem_set.reserve_area(MemoryArea::new(
+            VirtAddr(ustack_bottom).vpn(),
+            VirtAddr(ustack_top).vpn(),
+            Indirect,
+            MemPermission::R.bits() | MemPermission::W.bits() | MemPermission::U.bits(),
+        ));
+        // trap上下文在trampoline切换到内核页表之前就会被访问，不能按需分配
         mem_set.insert_area(
             MemoryArea::new(
                 VirtAddr(trap_ctx).vpn(),
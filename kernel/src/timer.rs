@@ -1,8 +1,8 @@
 use crate::arch::riscv::register::*;
-use crate::config::{CPUS, TIME_FREQ};
+use crate::config::{MAX_CPUS, TIME_FREQ};
 use riscv::register::{mhartid, mie, mscratch, mstatus, mtvec};
 
-static mut TIMER_SCRATCH: [[usize; 5]; CPUS] = [[0; 5]; CPUS];
+static mut TIMER_SCRATCH: [[usize; 5]; MAX_CPUS] = [[0; 5]; MAX_CPUS];
 
 pub unsafe fn timer_init() {
     let id = mhartid::read();
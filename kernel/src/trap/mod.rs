@@ -4,7 +4,8 @@ use crate::driver::plic::handle_irq;
 use crate::mem::address::VirtAddr;
 use crate::syscall::handle_syscall;
 use crate::task::scheduler::{
-    current_task, current_task_satp, current_task_trap_context, current_task_trap_va,
+    current_proc, current_process_exit_code, current_task_satp, current_task_trap_context,
+    current_task_trap_va, exit_current_process, exit_current_task, schedule_idle,
     yield_current_task,
 };
 use context::TrapContext;
@@ -18,6 +19,9 @@ use riscv::register::scause::{
 use riscv::register::{sepc, sstatus, stval, stvec};
 pub mod context;
 
+// 因为页错误被结束的进程的退出码，与Linux的SIGSEGV一致
+pub const PAGE_FAULT_EXIT_CODE: i32 = -11;
+
 extern "C" {
     fn _user_vec();
     fn _kernel_vec();
@@ -40,7 +44,8 @@ pub unsafe fn user_trap_handler() {
         Exception(UserEnvCall) => {
             // epc + 4使trap结束后能够跳到trap之后的一条指令
             ctx.sepc += 4;
-            let ret = handle_syscall(ctx.a[7], [ctx.a[0], ctx.a[1], ctx.a[2]]);
+            let args = [ctx.a[0], ctx.a[1], ctx.a[2], ctx.a[3], ctx.a[4], ctx.a[5]];
+            let ret = handle_syscall(ctx.a[7], args);
             // 因为syscall可能切换了另一个进程，所以这里要重新获取ctx
             ctx = current_task_trap_context();
             ctx.a[0] = ret as usize;
@@ -50,8 +55,10 @@ pub unsafe fn user_trap_handler() {
             panic!("illegal instruction")
         }
         Exception(LoadPageFault) => {
-            kernel!("user load page fault, stval: {:#x}", val);
-            panic!("load page fault")
+            user_page_fault("load page fault", val, false);
+        }
+        Exception(InstructionPageFault) => {
+            user_page_fault("instruction page fault", val, false);
         }
         Exception(LoadFault | StoreFault) => {
             kernel!("user load/store fault, stval: {:#x}", val);
@@ -60,14 +67,12 @@ pub unsafe fn user_trap_handler() {
         Interrupt(SupervisorSoft) => {
             // 清除sip的soft中断，避免重复中断
             clear_sip_soft();
+            // 时钟中断时处理协议栈的定时器
+            crate::net::poll();
             yield_current_task();
         }
         Exception(StorePageFault) => {
-            let pcb = current_task().inner.borrow().process.upgrade().unwrap();
-            if !pcb.copy_on_write(VirtAddr(val).vpn()) {
-                kernel!("store page fault, va: {:#x}", val);
-                panic!("store page fault");
-            }
+            user_page_fault("store page fault", val, true);
         }
         // 外设中断
         Interrupt(SupervisorExternal) => {
@@ -81,6 +86,24 @@ pub unsafe fn user_trap_handler() {
     user_trap_return();
 }
 
+// 加载文件映射的页或者CopyOnWrite，访问未映射或没有权限的页时结束整个进程
+fn user_page_fault(reason: &str, va: usize, write: bool) {
+    let pcb = current_proc();
+    if pcb.handle_page_fault(VirtAddr(va).vpn(), write) {
+        return;
+    }
+    kernel!(
+        "{} in process {}, va: {:#x}, sepc: {:#x}",
+        reason,
+        pcb.pid(),
+        va,
+        sepc::read()
+    );
+    drop(pcb);
+    exit_current_process(PAGE_FAULT_EXIT_CODE);
+    schedule_idle();
+}
+
 #[no_mangle]
 pub fn kernel_trap_handler() {
     let scause = scause::read();
@@ -100,6 +123,11 @@ pub fn user_trap_return() {
         fn _user_ret(ctx: *const TrapContext, satp: usize);
         fn _user_vec();
     }
+    // 进程已经被其他线程结束，当前线程不再返回用户态
+    if let Some(exit_code) = current_process_exit_code() {
+        exit_current_task(exit_code);
+        schedule_idle();
+    }
     let user_ret_va = _user_ret as usize - _user_vec as usize + TRAMPOLINE;
     let satp = current_task_satp();
     let trap_context = current_task_trap_va();
@@ -30,6 +30,33 @@ fn create_fs() {
         "../user_lib/target/riscv64gc-unknown-none-elf/release/stat",
         "../user_lib/target/riscv64gc-unknown-none-elf/release/ls",
         "../user_lib/target/riscv64gc-unknown-none-elf/release/mkdir",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/lsdev",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/ping",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/udp_echo_server",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/udp_echo_client",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/tcp_echo_server",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/net_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/ifconfig",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/wget",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/httpd",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/tcpdump",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/logd",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/logger",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/unix_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/mkfifo",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/fifo_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/mq_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/shm_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/mmap_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/mmap_file_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/brk_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/lazy_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/stack_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/swap_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/free",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/cow_test",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/slabinfo",
+        "../user_lib/target/riscv64gc-unknown-none-elf/release/slab_test",
     ];
     let app_names: Vec<&str> = vec![
         "hello_world",
@@ -44,6 +71,33 @@ fn create_fs() {
         "stat",
         "ls",
         "mkdir",
+        "lsdev",
+        "ping",
+        "udp_echo_server",
+        "udp_echo_client",
+        "tcp_echo_server",
+        "net_test",
+        "ifconfig",
+        "wget",
+        "httpd",
+        "tcpdump",
+        "logd",
+        "logger",
+        "unix_test",
+        "mkfifo",
+        "fifo_test",
+        "mq_test",
+        "shm_test",
+        "mmap_test",
+        "mmap_file_test",
+        "brk_test",
+        "lazy_test",
+        "stack_test",
+        "swap_test",
+        "free",
+        "cow_test",
+        "slabinfo",
+        "slab_test",
     ];
 
     let block_dev: Arc<dyn BlockDevice> = Arc::new(FileBlockDev::new("./fs.bin", true));
@@ -6,10 +6,11 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use spin::mutex::Mutex;
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum InodeType {
     File,
     Directory,
+    Fifo, // 命名管道，没有数据块，数据由内核的管道缓冲区传递
 }
 
 pub const INODE_SIZE: u32 = 128;
@@ -47,6 +48,9 @@ impl DiskInode {
     pub fn is_dir(&self) -> bool {
         return self.inode_type == InodeType::Directory;
     }
+    pub fn is_fifo(&self) -> bool {
+        return self.inode_type == InodeType::Fifo;
+    }
     pub fn index_blocks(&self) -> u32 {
         return index_blocks_for_size(self.size);
     }
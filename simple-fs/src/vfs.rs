@@ -32,6 +32,7 @@ pub struct InodeStat {
     pub io_block: u32,     // IO块大小
     pub index_blocks: u32, // 索引块数量
     pub dir: bool,
+    pub fifo: bool,
 }
 
 pub const FILE_EXIST_ERROR: isize = -1;
@@ -121,6 +122,7 @@ impl Inode {
             io_block: BLOCK_SIZE,
             inode: self.block_id,
             dir: disk_inode.is_dir(),
+            fifo: disk_inode.is_fifo(),
         });
         stat.inode = self.fs.lock().get_inode_seq(self.block_id, self.offset);
         return stat;
@@ -165,6 +167,15 @@ impl Inode {
         return self.read_disk_inode(|disk_inode| disk_inode.is_dir());
     }
 
+    pub fn is_fifo(&self) -> bool {
+        return self.read_disk_inode(|disk_inode| disk_inode.is_fifo());
+    }
+
+    // inode编号，同一个文件的不同Inode对象编号相同
+    pub fn inode_seq(&self) -> u32 {
+        return self.fs.lock().get_inode_seq(self.block_id, self.offset);
+    }
+
     fn find_inode(
         disk_inode: &DiskInode,
         name: &str,
@@ -214,6 +225,16 @@ impl Inode {
     }
 
     pub fn create(&self, name: &str, mkdir: bool) -> Result<Arc<Inode>, isize> {
+        let inode_type = if mkdir { InodeType::Directory } else { InodeType::File };
+        return self.create_inode(name, inode_type);
+    }
+
+    // 创建命名管道
+    pub fn mkfifo(&self, name: &str) -> Result<Arc<Inode>, isize> {
+        return self.create_inode(name, InodeType::Fifo);
+    }
+
+    fn create_inode(&self, name: &str, inode_type: InodeType) -> Result<Arc<Inode>, isize> {
         // 修改当前inode对应的disk inode，返回是否是dir，文件是否已经存在，以及文件的inode号
         let res = self.modify_disk_inode(|disk_inode| {
             if !disk_inode.is_dir() {
@@ -226,8 +247,11 @@ impl Inode {
             let inode_seq = file_system.alloc_inode().unwrap();
             drop(file_system);
             let offset = disk_inode.size();
-            // 当前inode扩容
-            self.grow_disk_inode(disk_inode, disk_inode.size() + DIR_ENTRY_SIZE);
+            // 当前inode扩容，磁盘空间不足时回收分配的inode
+            if !self.grow_disk_inode(disk_inode, disk_inode.size() + DIR_ENTRY_SIZE) {
+                self.fs.lock().dealloc_inode(inode_seq);
+                return Err(CREATE_FILE_ERROR);
+            }
             // 写入新文件的dir条目
             let entry = DirEntry::new(name, inode_seq);
             disk_inode.write(
@@ -252,11 +276,7 @@ impl Inode {
         );
         // 设置disk inode的类型
         inode.modify_disk_inode(|disk_inode| {
-            if mkdir {
-                disk_inode.set_type(InodeType::Directory);
-            } else {
-                disk_inode.set_type(InodeType::File);
-            }
+            disk_inode.set_type(inode_type);
         });
         return Ok(Arc::new(inode));
     }
@@ -267,15 +287,27 @@ impl Inode {
         });
     }
 
+    // 磁盘空间不足，无法扩容时返回0
     pub fn write(&self, offset: u32, buf: &[u8]) -> usize {
         return self.modify_disk_inode(|disk_inode| {
-            self.grow_disk_inode(disk_inode, disk_inode.size() + buf.len() as u32);
+            // 只有写入超过文件末尾时才扩容，覆盖写不改变文件大小
+            let end = offset + buf.len() as u32;
+            if end > disk_inode.size() && !self.grow_disk_inode(disk_inode, end) {
+                return 0;
+            }
             return disk_inode.write(offset, buf.len() as u32, buf, Arc::clone(&self.block_dev));
         });
     }
 
-    // 扩容disk inode到目标大小
-    fn grow_disk_inode(&self, disk_inode: &mut DiskInode, size: u32) {
+    // 预先分配数据块，使文件大小至少为size，磁盘空间不足时返回false，文件大小不变
+    pub fn reserve(&self, size: u32) -> bool {
+        return self.modify_disk_inode(|disk_inode| {
+            return size <= disk_inode.size() || self.grow_disk_inode(disk_inode, size);
+        });
+    }
+
+    // 扩容disk inode到目标大小，数据块不足时释放已经分配的块并返回false
+    fn grow_disk_inode(&self, disk_inode: &mut DiskInode, size: u32) -> bool {
         let old_idx_blks = disk_inode.index_blocks();
         let new_idx_blks = index_blocks_for_size(size);
         let old_data_blks = disk_inode.data_blocks();
@@ -284,12 +316,20 @@ impl Inode {
         let mut idx_blks: Vec<u32> = Vec::new();
         let mut data_blks: Vec<u32> = Vec::new();
         let mut fs = self.fs.lock();
-        for _ in old_idx_blks..new_idx_blks {
-            idx_blks.push(fs.alloc_data_block().unwrap());
-        }
-        for _ in old_data_blks..new_data_blks {
-            data_blks.push(fs.alloc_data_block().unwrap());
+        let count = (new_idx_blks - old_idx_blks) + (new_data_blks - old_data_blks);
+        for i in 0..count {
+            match fs.alloc_data_block() {
+                Some(block) if i < new_idx_blks - old_idx_blks => idx_blks.push(block),
+                Some(block) => data_blks.push(block),
+                None => {
+                    for block in idx_blks.iter().chain(data_blks.iter()) {
+                        fs.dealloc_data_block(*block);
+                    }
+                    return false;
+                }
+            }
         }
         disk_inode.grow(size, data_blks, idx_blks, Arc::clone(&self.block_dev));
+        return true;
     }
 }
@@ -0,0 +1,105 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::mem::*;
+use user_lib::{exit, fork, wait_pid, OOM_EXIT_CODE};
+
+const PAGE_FAULT_EXIT_CODE: isize = -11;
+// 超过原来固定大小的4MiB堆
+const LARGE_SIZE: usize = 8 * 1024 * 1024;
+
+fn load(addr: usize) -> u8 {
+    unsafe { core::ptr::read_volatile(addr as *const u8) }
+}
+
+fn store(addr: usize, value: u8) {
+    unsafe { core::ptr::write_volatile(addr as *mut u8, value) }
+}
+
+fn grow_test() -> bool {
+    let mut data: Vec<u8> = vec![0; LARGE_SIZE];
+    for (i, b) in data.iter_mut().enumerate() {
+        *b = (i % 251) as u8;
+    }
+    let mut ok = data.iter().enumerate().all(|(i, b)| *b == (i % 251) as u8);
+    // 多次小分配之后仍然可以继续扩展
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    for i in 0..64 {
+        blocks.push(vec![i; 1024]);
+    }
+    ok = ok && blocks.iter().enumerate().all(|(i, block)| block.iter().all(|v| *v == i));
+    drop(data);
+    drop(blocks);
+    println!("[grow] allocate beyond 4MiB: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn brk_test() -> bool {
+    let old = brk(0);
+    let start = match sbrk(2 * PAGE_SIZE) {
+        Ok(start) => start,
+        Err(code) => {
+            println!("[brk] sbrk failed: {}", code);
+            return false;
+        }
+    };
+    // 新扩展的页内容为0，可以读写
+    let mut ok = start == old && brk(0) == old + 2 * PAGE_SIZE;
+    ok = ok && load(start) == 0 && load(start + 2 * PAGE_SIZE - 1) == 0;
+    store(start + PAGE_SIZE, 7);
+    ok = ok && load(start + PAGE_SIZE) == 7;
+    // 子进程继承父进程的堆
+    let pid = fork();
+    if pid == 0 {
+        exit(if load(start + PAGE_SIZE) == 7 { 0 } else { -1 });
+    }
+    ok = ok && wait_pid(pid as usize) == 0;
+    // 收缩之后访问被释放的页导致进程退出
+    ok = ok && brk(old) == old;
+    let pid = fork();
+    if pid == 0 {
+        load(start + PAGE_SIZE);
+        exit(0);
+    }
+    ok = ok && wait_pid(pid as usize) == PAGE_FAULT_EXIT_CODE;
+    // 超出堆的范围时brk失败，返回原来的值
+    ok = ok && brk(1) == old && brk(usize::MAX) == old && brk(0x4000_0000 + PAGE_SIZE) == old;
+    println!("[brk] grow, shrink and bounds: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn oom_test() -> bool {
+    // 分配失败时try_reserve返回错误，不会结束进程
+    let mut data: Vec<u8> = Vec::new();
+    let mut ok = data.try_reserve(1 << 30).is_err();
+    ok = ok && data.try_reserve(PAGE_SIZE).is_ok();
+    // 普通的分配失败时进程以OOM_EXIT_CODE退出
+    let pid = fork();
+    if pid == 0 {
+        let data: Vec<u8> = vec![1; 1 << 30];
+        exit(data[0] as i32);
+    }
+    ok = ok && wait_pid(pid as usize) == OOM_EXIT_CODE as isize;
+    println!("[oom] allocation failure: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("brk test begin");
+    let grow = grow_test();
+    let brk = brk_test();
+    let oom = oom_test();
+    if grow && brk && oom {
+        println!("brk test passed");
+        return 0;
+    }
+    println!("brk test failed");
+    return -1;
+}
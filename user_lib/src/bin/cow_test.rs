@@ -0,0 +1,139 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mem::*;
+use user_lib::{exit, fork, wait_pid, yield_};
+
+// 父子进程通过CopyOnWrite共享的数据页
+const PAGES: usize = 16;
+static mut DATA: [usize; PAGES * PAGE_SIZE / 8] = [0; PAGES * PAGE_SIZE / 8];
+
+// 进程链的长度
+const CHAIN: usize = 3;
+
+// 共享内存中的退出顺序控制，turn是下一个可以退出的序号
+#[repr(C)]
+struct Control {
+    turn: usize,
+    ok: [bool; CHAIN],
+}
+
+fn fill(value: usize) {
+    for page in 0..PAGES {
+        unsafe { core::ptr::write_volatile(&mut DATA[page * PAGE_SIZE / 8], value + page) };
+    }
+}
+
+fn check(value: usize) -> bool {
+    (0..PAGES).all(|page| {
+        unsafe { core::ptr::read_volatile(&DATA[page * PAGE_SIZE / 8]) } == value + page
+    })
+}
+
+// 进程链中第rank个进程，写入自己的数据后fork下一个进程，轮到自己时检查数据并退出
+fn chain_member(ctrl: *mut Control, rank: usize, position: usize, order: &[usize]) -> ! {
+    let mut ok = check(rank * 100);
+    fill((rank + 1) * 100);
+    if rank + 1 < CHAIN {
+        let next = order.iter().position(|r| *r == rank + 1).unwrap();
+        if fork() == 0 {
+            chain_member(ctrl, rank + 1, next, order);
+        }
+    }
+    while unsafe { core::ptr::read_volatile(&(*ctrl).turn) } != position {
+        yield_();
+    }
+    // 其他进程的写入和退出不影响自己的页，再次写入时已经退出的进程不再共享
+    ok = ok && check((rank + 1) * 100);
+    fill((rank + 1) * 1000);
+    ok = ok && check((rank + 1) * 1000);
+    unsafe {
+        core::ptr::write_volatile(&mut (*ctrl).ok[rank], ok);
+        core::ptr::write_volatile(&mut (*ctrl).turn, position + 1);
+    }
+    exit(0);
+    unreachable!();
+}
+
+// fork出长度为CHAIN的进程链，按order的顺序退出
+fn chain_test(ctrl: *mut Control, order: &[usize]) -> bool {
+    unsafe {
+        core::ptr::write_volatile(&mut (*ctrl).turn, 0);
+        core::ptr::write_volatile(&mut (*ctrl).ok, [false; CHAIN]);
+    }
+    fill(0);
+    let pid = fork();
+    if pid == 0 {
+        let position = order.iter().position(|r| *r == 0).unwrap();
+        chain_member(ctrl, 0, position, order);
+    }
+    while unsafe { core::ptr::read_volatile(&(*ctrl).turn) } != CHAIN {
+        yield_();
+    }
+    let mut ok = wait_pid(pid as usize) == 0 && check(0);
+    ok = ok && unsafe { core::ptr::read_volatile(&(*ctrl).ok) }.iter().all(|ok| *ok);
+    println!(
+        "[chain] fork chain exiting in order {:?}: {}",
+        order,
+        if ok { "ok" } else { "failed" }
+    );
+    return ok;
+}
+
+// 子进程退出后父进程是唯一的持有者，写入时直接恢复写权限而不拷贝
+fn sole_owner_test() -> bool {
+    fill(7);
+    let pid = fork();
+    if pid == 0 {
+        exit(if check(7) { 0 } else { -1 });
+    }
+    let mut ok = wait_pid(pid as usize) == 0;
+    let free = mem_info().free;
+    fill(8);
+    ok = ok && check(8);
+    // 其他进程可能同时分配物理页，只要求没有为每一页都分配新的物理页
+    ok = ok && free < mem_info().free + PAGES / 2;
+    println!("[owner] sole owner writes without copying: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+// 只读的代码段在fork之后仍然不可写
+fn readonly_test() -> bool {
+    let pid = fork();
+    if pid == 0 {
+        let ptr = main as usize as *mut u8;
+        unsafe {
+            let byte = ptr.read_volatile();
+            ptr.write_volatile(byte);
+        }
+        exit(0);
+    }
+    let ok = wait_pid(pid as usize) == -11;
+    println!("[readonly] text stays read-only after fork: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("cow test begin");
+    let addr = mmap(0, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_ANONYMOUS);
+    if addr < 0 {
+        println!("[cow] mmap failed: {}", addr);
+        return -1;
+    }
+    let ctrl = addr as *mut Control;
+    let mut ok = sole_owner_test();
+    ok = readonly_test() && ok;
+    for order in [[0, 1, 2], [2, 1, 0], [1, 0, 2], [0, 2, 1]].iter() {
+        ok = chain_test(ctrl, order) && ok;
+    }
+    if ok {
+        println!("cow test passed");
+        return 0;
+    }
+    println!("cow test failed");
+    return -1;
+}
@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::file::{self, File, OpenFlags, FILE_EXIST_ERROR};
+use user_lib::{spawn, wait_pid};
+
+const FIFO_PATH: &str = "/fifo_test.pipe\0";
+// 超过内核管道缓冲区的大小，写入方会等待读取
+const DATA_LEN: usize = 10000;
+
+// 由fifo_test spawn出的读进程，校验收到的数据
+fn reader() -> i32 {
+    let fifo = match File::open(FIFO_PATH, OpenFlags::RDONLY) {
+        Ok(file) => file,
+        Err(code) => {
+            println!("[reader] open failed: {}", code);
+            return -1;
+        }
+    };
+    let mut received = 0;
+    let mut ok = true;
+    let mut buf = [0u8; 256];
+    loop {
+        let len = fifo.read(&mut buf);
+        if len <= 0 {
+            break;
+        }
+        for i in 0..len as usize {
+            if buf[i] != (received + i) as u8 {
+                ok = false;
+            }
+        }
+        received += len as usize;
+    }
+    fifo.close();
+    println!("[reader] received {} bytes", received);
+    return if ok && received == DATA_LEN { 0 } else { -1 };
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是当前路径
+    if argc > 1 && argv[0] == "reader" {
+        return reader();
+    }
+    println!("fifo test begin");
+    let code = file::mkfifo(FIFO_PATH);
+    if code != 0 && code != FILE_EXIST_ERROR {
+        println!("[error] mkfifo failed: {}", code);
+        return -1;
+    }
+    // 读进程与当前进程没有共享的fd，只通过路径找到同一个管道
+    let mut cur_path = String::from(argv[argc - 1]);
+    cur_path.push('\0');
+    let args_ptrs = ["reader\0".as_ptr(), cur_path.as_ptr()];
+    let pid = match spawn("/bin/fifo_test\0", &args_ptrs) {
+        Some(pid) => pid,
+        None => {
+            println!("[error] spawn reader failed");
+            return -1;
+        }
+    };
+    // 阻塞到读进程打开管道
+    let fifo = match File::open(FIFO_PATH, OpenFlags::WRONLY) {
+        Ok(file) => file,
+        Err(code) => {
+            println!("[error] open failed: {}", code);
+            return -1;
+        }
+    };
+    let mut buf = [0u8; 1000];
+    let mut sent = 0;
+    while sent < DATA_LEN {
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = (sent + i) as u8;
+        }
+        fifo.write(&buf);
+        sent += buf.len();
+    }
+    fifo.close();
+    let ok = wait_pid(pid) == 0;
+    println!("fifo test {}", if ok { "passed" } else { "failed" });
+    return if ok { 0 } else { -1 };
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mem::{mem_info, MAX_ORDER, PAGE_SIZE};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let info = mem_info();
+    println!("{:<8} {:>12} {:>12} {:>12}", "", "TOTAL", "USED", "FREE");
+    println!(
+        "{:<8} {:>10}Ki {:>10}Ki {:>10}Ki",
+        "Mem:",
+        info.total * PAGE_SIZE / 1024,
+        (info.total - info.free) * PAGE_SIZE / 1024,
+        info.free * PAGE_SIZE / 1024
+    );
+    println!(
+        "{:<8} {:>10}Ki {:>10}Ki {:>10}Ki",
+        "Heap:",
+        info.heap_total / 1024,
+        info.heap_used / 1024,
+        (info.heap_total - info.heap_used) / 1024
+    );
+    // 伙伴系统每个order的空闲块
+    println!("{:<8} {:>12} {:>12}", "ORDER", "BLOCK", "FREE");
+    for order in 0..=MAX_ORDER {
+        println!(
+            "{:<8} {:>10}Ki {:>12}",
+            order,
+            (PAGE_SIZE << order) / 1024,
+            info.free_blocks[order]
+        );
+    }
+    return 0;
+}
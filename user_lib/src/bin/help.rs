@@ -19,6 +19,33 @@ pub fn main() -> i32 {
     println!("fork_test                    Run a fork and waitpid test");
     println!("thread_test                  Run a multi-thread test");
     println!("timeshard_test               Run a test to see Round-robin with TimeShards");
+    println!("lsdev                        List devices and bound drivers");
+    println!("ping                         Send ICMP echo requests to a host");
+    println!("udp_echo_server              Run a UDP echo server (default port 2000)");
+    println!("udp_echo_client              Send a message to a UDP echo server");
+    println!("tcp_echo_server              tcp echo server, usage: tcp_echo_server [port]");
+    println!("net_test                     network stack test over the loopback interface");
+    println!("ifconfig                     show or configure network interfaces");
+    println!("wget                         download a file over http, usage: wget <url> [file]");
+    println!("httpd                        http file server, usage: httpd [port] [root]");
+    println!("tcpdump                      capture packets, usage: tcpdump [-i iface] [-c count] [-w file]");
+    println!("logd                         log daemon, usage: logd [socket] [logfile]");
+    println!("logger                       send a message to logd, usage: logger [-s socket] msg");
+    println!("unix_test                    test unix domain sockets");
+    println!("mkfifo                       create a named pipe, usage: mkfifo <path>");
+    println!("fifo_test                    named pipe test between two spawned processes");
+    println!("mq_test                      message queue test between two spawned processes");
+    println!("shm_test                     shared memory test, usage: shm_test");
+    println!("mmap_test                    anonymous mmap, munmap and mprotect test, usage: mmap_test");
+    println!("mmap_file_test               file-backed private and shared mmap test, usage: mmap_file_test");
+    println!("brk_test                     Test brk and the growable user heap");
+    println!("lazy_test                    Test demand paging and lazy elf loading");
+    println!("stack_test                   Test growing user stacks and the stack rlimit");
+    println!("swap_test                    Test swapping pages out under memory pressure");
+    println!("free                         Show physical memory usage");
+    println!("cow_test                     Test copy-on-write fork chains and exit orders");
+    println!("slabinfo                     Show kernel slab cache statistics");
+    println!("slab_test                    Test kernel slab caches for pipes and processes");
     println!("shell                        Open a new shell");
     return 0;
 }
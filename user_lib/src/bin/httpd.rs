@@ -0,0 +1,192 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::file::{self, get_absolute_path, File, OpenFlags};
+use user_lib::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+
+// qemu把宿主机的tcp 6201端口转发到80端口
+const DEFAULT_PORT: u16 = 80;
+const MAX_REQUEST_SIZE: usize = 4096;
+// 客户端发送请求的超时时间，单位ms
+const REQUEST_TIMEOUT: usize = 5000;
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next().unwrap_or("") {
+        "html" | "htm" => "text/html",
+        "txt" | "rs" | "md" => "text/plain",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+fn send_response(stream: &TcpStream, status: &str, content_type: &str, len: usize) {
+    let header = format!(
+        "HTTP/1.0 {}\r\nServer: bitos-httpd\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, content_type, len
+    );
+    stream.write(header.as_bytes());
+}
+
+fn send_error(stream: &TcpStream, status: &str) {
+    let body = format!("<html><body><h1>{}</h1></body></html>\n", status);
+    send_response(stream, status, "text/html", body.len());
+    stream.write(body.as_bytes());
+}
+
+// 读取请求行，返回(方法, 路径)
+fn read_request(stream: &TcpStream) -> Option<(String, String)> {
+    let mut request: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 512];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let len = stream.read(&mut buf);
+        if len <= 0 || request.len() > MAX_REQUEST_SIZE {
+            return None;
+        }
+        request.extend_from_slice(&buf[0..len as usize]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next()?.split(' ');
+    let method = String::from(parts.next()?);
+    let path = String::from(parts.next()?);
+    return Some((method, path));
+}
+
+fn send_file(stream: &TcpStream, path: &str, head: bool) {
+    let mut file_path = String::from(path);
+    file_path.push('\0');
+    let file = match File::open(file_path.as_str(), OpenFlags::RDONLY) {
+        Ok(file) => file,
+        Err(_) => return send_error(stream, "404 Not Found"),
+    };
+    let size = file.fstat().map(|s| s.size as usize).unwrap_or(0);
+    send_response(stream, "200 OK", content_type(path), size);
+    if !head {
+        let mut buf = [0u8; 512];
+        let mut sent = 0;
+        while sent < size {
+            let len = file.read(&mut buf);
+            if len <= 0 {
+                break;
+            }
+            let len = (len as usize).min(size - sent);
+            if stream.write(&buf[0..len]) < 0 {
+                break;
+            }
+            sent += len;
+        }
+    }
+    file.close();
+}
+
+// 目录中没有index.html时返回文件列表
+fn send_dir(stream: &TcpStream, url: &str, path: &str, head: bool) {
+    let mut dir_path = String::from(path);
+    dir_path.push('\0');
+    let names = match file::ls(dir_path.as_str()) {
+        Ok(names) => names,
+        Err(_) => return send_error(stream, "404 Not Found"),
+    };
+    let base = url.trim_end_matches('/');
+    let mut body = format!("<html><body><h1>Index of {}</h1><ul>\n", url);
+    for name in names.iter() {
+        let name = name.trim_matches('\0');
+        body.push_str(&format!("<li><a href=\"{}/{}\">{}</a></li>\n", base, name, name));
+    }
+    body.push_str("</ul></body></html>\n");
+    send_response(stream, "200 OK", "text/html", body.len());
+    if !head {
+        stream.write(body.as_bytes());
+    }
+}
+
+fn handle(stream: &TcpStream, root: &str) {
+    let (method, url) = match read_request(stream) {
+        Some(request) => request,
+        None => return send_error(stream, "400 Bad Request"),
+    };
+    let head = method == "HEAD";
+    if method != "GET" && !head {
+        return send_error(stream, "405 Method Not Allowed");
+    }
+    let url = String::from(url.split('?').next().unwrap());
+    // 不允许访问根目录之外的文件
+    if url.split('/').any(|part| part == "..") {
+        return send_error(stream, "403 Forbidden");
+    }
+    let path = get_absolute_path(url.clone(), String::from(root));
+    let mut stat_path = path.clone();
+    stat_path.push('\0');
+    match file::stat(stat_path.as_str()) {
+        Some(stat) if stat.dir => {
+            let mut index = path.clone();
+            if !index.ends_with('/') {
+                index.push('/');
+            }
+            index.push_str("index.html");
+            let mut index_path = index.clone();
+            index_path.push('\0');
+            if file::stat(index_path.as_str()).is_some() {
+                send_file(stream, index.as_str(), head);
+            } else {
+                send_dir(stream, url.as_str(), path.as_str(), head);
+            }
+        }
+        Some(_) => send_file(stream, path.as_str(), head),
+        None => send_error(stream, "404 Not Found"),
+    }
+    println!("{} {}", method, url);
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径，root默认为当前路径
+    let args = &argv[0..argc - 1];
+    let port = match args.get(0) {
+        Some(port) => match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => {
+                println!("usage: httpd [port] [root]");
+                return -1;
+            }
+        },
+        None => DEFAULT_PORT,
+    };
+    let cur_path = String::from(argv[argc - 1]);
+    let root = match args.get(1) {
+        Some(root) => get_absolute_path(String::from(*root), cur_path),
+        None => get_absolute_path(String::from(""), cur_path),
+    };
+    let listener = match TcpListener::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, port), 8) {
+        Ok(listener) => listener,
+        Err(code) => {
+            println!("[error] listen on port {} failed: {}", port, code);
+            return -1;
+        }
+    };
+    println!("httpd serving {} on {}", root, listener.local_addr());
+    loop {
+        let (stream, _) = match listener.accept() {
+            Ok(conn) => conn,
+            Err(code) => {
+                println!("[error] accept failed: {}", code);
+                break;
+            }
+        };
+        stream.set_read_timeout(REQUEST_TIMEOUT);
+        handle(&stream, root.as_str());
+        stream.close();
+    }
+    listener.close();
+    return 0;
+}
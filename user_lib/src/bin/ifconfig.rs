@@ -0,0 +1,109 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::net::{self, IfInfo, Ipv4Addr, IFF_LOOPBACK, IFF_RUNNING, IFF_UP};
+use user_lib::time::get_time_ms;
+use user_lib::yield_;
+
+// 等待DHCP的时间，单位ms
+const DHCP_TIMEOUT: usize = 10000;
+
+fn print_interface(iface: &IfInfo) {
+    let mut flags = Vec::new();
+    if iface.flags & IFF_UP != 0 {
+        flags.push("UP");
+    }
+    if iface.flags & IFF_LOOPBACK != 0 {
+        flags.push("LOOPBACK");
+    }
+    if iface.flags & IFF_RUNNING != 0 {
+        flags.push("RUNNING");
+    }
+    println!("{}: <{}>", iface.name(), flags.join(","));
+    let m = iface.mac;
+    println!(
+        "    ether {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        m[0], m[1], m[2], m[3], m[4], m[5]
+    );
+    println!("    inet {} netmask {}", iface.ip(), iface.netmask());
+    println!("    gateway {} dns {}", iface.gateway(), iface.dns());
+}
+
+fn usage() -> i32 {
+    println!("usage: ifconfig [name]");
+    println!("       ifconfig <name> <ip> [netmask] [gateway] [dns]");
+    println!("       ifconfig <name> up|down|dhcp");
+    return -1;
+}
+
+// 重新获取地址，等待DHCP完成
+fn dhcp(iface: &IfInfo) -> i32 {
+    if net::dhcp(iface.dev) < 0 {
+        println!("[error] dhcp not supported on {}", iface.name());
+        return -1;
+    }
+    let start = get_time_ms();
+    while get_time_ms() - start < DHCP_TIMEOUT {
+        if let Some(info) = net::interface(iface.name()) {
+            if info.ip != 0 {
+                print_interface(&info);
+                return 0;
+            }
+        }
+        yield_();
+    }
+    println!("[error] dhcp timeout");
+    return -1;
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    let args = &argv[0..argc - 1];
+    if args.is_empty() {
+        for iface in net::interfaces() {
+            print_interface(&iface);
+        }
+        return 0;
+    }
+    let iface = match net::interface(args[0]) {
+        Some(iface) => iface,
+        None => {
+            println!("[error] interface {} not found", args[0]);
+            return -1;
+        }
+    };
+    if args.len() == 1 {
+        print_interface(&iface);
+        return 0;
+    }
+    let ret = match args[1] {
+        "up" => net::if_set_up(iface.dev, true),
+        "down" => net::if_set_up(iface.dev, false),
+        "dhcp" => return dhcp(&iface),
+        _ => {
+            // 省略的参数保持原来的值
+            let mut addrs = [iface.ip(), iface.netmask(), iface.gateway(), iface.dns()];
+            for (i, arg) in args[1..].iter().enumerate() {
+                if i >= addrs.len() {
+                    return usage();
+                }
+                addrs[i] = match Ipv4Addr::parse(arg) {
+                    Some(addr) => addr,
+                    None => return usage(),
+                };
+            }
+            net::if_config(iface.dev, addrs[0], addrs[1], addrs[2], addrs[3])
+        }
+    };
+    if ret < 0 {
+        println!("[error] ifconfig {} failed", iface.name());
+        return -1;
+    }
+    return 0;
+}
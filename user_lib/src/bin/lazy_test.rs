@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mem::PAGE_SIZE;
+use user_lib::{exit, fork, wait_pid};
+
+// 64MiB的.bss，按需分配时只有访问过的页占用物理内存
+const BSS_SIZE: usize = 64 * 1024 * 1024;
+static mut BSS: [u8; BSS_SIZE] = [0; BSS_SIZE];
+
+// 64KiB的.data，页在访问时从elf文件读取
+const TABLE_LEN: usize = 16 * 1024;
+static mut TABLE: [u32; TABLE_LEN] = table();
+
+const fn table() -> [u32; TABLE_LEN] {
+    let mut table = [0u32; TABLE_LEN];
+    let mut i = 0;
+    while i < TABLE_LEN {
+        table[i] = (i as u32).wrapping_mul(2654435761);
+        i += 1;
+    }
+    return table;
+}
+
+fn bss_addr(offset: usize) -> *mut u8 {
+    unsafe { (BSS.as_mut_ptr() as usize + offset) as *mut u8 }
+}
+
+fn bss_test() -> bool {
+    let mut ok = true;
+    // 每隔1MiB访问一页
+    for i in 0..BSS_SIZE / (1024 * 1024) {
+        let ptr = bss_addr(i * 1024 * 1024 + 7);
+        unsafe {
+            ok = ok && ptr.read_volatile() == 0;
+            ptr.write_volatile(i as u8 + 1);
+        }
+    }
+    for i in 0..BSS_SIZE / (1024 * 1024) {
+        ok = ok && unsafe { bss_addr(i * 1024 * 1024 + 7).read_volatile() } == i as u8 + 1;
+    }
+    ok = ok && unsafe { bss_addr(BSS_SIZE - 1).read_volatile() } == 0;
+    println!("[bss] zero pages on first touch: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn data_test() -> bool {
+    // 从后往前访问，每一页都是第一次读取
+    let mut ok = true;
+    for i in (0..TABLE_LEN).rev().step_by(PAGE_SIZE / 4 - 3) {
+        ok = ok && unsafe { core::ptr::read_volatile(&TABLE[i]) } == (i as u32).wrapping_mul(2654435761);
+    }
+    unsafe {
+        core::ptr::write_volatile(&mut TABLE[5], 5);
+        ok = ok && core::ptr::read_volatile(&TABLE[5]) == 5;
+        ok = ok && core::ptr::read_volatile(&TABLE[6]) == 6u32.wrapping_mul(2654435761);
+    }
+    println!("[data] pages loaded from elf: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn fork_test() -> bool {
+    let pid = fork();
+    if pid == 0 {
+        // 子进程看到父进程修改过的页，没有访问过的页仍然按需分配
+        let mut ok = unsafe { bss_addr(7).read_volatile() } == 1;
+        ok = ok && unsafe { bss_addr(PAGE_SIZE * 3).read_volatile() } == 0;
+        ok = ok && unsafe { core::ptr::read_volatile(&TABLE[5]) } == 5;
+        ok = ok && unsafe { core::ptr::read_volatile(&TABLE[TABLE_LEN / 2]) }
+            == ((TABLE_LEN / 2) as u32).wrapping_mul(2654435761);
+        unsafe { bss_addr(PAGE_SIZE * 3).write_volatile(9) };
+        exit(if ok { 0 } else { -1 });
+    }
+    let ok = wait_pid(pid as usize) == 0 && unsafe { bss_addr(PAGE_SIZE * 3).read_volatile() } == 0;
+    println!("[fork] lazy pages after fork: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("lazy loading test begin");
+    let bss = bss_test();
+    let data = data_test();
+    let fork = fork_test();
+    if bss && data && fork {
+        println!("lazy loading test passed");
+        return 0;
+    }
+    println!("lazy loading test failed");
+    return -1;
+}
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::file::{get_absolute_path, File, OpenFlags, SeekFrom};
+use user_lib::net::UnixDatagram;
+use user_lib::time::get_time_ms;
+use user_lib::{exit, fork};
+
+const DEFAULT_SOCKET: &str = "/logd.sock";
+const DEFAULT_LOG_FILE: &str = "/log.txt";
+
+fn resolve_path(path: &str, cur_path: &str) -> String {
+    if path.starts_with('/') {
+        return String::from(path);
+    }
+    return get_absolute_path(String::from(path), String::from(cur_path));
+}
+
+// 日志守护进程，从unix数据报socket接收日志，追加到日志文件
+// 用法: logd [socket] [logfile]
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    let cur_path = argv[argc - 1];
+    let socket_path = resolve_path(if argc > 1 { argv[0] } else { DEFAULT_SOCKET }, cur_path);
+    let log_path = resolve_path(if argc > 2 { argv[1] } else { DEFAULT_LOG_FILE }, cur_path);
+    let mut log_path_c = log_path.clone();
+    log_path_c.push('\0');
+    let log = match File::open(&log_path_c, OpenFlags::CREATE | OpenFlags::WRONLY) {
+        Ok(file) => file,
+        Err(code) => {
+            println!("[error] open {} failed: {}", log_path, code);
+            return -1;
+        }
+    };
+    // 在父进程中绑定，出错时可以直接报告
+    let socket = match UnixDatagram::bind(&socket_path) {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[error] bind {} failed: {}", socket_path, code);
+            log.close();
+            return -1;
+        }
+    };
+    let pid = fork();
+    if pid < 0 {
+        println!("[error] fork failed");
+        return -1;
+    }
+    if pid > 0 {
+        println!("logd started, pid: {}, socket: {}, log: {}", pid, socket_path, log_path);
+        socket.close();
+        log.close();
+        return 0;
+    }
+    let mut buf = [0u8; 1024];
+    loop {
+        let len = socket.recv(&mut buf);
+        if len < 0 {
+            break;
+        }
+        let msg = core::str::from_utf8(&buf[0..len as usize]).unwrap_or("<invalid utf8>");
+        let line = format!("[{:>8}] {}\n", get_time_ms(), msg.trim_end());
+        log.lseek(0, SeekFrom::END);
+        if log.write(line.as_bytes()) < 0 {
+            break;
+        }
+    }
+    socket.close();
+    log.close();
+    exit(0);
+    return 0;
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::file::get_absolute_path;
+use user_lib::net::UnixDatagram;
+
+const DEFAULT_SOCKET: &str = "/logd.sock";
+
+// 把消息发送给logd
+// 用法: logger [-s socket] message...
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    let args = &argv[0..argc - 1];
+    let (socket_path, words) = if args.len() >= 2 && args[0] == "-s" {
+        (args[1], &args[2..])
+    } else {
+        (DEFAULT_SOCKET, args)
+    };
+    if words.is_empty() {
+        println!("usage: logger [-s socket] message...");
+        return -1;
+    }
+    let mut msg = String::new();
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            msg.push(' ');
+        }
+        msg.push_str(word);
+    }
+    let socket = match UnixDatagram::unbound() {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[error] socket failed: {}", code);
+            return -1;
+        }
+    };
+    let socket_path = if socket_path.starts_with('/') {
+        String::from(socket_path)
+    } else {
+        get_absolute_path(String::from(socket_path), String::from(argv[argc - 1]))
+    };
+    let ret = socket.send_to(msg.as_bytes(), &socket_path);
+    socket.close();
+    if ret < 0 {
+        println!("[error] send to {} failed: {}, is logd running?", socket_path, ret);
+        return -1;
+    }
+    return 0;
+}
@@ -36,7 +36,13 @@ pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
                 file_path.push_str(f.trim_matches('\0'));
                 file_path.push('\0');
                 if let Some(stat) = file::stat(file_path.as_str()) {
-                    let _type = if stat.dir { "dir" } else { "file" };
+                    let _type = if stat.dir {
+                        "dir"
+                    } else if stat.fifo {
+                        "fifo"
+                    } else {
+                        "file"
+                    };
                     println!("{:10}  {:4}  {:28}", stat.size, _type, f);
                 }
             }
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::device::list_devices;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!(
+        "{:<16} {:<12} {:<20} {:<12} {:<12} {}",
+        "DEVICE", "BUS", "COMPATIBLE", "DRIVER", "BASE", "IRQ"
+    );
+    for dev in list_devices() {
+        let driver = if dev.driver().is_empty() { "-" } else { dev.driver() };
+        println!(
+            "{:<16} {:<12} {:<20} {:<12} {:<#12x} {}",
+            dev.name(),
+            dev.bus(),
+            dev.compatible(),
+            driver,
+            dev.base,
+            dev.irq
+        );
+    }
+    return 0;
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::file::{self, get_absolute_path, FILE_EXIST_ERROR, FILE_NOT_FOUND_ERROR, NOT_DIR_ERROR};
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    if argc <= 1 || argv[0].is_empty() {
+        println!("[error] empty file name");
+        return -1;
+    }
+    let relative = String::from(argv[0]);
+    let cur_path = String::from(argv[argc - 1]);
+    let absolute_path = get_absolute_path(relative, cur_path);
+    let mut file_path = absolute_path.clone();
+    file_path.push('\0');
+    match file::mkfifo(file_path.as_str()) {
+        0 => return 0,
+        FILE_EXIST_ERROR => println!("cannot create fifo '{}': File exists", absolute_path),
+        NOT_DIR_ERROR => println!("cannot create fifo '{}': Not directory", absolute_path),
+        FILE_NOT_FOUND_ERROR => println!("cannot create fifo '{}': No such directory", absolute_path),
+        code => println!("fs error, code: {}", code),
+    }
+    return -1;
+}
@@ -0,0 +1,210 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec;
+use user_lib::file::{File, OpenFlags, SeekFrom};
+use user_lib::mem::*;
+use user_lib::{exit, fork, spawn, wait_pid};
+
+const FILE_PATH: &str = "/mmap_test.dat\0";
+// 最后一页只有一半在文件内
+const FILE_SIZE: usize = 2 * PAGE_SIZE + PAGE_SIZE / 2;
+const MAP_LEN: usize = 3 * PAGE_SIZE;
+
+fn pattern(i: usize) -> u8 {
+    (i % 253) as u8
+}
+
+// 映射可能被其他进程修改，读取时不能使用缓存的值
+fn load(buf: &[u8], i: usize) -> u8 {
+    unsafe { core::ptr::read_volatile(&buf[i]) }
+}
+
+fn store(buf: &mut [u8], i: usize, value: u8) {
+    unsafe { core::ptr::write_volatile(&mut buf[i], value) }
+}
+
+fn open(flags: OpenFlags) -> Option<File> {
+    match File::open(FILE_PATH, flags) {
+        Ok(file) => Some(file),
+        Err(code) => {
+            println!("[error] open failed: {}", code);
+            None
+        }
+    }
+}
+
+fn create_file() -> bool {
+    let file = match open(OpenFlags::CREATE | OpenFlags::RDWR) {
+        Some(file) => file,
+        None => return false,
+    };
+    let data: alloc::vec::Vec<u8> = (0..FILE_SIZE).map(pattern).collect();
+    let ok = file.write(&data) == FILE_SIZE as isize;
+    file.close();
+    return ok;
+}
+
+// 通过read读取文件中的一个字节
+fn file_byte(index: usize) -> u8 {
+    let file = match open(OpenFlags::RDONLY) {
+        Some(file) => file,
+        None => return 0,
+    };
+    let mut data = vec![0u8; FILE_SIZE];
+    file.lseek(0, SeekFrom::START);
+    file.read(&mut data);
+    file.close();
+    return data[index];
+}
+
+fn private_test() -> bool {
+    let file = match open(OpenFlags::RDONLY) {
+        Some(file) => file,
+        None => return false,
+    };
+    let buf = match mmap_file(&file, MAP_LEN, PROT_READ | PROT_WRITE, MAP_PRIVATE, 0) {
+        Ok(buf) => buf,
+        Err(code) => {
+            println!("[private] mmap failed: {}", code);
+            file.close();
+            return false;
+        }
+    };
+    // 关闭文件之后映射仍然有效
+    file.close();
+    let mut ok = (0..FILE_SIZE).all(|i| buf[i] == pattern(i));
+    // 文件末尾之后的部分为0
+    ok = ok && (FILE_SIZE..MAP_LEN).all(|i| buf[i] == 0);
+    // 私有映射的修改不写回文件
+    buf[10] = !pattern(10);
+    ok = ok && buf[10] == !pattern(10);
+    ok = ok && munmap(buf.as_ptr() as usize, MAP_LEN) == 0;
+    ok = ok && file_byte(10) == pattern(10);
+    println!("[private] lazy load and copy on write: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn shared_test() -> bool {
+    let file = match open(OpenFlags::RDWR) {
+        Some(file) => file,
+        None => return false,
+    };
+    let buf = match mmap_file(&file, MAP_LEN, PROT_READ | PROT_WRITE, MAP_SHARED, 0) {
+        Ok(buf) => buf,
+        Err(code) => {
+            println!("[shared] mmap failed: {}", code);
+            file.close();
+            return false;
+        }
+    };
+    file.close();
+    // 子进程的写入直接修改共享的缓存页
+    let pid = fork();
+    if pid == 0 {
+        store(buf, PAGE_SIZE + 1, 0xaa);
+        exit(0);
+    }
+    let mut ok = wait_pid(pid as usize) == 0 && load(buf, PAGE_SIZE + 1) == 0xaa;
+    store(buf, 5, 0x55);
+    ok = ok && msync(buf.as_ptr() as usize, MAP_LEN) == 0;
+    ok = ok && file_byte(5) == 0x55 && file_byte(PAGE_SIZE + 1) == 0xaa;
+    // munmap时写回
+    store(buf, 2 * PAGE_SIZE + 7, 0x77);
+    ok = ok && munmap(buf.as_ptr() as usize, MAP_LEN) == 0;
+    ok = ok && file_byte(2 * PAGE_SIZE + 7) == 0x77;
+    println!("[shared] write back on msync and munmap: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+// 由mmap_file_test spawn出的子进程，映射同一个文件，检查父进程还没有写回的修改
+fn child() -> i32 {
+    let file = match open(OpenFlags::RDWR) {
+        Some(file) => file,
+        None => return -1,
+    };
+    let buf = match mmap_file(&file, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, 0) {
+        Ok(buf) => buf,
+        Err(_) => return -1,
+    };
+    file.close();
+    if load(buf, 100) != 0x11 {
+        return -1;
+    }
+    store(buf, 200, 0x22);
+    return 0;
+}
+
+fn cache_test(cwd: &str) -> bool {
+    let file = match open(OpenFlags::RDWR) {
+        Some(file) => file,
+        None => return false,
+    };
+    let buf = match mmap_file(&file, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, 0) {
+        Ok(buf) => buf,
+        Err(_) => {
+            file.close();
+            return false;
+        }
+    };
+    file.close();
+    store(buf, 100, 0x11);
+    let mut cur_path = String::from(cwd);
+    cur_path.push('\0');
+    let args_ptrs = ["child\0".as_ptr(), cur_path.as_ptr()];
+    let ok = match spawn("/bin/mmap_file_test\0", &args_ptrs) {
+        Some(pid) => wait_pid(pid) == 0 && load(buf, 200) == 0x22,
+        None => false,
+    };
+    munmap(buf.as_ptr() as usize, FILE_SIZE);
+    println!("[cache] page cache shared between processes: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn permission_test() -> bool {
+    let file = match open(OpenFlags::RDONLY) {
+        Some(file) => file,
+        None => return false,
+    };
+    // 只读打开的文件不能共享可写映射，但可以私有可写映射
+    let mut ok = mmap_file(&file, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, 0).err() == Some(MMAP_ERROR);
+    ok = ok && mmap_file(&file, PAGE_SIZE, PROT_READ, MAP_PRIVATE, 1).err() == Some(MMAP_ERROR);
+    match mmap_file(&file, PAGE_SIZE, PROT_READ, MAP_SHARED, PAGE_SIZE) {
+        Ok(buf) => {
+            ok = ok && buf[0] == pattern(PAGE_SIZE);
+            munmap(buf.as_ptr() as usize, PAGE_SIZE);
+        }
+        Err(_) => ok = false,
+    }
+    file.close();
+    println!("[permission] open mode and offset checks: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是当前路径
+    if argc > 1 && argv[0] == "child" {
+        return child();
+    }
+    println!("file mmap test begin");
+    if !create_file() {
+        println!("file mmap test failed");
+        return -1;
+    }
+    let private = private_test();
+    let permission = permission_test();
+    let shared = shared_test();
+    let cache = cache_test(argv[argc - 1]);
+    if private && permission && shared && cache {
+        println!("file mmap test passed");
+        return 0;
+    }
+    println!("file mmap test failed");
+    return -1;
+}
@@ -0,0 +1,123 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mem::*;
+use user_lib::{exit, fork, wait_pid};
+
+const PAGE_FAULT_EXIT_CODE: isize = -11;
+
+fn load(addr: usize) -> u8 {
+    unsafe { core::ptr::read_volatile(addr as *const u8) }
+}
+
+fn store(addr: usize, value: u8) {
+    unsafe { core::ptr::write_volatile(addr as *mut u8, value) }
+}
+
+// 在子进程中访问addr，返回子进程的退出码
+fn access_in_child(addr: usize, write: bool) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        if write {
+            store(addr, 1);
+        } else {
+            load(addr);
+        }
+        exit(0);
+    }
+    return wait_pid(pid as usize);
+}
+
+fn anonymous_test() -> bool {
+    let buf = match mmap_anonymous(3 * PAGE_SIZE, PROT_READ | PROT_WRITE) {
+        Ok(buf) => buf,
+        Err(code) => {
+            println!("[anonymous] mmap failed: {}", code);
+            return false;
+        }
+    };
+    let mut ok = buf.iter().all(|b| *b == 0);
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = (i % 256) as u8;
+    }
+    ok = ok && buf.iter().enumerate().all(|(i, b)| *b == (i % 256) as u8);
+    ok = ok && munmap(buf.as_ptr() as usize, buf.len()) == 0;
+    println!("[anonymous] zeroed read/write pages: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn protect_test() -> bool {
+    let addr = mmap(0, 3 * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+    if addr < 0 {
+        println!("[protect] mmap failed: {}", addr);
+        return false;
+    }
+    let addr = addr as usize;
+    let middle = addr + PAGE_SIZE;
+    store(middle, 5);
+    // 中间一页只读，写入时进程被结束，读取正常
+    let mut ok = mprotect(middle, PAGE_SIZE, PROT_READ) == 0;
+    ok = ok && access_in_child(middle, true) == PAGE_FAULT_EXIT_CODE;
+    ok = ok && access_in_child(middle, false) == 0 && load(middle) == 5;
+    // 前后两页仍然可写
+    ok = ok && access_in_child(addr, true) == 0 && access_in_child(middle + PAGE_SIZE, true) == 0;
+    ok = ok && mprotect(middle, PAGE_SIZE, PROT_NONE) == 0;
+    ok = ok && access_in_child(middle, false) == PAGE_FAULT_EXIT_CODE;
+    // 恢复权限后数据不变
+    ok = ok && mprotect(middle, PAGE_SIZE, PROT_READ | PROT_WRITE) == 0 && load(middle) == 5;
+    store(middle, 6);
+    ok = ok && load(middle) == 6;
+    ok = ok && munmap(addr, 3 * PAGE_SIZE) == 0;
+    println!("[protect] read-only and inaccessible pages: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn split_test() -> bool {
+    let addr = mmap(0, 4 * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+    if addr < 0 {
+        println!("[split] mmap failed: {}", addr);
+        return false;
+    }
+    let addr = addr as usize;
+    for i in 0..4 {
+        store(addr + i * PAGE_SIZE, i as u8 + 1);
+    }
+    // 解除中间两页，剩下的两段互不影响
+    let mut ok = munmap(addr + PAGE_SIZE, 2 * PAGE_SIZE) == 0;
+    ok = ok && load(addr) == 1 && load(addr + 3 * PAGE_SIZE) == 4;
+    ok = ok && access_in_child(addr + PAGE_SIZE, false) == PAGE_FAULT_EXIT_CODE;
+    ok = ok && mprotect(addr, 4 * PAGE_SIZE, PROT_READ) == MMAP_ERROR;
+    // 空出的范围可以被MAP_FIXED重新使用，新的页内容为0
+    let fixed = mmap(addr + PAGE_SIZE, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED);
+    ok = ok && fixed == (addr + PAGE_SIZE) as isize && load(addr + PAGE_SIZE) == 0;
+    // 建议的地址已经被占用时由内核另外选择
+    let hinted = mmap(addr, PAGE_SIZE, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS);
+    ok = ok && hinted > 0 && hinted != addr as isize;
+    // MAP_FIXED替换已有的映射
+    let replaced = mmap(addr, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED);
+    ok = ok && replaced == addr as isize && load(addr) == 0;
+    munmap(addr, 4 * PAGE_SIZE);
+    if hinted > 0 {
+        munmap(hinted as usize, PAGE_SIZE);
+    }
+    ok = ok && mmap(addr + 1, PAGE_SIZE, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) == MMAP_ERROR;
+    println!("[split] partial unmap and fixed mapping: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("mmap test begin");
+    let anonymous = anonymous_test();
+    let protect = protect_test();
+    let split = split_test();
+    if anonymous && protect && split {
+        println!("mmap test passed");
+        return 0;
+    }
+    println!("mmap test failed");
+    return -1;
+}
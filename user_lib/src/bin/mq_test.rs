@@ -0,0 +1,143 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::mqueue::*;
+use user_lib::{spawn, wait_pid};
+
+const REQUEST_QUEUE: &str = "/mq_test.request";
+const REPLY_QUEUE: &str = "/mq_test.reply";
+const MSG_SIZE: usize = 64;
+
+// 由mq_test spawn出的子进程，只通过队列名字与父进程通信
+fn child() -> i32 {
+    let request = match MessageQueue::open(REQUEST_QUEUE, MQ_RDONLY, None) {
+        Ok(queue) => queue,
+        Err(code) => {
+            println!("[child] open request queue failed: {}", code);
+            return -1;
+        }
+    };
+    let reply = match MessageQueue::open(REPLY_QUEUE, MQ_WRONLY, None) {
+        Ok(queue) => queue,
+        Err(code) => {
+            println!("[child] open reply queue failed: {}", code);
+            request.close();
+            return -1;
+        }
+    };
+    let mut buf = [0u8; MSG_SIZE];
+    // 把收到的消息按顺序原样发回，quit结束
+    loop {
+        let (len, prio) = match request.receive(&mut buf) {
+            Ok(msg) => msg,
+            Err(_) => break,
+        };
+        if &buf[0..len] == b"quit" {
+            break;
+        }
+        reply.send(&buf[0..len], prio);
+    }
+    request.close();
+    reply.close();
+    return 0;
+}
+
+// 在子进程启动之前发送，quit的优先级最低，最后被接收
+fn send_requests(request: &MessageQueue) {
+    let messages: [(&[u8], usize); 5] = [(b"low", 1), (b"high-1", 10), (b"quit", 0), (b"high-2", 10), (b"mid", 5)];
+    for (data, prio) in messages.iter() {
+        request.send(data, *prio);
+    }
+}
+
+fn order_test(reply: &MessageQueue) -> bool {
+    // 高优先级先接收，同优先级先进先出
+    let expected: [&[u8]; 4] = [b"high-1", b"high-2", b"mid", b"low"];
+    let mut buf = [0u8; MSG_SIZE];
+    let mut ok = true;
+    for data in expected.iter() {
+        match reply.receive_timeout(&mut buf, 1000) {
+            Ok((len, _)) => ok = ok && &buf[0..len] == *data,
+            Err(_) => ok = false,
+        }
+    }
+    println!("[order] priority order: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn limit_test() -> bool {
+    let queue = match MessageQueue::open("/mq_test.limit", MQ_RDWR | MQ_CREATE | MQ_EXCL, Some(MqAttr::new(2, 8))) {
+        Ok(queue) => queue,
+        Err(code) => {
+            println!("[limit] open failed: {}", code);
+            return false;
+        }
+    };
+    let mut buf = [0u8; 8];
+    let mut ok = queue.send(b"too long message", 0) == MQ_MSG_SIZE_ERROR;
+    ok = ok && queue.receive_timeout(&mut buf, 50).err() == Some(MQ_TIMEOUT_ERROR);
+    queue.send(b"1", 0);
+    queue.send(b"2", 0);
+    ok = ok && queue.attr().cur_msgs == 2;
+    ok = ok && queue.send_timeout(b"3", 0, 50) == MQ_TIMEOUT_ERROR;
+    queue.set_nonblocking(true);
+    ok = ok && queue.send(b"3", 0) == MQ_WOULD_BLOCK_ERROR;
+    ok = ok && queue.receive(&mut buf).is_ok() && queue.receive(&mut buf).is_ok();
+    ok = ok && queue.receive(&mut buf).err() == Some(MQ_WOULD_BLOCK_ERROR);
+    // buf小于msg_size时不接收
+    ok = ok && queue.receive(&mut buf[0..4]).err() == Some(MQ_MSG_SIZE_ERROR);
+    ok = ok && MessageQueue::open("/mq_test.limit", MQ_RDWR | MQ_CREATE | MQ_EXCL, None).err() == Some(MQ_EXIST_ERROR);
+    queue.close();
+    MessageQueue::unlink("/mq_test.limit");
+    ok = ok && MessageQueue::open("/mq_test.limit", MQ_RDWR, None).err() == Some(MQ_NOT_FOUND_ERROR);
+    println!("[limit] size, count and timeout: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是当前路径
+    if argc > 1 && argv[0] == "child" {
+        return child();
+    }
+    println!("message queue test begin");
+    let attr = MqAttr::new(8, MSG_SIZE);
+    let request = MessageQueue::open(REQUEST_QUEUE, MQ_WRONLY | MQ_CREATE, Some(attr));
+    let reply = MessageQueue::open(REPLY_QUEUE, MQ_RDONLY | MQ_CREATE, Some(attr));
+    let (request, reply) = match (request, reply) {
+        (Ok(request), Ok(reply)) => (request, reply),
+        _ => {
+            println!("[error] create queues failed");
+            return -1;
+        }
+    };
+    send_requests(&request);
+    let mut cur_path = String::from(argv[argc - 1]);
+    cur_path.push('\0');
+    let args_ptrs = ["child\0".as_ptr(), cur_path.as_ptr()];
+    let pid = match spawn("/bin/mq_test\0", &args_ptrs) {
+        Some(pid) => pid,
+        None => {
+            println!("[error] spawn child failed");
+            return -1;
+        }
+    };
+    let order = order_test(&reply);
+    let child_ok = wait_pid(pid) == 0;
+    request.close();
+    reply.close();
+    MessageQueue::unlink(REQUEST_QUEUE);
+    MessageQueue::unlink(REPLY_QUEUE);
+    let limit = limit_test();
+    if order && child_ok && limit {
+        println!("message queue test passed");
+        return 0;
+    }
+    println!("message queue test failed");
+    return -1;
+}
@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+
+// 通过回环接口测试UDP和TCP，不需要网卡
+const LOCALHOST: Ipv4Addr = Ipv4Addr([127, 0, 0, 1]);
+
+fn udp_test() -> bool {
+    let server = match UdpSocket::bind(SocketAddr::new(LOCALHOST, 0)) {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[udp] bind server failed: {}", code);
+            return false;
+        }
+    };
+    let client = match UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0)) {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[udp] bind client failed: {}", code);
+            server.close();
+            return false;
+        }
+    };
+    server.set_read_timeout(1000);
+    client.set_read_timeout(1000);
+    let msg = b"hello loopback";
+    let mut buf = [0u8; 64];
+    client.send_to(msg, server.local_addr());
+    let ok = match server.recv_from(&mut buf) {
+        Ok((len, peer)) => {
+            server.send_to(&buf[0..len], peer);
+            &buf[0..len] == msg && client.recv(&mut buf) == msg.len() as isize
+        }
+        Err(_) => false,
+    };
+    server.close();
+    client.close();
+    println!("[udp] echo: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn tcp_test() -> bool {
+    let listener = match TcpListener::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0), 1) {
+        Ok(listener) => listener,
+        Err(code) => {
+            println!("[tcp] listen failed: {}", code);
+            return false;
+        }
+    };
+    let port = listener.local_addr().port;
+    // 握手由内核完成，同一个进程可以先connect再accept
+    let client = match TcpStream::connect(SocketAddr::new(LOCALHOST, port)) {
+        Ok(stream) => stream,
+        Err(code) => {
+            println!("[tcp] connect failed: {}", code);
+            listener.close();
+            return false;
+        }
+    };
+    let (server, peer) = match listener.accept() {
+        Ok(conn) => conn,
+        Err(code) => {
+            println!("[tcp] accept failed: {}", code);
+            client.close();
+            listener.close();
+            return false;
+        }
+    };
+    println!("[tcp] accepted connection from {}", peer);
+    // 发送超过一个MSS的数据，检查分段和顺序
+    let mut data = [0u8; 4000];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    client.write(&data);
+    client.shutdown(user_lib::net::SHUT_WR);
+    let mut received = 0;
+    let mut ok = true;
+    let mut buf = [0u8; 512];
+    loop {
+        let len = server.read(&mut buf);
+        if len <= 0 {
+            break;
+        }
+        for i in 0..len as usize {
+            if buf[i] != (received + i) as u8 {
+                ok = false;
+            }
+        }
+        received += len as usize;
+    }
+    ok = ok && received == data.len();
+    server.close();
+    client.close();
+    listener.close();
+    println!("[tcp] received {} bytes: {}", received, if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("net test begin");
+    let udp = udp_test();
+    let tcp = tcp_test();
+    if udp && tcp {
+        println!("net test passed");
+        return 0;
+    }
+    println!("net test failed");
+    return -1;
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::net::{ping, ping_reply, Ipv4Addr};
+use user_lib::time::get_time_ms;
+use user_lib::yield_;
+
+const DATA_LEN: usize = 56;
+const TIMEOUT_MS: usize = 1000;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    if argc < 2 {
+        println!("usage: ping <ip> [count]");
+        return -1;
+    }
+    let addr = match Ipv4Addr::parse(argv[0]) {
+        Some(addr) => addr,
+        None => {
+            println!("[error] invalid address: {}", argv[0]);
+            return -1;
+        }
+    };
+    let count = if argc > 2 {
+        argv[1].parse::<u16>().unwrap_or(4)
+    } else {
+        4
+    };
+    println!("PING {}: {} data bytes", addr, DATA_LEN);
+    let mut received = 0;
+    for seq in 0..count {
+        let start = get_time_ms();
+        if ping(addr, seq, DATA_LEN) != 0 {
+            println!("[error] network is unreachable");
+            return -1;
+        }
+        let mut replied = false;
+        // 等待回复，超时或收到回复后等满1秒再发送下一个
+        while get_time_ms() - start < TIMEOUT_MS {
+            if !replied {
+                if let Some(ttl) = ping_reply(addr, seq) {
+                    println!(
+                        "{} bytes from {}: icmp_seq={} ttl={} time={} ms",
+                        DATA_LEN + 8,
+                        addr,
+                        seq,
+                        ttl,
+                        get_time_ms() - start
+                    );
+                    replied = true;
+                    received += 1;
+                }
+            }
+            yield_();
+        }
+        if !replied {
+            println!("Request timeout for icmp_seq {}", seq);
+        }
+    }
+    println!(
+        "--- {} ping statistics ---\n{} packets transmitted, {} packets received, {}% packet loss",
+        addr,
+        count,
+        received,
+        if count == 0 { 0 } else { (count as usize - received) * 100 / count as usize }
+    );
+    return 0;
+}
@@ -59,6 +59,11 @@ pub fn main() -> i32 {
                     .split_whitespace()
                     .map(|arg| String::from(arg))
                     .collect();
+                // 以&结尾的命令在后台运行，shell不等待其退出
+                let background = args.len() > 1 && args.last().map(|arg| arg == "&").unwrap_or(false);
+                if background {
+                    args.pop();
+                }
                 // 将shell当前的目录添加到参数列表末尾
                 args.push(cur_path.clone());
                 // 获取要执行的命令
@@ -66,7 +71,7 @@ pub fn main() -> i32 {
                 match app.as_str() {
                     "cd" => cur_path = exec_cd(args, &mut cur_path),
                     "type" => exec_type(args, &mut app_absolute_path),
-                    _ => _ = exec_app(args, app.clone(), &mut app_absolute_path),
+                    _ => _ = exec_app(args, app.clone(), &mut app_absolute_path, background),
                 }
 
                 cmd.clear();
@@ -125,7 +130,7 @@ fn exec_cd(args: Vec<String>, cur_path: &mut String) -> String {
     }
 }
 
-fn exec_app(args: Vec<String>, app: String, abs_path: &mut String) -> isize {
+fn exec_app(args: Vec<String>, app: String, abs_path: &mut String, background: bool) -> isize {
     let length = abs_path.len();
     abs_path.push_str(app.as_str());
     abs_path.push('\0');
@@ -134,7 +139,12 @@ fn exec_app(args: Vec<String>, app: String, abs_path: &mut String) -> isize {
     let args_ptrs: Vec<_> = c_args.iter().map(|arg| (*arg).as_ptr()).collect();
     let code: isize;
     if let Some(pid) = spawn(abs_path.as_str(), args_ptrs.as_slice()) {
-        code = wait_pid(pid);
+        if background {
+            println!("[{}] {}", pid, app);
+            code = 0;
+        } else {
+            code = wait_pid(pid);
+        }
     } else {
         println!("command not found");
         code = 0;
@@ -0,0 +1,155 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::shm::*;
+use user_lib::{exit, fork, spawn, wait_pid};
+
+const SHM_KEY: usize = 0x5348;
+const SHM_SIZE: usize = 3 * 4096;
+
+fn pattern(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+// 共享内存可能被其他进程或其他映射修改，读取时不能使用缓存的值
+fn load(buf: &[u8], i: usize) -> u8 {
+    unsafe { core::ptr::read_volatile(&buf[i]) }
+}
+
+// 由shm_test spawn出的子进程，只通过key找到父进程创建的段
+fn child() -> i32 {
+    let shm = match SharedMemory::get(SHM_KEY, SHM_SIZE, 0) {
+        Ok(shm) => shm,
+        Err(code) => {
+            println!("[child] shmget failed: {}", code);
+            return -1;
+        }
+    };
+    let buf = match shm.attach(false) {
+        Ok(buf) => buf,
+        Err(code) => {
+            println!("[child] shmat failed: {}", code);
+            return -1;
+        }
+    };
+    // 第一个字节留给父进程检查
+    for i in 1..SHM_SIZE {
+        buf[i] = pattern(i);
+    }
+    buf[0] = 1;
+    SharedMemory::detach(buf);
+    return 0;
+}
+
+// spawn的进程通过key attach，写入的数据父进程可见
+fn spawn_test(shm: &SharedMemory, buf: &mut [u8], cwd: &str) -> bool {
+    let mut cur_path = String::from(cwd);
+    cur_path.push('\0');
+    let args_ptrs = ["child\0".as_ptr(), cur_path.as_ptr()];
+    let pid = match spawn("/bin/shm_test\0", &args_ptrs) {
+        Some(pid) => pid,
+        None => {
+            println!("[spawn] spawn child failed");
+            return false;
+        }
+    };
+    let mut ok = wait_pid(pid) == 0 && load(buf, 0) == 1;
+    ok = ok && (1..SHM_SIZE).all(|i| load(buf, i) == pattern(i));
+    // 子进程已经detach，只剩父进程的映射
+    ok = ok && shm.stat().map(|stat| stat.attaches) == Ok(1);
+    println!("[spawn] data written by another process: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+// fork后父子进程仍然共享同一组物理页，普通内存则各自CopyOnWrite
+fn fork_test(shm: &SharedMemory, buf: &mut [u8]) -> bool {
+    let mut private = [0u8; 16];
+    buf[1] = 0;
+    let pid = fork();
+    if pid == 0 {
+        buf[1] = 42;
+        private[0] = 42;
+        exit(0);
+    }
+    let attaches = shm.stat().map(|stat| stat.attaches).unwrap_or(0);
+    let ok = wait_pid(pid as usize) == 0 && load(buf, 1) == 42 && private[0] == 0 && attaches >= 1;
+    println!("[fork] shared after fork: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn key_test() -> bool {
+    let mut ok = SharedMemory::get(SHM_KEY, SHM_SIZE, IPC_CREAT | IPC_EXCL).err() == Some(SHM_EXIST_ERROR);
+    // 已存在的段不能按更大的大小获取
+    ok = ok && SharedMemory::get(SHM_KEY, SHM_SIZE + 1, 0).err() == Some(SHM_ERROR);
+    // IPC_PRIVATE每次都创建新的段
+    let a = SharedMemory::get(IPC_PRIVATE, 4096, IPC_CREAT);
+    let b = SharedMemory::get(IPC_PRIVATE, 4096, IPC_CREAT);
+    match (a, b) {
+        (Ok(a), Ok(b)) => {
+            ok = ok && a.id() != b.id();
+            a.remove();
+            b.remove();
+        }
+        _ => ok = false,
+    }
+    println!("[key] exclusive and private keys: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn remove_test(shm: &SharedMemory, buf: &mut [u8]) -> bool {
+    let readonly = match shm.attach(true) {
+        Ok(readonly) => readonly,
+        Err(_) => return false,
+    };
+    // 两个映射指向同一组物理页
+    buf[2] = 7;
+    let mut ok = load(readonly, 2) == 7 && shm.stat().map(|stat| stat.attaches) == Ok(2);
+    // 删除id之后已有的映射仍然有效
+    ok = ok && shm.remove() == 0;
+    ok = ok && SharedMemory::get(SHM_KEY, SHM_SIZE, 0).err() == Some(SHM_NOT_FOUND_ERROR);
+    buf[3] = 9;
+    ok = ok && load(readonly, 3) == 9;
+    ok = ok && SharedMemory::detach(readonly) == 0 && SharedMemory::detach(buf) == 0;
+    ok = ok && SharedMemory::detach(buf) == SHM_ERROR;
+    println!("[remove] detach after remove: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是当前路径
+    if argc > 1 && argv[0] == "child" {
+        return child();
+    }
+    println!("shared memory test begin");
+    let shm = match SharedMemory::get(SHM_KEY, SHM_SIZE, IPC_CREAT | IPC_EXCL) {
+        Ok(shm) => shm,
+        Err(code) => {
+            println!("[error] shmget failed: {}", code);
+            return -1;
+        }
+    };
+    let buf = match shm.attach(false) {
+        Ok(buf) => buf,
+        Err(code) => {
+            println!("[error] shmat failed: {}", code);
+            shm.remove();
+            return -1;
+        }
+    };
+    let spawned = spawn_test(&shm, buf, argv[argc - 1]);
+    let forked = fork_test(&shm, buf);
+    let key = key_test();
+    let removed = remove_test(&shm, buf);
+    if spawned && forked && key && removed {
+        println!("shared memory test passed");
+        return 0;
+    }
+    println!("shared memory test failed");
+    return -1;
+}
@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::file::{pipe, File};
+use user_lib::mem::slab_info;
+use user_lib::{exit, fork, wait_pid};
+
+const PIPES: usize = 20;
+
+// 缓存中已经分配的对象数量
+fn active(name: &str) -> usize {
+    match slab_info().iter().find(|cache| cache.name() == name) {
+        Some(cache) => cache.active,
+        None => 0,
+    }
+}
+
+fn pipe_test() -> bool {
+    let before = active("pipe-buffer");
+    let mut pipes: Vec<(File, File)> = Vec::new();
+    for _ in 0..PIPES {
+        match pipe() {
+            Ok(ends) => pipes.push(ends),
+            Err(_) => break,
+        }
+    }
+    let opened = active("pipe-buffer");
+    for (read_end, write_end) in pipes.iter() {
+        read_end.close();
+        write_end.close();
+    }
+    let closed = active("pipe-buffer");
+    let ok = pipes.len() == PIPES && opened >= before + PIPES && closed + PIPES <= opened;
+    println!(
+        "[pipe] pipe buffers from slab cache ({} -> {} -> {}): {}",
+        before,
+        opened,
+        closed,
+        if ok { "ok" } else { "failed" }
+    );
+    return ok;
+}
+
+fn process_test() -> bool {
+    let (read_end, write_end) = pipe().unwrap();
+    let before = active("process");
+    let pid = fork();
+    if pid == 0 {
+        // 等待父进程检查完统计信息
+        let mut buf = [0u8; 1];
+        read_end.read(&mut buf);
+        exit(0);
+    }
+    let running = active("process");
+    let mut ok = running > before && active("task") > 0;
+    write_end.write(b"x");
+    ok = wait_pid(pid as usize) == 0 && ok;
+    read_end.close();
+    write_end.close();
+    // 回收子进程后pcb被释放
+    ok = ok && active("process") < running;
+    println!("[process] pcb and tcb from slab cache: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("slab test begin");
+    let mut ok = pipe_test();
+    ok = process_test() && ok;
+    if ok {
+        println!("slab test passed");
+        return 0;
+    }
+    println!("slab test failed");
+    return -1;
+}
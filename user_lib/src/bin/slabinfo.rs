@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mem::slab_info;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!(
+        "{:<16} {:>8} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10}",
+        "CACHE", "SIZE", "ACTIVE", "OBJS", "PER_SLAB", "PAGES", "ALLOCS", "FREES"
+    );
+    for cache in slab_info() {
+        println!(
+            "{:<16} {:>8} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10}",
+            cache.name(),
+            cache.size,
+            cache.active,
+            cache.slabs * cache.per_slab,
+            cache.per_slab,
+            cache.slabs * cache.pages,
+            cache.allocs,
+            cache.frees
+        );
+    }
+    return 0;
+}
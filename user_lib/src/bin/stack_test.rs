@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::resource::*;
+use user_lib::{create_thread, exit, fork, wait_pid, wait_tid};
+
+const PAGE_FAULT_EXIT_CODE: isize = -11;
+// 每层递归在栈上占用的大小
+const FRAME_SIZE: usize = 1024;
+
+// 每层递归使用FRAME_SIZE字节的栈，返回值依赖每一层的数据，避免被优化
+fn recurse(depth: usize) -> usize {
+    let mut buf = [0u8; FRAME_SIZE];
+    unsafe { core::ptr::write_volatile(&mut buf[depth % FRAME_SIZE], 1) };
+    if depth == 0 {
+        return 0;
+    }
+    let sum = recurse(depth - 1);
+    return sum + unsafe { core::ptr::read_volatile(&buf[depth % FRAME_SIZE]) } as usize;
+}
+
+// 在子进程中使用size字节的栈，返回子进程的退出码
+fn use_stack_in_child(size: usize, limit: Option<usize>) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        if let Some(cur) = limit {
+            let mut rlimit = getrlimit(RLIMIT_STACK).unwrap();
+            rlimit.cur = cur;
+            if setrlimit(RLIMIT_STACK, rlimit) != 0 {
+                exit(-1);
+            }
+        }
+        let depth = size / FRAME_SIZE;
+        exit(if recurse(depth) == depth { 0 } else { -1 });
+    }
+    return wait_pid(pid as usize);
+}
+
+fn grow_test() -> bool {
+    // 远超过初始的8KiB栈
+    let depth = 256;
+    let ok = recurse(depth) == depth;
+    println!("[grow] 256KiB of stack: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn limit_test() -> bool {
+    // 超过默认上限时只有子进程退出
+    let mut ok = use_stack_in_child(4 * 1024 * 1024, None) == PAGE_FAULT_EXIT_CODE;
+    // 降低上限之后原本可以使用的栈大小也会超出
+    ok = ok && use_stack_in_child(128 * 1024, Some(64 * 1024)) == PAGE_FAULT_EXIT_CODE;
+    // 提高上限之后可以使用更大的栈
+    ok = ok && use_stack_in_child(2 * 1024 * 1024, Some(4 * 1024 * 1024)) == 0;
+    println!("[limit] stack rlimit: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn rlimit_test() -> bool {
+    let limit = match getrlimit(RLIMIT_STACK) {
+        Ok(limit) => limit,
+        Err(_) => return false,
+    };
+    let mut ok = limit.cur <= limit.max;
+    // cur不能超过max，max不能提高
+    ok = ok && setrlimit(RLIMIT_STACK, RLimit { cur: limit.max + 1, max: limit.max }) == RLIMIT_ERROR;
+    ok = ok && setrlimit(RLIMIT_STACK, RLimit { cur: limit.cur, max: limit.max + 1 }) == RLIMIT_ERROR;
+    ok = ok && getrlimit(0).is_err();
+    // 子进程继承父进程的限制
+    ok = ok && setrlimit(RLIMIT_STACK, RLimit { cur: 512 * 1024, max: limit.max }) == 0;
+    ok = ok && use_stack_in_child(768 * 1024, None) == PAGE_FAULT_EXIT_CODE;
+    ok = ok && setrlimit(RLIMIT_STACK, limit) == 0 && getrlimit(RLIMIT_STACK) == Ok(limit);
+    println!("[rlimit] get and set stack limit: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn thread_func(depth: usize) {
+    exit(if recurse(depth) == depth { 0 } else { -1 });
+}
+
+fn thread_test() -> bool {
+    // 每个线程有独立的栈区域，可以同时增长
+    let t1 = create_thread(thread_func as usize, 128);
+    let t2 = create_thread(thread_func as usize, 192);
+    let ok = wait_tid(t1) == 0 && wait_tid(t2) == 0;
+    println!("[thread] thread stacks grow independently: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn thread_overflow_test() -> bool {
+    // 子线程超过栈上限时整个进程退出，main线程不会从wait_tid返回
+    let pid = fork();
+    if pid == 0 {
+        let tid = create_thread(thread_func as usize, 4 * 1024);
+        wait_tid(tid);
+        exit(0);
+    }
+    let ok = wait_pid(pid as usize) == PAGE_FAULT_EXIT_CODE;
+    println!("[thread] overflow in a thread kills the process: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("stack test begin");
+    let grow = grow_test();
+    let limit = limit_test();
+    let rlimit = rlimit_test();
+    let thread = thread_test();
+    let overflow = thread_overflow_test();
+    if grow && limit && rlimit && thread && overflow {
+        println!("stack test passed");
+        return 0;
+    }
+    println!("stack test failed");
+    return -1;
+}
@@ -6,7 +6,7 @@ extern crate user_lib;
 extern crate alloc;
 
 use alloc::string::String;
-use user_lib::file::{get_absolute_path, File, OpenFlags};
+use user_lib::file::{self, get_absolute_path};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
@@ -19,14 +19,16 @@ pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
     let absolute_path = get_absolute_path(name, cur_path);
     let mut file_path = absolute_path.clone();
     file_path.push('\0');
-    match File::open(file_path.as_str(), OpenFlags::RDONLY) {
-        Ok(file) => {
-            let stat = file.fstat().unwrap();
+    // 不打开文件，打开命名管道会阻塞
+    match file::stat(file_path.as_str()) {
+        Some(stat) => {
             println!("File:  {}", absolute_path);
             println!(
                 "Type:  {}",
                 if stat.dir {
                     "directory"
+                } else if stat.fifo {
+                    "fifo"
                 } else {
                     "regular file"
                 }
@@ -39,9 +41,8 @@ pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
                 "Inode: {:<16} Index Blocks: {:<16}",
                 stat.inode, stat.index_blocks
             );
-            file.close();
         }
-        Err(_) => {
+        None => {
             println!("[error] File not found: {}", absolute_path);
         }
     }
@@ -0,0 +1,102 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::mem::*;
+
+// 每次映射1MiB，映射的页在第一次访问时才分配，写满空闲的物理内存后再使用一部分交换区
+const CHUNK_SIZE: usize = 1024 * 1024;
+const MAX_CHUNKS: usize = 512;
+// 交换区共8MiB，超过空闲物理内存的部分不能用完交换区
+const SWAP_CHUNKS: usize = 4;
+
+// 每一页写入不同的值，读回时检查换出再换入的数据是否正确
+fn page_value(chunk: usize, page: usize) -> usize {
+    return chunk * 1000003 + page * 7919 + 1;
+}
+
+fn fill(addr: usize, chunk: usize) {
+    for page in 0..CHUNK_SIZE / PAGE_SIZE {
+        let ptr = (addr + page * PAGE_SIZE) as *mut usize;
+        unsafe {
+            ptr.write_volatile(page_value(chunk, page));
+            ptr.add(PAGE_SIZE / 8 - 1).write_volatile(!page_value(chunk, page));
+        }
+    }
+}
+
+fn check(addr: usize, chunk: usize) -> bool {
+    (0..CHUNK_SIZE / PAGE_SIZE).all(|page| {
+        let ptr = (addr + page * PAGE_SIZE) as *const usize;
+        unsafe {
+            ptr.read_volatile() == page_value(chunk, page)
+                && ptr.add(PAGE_SIZE / 8 - 1).read_volatile() == !page_value(chunk, page)
+        }
+    })
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("swap test begin");
+    // 超过动态映射区域的长度直接返回错误，不会分配物理页
+    let addr = mmap(0, 1 << 40, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+    if addr != MMAP_ERROR {
+        println!("[swap] oversized mmap not rejected: {}", addr);
+        return -1;
+    }
+    let free_chunks = mem_info().free * PAGE_SIZE / CHUNK_SIZE;
+    let target = free_chunks.min(MAX_CHUNKS - SWAP_CHUNKS) + SWAP_CHUNKS;
+    let mut chunks: Vec<usize> = Vec::new();
+    while chunks.len() < target {
+        let addr = mmap(0, CHUNK_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+        if addr < 0 {
+            if addr != MMAP_NO_MEMORY_ERROR {
+                println!("[swap] unexpected mmap error: {}", addr);
+                return -1;
+            }
+            break;
+        }
+        fill(addr as usize, chunks.len());
+        chunks.push(addr as usize);
+    }
+    println!("[swap] mapped {} MiB", chunks.len() * CHUNK_SIZE / (1024 * 1024));
+    // 按顺序读回，前面的页已经被换出
+    let mut ok = chunks.len() > 0;
+    for (i, addr) in chunks.iter().enumerate() {
+        ok = ok && check(*addr, i);
+    }
+    // 再次读回，换入时会换出其他页
+    for (i, addr) in chunks.iter().enumerate().rev() {
+        ok = ok && check(*addr, i);
+    }
+    println!("[swap] data kept across swap out and in: {}", if ok { "ok" } else { "failed" });
+    // 解除映射后交换区的槽位被释放，可以再次分配
+    for addr in chunks.iter() {
+        munmap(*addr, CHUNK_SIZE);
+    }
+    let count = chunks.len();
+    chunks.clear();
+    for i in 0..count {
+        let addr = mmap(0, CHUNK_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+        if addr < 0 {
+            ok = false;
+            break;
+        }
+        fill(addr as usize, i);
+        chunks.push(addr as usize);
+    }
+    for addr in chunks.iter() {
+        munmap(*addr, CHUNK_SIZE);
+    }
+    println!("[swap] swap slots released on munmap: {}", if ok { "ok" } else { "failed" });
+    if ok {
+        println!("swap test passed");
+        return 0;
+    }
+    println!("swap test failed");
+    return -1;
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::net::{Ipv4Addr, SocketAddr, TcpListener};
+
+// qemu把宿主机的tcp 6202端口转发到7端口
+const DEFAULT_PORT: u16 = 7;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    let port = if argc > 1 {
+        argv[0].parse::<u16>().unwrap_or(DEFAULT_PORT)
+    } else {
+        DEFAULT_PORT
+    };
+    let listener = match TcpListener::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, port), 8) {
+        Ok(listener) => listener,
+        Err(code) => {
+            println!("[error] listen on port {} failed: {}", port, code);
+            return -1;
+        }
+    };
+    println!("tcp echo server listening on {}", listener.local_addr());
+    let mut buf = [0u8; 1024];
+    loop {
+        let (stream, peer) = match listener.accept() {
+            Ok(conn) => conn,
+            Err(code) => {
+                println!("[error] accept failed: {}", code);
+                break;
+            }
+        };
+        println!("connection from {}", peer);
+        let mut quit = false;
+        loop {
+            let len = stream.read(&mut buf);
+            if len <= 0 {
+                break;
+            }
+            let data = &buf[0..len as usize];
+            // 收到quit时关闭服务器
+            if core::str::from_utf8(data).map(|s| s.trim_end() == "quit").unwrap_or(false) {
+                quit = true;
+                break;
+            }
+            if stream.write(data) < 0 {
+                break;
+            }
+        }
+        println!("connection from {} closed", peer);
+        stream.close();
+        if quit {
+            break;
+        }
+    }
+    listener.close();
+    return 0;
+}
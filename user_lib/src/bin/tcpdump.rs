@@ -0,0 +1,268 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::file::{get_absolute_path, File, OpenFlags};
+use user_lib::net::{self, CaptureHeader, Ipv4Addr, PacketCapture, DIRECTION_OUT};
+
+// 默认抓取的帧数量，没有信号无法中断，所以需要有上限
+const DEFAULT_COUNT: usize = 20;
+// pcap文件格式，see: https://wiki.wireshark.org/Development/LibpcapFileFormat
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_SNAP_LEN: u32 = 65535;
+const LINKTYPE_ETHERNET: u32 = 1;
+
+fn usage() -> i32 {
+    println!("usage: tcpdump [-i iface] [-c count] [-w file]");
+    return -1;
+}
+
+fn be16(data: &[u8], off: usize) -> u16 {
+    u16::from_be_bytes([data[off], data[off + 1]])
+}
+
+fn be32(data: &[u8], off: usize) -> u32 {
+    u32::from_be_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]])
+}
+
+fn ip_at(data: &[u8], off: usize) -> Ipv4Addr {
+    Ipv4Addr([data[off], data[off + 1], data[off + 2], data[off + 3]])
+}
+
+fn decode_arp(data: &[u8]) -> String {
+    if data.len() < 28 {
+        return String::from("ARP, truncated");
+    }
+    match be16(data, 6) {
+        1 => format!("ARP, Request who-has {} tell {}", ip_at(data, 24), ip_at(data, 14)),
+        2 => format!(
+            "ARP, Reply {} is-at {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            ip_at(data, 14),
+            data[8],
+            data[9],
+            data[10],
+            data[11],
+            data[12],
+            data[13]
+        ),
+        op => format!("ARP, op {}", op),
+    }
+}
+
+fn decode_tcp_flags(flags: u8) -> String {
+    let mut s = String::new();
+    for (bit, c) in [(0x02, 'S'), (0x01, 'F'), (0x04, 'R'), (0x08, 'P'), (0x10, '.')].iter() {
+        if flags & bit != 0 {
+            s.push(*c);
+        }
+    }
+    if s.is_empty() {
+        s.push_str("none");
+    }
+    return s;
+}
+
+fn decode_ipv4(data: &[u8]) -> String {
+    if data.len() < 20 {
+        return String::from("IP, truncated");
+    }
+    let header_len = ((data[0] & 0xf) as usize) * 4;
+    let total_len = (be16(data, 2) as usize).min(data.len());
+    let src = ip_at(data, 12);
+    let dst = ip_at(data, 16);
+    let flags_offset = be16(data, 6);
+    if flags_offset & 0x1fff != 0 {
+        return format!("IP {} > {}: fragment offset {}", src, dst, (flags_offset & 0x1fff) * 8);
+    }
+    if header_len < 20 || header_len > total_len {
+        return format!("IP {} > {}: bad header", src, dst);
+    }
+    let payload = &data[header_len..total_len];
+    match data[9] {
+        1 if payload.len() >= 8 => {
+            let kind = match payload[0] {
+                0 => "echo reply",
+                3 => "destination unreachable",
+                8 => "echo request",
+                11 => "time exceeded",
+                _ => "type",
+            };
+            format!(
+                "IP {} > {}: ICMP {}, id {}, seq {}, length {}",
+                src,
+                dst,
+                kind,
+                be16(payload, 4),
+                be16(payload, 6),
+                payload.len()
+            )
+        }
+        6 if payload.len() >= 20 => {
+            let data_offset = ((payload[12] >> 4) as usize) * 4;
+            let len = payload.len().saturating_sub(data_offset);
+            format!(
+                "IP {}.{} > {}.{}: Flags [{}], seq {}, ack {}, win {}, length {}",
+                src,
+                be16(payload, 0),
+                dst,
+                be16(payload, 2),
+                decode_tcp_flags(payload[13]),
+                be32(payload, 4),
+                be32(payload, 8),
+                be16(payload, 14),
+                len
+            )
+        }
+        17 if payload.len() >= 8 => format!(
+            "IP {}.{} > {}.{}: UDP, length {}",
+            src,
+            be16(payload, 0),
+            dst,
+            be16(payload, 2),
+            be16(payload, 4).saturating_sub(8)
+        ),
+        proto => format!("IP {} > {}: proto {}, length {}", src, dst, proto, payload.len()),
+    }
+}
+
+fn decode(frame: &[u8]) -> String {
+    if frame.len() < 14 {
+        return String::from("truncated frame");
+    }
+    let payload = &frame[14..];
+    match be16(frame, 12) {
+        0x0806 => decode_arp(payload),
+        0x0800 => decode_ipv4(payload),
+        ether_type => format!("ethertype {:#06x}, length {}", ether_type, frame.len()),
+    }
+}
+
+fn write_u32s(file: &File, values: &[u32]) {
+    for v in values {
+        file.write(&v.to_ne_bytes());
+    }
+}
+
+fn write_pcap_header(file: &File) {
+    file.write(&PCAP_MAGIC.to_ne_bytes());
+    file.write(&2u16.to_ne_bytes()); // version 2.4
+    file.write(&4u16.to_ne_bytes());
+    write_u32s(file, &[0, 0, PCAP_SNAP_LEN, LINKTYPE_ETHERNET]);
+}
+
+fn write_pcap_record(file: &File, header: &CaptureHeader, frame: &[u8]) {
+    write_u32s(file, &[header.sec, header.usec, frame.len() as u32, header.len]);
+    file.write(frame);
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    let args = &argv[0..argc - 1];
+    let mut dev = None;
+    let mut count = DEFAULT_COUNT;
+    let mut output = None;
+    let mut i = 0;
+    while i < args.len() {
+        if i + 1 >= args.len() {
+            return usage();
+        }
+        match args[i] {
+            "-i" => match net::interface(args[i + 1]) {
+                Some(iface) => dev = Some((iface.dev as u16, args[i + 1])),
+                None => {
+                    println!("[error] interface {} not found", args[i + 1]);
+                    return -1;
+                }
+            },
+            "-c" => match args[i + 1].parse::<usize>() {
+                Ok(n) if n > 0 => count = n,
+                _ => return usage(),
+            },
+            "-w" => output = Some(args[i + 1]),
+            _ => return usage(),
+        }
+        i += 2;
+    }
+    let file = match output {
+        Some(name) => {
+            let mut path = get_absolute_path(String::from(name), String::from(argv[argc - 1]));
+            path.push('\0');
+            match File::open(path.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY) {
+                Ok(file) => {
+                    write_pcap_header(&file);
+                    Some(file)
+                }
+                Err(code) => {
+                    println!("[error] cannot create {}: {}", name, code);
+                    return -1;
+                }
+            }
+        }
+        None => None,
+    };
+    let capture = match PacketCapture::open() {
+        Ok(capture) => capture,
+        Err(code) => {
+            println!("[error] open capture failed: {}", code);
+            return -1;
+        }
+    };
+    println!(
+        "listening on {}, capturing {} packets",
+        dev.map(|(_, name)| name).unwrap_or("all interfaces"),
+        count
+    );
+    let names: Vec<(u16, String)> = net::interfaces()
+        .iter()
+        .map(|i| (i.dev as u16, String::from(i.name())))
+        .collect();
+    let mut buf = [0u8; 2048];
+    let mut captured = 0;
+    while captured < count {
+        let (header, frame) = match capture.next(&mut buf) {
+            Ok(packet) => packet,
+            Err(code) => {
+                println!("[error] capture failed: {}", code);
+                break;
+            }
+        };
+        if let Some((id, _)) = dev {
+            if header.dev != id {
+                continue;
+            }
+        }
+        captured += 1;
+        match file.as_ref() {
+            Some(file) => write_pcap_record(file, &header, frame),
+            None => {
+                let name = names
+                    .iter()
+                    .find(|(id, _)| *id == header.dev)
+                    .map(|(_, name)| name.as_str())
+                    .unwrap_or("?");
+                let direction = if header.direction == DIRECTION_OUT { "Out" } else { "In" };
+                println!(
+                    "{}.{:06} {} {:<3} {}",
+                    header.sec,
+                    header.usec,
+                    name,
+                    direction,
+                    decode(frame)
+                );
+            }
+        }
+    }
+    capture.close();
+    if let Some(file) = file {
+        file.close();
+        println!("{} packets written to {}", captured, output.unwrap());
+    }
+    return 0;
+}
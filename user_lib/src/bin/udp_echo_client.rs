@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::net::{Ipv4Addr, SocketAddr, UdpSocket, TIMEOUT_ERROR};
+
+const TIMEOUT_MS: usize = 2000;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    if argc < 4 {
+        println!("usage: udp_echo_client <ip> <port> <message>");
+        return -1;
+    }
+    let ip = match Ipv4Addr::parse(argv[0]) {
+        Some(ip) => ip,
+        None => {
+            println!("[error] invalid address: {}", argv[0]);
+            return -1;
+        }
+    };
+    let port = match argv[1].parse::<u16>() {
+        Ok(port) => port,
+        Err(_) => {
+            println!("[error] invalid port: {}", argv[1]);
+            return -1;
+        }
+    };
+    let server = SocketAddr::new(ip, port);
+    let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+    socket.set_read_timeout(TIMEOUT_MS);
+    socket.connect(server);
+    let msg = argv[2];
+    if socket.send(msg.as_bytes()) < 0 {
+        println!("[error] send to {} failed", server);
+        socket.close();
+        return -1;
+    }
+    let mut buf = [0u8; 1500];
+    let ret = socket.recv(&mut buf);
+    socket.close();
+    if ret == TIMEOUT_ERROR {
+        println!("[error] no reply from {} in {} ms", server, TIMEOUT_MS);
+        return -1;
+    } else if ret < 0 {
+        println!("[error] recv failed: {}", ret);
+        return -1;
+    }
+    let reply = core::str::from_utf8(&buf[0..ret as usize]).unwrap_or("<binary>");
+    println!("reply from {}: {}", server, reply);
+    return 0;
+}
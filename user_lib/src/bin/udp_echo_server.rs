@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+// qemu把宿主机的udp 6200端口转发到2000端口
+const DEFAULT_PORT: u16 = 2000;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    let port = if argc > 1 {
+        argv[0].parse::<u16>().unwrap_or(DEFAULT_PORT)
+    } else {
+        DEFAULT_PORT
+    };
+    let socket = match UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, port)) {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[error] bind port {} failed: {}", port, code);
+            return -1;
+        }
+    };
+    println!("udp echo server listening on {}", socket.local_addr());
+    let mut buf = [0u8; 1500];
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((len, peer)) => {
+                let msg = core::str::from_utf8(&buf[0..len]).unwrap_or("<binary>");
+                println!("{} bytes from {}: {}", len, peer, msg.trim_end());
+                // 收到quit时退出
+                if msg.trim_end() == "quit" {
+                    break;
+                }
+                socket.send_to(&buf[0..len], peer);
+            }
+            Err(code) => {
+                println!("[error] recv failed: {}", code);
+                break;
+            }
+        }
+    }
+    socket.close();
+    return 0;
+}
@@ -0,0 +1,152 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::net::{
+    UnixDatagram, UnixListener, UnixStream, BROKEN_PIPE_ERROR, CONN_REFUSED_ERROR,
+};
+use user_lib::{exit, fork, wait_pid};
+
+const STREAM_PATH: &str = "/unix_test.sock";
+const DGRAM_PATH: &str = "/unix_test_dgram.sock";
+// 超过内核缓冲区的大小，写入方会阻塞等待读取
+const PAIR_DATA_LEN: usize = 40000;
+
+// socketpair和fork，子进程写入，父进程读取
+fn pair_test() -> bool {
+    let (parent, child) = match UnixStream::pair() {
+        Ok(pair) => pair,
+        Err(code) => {
+            println!("[pair] socketpair failed: {}", code);
+            return false;
+        }
+    };
+    let pid = fork();
+    if pid == 0 {
+        parent.close();
+        let mut buf = [0u8; 1000];
+        let mut sent = 0;
+        while sent < PAIR_DATA_LEN {
+            for (i, byte) in buf.iter_mut().enumerate() {
+                *byte = (sent + i) as u8;
+            }
+            if child.write(&buf) < 0 {
+                exit(-1);
+            }
+            sent += buf.len();
+        }
+        child.close();
+        exit(0);
+    }
+    child.close();
+    let mut received = 0;
+    let mut ok = true;
+    let mut buf = [0u8; 512];
+    loop {
+        let len = parent.read(&mut buf);
+        if len <= 0 {
+            break;
+        }
+        for i in 0..len as usize {
+            if buf[i] != (received + i) as u8 {
+                ok = false;
+            }
+        }
+        received += len as usize;
+    }
+    parent.close();
+    ok = ok && received == PAIR_DATA_LEN && wait_pid(pid as usize) == 0;
+    println!("[pair] received {} bytes: {}", received, if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn stream_test() -> bool {
+    let listener = match UnixListener::bind(STREAM_PATH, 1) {
+        Ok(listener) => listener,
+        Err(code) => {
+            println!("[stream] listen failed: {}", code);
+            return false;
+        }
+    };
+    // 连接立即建立，同一个进程可以先connect再accept
+    let client = match UnixStream::connect(STREAM_PATH) {
+        Ok(stream) => stream,
+        Err(code) => {
+            println!("[stream] connect failed: {}", code);
+            listener.close();
+            return false;
+        }
+    };
+    let server = match listener.accept() {
+        Ok(stream) => stream,
+        Err(code) => {
+            println!("[stream] accept failed: {}", code);
+            client.close();
+            listener.close();
+            return false;
+        }
+    };
+    let msg = b"hello unix";
+    let mut buf = [0u8; 64];
+    client.write(msg);
+    let len = server.read(&mut buf);
+    let mut ok = len == msg.len() as isize && &buf[0..len as usize] == msg;
+    server.write(&buf[0..len.max(0) as usize]);
+    ok = ok && client.read(&mut buf) == msg.len() as isize;
+    // 对端关闭后读取返回0，写入返回错误
+    server.close();
+    ok = ok && client.read(&mut buf) == 0;
+    ok = ok && client.write(msg) == BROKEN_PIPE_ERROR;
+    client.close();
+    listener.close();
+    // 监听的socket关闭后连接被拒绝
+    ok = ok && UnixStream::connect(STREAM_PATH).err() == Some(CONN_REFUSED_ERROR);
+    println!("[stream] echo: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+fn dgram_test() -> bool {
+    let server = match UnixDatagram::bind(DGRAM_PATH) {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[dgram] bind failed: {}", code);
+            return false;
+        }
+    };
+    let client = match UnixDatagram::unbound() {
+        Ok(socket) => socket,
+        Err(code) => {
+            println!("[dgram] socket failed: {}", code);
+            server.close();
+            return false;
+        }
+    };
+    let mut buf = [0u8; 64];
+    client.send_to(b"first", DGRAM_PATH);
+    client.send_to(b"second", DGRAM_PATH);
+    // 报文边界保持不变
+    let mut ok = server.recv(&mut buf) == 5 && &buf[0..5] == b"first";
+    ok = ok && server.recv(&mut buf) == 6 && &buf[0..6] == b"second";
+    ok = ok && server.local_path().path() == DGRAM_PATH;
+    ok = ok && client.send_to(b"lost", "/no_such.sock") == CONN_REFUSED_ERROR;
+    server.close();
+    client.close();
+    println!("[dgram] send/recv: {}", if ok { "ok" } else { "failed" });
+    return ok;
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("unix socket test begin");
+    let pair = pair_test();
+    let stream = stream_test();
+    let dgram = dgram_test();
+    if pair && stream && dgram {
+        println!("unix socket test passed");
+        return 0;
+    }
+    println!("unix socket test failed");
+    return -1;
+}
@@ -0,0 +1,140 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::file::{get_absolute_path, File, OpenFlags};
+use user_lib::net::{resolve, SocketAddr, TcpStream};
+
+// 响应头的最大长度
+const MAX_HEADER_SIZE: usize = 8192;
+
+// 解析http://host[:port]/path，返回(host, port, path)
+fn parse_url(url: &str) -> Option<(&str, u16, &str)> {
+    let rest = url.strip_prefix("http://").unwrap_or(url);
+    if rest.contains("://") {
+        return None;
+    }
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => (&rest[0..idx], &rest[idx..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.find(':') {
+        Some(idx) => (&authority[0..idx], authority[idx + 1..].parse::<u16>().ok()?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    return Some((host, port, path));
+}
+
+// 默认使用url路径的最后一段作为文件名
+fn default_file_name(path: &str) -> &str {
+    match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() => name.split('?').next().unwrap(),
+        _ => "index.html",
+    }
+}
+
+fn find_header_end(data: &[u8]) -> Option<usize> {
+    data.windows(4).position(|w| w == b"\r\n\r\n").map(|idx| idx + 4)
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&'static str]) -> i32 {
+    // 最后一个参数是shell的当前路径
+    if argc < 2 {
+        println!("usage: wget <url> [file]");
+        return -1;
+    }
+    let (host, port, path) = match parse_url(argv[0]) {
+        Some(url) => url,
+        None => {
+            println!("[error] invalid url, only http is supported: {}", argv[0]);
+            return -1;
+        }
+    };
+    let name = if argc > 2 { argv[1] } else { default_file_name(path) };
+    let cur_path = String::from(argv[argc - 1]);
+    let mut file_path = get_absolute_path(String::from(name), cur_path);
+    file_path.push('\0');
+
+    let ip = match resolve(host) {
+        Some(ip) => ip,
+        None => {
+            println!("[error] cannot resolve host {}", host);
+            return -1;
+        }
+    };
+    println!("connecting to {} ({}:{})", host, ip, port);
+    let stream = match TcpStream::connect(SocketAddr::new(ip, port)) {
+        Ok(stream) => stream,
+        Err(code) => {
+            println!("[error] connect failed: {}", code);
+            return -1;
+        }
+    };
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: bitos-wget\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    if stream.write(request.as_bytes()) < 0 {
+        println!("[error] send request failed");
+        stream.close();
+        return -1;
+    }
+    // 先读取完整的响应头
+    let mut header: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 1024];
+    let body_start = loop {
+        if let Some(end) = find_header_end(&header) {
+            break end;
+        }
+        let len = stream.read(&mut buf);
+        if len <= 0 || header.len() > MAX_HEADER_SIZE {
+            println!("[error] invalid response");
+            stream.close();
+            return -1;
+        }
+        header.extend_from_slice(&buf[0..len as usize]);
+    };
+    let status_line = String::from_utf8_lossy(&header[0..body_start]);
+    let status_line = status_line.lines().next().unwrap_or("");
+    println!("{}", status_line);
+    let status = status_line.split(' ').nth(1).and_then(|s| s.parse::<u32>().ok());
+    if status != Some(200) {
+        stream.close();
+        return -1;
+    }
+    let file = match File::open(file_path.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY) {
+        Ok(file) => file,
+        Err(code) => {
+            println!("[error] cannot create {}: {}", name, code);
+            stream.close();
+            return -1;
+        }
+    };
+    // 响应头之后已经读到的部分
+    let mut total = header.len() - body_start;
+    if total > 0 {
+        file.write(&header[body_start..]);
+    }
+    loop {
+        let len = stream.read(&mut buf);
+        if len <= 0 {
+            break;
+        }
+        file.write(&buf[0..len as usize]);
+        total += len as usize;
+    }
+    file.close();
+    stream.close();
+    println!("saved {} bytes to {}", total, name);
+    return 0;
+}
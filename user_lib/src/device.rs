@@ -0,0 +1,63 @@
+use crate::syscall;
+use alloc::vec::Vec;
+
+const MAX_DEVICES: usize = 32;
+
+// 内核返回的设备信息，与内核的DeviceInfo布局一致
+#[repr(C)]
+pub struct DeviceInfo {
+    pub name: [u8; 32],
+    pub compatible: [u8; 32],
+    pub driver: [u8; 16],
+    pub bus: u32,
+    pub irq: u32,
+    pub base: usize,
+}
+
+impl DeviceInfo {
+    pub fn empty() -> Self {
+        Self {
+            name: [0; 32],
+            compatible: [0; 32],
+            driver: [0; 16],
+            bus: 0,
+            irq: 0,
+            base: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        c_str(&self.name)
+    }
+
+    pub fn compatible(&self) -> &str {
+        c_str(&self.compatible)
+    }
+
+    // 没有绑定驱动时返回空字符串
+    pub fn driver(&self) -> &str {
+        c_str(&self.driver)
+    }
+
+    pub fn bus(&self) -> &str {
+        match self.bus {
+            0 => "platform",
+            1 => "pci",
+            2 => "virtio-mmio",
+            _ => "unknown",
+        }
+    }
+}
+
+// 列出内核发现的所有设备
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let mut devices: Vec<DeviceInfo> = (0..MAX_DEVICES).map(|_| DeviceInfo::empty()).collect();
+    let total = syscall::ls_dev(devices.as_mut_slice());
+    devices.truncate((total.max(0) as usize).min(MAX_DEVICES));
+    return devices;
+}
+
+pub(crate) fn c_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[0..len]).unwrap_or("")
+}
@@ -36,6 +36,7 @@ pub struct FileStat {
     pub io_block: u32,     // IO块大小
     pub index_blocks: u32, // 索引块数量
     pub dir: bool,
+    pub fifo: bool,
 }
 
 pub const FILE_EXIST_ERROR: isize = -1;
@@ -58,6 +59,21 @@ pub fn stat(path: &str) -> Option<FileStat> {
     None
 }
 
+// 创建命名管道，path需要以'\0'结尾
+pub fn mkfifo(path: &str) -> isize {
+    syscall::mkfifo(path)
+}
+
+// 创建匿名管道，返回(读端, 写端)
+pub fn pipe() -> Result<(File, File), isize> {
+    let mut fds = [0usize; 2];
+    let code = syscall::pipe(&mut fds);
+    if code < 0 {
+        return Err(code);
+    }
+    return Ok((File(fds[0]), File(fds[1])));
+}
+
 pub fn ls(path: &str) -> Result<Vec<String>, isize> {
     if let Some(stat) = stat(path) {
         if !stat.dir {
@@ -103,6 +119,10 @@ impl File {
         close(self.0)
     }
 
+    pub fn fd(&self) -> usize {
+        self.0
+    }
+
     pub fn read(&self, buf: &mut [u8]) -> isize {
         read(self.0, buf)
     }
@@ -142,6 +162,7 @@ impl FileStat {
             io_block: 0,
             index_blocks: 0,
             dir: false,
+            fifo: false,
         }
     }
 }
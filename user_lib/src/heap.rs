@@ -0,0 +1,41 @@
+use crate::mem::sbrk;
+use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+
+// 每次至少扩展64KiB，减少brk的次数
+const HEAP_GROW_SIZE: usize = 64 * 1024;
+
+// 用户堆，空间不足时通过brk向内核申请
+pub struct UserHeap(LockedHeap);
+
+impl UserHeap {
+    pub const fn new() -> Self {
+        return Self(LockedHeap::new());
+    }
+}
+
+unsafe impl GlobalAlloc for UserHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // buddy分配器需要一个对齐的完整块，扩展两倍的大小保证新的内存中有足够大的对齐块
+        let size = layout.size().max(layout.align()).next_power_of_two();
+        let size = (size * 2).max(HEAP_GROW_SIZE);
+        let start = match sbrk(size) {
+            Ok(start) => start,
+            Err(_) => return null_mut(),
+        };
+        heap.add_to_heap(start, start + size);
+        match heap.alloc(layout) {
+            Ok(ptr) => ptr.as_ptr(),
+            Err(_) => null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout);
+    }
+}
@@ -1,37 +1,37 @@
 #![no_std]
 #![no_main]
 #![feature(linkage)]
+#![feature(alloc_error_handler)]
 #![feature(panic_info_message)]
 
+mod heap;
 mod syscall;
 #[macro_use]
 pub mod utils;
+pub mod device;
 pub mod file;
+pub mod mem;
+pub mod mqueue;
+pub mod net;
+pub mod resource;
+pub mod shm;
 pub mod sync;
 pub mod time;
 
-const USER_HEAP_SIZE: usize = 4096 * 1024;
+// 内存不足时进程的退出码
+pub const OOM_EXIT_CODE: i32 = -12;
 
+// 堆在第一次分配时通过brk从内核获取内存，之后按需扩展
 #[global_allocator]
-static HEAP: buddy_system_allocator::LockedHeap = buddy_system_allocator::LockedHeap::new();
-static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+static HEAP: heap::UserHeap = heap::UserHeap::new();
 
 extern crate alloc;
 
 use alloc::vec::Vec;
 
-fn init_heap() {
-    unsafe {
-        let mut heap = HEAP.lock();
-        heap.init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
-        drop(heap);
-    }
-}
-
 // argc为命令行参数个数，argv为命令行参数指针数组的地址
 #[no_mangle]
 pub extern "C" fn _start(argc: usize, argv: usize) {
-    init_heap();
     let mut args: Vec<&'static str> = Vec::new();
     unsafe {
         for i in 0..argc {
@@ -55,6 +55,13 @@ fn main(_: usize, _: &[&'static str]) -> i32 {
     panic!("no main found")
 }
 
+#[alloc_error_handler]
+fn alloc_error_handler(layout: core::alloc::Layout) -> ! {
+    println!("out of memory: failed to allocate {} bytes", layout.size());
+    exit(OOM_EXIT_CODE);
+    loop {}
+}
+
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     match info.location() {
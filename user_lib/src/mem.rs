@@ -0,0 +1,145 @@
+use crate::device::c_str;
+use crate::file::File;
+use crate::syscall;
+use alloc::vec::Vec;
+
+// 与内核一致的mmap prot和flags
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1 << 0;
+pub const PROT_WRITE: usize = 1 << 1;
+pub const PROT_EXEC: usize = 1 << 2;
+
+pub const MAP_SHARED: usize = 1 << 0;
+pub const MAP_PRIVATE: usize = 1 << 1;
+pub const MAP_FIXED: usize = 1 << 4;
+pub const MAP_ANONYMOUS: usize = 1 << 5;
+
+pub const MMAP_ERROR: isize = -1;
+pub const MMAP_NO_MEMORY_ERROR: isize = -13;
+
+pub const PAGE_SIZE: usize = 4096;
+
+// 伙伴系统最大的块包含2^MAX_ORDER个物理页
+pub const MAX_ORDER: usize = 10;
+
+// 内核返回的物理内存使用情况，与内核的MemInfo布局一致
+#[repr(C)]
+pub struct MemInfo {
+    pub total: usize,
+    pub free: usize,
+    pub free_blocks: [usize; MAX_ORDER + 1],
+    pub heap_total: usize,
+    pub heap_used: usize,
+}
+
+// 设置堆的结束地址，返回新的结束地址，失败时返回原来的值，addr为0时返回当前值
+pub fn brk(addr: usize) -> usize {
+    syscall::brk(addr) as usize
+}
+
+// 堆扩展increment字节，返回扩展前的结束地址，即新内存的起始地址
+pub fn sbrk(increment: usize) -> Result<usize, isize> {
+    let old = brk(0);
+    let new = match old.checked_add(increment) {
+        Some(new) => new,
+        None => return Err(MMAP_NO_MEMORY_ERROR),
+    };
+    if brk(new) != new {
+        return Err(MMAP_NO_MEMORY_ERROR);
+    }
+    return Ok(old);
+}
+
+// addr为0时由内核选择地址，返回映射的起始地址
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize) -> isize {
+    syscall::mmap(addr, len, prot, flags, usize::MAX, 0)
+}
+
+// 分配len字节的匿名内存，内容初始化为0
+pub fn mmap_anonymous(len: usize, prot: usize) -> Result<&'static mut [u8], isize> {
+    let addr = mmap(0, len, prot, MAP_PRIVATE | MAP_ANONYMOUS);
+    if addr < 0 {
+        return Err(addr);
+    }
+    return Ok(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) });
+}
+
+// 映射文件从offset开始的len字节，offset需要页对齐
+// MAP_PRIVATE的修改只对当前进程可见，MAP_SHARED的修改在msync、munmap或进程退出时写回文件
+pub fn mmap_file(file: &File, len: usize, prot: usize, flags: usize, offset: usize) -> Result<&'static mut [u8], isize> {
+    let addr = syscall::mmap(0, len, prot, flags, file.fd(), offset);
+    if addr < 0 {
+        return Err(addr);
+    }
+    return Ok(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) });
+}
+
+// 将共享文件映射的修改写回文件
+pub fn msync(addr: usize, len: usize) -> isize {
+    syscall::msync(addr, len, 0)
+}
+
+// 解除映射，范围可以只覆盖一个映射的一部分
+pub fn munmap(addr: usize, len: usize) -> isize {
+    syscall::munmap(addr, len)
+}
+
+// 范围内的页必须都已经映射，访问没有权限的页时进程以-11退出
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall::mprotect(addr, len, prot)
+}
+
+// 内核返回的slab缓存统计信息，与内核的SlabInfo布局一致
+#[repr(C)]
+pub struct SlabInfo {
+    pub name: [u8; 16],
+    pub size: usize,
+    pub per_slab: usize,
+    pub pages: usize,
+    pub slabs: usize,
+    pub active: usize,
+    pub allocs: usize,
+    pub frees: usize,
+}
+
+impl SlabInfo {
+    pub fn empty() -> Self {
+        Self {
+            name: [0; 16],
+            size: 0,
+            per_slab: 0,
+            pages: 0,
+            slabs: 0,
+            active: 0,
+            allocs: 0,
+            frees: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        c_str(&self.name)
+    }
+}
+
+// 物理内存的页数、空闲页数和每个order的空闲块数量
+pub fn mem_info() -> MemInfo {
+    let mut info = MemInfo {
+        total: 0,
+        free: 0,
+        free_blocks: [0; MAX_ORDER + 1],
+        heap_total: 0,
+        heap_used: 0,
+    };
+    syscall::mem_info(&mut info);
+    return info;
+}
+
+const MAX_SLAB_CACHES: usize = 16;
+
+// 列出内核所有slab缓存的统计信息
+pub fn slab_info() -> Vec<SlabInfo> {
+    let mut caches: Vec<SlabInfo> = (0..MAX_SLAB_CACHES).map(|_| SlabInfo::empty()).collect();
+    let total = syscall::slab_info(caches.as_mut_slice());
+    caches.truncate((total.max(0) as usize).min(MAX_SLAB_CACHES));
+    return caches;
+}
@@ -0,0 +1,113 @@
+use crate::syscall;
+use alloc::string::String;
+
+// 与内核一致的mq_open flags
+pub const MQ_RDONLY: usize = 0;
+pub const MQ_WRONLY: usize = 1 << 0;
+pub const MQ_RDWR: usize = 1 << 1;
+pub const MQ_CREATE: usize = 1 << 9;
+pub const MQ_EXCL: usize = 1 << 10;
+pub const MQ_NONBLOCK: usize = 1 << 11;
+
+pub const MQ_PRIO_MAX: usize = 32;
+
+pub const MQ_ERROR: isize = -1;
+pub const MQ_EXIST_ERROR: isize = -2;
+pub const MQ_NOT_FOUND_ERROR: isize = -3;
+pub const MQ_TIMEOUT_ERROR: isize = -6;
+pub const MQ_WOULD_BLOCK_ERROR: isize = -8;
+pub const MQ_MSG_SIZE_ERROR: isize = -12;
+
+// 与内核一致的mq_attr
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MqAttr {
+    pub flags: usize,
+    pub max_msgs: usize,
+    pub msg_size: usize,
+    pub cur_msgs: usize,
+}
+
+impl MqAttr {
+    pub fn new(max_msgs: usize, msg_size: usize) -> Self {
+        Self {
+            flags: 0,
+            max_msgs: max_msgs,
+            msg_size: msg_size,
+            cur_msgs: 0,
+        }
+    }
+}
+
+fn c_name(name: &str) -> String {
+    let mut c_name = String::from(name);
+    c_name.push('\0');
+    return c_name;
+}
+
+// 命名消息队列，所有进程通过名字访问同一个队列
+pub struct MessageQueue {
+    fd: usize,
+}
+
+impl MessageQueue {
+    // 打开队列，flags包含MQ_CREATE时不存在则按attr创建
+    pub fn open(name: &str, flags: usize, attr: Option<MqAttr>) -> Result<Self, isize> {
+        let fd = syscall::mq_open(&c_name(name), flags, attr.as_ref());
+        if fd < 0 {
+            return Err(fd);
+        }
+        return Ok(Self { fd: fd as usize });
+    }
+
+    // 删除队列的名字，已经打开的队列不受影响
+    pub fn unlink(name: &str) -> isize {
+        syscall::mq_unlink(&c_name(name))
+    }
+
+    pub fn fd(&self) -> usize {
+        self.fd
+    }
+
+    // 队列满时阻塞
+    pub fn send(&self, buf: &[u8], prio: usize) -> isize {
+        syscall::mq_timedsend(self.fd, buf, prio, 0)
+    }
+
+    // 队列满时最多等待timeout_ms（至少1ms），超时返回MQ_TIMEOUT_ERROR
+    pub fn send_timeout(&self, buf: &[u8], prio: usize, timeout_ms: usize) -> isize {
+        syscall::mq_timedsend(self.fd, buf, prio, timeout_ms.max(1))
+    }
+
+    // 接收优先级最高的消息，返回(长度, 优先级)，buf不能小于队列的msg_size
+    pub fn receive(&self, buf: &mut [u8]) -> Result<(usize, usize), isize> {
+        self.receive_timeout(buf, 0)
+    }
+
+    // timeout_ms为0时一直等待
+    pub fn receive_timeout(&self, buf: &mut [u8], timeout_ms: usize) -> Result<(usize, usize), isize> {
+        let mut prio = 0u32;
+        let len = syscall::mq_timedreceive(self.fd, buf, &mut prio, timeout_ms);
+        if len < 0 {
+            return Err(len);
+        }
+        return Ok((len as usize, prio as usize));
+    }
+
+    pub fn attr(&self) -> MqAttr {
+        let mut attr = MqAttr::new(0, 0);
+        syscall::mq_getsetattr(self.fd, None, &mut attr);
+        return attr;
+    }
+
+    pub fn set_nonblocking(&self, nonblock: bool) -> isize {
+        let mut attr = MqAttr::new(0, 0);
+        attr.flags = if nonblock { MQ_NONBLOCK } else { 0 };
+        let mut old = MqAttr::new(0, 0);
+        syscall::mq_getsetattr(self.fd, Some(&attr), &mut old)
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
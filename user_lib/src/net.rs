@@ -0,0 +1,734 @@
+use crate::device::c_str;
+use crate::syscall;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+pub const AF_UNIX: usize = 1;
+pub const AF_INET: usize = 2;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+pub const AF_PACKET: usize = 17;
+pub const SOCK_RAW: usize = 3;
+pub const SOL_SOCKET: usize = 1;
+pub const SO_RCVTIMEO: usize = 20;
+pub const MSG_DONTWAIT: usize = 0x40;
+
+// socket系统调用的错误码
+pub const SOCKET_ERROR: isize = -1;
+pub const ADDR_IN_USE_ERROR: isize = -4;
+pub const NOT_CONNECTED_ERROR: isize = -5;
+pub const TIMEOUT_ERROR: isize = -6;
+pub const UNREACHABLE_ERROR: isize = -7;
+pub const WOULD_BLOCK_ERROR: isize = -8;
+pub const CONN_REFUSED_ERROR: isize = -9;
+pub const CONN_RESET_ERROR: isize = -10;
+pub const BROKEN_PIPE_ERROR: isize = -11;
+
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Addr(pub [u8; 4]);
+
+impl Ipv4Addr {
+    pub const UNSPECIFIED: Ipv4Addr = Ipv4Addr([0; 4]);
+
+    // 解析点分十进制的ip地址，如：10.0.2.2
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut addr = [0u8; 4];
+        let mut count = 0;
+        for part in s.split('.') {
+            if count >= 4 {
+                return None;
+            }
+            addr[count] = part.parse::<u8>().ok()?;
+            count += 1;
+        }
+        if count != 4 {
+            return None;
+        }
+        return Some(Self(addr));
+    }
+
+    // 系统调用使用的u32格式
+    pub fn to_u32(&self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    pub fn from_u32(addr: u32) -> Self {
+        Self(addr.to_be_bytes())
+    }
+}
+
+impl Display for Ipv4Addr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+}
+
+const MAX_INTERFACES: usize = 8;
+pub const IFF_UP: u16 = 1;
+pub const IFF_LOOPBACK: u16 = 2;
+pub const IFF_RUNNING: u16 = 4;
+
+// 内核返回的接口信息，与内核的IfInfo布局一致
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IfInfo {
+    pub name: [u8; 8],
+    pub dev: u32,
+    pub ip: u32,
+    pub netmask: u32,
+    pub gateway: u32,
+    pub dns: u32,
+    pub mac: [u8; 6],
+    pub flags: u16,
+}
+
+impl IfInfo {
+    pub fn empty() -> Self {
+        Self {
+            name: [0; 8],
+            dev: 0,
+            ip: 0,
+            netmask: 0,
+            gateway: 0,
+            dns: 0,
+            mac: [0; 6],
+            flags: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        c_str(&self.name)
+    }
+
+    pub fn ip(&self) -> Ipv4Addr {
+        Ipv4Addr::from_u32(self.ip)
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from_u32(self.netmask)
+    }
+
+    pub fn gateway(&self) -> Ipv4Addr {
+        Ipv4Addr::from_u32(self.gateway)
+    }
+
+    pub fn dns(&self) -> Ipv4Addr {
+        Ipv4Addr::from_u32(self.dns)
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.flags & IFF_UP != 0
+    }
+}
+
+// 列出所有网络接口
+pub fn interfaces() -> Vec<IfInfo> {
+    let mut list: Vec<IfInfo> = (0..MAX_INTERFACES).map(|_| IfInfo::empty()).collect();
+    let total = syscall::if_list(list.as_mut_slice());
+    list.truncate((total.max(0) as usize).min(MAX_INTERFACES));
+    return list;
+}
+
+pub fn interface(name: &str) -> Option<IfInfo> {
+    interfaces().into_iter().find(|i| i.name() == name)
+}
+
+// 静态配置接口，会停止接口上的DHCP
+pub fn if_config(dev: u32, ip: Ipv4Addr, netmask: Ipv4Addr, gateway: Ipv4Addr, dns: Ipv4Addr) -> isize {
+    syscall::if_config(dev as usize, ip.to_u32(), netmask.to_u32(), gateway.to_u32(), dns.to_u32())
+}
+
+pub fn if_set_up(dev: u32, up: bool) -> isize {
+    syscall::if_set_up(dev as usize, up)
+}
+
+// 重新通过DHCP获取地址，结果通过interfaces查询
+pub fn dhcp(dev: u32) -> isize {
+    syscall::dhcp(dev as usize)
+}
+
+// 发送ICMP echo request，len为echo数据长度
+pub fn ping(addr: Ipv4Addr, seq: u16, len: usize) -> isize {
+    syscall::ping(addr.to_u32(), seq, len)
+}
+
+// 查询echo reply，收到时返回Some(ttl)
+pub fn ping_reply(addr: Ipv4Addr, seq: u16) -> Option<u8> {
+    match syscall::ping_reply(addr.to_u32(), seq) {
+        -2 => None,
+        ttl => Some(ttl as u8),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SocketAddr {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+}
+
+impl SocketAddr {
+    pub fn new(ip: Ipv4Addr, port: u16) -> Self {
+        Self { ip, port }
+    }
+}
+
+impl Display for SocketAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.ip, self.port)
+    }
+}
+
+// 与内核一致的sockaddr_in，port和addr都是网络字节序
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockAddrIn {
+    pub family: u16,
+    pub port: [u8; 2],
+    pub addr: [u8; 4],
+    pub zero: [u8; 8],
+}
+
+impl SockAddrIn {
+    pub fn from_addr(addr: SocketAddr) -> Self {
+        Self {
+            family: AF_INET as u16,
+            port: addr.port.to_be_bytes(),
+            addr: addr.ip.0,
+            zero: [0; 8],
+        }
+    }
+
+    pub fn to_addr(&self) -> SocketAddr {
+        SocketAddr::new(Ipv4Addr(self.addr), u16::from_be_bytes(self.port))
+    }
+}
+
+pub struct UdpSocket {
+    fd: usize,
+}
+
+impl UdpSocket {
+    // 创建socket并绑定到addr，端口为0时由内核分配
+    pub fn bind(addr: SocketAddr) -> Result<Self, isize> {
+        let fd = syscall::socket(AF_INET, SOCK_DGRAM, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        let socket = Self { fd: fd as usize };
+        let ret = syscall::bind(socket.fd, &SockAddrIn::from_addr(addr));
+        if ret < 0 {
+            socket.close();
+            return Err(ret);
+        }
+        return Ok(socket);
+    }
+
+    pub fn fd(&self) -> usize {
+        self.fd
+    }
+
+    // 设置默认的对端地址，之后可以使用send、recv
+    pub fn connect(&self, addr: SocketAddr) -> isize {
+        syscall::connect(self.fd, &SockAddrIn::from_addr(addr))
+    }
+
+    pub fn send_to(&self, buf: &[u8], addr: SocketAddr) -> isize {
+        syscall::sendto(self.fd, buf, 0, Some(&SockAddrIn::from_addr(addr)))
+    }
+
+    pub fn send(&self, buf: &[u8]) -> isize {
+        syscall::sendto(self.fd, buf, 0, None)
+    }
+
+    // 接收一个报文，返回(长度, 对端地址)
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), isize> {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        let ret = syscall::recvfrom(self.fd, buf, 0, &mut addr);
+        if ret < 0 {
+            return Err(ret);
+        }
+        return Ok((ret as usize, addr.to_addr()));
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> isize {
+        match self.recv_from(buf) {
+            Ok((len, _)) => len as isize,
+            Err(code) => code,
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        syscall::getsockname(self.fd, &mut addr);
+        return addr.to_addr();
+    }
+
+    // 接收超时，单位ms，0表示一直等待
+    pub fn set_read_timeout(&self, timeout_ms: usize) -> isize {
+        syscall::setsockopt(self.fd, SOL_SOCKET, SO_RCVTIMEO, timeout_ms)
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+pub struct TcpListener {
+    fd: usize,
+}
+
+impl TcpListener {
+    // 创建socket，绑定到addr并开始监听
+    pub fn bind(addr: SocketAddr, backlog: usize) -> Result<Self, isize> {
+        let fd = syscall::socket(AF_INET, SOCK_STREAM, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        let listener = Self { fd: fd as usize };
+        let mut ret = syscall::bind(listener.fd, &SockAddrIn::from_addr(addr));
+        if ret == 0 {
+            ret = syscall::listen(listener.fd, backlog);
+        }
+        if ret < 0 {
+            listener.close();
+            return Err(ret);
+        }
+        return Ok(listener);
+    }
+
+    // 等待新连接，返回(连接, 对端地址)
+    pub fn accept(&self) -> Result<(TcpStream, SocketAddr), isize> {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        let fd = syscall::accept(self.fd, &mut addr);
+        if fd < 0 {
+            return Err(fd);
+        }
+        return Ok((TcpStream { fd: fd as usize }, addr.to_addr()));
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        syscall::getsockname(self.fd, &mut addr);
+        return addr.to_addr();
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+pub struct TcpStream {
+    fd: usize,
+}
+
+impl TcpStream {
+    // 连接到addr，阻塞直到握手完成
+    pub fn connect(addr: SocketAddr) -> Result<Self, isize> {
+        let fd = syscall::socket(AF_INET, SOCK_STREAM, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        let stream = Self { fd: fd as usize };
+        let ret = syscall::connect(stream.fd, &SockAddrIn::from_addr(addr));
+        if ret < 0 {
+            stream.close();
+            return Err(ret);
+        }
+        return Ok(stream);
+    }
+
+    pub fn fd(&self) -> usize {
+        self.fd
+    }
+
+    // 读取数据，对端关闭时返回0
+    pub fn read(&self, buf: &mut [u8]) -> isize {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        syscall::recvfrom(self.fd, buf, 0, &mut addr)
+    }
+
+    // 发送全部数据
+    pub fn write(&self, buf: &[u8]) -> isize {
+        syscall::sendto(self.fd, buf, 0, None)
+    }
+
+    pub fn shutdown(&self, how: usize) -> isize {
+        syscall::shutdown(self.fd, how)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        syscall::getsockname(self.fd, &mut addr);
+        return addr.to_addr();
+    }
+
+    // 接收超时，单位ms，0表示一直等待
+    pub fn set_read_timeout(&self, timeout_ms: usize) -> isize {
+        syscall::setsockopt(self.fd, SOL_SOCKET, SO_RCVTIMEO, timeout_ms)
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+const UNIX_PATH_MAX: usize = 108;
+
+// 与内核一致的sockaddr_un，path以'\0'结尾
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockAddrUn {
+    pub family: u16,
+    pub path: [u8; UNIX_PATH_MAX],
+}
+
+impl SockAddrUn {
+    // 路径过长时截断
+    pub fn from_path(path: &str) -> Self {
+        let mut addr = Self {
+            family: AF_UNIX as u16,
+            path: [0; UNIX_PATH_MAX],
+        };
+        let len = path.len().min(UNIX_PATH_MAX - 1);
+        addr.path[0..len].copy_from_slice(&path.as_bytes()[0..len]);
+        return addr;
+    }
+
+    pub fn path(&self) -> &str {
+        c_str(&self.path)
+    }
+}
+
+pub struct UnixListener {
+    fd: usize,
+}
+
+impl UnixListener {
+    // 创建socket，绑定到path并开始监听，path不存在时创建一个文件
+    pub fn bind(path: &str, backlog: usize) -> Result<Self, isize> {
+        let fd = syscall::socket(AF_UNIX, SOCK_STREAM, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        let listener = Self { fd: fd as usize };
+        let mut ret = syscall::bind_unix(listener.fd, &SockAddrUn::from_path(path));
+        if ret == 0 {
+            ret = syscall::listen(listener.fd, backlog);
+        }
+        if ret < 0 {
+            listener.close();
+            return Err(ret);
+        }
+        return Ok(listener);
+    }
+
+    // 等待新连接
+    pub fn accept(&self) -> Result<UnixStream, isize> {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        let fd = syscall::accept(self.fd, &mut addr);
+        if fd < 0 {
+            return Err(fd);
+        }
+        return Ok(UnixStream { fd: fd as usize });
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+pub struct UnixStream {
+    fd: usize,
+}
+
+impl UnixStream {
+    // 连接到path上监听的socket，没有监听的socket时返回CONN_REFUSED_ERROR
+    pub fn connect(path: &str) -> Result<Self, isize> {
+        let fd = syscall::socket(AF_UNIX, SOCK_STREAM, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        let stream = Self { fd: fd as usize };
+        let ret = syscall::connect_unix(stream.fd, &SockAddrUn::from_path(path));
+        if ret < 0 {
+            stream.close();
+            return Err(ret);
+        }
+        return Ok(stream);
+    }
+
+    // 创建一对互相连接的流，fork之后父子进程各使用一端
+    pub fn pair() -> Result<(Self, Self), isize> {
+        let (a, b) = syscall::socketpair(AF_UNIX, SOCK_STREAM, 0)?;
+        return Ok((Self { fd: a }, Self { fd: b }));
+    }
+
+    pub fn fd(&self) -> usize {
+        self.fd
+    }
+
+    // 读取数据，对端关闭时返回0
+    pub fn read(&self, buf: &mut [u8]) -> isize {
+        syscall::read(self.fd, buf)
+    }
+
+    // 发送全部数据，对端关闭时返回BROKEN_PIPE_ERROR
+    pub fn write(&self, buf: &[u8]) -> isize {
+        syscall::sendto(self.fd, buf, 0, None)
+    }
+
+    pub fn shutdown(&self, how: usize) -> isize {
+        syscall::shutdown(self.fd, how)
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+pub struct UnixDatagram {
+    fd: usize,
+}
+
+impl UnixDatagram {
+    fn new() -> Result<Self, isize> {
+        let fd = syscall::socket(AF_UNIX, SOCK_DGRAM, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        return Ok(Self { fd: fd as usize });
+    }
+
+    // 创建socket并绑定到path
+    pub fn bind(path: &str) -> Result<Self, isize> {
+        let socket = Self::new()?;
+        let ret = syscall::bind_unix(socket.fd, &SockAddrUn::from_path(path));
+        if ret < 0 {
+            socket.close();
+            return Err(ret);
+        }
+        return Ok(socket);
+    }
+
+    // 不绑定路径的socket，只能发送
+    pub fn unbound() -> Result<Self, isize> {
+        Self::new()
+    }
+
+    pub fn pair() -> Result<(Self, Self), isize> {
+        let (a, b) = syscall::socketpair(AF_UNIX, SOCK_DGRAM, 0)?;
+        return Ok((Self { fd: a }, Self { fd: b }));
+    }
+
+    pub fn fd(&self) -> usize {
+        self.fd
+    }
+
+    // 设置默认的对端路径，之后可以使用send
+    pub fn connect(&self, path: &str) -> isize {
+        syscall::connect_unix(self.fd, &SockAddrUn::from_path(path))
+    }
+
+    pub fn send_to(&self, buf: &[u8], path: &str) -> isize {
+        syscall::sendto_unix(self.fd, buf, 0, &SockAddrUn::from_path(path))
+    }
+
+    pub fn send(&self, buf: &[u8]) -> isize {
+        syscall::sendto(self.fd, buf, 0, None)
+    }
+
+    // 接收一个报文，buf不足时报文被截断
+    pub fn recv(&self, buf: &mut [u8]) -> isize {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        syscall::recvfrom(self.fd, buf, 0, &mut addr)
+    }
+
+    // 绑定的路径，没有绑定时为空
+    pub fn local_path(&self) -> SockAddrUn {
+        let mut addr = SockAddrUn::from_path("");
+        syscall::getsockname_unix(self.fd, &mut addr);
+        return addr;
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+pub const DIRECTION_IN: u16 = 0;
+pub const DIRECTION_OUT: u16 = 1;
+
+// 抓包socket每个帧之前的头部，与内核的CaptureHeader布局一致
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CaptureHeader {
+    pub sec: u32,
+    pub usec: u32,
+    pub dev: u16,
+    pub direction: u16,
+    pub len: u32,     // 帧的原始长度
+    pub cap_len: u32, // 保存的长度
+}
+
+// 抓取所有网卡收发的以太网帧
+pub struct PacketCapture {
+    fd: usize,
+}
+
+impl PacketCapture {
+    pub fn open() -> Result<Self, isize> {
+        let fd = syscall::socket(AF_PACKET, SOCK_RAW, 0);
+        if fd < 0 {
+            return Err(fd);
+        }
+        return Ok(Self { fd: fd as usize });
+    }
+
+    // 读取下一个帧，返回头部和帧数据，buf不足时帧被截断
+    pub fn next<'a>(&self, buf: &'a mut [u8]) -> Result<(CaptureHeader, &'a [u8]), isize> {
+        let mut addr = SockAddrIn::from_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0));
+        let len = syscall::recvfrom(self.fd, buf, 0, &mut addr);
+        if len < 0 {
+            return Err(len);
+        }
+        let header_size = core::mem::size_of::<CaptureHeader>();
+        let header = unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const CaptureHeader) };
+        return Ok((header, &buf[header_size..len as usize]));
+    }
+
+    // 等待超时，单位ms，0表示一直等待
+    pub fn set_read_timeout(&self, timeout_ms: usize) -> isize {
+        syscall::setsockopt(self.fd, SOL_SOCKET, SO_RCVTIMEO, timeout_ms)
+    }
+
+    pub fn close(&self) -> isize {
+        syscall::close(self.fd)
+    }
+}
+
+// DNS查询，see: RFC 1035
+const DNS_PORT: u16 = 53;
+const DNS_TIMEOUT: usize = 2000;
+const DNS_RETRIES: usize = 3;
+const DNS_HEADER_SIZE: usize = 12;
+const DNS_TYPE_A: u16 = 1;
+const DNS_CLASS_IN: u16 = 1;
+
+// 解析域名，name是ip地址时直接返回，DNS服务器来自DHCP或者ifconfig的配置
+pub fn resolve(name: &str) -> Option<Ipv4Addr> {
+    if let Some(addr) = Ipv4Addr::parse(name) {
+        return Some(addr);
+    }
+    if name == "localhost" {
+        return Some(Ipv4Addr([127, 0, 0, 1]));
+    }
+    let server = interfaces()
+        .iter()
+        .find(|i| i.is_up() && i.dns != 0)
+        .map(|i| i.dns())?;
+    let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.set_read_timeout(DNS_TIMEOUT);
+    let id = crate::time::get_time_ms() as u16;
+    let query = build_dns_query(id, name)?;
+    let server = SocketAddr::new(server, DNS_PORT);
+    let mut buf = [0u8; 512];
+    let mut result = None;
+    'retry: for _ in 0..DNS_RETRIES {
+        socket.send_to(&query, server);
+        // 忽略其他来源和id不匹配的报文，直到超时
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((len, peer)) => {
+                    if peer != server {
+                        continue;
+                    }
+                    if let Some(addr) = parse_dns_response(&buf[0..len], id) {
+                        result = addr;
+                        break 'retry;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    }
+    socket.close();
+    return result;
+}
+
+fn build_dns_query(id: u16, name: &str) -> Option<Vec<u8>> {
+    let mut query: Vec<u8> = Vec::with_capacity(DNS_HEADER_SIZE + name.len() + 6);
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&0x0100u16.to_be_bytes()); // 标准查询，期望递归
+    query.extend_from_slice(&1u16.to_be_bytes()); // qdcount
+    query.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&DNS_TYPE_A.to_be_bytes());
+    query.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    return Some(query);
+}
+
+// 跳过报文中的域名，返回域名之后的位置
+fn skip_dns_name(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(pos)? as usize;
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        // 压缩的域名是指向报文中其他位置的指针，占两个字节
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        pos += 1 + len;
+    }
+}
+
+// 解析回复，不是对应的回复时返回None，域名不存在时返回Some(None)
+fn parse_dns_response(data: &[u8], id: u16) -> Option<Option<Ipv4Addr>> {
+    if data.len() < DNS_HEADER_SIZE || u16::from_be_bytes([data[0], data[1]]) != id {
+        return None;
+    }
+    let flags = u16::from_be_bytes([data[2], data[3]]);
+    // 不是回复
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    if flags & 0xf != 0 {
+        return Some(None);
+    }
+    let questions = u16::from_be_bytes([data[4], data[5]]);
+    let answers = u16::from_be_bytes([data[6], data[7]]);
+    let mut pos = DNS_HEADER_SIZE;
+    for _ in 0..questions {
+        pos = skip_dns_name(data, pos)? + 4;
+    }
+    // 跳过CNAME等其他类型的记录，返回第一个A记录
+    for _ in 0..answers {
+        pos = skip_dns_name(data, pos)?;
+        if pos + 10 > data.len() {
+            return Some(None);
+        }
+        let rtype = u16::from_be_bytes([data[pos], data[pos + 1]]);
+        let class = u16::from_be_bytes([data[pos + 2], data[pos + 3]]);
+        let len = u16::from_be_bytes([data[pos + 8], data[pos + 9]]) as usize;
+        pos += 10;
+        if pos + len > data.len() {
+            return Some(None);
+        }
+        if rtype == DNS_TYPE_A && class == DNS_CLASS_IN && len == 4 {
+            return Some(Some(Ipv4Addr([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])));
+        }
+        pos += len;
+    }
+    return Some(None);
+}
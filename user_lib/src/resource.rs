@@ -0,0 +1,28 @@
+use crate::syscall;
+
+// 线程栈的大小上限，栈在访问时自动增长，超过上限时进程以-11退出
+pub const RLIMIT_STACK: usize = 3;
+
+pub const RLIMIT_ERROR: isize = -1;
+
+// cur为当前生效的限制，max为cur能设置的最大值
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+pub fn getrlimit(resource: usize) -> Result<RLimit, isize> {
+    let mut limit = RLimit { cur: 0, max: 0 };
+    let res = syscall::getrlimit(resource, &mut limit);
+    if res < 0 {
+        return Err(res);
+    }
+    return Ok(limit);
+}
+
+// max只能降低，不能提高
+pub fn setrlimit(resource: usize, limit: RLimit) -> isize {
+    syscall::setrlimit(resource, &limit)
+}
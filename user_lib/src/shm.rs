@@ -0,0 +1,76 @@
+use crate::syscall;
+
+// 与内核一致的shmget flags和shmctl命令
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_CREAT: usize = 1 << 9;
+pub const IPC_EXCL: usize = 1 << 10;
+pub const SHM_RDONLY: usize = 1 << 12;
+
+pub const IPC_RMID: usize = 0;
+pub const IPC_STAT: usize = 2;
+
+pub const SHM_ERROR: isize = -1;
+pub const SHM_EXIST_ERROR: isize = -2;
+pub const SHM_NOT_FOUND_ERROR: isize = -3;
+
+// 与内核一致的shmid_ds
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ShmStat {
+    pub size: usize,
+    pub attaches: usize,
+}
+
+// 共享内存段，不同进程通过相同的key或者继承的shmid访问同一个段
+pub struct SharedMemory {
+    id: usize,
+}
+
+impl SharedMemory {
+    // flags包含IPC_CREAT时key不存在则创建，key为IPC_PRIVATE时总是创建新的段
+    pub fn get(key: usize, size: usize, flags: usize) -> Result<Self, isize> {
+        let id = syscall::shmget(key, size, flags);
+        if id < 0 {
+            return Err(id);
+        }
+        return Ok(Self { id: id as usize });
+    }
+
+    pub fn from_id(id: usize) -> Self {
+        Self { id: id }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    // 映射到当前进程，返回的切片覆盖整个段（按页对齐）
+    pub fn attach(&self, readonly: bool) -> Result<&'static mut [u8], isize> {
+        let size = self.stat()?.size;
+        let flags = if readonly { SHM_RDONLY } else { 0 };
+        let addr = syscall::shmat(self.id, 0, flags);
+        if addr < 0 {
+            return Err(addr);
+        }
+        return Ok(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, size) });
+    }
+
+    // 解除attach返回的映射，之后不能再访问该切片
+    pub fn detach(buf: &mut [u8]) -> isize {
+        syscall::shmdt(buf.as_ptr() as usize)
+    }
+
+    pub fn stat(&self) -> Result<ShmStat, isize> {
+        let mut stat = ShmStat { size: 0, attaches: 0 };
+        let res = syscall::shmctl(self.id, IPC_STAT, Some(&mut stat));
+        if res < 0 {
+            return Err(res);
+        }
+        return Ok(stat);
+    }
+
+    // 删除段的id，已经attach的进程不受影响，全部detach后内存被回收
+    pub fn remove(&self) -> isize {
+        syscall::shmctl(self.id, IPC_RMID, None)
+    }
+}
@@ -1,5 +1,12 @@
 use core::arch::asm;
 
+use crate::device::DeviceInfo;
+use crate::mem::{MemInfo, SlabInfo};
+use crate::mqueue::MqAttr;
+use crate::net::{IfInfo, SockAddrIn, SockAddrUn};
+use crate::resource::RLimit;
+use crate::shm::ShmStat;
+use core::mem::size_of;
 use crate::println;
 
 const SYSCALL_OPEN: usize = 56;
@@ -8,11 +15,52 @@ const SYSCALL_STAT: usize = 2001;
 const SYSCALL_FSTAT: usize = 2002;
 const SYSCALL_LSEEK: usize = 2003;
 const SYSCALL_LS_DIR: usize = 2004;
+const SYSCALL_MKFIFO: usize = 2005;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_TIMEDSEND: usize = 182;
+const SYSCALL_MQ_TIMEDRECEIVE: usize = 183;
+const SYSCALL_MQ_GETSETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MSYNC: usize = 227;
+
+const SYSCALL_LS_DEV: usize = 2010;
+const SYSCALL_MEM_INFO: usize = 2011;
+const SYSCALL_SLAB_INFO: usize = 2012;
+
+const SYSCALL_PING: usize = 2020;
+const SYSCALL_PING_REPLY: usize = 2021;
+const SYSCALL_IF_LIST: usize = 2022;
+const SYSCALL_IF_CONFIG: usize = 2023;
+const SYSCALL_IF_SET_UP: usize = 2024;
+const SYSCALL_DHCP: usize = 2025;
+
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_ACCEPT: usize = 202;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SETSOCKOPT: usize = 208;
+const SYSCALL_SHUTDOWN: usize = 210;
 
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_YIELD: usize = 124;
 #[allow(unused)]
@@ -43,6 +91,22 @@ fn ecall(id: usize, args: [usize; 3]) -> isize {
     return ret;
 }
 
+// 参数多于3个的系统调用，使用a0~a5传递参数
+fn ecall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!("ecall",
+        inlateout("x10") args[0] => ret,
+        in("x11") args[1],
+        in("x12") args[2],
+        in("x13") args[3],
+        in("x14") args[4],
+        in("x15") args[5],
+        in("x17") id);
+    }
+    return ret;
+}
+
 pub fn exit(code: i32) {
     ecall(SYSCALL_EXIT, [code as usize, 0, 0]);
 }
@@ -98,6 +162,14 @@ pub fn get_time(res_ptr: usize) -> isize {
     ecall(SYSCALL_GET_TIME, [res_ptr, 0, 0])
 }
 
+pub fn getrlimit(resource: usize, limit: &mut RLimit) -> isize {
+    ecall(SYSCALL_GETRLIMIT, [resource, limit as *mut RLimit as usize, 0])
+}
+
+pub fn setrlimit(resource: usize, limit: &RLimit) -> isize {
+    ecall(SYSCALL_SETRLIMIT, [resource, limit as *const RLimit as usize, 0])
+}
+
 pub fn cond_create() -> isize {
     ecall(SYSCALL_COND_CREATE, [0, 0, 0])
 }
@@ -130,6 +202,100 @@ pub fn lseek(fd: usize, offset: u32, from: u8) -> isize {
     ecall(SYSCALL_LSEEK, [fd, offset as usize, from as usize])
 }
 
+pub fn mkfifo(path: &str) -> isize {
+    ecall(SYSCALL_MKFIFO, [path.as_ptr() as usize, 0, 0])
+}
+
+// fds[0]为读端，fds[1]为写端
+pub fn pipe(fds: &mut [usize; 2]) -> isize {
+    ecall(SYSCALL_PIPE, [fds.as_mut_ptr() as usize, 0, 0])
+}
+
+// name需要以'\0'结尾，attr为None时使用默认属性
+pub fn mq_open(name: &str, flags: usize, attr: Option<&MqAttr>) -> isize {
+    let attr_ptr = match attr {
+        Some(attr) => attr as *const MqAttr as usize,
+        None => 0,
+    };
+    ecall(SYSCALL_MQ_OPEN, [name.as_ptr() as usize, flags, attr_ptr])
+}
+
+pub fn mq_unlink(name: &str) -> isize {
+    ecall(SYSCALL_MQ_UNLINK, [name.as_ptr() as usize, 0, 0])
+}
+
+pub fn mq_timedsend(fd: usize, buf: &[u8], prio: usize, timeout_ms: usize) -> isize {
+    ecall6(
+        SYSCALL_MQ_TIMEDSEND,
+        [fd, buf.as_ptr() as usize, buf.len(), prio, timeout_ms, 0],
+    )
+}
+
+pub fn mq_timedreceive(fd: usize, buf: &mut [u8], prio: &mut u32, timeout_ms: usize) -> isize {
+    ecall6(
+        SYSCALL_MQ_TIMEDRECEIVE,
+        [
+            fd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            prio as *mut u32 as usize,
+            timeout_ms,
+            0,
+        ],
+    )
+}
+
+pub fn mq_getsetattr(fd: usize, new_attr: Option<&MqAttr>, old_attr: &mut MqAttr) -> isize {
+    let new_ptr = match new_attr {
+        Some(attr) => attr as *const MqAttr as usize,
+        None => 0,
+    };
+    ecall(SYSCALL_MQ_GETSETATTR, [fd, new_ptr, old_attr as *mut MqAttr as usize])
+}
+
+pub fn shmget(key: usize, size: usize, flags: usize) -> isize {
+    ecall(SYSCALL_SHMGET, [key, size, flags])
+}
+
+// 返回映射的起始地址
+pub fn shmat(shmid: usize, addr: usize, flags: usize) -> isize {
+    ecall(SYSCALL_SHMAT, [shmid, addr, flags])
+}
+
+pub fn shmdt(addr: usize) -> isize {
+    ecall(SYSCALL_SHMDT, [addr, 0, 0])
+}
+
+pub fn shmctl(shmid: usize, cmd: usize, stat: Option<&mut ShmStat>) -> isize {
+    let stat_ptr = match stat {
+        Some(stat) => stat as *mut ShmStat as usize,
+        None => 0,
+    };
+    ecall(SYSCALL_SHMCTL, [shmid, cmd, stat_ptr])
+}
+
+// 返回新的堆结束地址，失败时返回原来的值
+pub fn brk(addr: usize) -> isize {
+    ecall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+// 返回映射的起始地址
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    ecall6(SYSCALL_MMAP, [addr, len, prot, flags, fd, offset])
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    ecall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    ecall(SYSCALL_MPROTECT, [addr, len, prot])
+}
+
+pub fn msync(addr: usize, len: usize, flags: usize) -> isize {
+    ecall(SYSCALL_MSYNC, [addr, len, flags])
+}
+
 pub fn ls_dir(path: &str, result: &mut [usize]) -> isize {
     ecall(
         SYSCALL_LS_DIR,
@@ -140,3 +306,178 @@ pub fn ls_dir(path: &str, result: &mut [usize]) -> isize {
         ],
     )
 }
+
+pub fn ls_dev(devices: &mut [DeviceInfo]) -> isize {
+    ecall(
+        SYSCALL_LS_DEV,
+        [devices.as_mut_ptr() as usize, devices.len(), 0],
+    )
+}
+
+pub fn mem_info(info: &mut MemInfo) -> isize {
+    ecall(SYSCALL_MEM_INFO, [info as *mut MemInfo as usize, 0, 0])
+}
+
+pub fn slab_info(caches: &mut [SlabInfo]) -> isize {
+    ecall(
+        SYSCALL_SLAB_INFO,
+        [caches.as_mut_ptr() as usize, caches.len(), 0],
+    )
+}
+
+pub fn ping(addr: u32, seq: u16, len: usize) -> isize {
+    ecall(SYSCALL_PING, [addr as usize, seq as usize, len])
+}
+
+pub fn ping_reply(addr: u32, seq: u16) -> isize {
+    ecall(SYSCALL_PING_REPLY, [addr as usize, seq as usize, 0])
+}
+
+pub fn if_list(interfaces: &mut [IfInfo]) -> isize {
+    ecall(
+        SYSCALL_IF_LIST,
+        [interfaces.as_mut_ptr() as usize, interfaces.len(), 0],
+    )
+}
+
+pub fn if_config(dev: usize, ip: u32, netmask: u32, gateway: u32, dns: u32) -> isize {
+    ecall6(
+        SYSCALL_IF_CONFIG,
+        [dev, ip as usize, netmask as usize, gateway as usize, dns as usize, 0],
+    )
+}
+
+pub fn if_set_up(dev: usize, up: bool) -> isize {
+    ecall(SYSCALL_IF_SET_UP, [dev, up as usize, 0])
+}
+
+pub fn dhcp(dev: usize) -> isize {
+    ecall(SYSCALL_DHCP, [dev, 0, 0])
+}
+
+pub fn socket(domain: usize, socket_type: usize, protocol: usize) -> isize {
+    ecall(SYSCALL_SOCKET, [domain, socket_type, protocol])
+}
+
+// 创建一对互相连接的AF_UNIX socket，返回两个fd
+pub fn socketpair(domain: usize, socket_type: usize, protocol: usize) -> Result<(usize, usize), isize> {
+    let mut fds = [0i32; 2];
+    let ret = ecall6(
+        SYSCALL_SOCKETPAIR,
+        [domain, socket_type, protocol, fds.as_mut_ptr() as usize, 0, 0],
+    );
+    if ret < 0 {
+        return Err(ret);
+    }
+    return Ok((fds[0] as usize, fds[1] as usize));
+}
+
+pub fn bind_unix(fd: usize, addr: &SockAddrUn) -> isize {
+    ecall(
+        SYSCALL_BIND,
+        [fd, addr as *const SockAddrUn as usize, size_of::<SockAddrUn>()],
+    )
+}
+
+pub fn connect_unix(fd: usize, addr: &SockAddrUn) -> isize {
+    ecall(
+        SYSCALL_CONNECT,
+        [fd, addr as *const SockAddrUn as usize, size_of::<SockAddrUn>()],
+    )
+}
+
+pub fn getsockname_unix(fd: usize, addr: &mut SockAddrUn) -> isize {
+    ecall(
+        SYSCALL_GETSOCKNAME,
+        [fd, addr as *mut SockAddrUn as usize, 0],
+    )
+}
+
+pub fn sendto_unix(fd: usize, buf: &[u8], flags: usize, addr: &SockAddrUn) -> isize {
+    ecall6(
+        SYSCALL_SENDTO,
+        [
+            fd,
+            buf.as_ptr() as usize,
+            buf.len(),
+            flags,
+            addr as *const SockAddrUn as usize,
+            size_of::<SockAddrUn>(),
+        ],
+    )
+}
+
+pub fn bind(fd: usize, addr: &SockAddrIn) -> isize {
+    ecall(
+        SYSCALL_BIND,
+        [fd, addr as *const SockAddrIn as usize, size_of::<SockAddrIn>()],
+    )
+}
+
+pub fn listen(fd: usize, backlog: usize) -> isize {
+    ecall(SYSCALL_LISTEN, [fd, backlog, 0])
+}
+
+pub fn accept(fd: usize, addr: &mut SockAddrIn) -> isize {
+    ecall(
+        SYSCALL_ACCEPT,
+        [fd, addr as *mut SockAddrIn as usize, 0],
+    )
+}
+
+pub fn connect(fd: usize, addr: &SockAddrIn) -> isize {
+    ecall(
+        SYSCALL_CONNECT,
+        [fd, addr as *const SockAddrIn as usize, size_of::<SockAddrIn>()],
+    )
+}
+
+pub fn getsockname(fd: usize, addr: &mut SockAddrIn) -> isize {
+    ecall(
+        SYSCALL_GETSOCKNAME,
+        [fd, addr as *mut SockAddrIn as usize, 0],
+    )
+}
+
+pub fn sendto(fd: usize, buf: &[u8], flags: usize, addr: Option<&SockAddrIn>) -> isize {
+    let (addr_ptr, addr_len) = match addr {
+        Some(addr) => (addr as *const SockAddrIn as usize, size_of::<SockAddrIn>()),
+        None => (0, 0),
+    };
+    ecall6(
+        SYSCALL_SENDTO,
+        [fd, buf.as_ptr() as usize, buf.len(), flags, addr_ptr, addr_len],
+    )
+}
+
+pub fn recvfrom(fd: usize, buf: &mut [u8], flags: usize, addr: &mut SockAddrIn) -> isize {
+    ecall6(
+        SYSCALL_RECVFROM,
+        [
+            fd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            flags,
+            addr as *mut SockAddrIn as usize,
+            0,
+        ],
+    )
+}
+
+pub fn setsockopt(fd: usize, level: usize, name: usize, value: usize) -> isize {
+    ecall6(
+        SYSCALL_SETSOCKOPT,
+        [
+            fd,
+            level,
+            name,
+            &value as *const usize as usize,
+            size_of::<usize>(),
+            0,
+        ],
+    )
+}
+
+pub fn shutdown(fd: usize, how: usize) -> isize {
+    ecall(SYSCALL_SHUTDOWN, [fd, how, 0])
+}